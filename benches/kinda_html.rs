@@ -19,7 +19,11 @@ fn tag_pair<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<(), Parsing
     let close_text = closing_tag(walker)?;
 
     if open_text.data != close_text.data {
-        return Err(ParsingError(open_text.location, ErrorKind::DemoError));
+        return Err(ParsingError(close_text.location, ErrorKind::MismatchedDelimiter {
+            open_span: open_text,
+            expected: open_text.data,
+            found: close_text.data
+        }));
     }
 
     Ok(())
@@ -0,0 +1,405 @@
+//! The pipeline of a tiny calculator language, from the text of a file to its output and diagnostics
+//!
+//! A program is a list of statements, `let name = expression;` and `print expression;`, where an expression is built from integers,
+//! names, the four arithmetic operators, negation and parentheses. Each stage is a function of its own, so a language can start from
+//! this and replace them one at a time
+
+use std::collections::HashMap;
+
+use compiler_utils::parser::intern::{Interner, Symbol};
+use compiler_utils::parser::lexer::{TokenStream, TokenWalker, token, token_if};
+use compiler_utils::prelude::*;
+use compiler_utils::parser::PartialResult;
+
+compiler_utils::diagnostic_codes! {
+    pub static CODES;
+
+    pub UNKNOWN_NAME {
+        code: "E0001", name: "unknown-name", level: Error,
+        message: "unknown name `{name}`",
+        explanation: "A name was used before it was declared. Declare it with `let` first."
+    }
+
+    pub DIVISION_BY_ZERO {
+        code: "E0002", name: "division-by-zero", level: Error,
+        message: "attempt to divide by zero",
+        explanation: "The right side of a `/` evaluated to zero."
+    }
+
+    pub OVERFLOW {
+        code: "E0003", name: "overflow", level: Error,
+        message: "arithmetic overflow",
+        explanation: "Values are 64 bit signed integers, and a number or the result of an operation did not fit."
+    }
+
+    pub UNUSED_NAME {
+        code: "W0001", name: "unused-name", level: Warning,
+        message: "`{name}` is never used",
+        explanation: "A name was declared but never read, either remove it or print it."
+    }
+}
+
+/// The kinds of token of the language
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    Let,
+    Print,
+    Name,
+    Number,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Open,
+    Close,
+    Equals,
+    Semicolon
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExprKind<'filedata> {
+    /// An integer, whose value is read from the span when it is evaluated
+    Number,
+    Name,
+    Negate(Box<Expr<'filedata>>),
+    Binary(Box<Expr<'filedata>>, Spanned<'filedata, BinaryOp>, Box<Expr<'filedata>>)
+}
+
+/// An expression and the span of the text it was parsed from, not including any parentheses around it
+pub type Expr<'filedata> = Spanned<'filedata, ExprKind<'filedata>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement<'filedata> {
+    Let(Span<'filedata>, Expr<'filedata>),
+    Print(Expr<'filedata>)
+}
+
+/// The result of running a file through every stage
+#[derive(Debug)]
+pub struct Run<'filedata> {
+    /// The statements which were parsed, statements with syntax errors are left out
+    pub statements: Vec<Statement<'filedata>>,
+    /// The values printed
+    pub output: Vec<i64>,
+    pub diagnostics: Vec<Diagnostic<'filedata>>
+}
+
+impl BinaryOp {
+    fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+        }
+    }
+
+    /// How tightly the operator binds, all of the operators are left associative
+    fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::Add | BinaryOp::Subtract => 1,
+            BinaryOp::Multiply | BinaryOp::Divide => 2,
+        }
+    }
+}
+
+/// Lex one token, or None for whitespace and `#` comments
+fn lex_token<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Option<Token>, ParsingError<'filedata>> {
+    let symbol = |span: Span| match span.data {
+        "+" => Token::Plus,
+        "-" => Token::Minus,
+        "*" => Token::Star,
+        "/" => Token::Slash,
+        "(" => Token::Open,
+        ")" => Token::Close,
+        "=" => Token::Equals,
+        _ => Token::Semicolon,
+    };
+
+    expected(choice((
+        map(take_while(char::is_whitespace, "whitespace"), |_| None),
+        map(pair(tag("#"), opt(take_while(|c| c != '\n', "a comment"))), |_| None),
+        map(take_while(|c| c.is_ascii_digit(), "a number"), |_| Some(Token::Number)),
+        map(take_while(|c| c.is_ascii_alphanumeric() || c == '_', "a name"), |name| Some(match name.data {
+            "let" => Token::Let,
+            "print" => Token::Print,
+            _ => Token::Name
+        })),
+        map(one_of("+-*/()=;"), move |span| Some(symbol(span))),
+    )), "a token")(walker)
+}
+
+/// Split the rest of the walker's input into tokens
+pub fn lex<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<TokenStream<'filedata, Token>, ParsingError<'filedata>> {
+    TokenStream::lex(walker, lex_token)
+}
+
+type Tokens<'stream, 'filedata> = TokenWalker<'stream, 'filedata, Token>;
+
+/// Parse every statement of a stream
+///
+/// After a syntax error the tokens are skipped up to the next `;`, so the statements after it are still parsed and every syntax
+/// error in the file is reported at once
+pub fn parse<'filedata>(stream: &TokenStream<'filedata, Token>) -> PartialResult<'filedata, Vec<Statement<'filedata>>> {
+    let mut tokens = stream.walker();
+    let mut statements = Vec::new();
+    let mut errors = Vec::new();
+
+    while tokens.peek().is_some() {
+        match statement(&mut tokens) {
+            Ok(statement) => statements.push(statement),
+            Err(error) => {
+                errors.push(error);
+                while tokens.step().is_some_and(|token| token.value != Token::Semicolon) {}
+            }
+        }
+    }
+
+    (Some(statements), errors)
+}
+
+fn statement<'filedata>(tokens: &mut Tokens<'_, 'filedata>) -> Result<Statement<'filedata>, ParsingError<'filedata>> {
+    let start = tokens.get_marker();
+    let keyword = token_if(|kind| matches!(kind, Token::Let | Token::Print), "a statement")(tokens)?;
+
+    let statement = if keyword.value == Token::Let {
+        let name = token(Token::Name, "a name")(tokens)?;
+        token(Token::Equals, "`=`")(tokens)?;
+        Statement::Let(name, expression(tokens)?)
+    }
+    else {
+        Statement::Print(expression(tokens)?)
+    };
+
+    // A missing `;` is reported just after the statement rather than at the start of whatever follows it
+    let end = tokens.span_from_marker_to_here(start).unwrap().end_location();
+    token(Token::Semicolon, "`;`")(tokens).map_err(|_| ParsingError(end, ErrorKind::ExpectedKind("`;`")))?;
+
+    Ok(statement)
+}
+
+fn expression<'filedata>(tokens: &mut Tokens<'_, 'filedata>) -> Result<Expr<'filedata>, ParsingError<'filedata>> {
+    binary(tokens, &[(Token::Plus, BinaryOp::Add), (Token::Minus, BinaryOp::Subtract)], term)
+}
+
+fn term<'filedata>(tokens: &mut Tokens<'_, 'filedata>) -> Result<Expr<'filedata>, ParsingError<'filedata>> {
+    binary(tokens, &[(Token::Star, BinaryOp::Multiply), (Token::Slash, BinaryOp::Divide)], factor)
+}
+
+/// Parse operands separated by any of the operators, which associate to the left
+fn binary<'stream, 'filedata>(
+    tokens: &mut Tokens<'stream, 'filedata>,
+    operators: &[(Token, BinaryOp)],
+    operand: fn(&mut Tokens<'stream, 'filedata>) -> Result<Expr<'filedata>, ParsingError<'filedata>>
+) -> Result<Expr<'filedata>, ParsingError<'filedata>> {
+    let start = tokens.get_marker();
+    let mut left = operand(tokens)?;
+
+    while let Some(next) = tokens.peek() {
+        let Some(&(_, op)) = operators.iter().find(|(kind, _)| *kind == next.value) else {
+            break;
+        };
+        tokens.step();
+
+        let right = operand(tokens)?;
+        let kind = ExprKind::Binary(Box::new(left), Spanned::new(next.span, op), Box::new(right));
+        left = Spanned::new(tokens.span_from_marker_to_here(start).unwrap(), kind);
+    }
+
+    Ok(left)
+}
+
+fn factor<'filedata>(tokens: &mut Tokens<'_, 'filedata>) -> Result<Expr<'filedata>, ParsingError<'filedata>> {
+    let start = tokens.get_marker();
+    let first = token_if(|kind| matches!(kind, Token::Number | Token::Name | Token::Minus | Token::Open), "an expression")(tokens)?;
+
+    let kind = match first.value {
+        Token::Number => ExprKind::Number,
+        Token::Name => ExprKind::Name,
+        Token::Minus => ExprKind::Negate(Box::new(factor(tokens)?)),
+        _ => {
+            let inner = expression(tokens)?;
+            token(Token::Close, "`)`")(tokens)?;
+            return Ok(inner);
+        }
+    };
+
+    Ok(Spanned::new(tokens.span_from_marker_to_here(start).unwrap(), kind))
+}
+
+/// Print statements in a canonical form, with one statement per line and only the parentheses the precedence of the operators needs
+pub fn pretty(statements: &[Statement]) -> String {
+    let mut text = String::new();
+
+    for statement in statements {
+        let value = match statement {
+            Statement::Let(name, value) => {
+                text += &format!("let {} = ", name.data);
+                value
+            }
+            Statement::Print(value) => {
+                text += "print ";
+                value
+            }
+        };
+
+        write_expression(&mut text, value, 0);
+        text += ";\n";
+    }
+
+    text
+}
+
+/// Write an expression, in parentheses if it binds less tightly than the precedence its position needs
+fn write_expression(text: &mut String, expr: &Expr, precedence: u8) {
+    match &expr.value {
+        ExprKind::Number | ExprKind::Name => text.push_str(expr.span.data),
+        ExprKind::Negate(operand) => {
+            text.push('-');
+            write_expression(text, operand, 3);
+        }
+        ExprKind::Binary(left, op, right) => {
+            let own = op.value.precedence();
+            if own < precedence {
+                text.push('(');
+            }
+
+            write_expression(text, left, own);
+            text.push_str(&format!(" {} ", op.value.symbol()));
+            write_expression(text, right, own + 1);
+
+            if own < precedence {
+                text.push(')');
+            }
+        }
+    }
+}
+
+/// A name declared by a `let`
+#[derive(Debug)]
+struct Binding<'filedata> {
+    name: Span<'filedata>,
+    /// The value, or None if evaluating it failed, in which case uses of the name are not reported again
+    value: Option<i64>,
+    used: bool
+}
+
+/// Runs statements in order, reporting errors as it finds them
+#[derive(Debug, Default)]
+pub struct Interpreter<'filedata> {
+    names: Interner,
+    /// Every binding in the order they were declared, including those shadowed by a later `let` of the same name
+    bindings: Vec<Binding<'filedata>>,
+    /// The binding each name currently refers to
+    scope: HashMap<Symbol, usize>,
+    output: Vec<i64>,
+    diagnostics: Vec<Diagnostic<'filedata>>
+}
+
+impl<'filedata> Interpreter<'filedata> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run statements, a statement whose expression fails is skipped
+    pub fn run(&mut self, statements: &[Statement<'filedata>]) {
+        for statement in statements {
+            match statement {
+                Statement::Let(name, value) => {
+                    // The value is evaluated first, so it refers to any earlier binding of the same name
+                    let value = self.evaluate(value);
+                    self.scope.insert(self.names.intern(name.data), self.bindings.len());
+                    self.bindings.push(Binding { name: *name, value, used: false });
+                }
+                Statement::Print(value) => {
+                    if let Some(value) = self.evaluate(value) {
+                        self.output.push(value);
+                    }
+                }
+            }
+        }
+    }
+
+    fn evaluate(&mut self, expr: &Expr<'filedata>) -> Option<i64> {
+        let value = match &expr.value {
+            ExprKind::Number => expr.span.data.parse().ok(),
+            ExprKind::Name => {
+                let Some(&index) = self.scope.get(&self.names.intern(expr.span.data)) else {
+                    let diagnostic = UNKNOWN_NAME.diagnostic(expr.span.location, &[("name", &expr.span.data)]);
+                    self.diagnostics.push(diagnostic.with_note(&expr.span, "not declared", ErrorLevel::Error));
+                    return None;
+                };
+
+                let binding = &mut self.bindings[index];
+                binding.used = true;
+                return binding.value;
+            }
+            ExprKind::Negate(operand) => self.evaluate(operand)?.checked_neg(),
+            ExprKind::Binary(left, op, right) => {
+                // Both sides are evaluated before either fails, so the errors of both are reported
+                let (left, right_value) = (self.evaluate(left), self.evaluate(right));
+                let (left, right_value) = (left?, right_value?);
+
+                match op.value {
+                    BinaryOp::Add => left.checked_add(right_value),
+                    BinaryOp::Subtract => left.checked_sub(right_value),
+                    BinaryOp::Multiply => left.checked_mul(right_value),
+                    BinaryOp::Divide if right_value == 0 => {
+                        let diagnostic = DIVISION_BY_ZERO.diagnostic(op.span.location, &[]);
+                        self.diagnostics.push(diagnostic.with_note(&right.span, "this is zero", ErrorLevel::Error));
+                        return None;
+                    }
+                    BinaryOp::Divide => left.checked_div(right_value),
+                }
+            }
+        };
+
+        if value.is_none() {
+            let diagnostic = OVERFLOW.diagnostic(expr.span.location, &[]);
+            self.diagnostics.push(diagnostic.with_note(&expr.span, "does not fit in 64 bits", ErrorLevel::Error));
+        }
+
+        value
+    }
+
+    /// Finish running, warning about names which were never used, and return the output and diagnostics
+    pub fn finish(mut self) -> (Vec<i64>, Vec<Diagnostic<'filedata>>) {
+        for binding in &self.bindings {
+            if !binding.used && binding.value.is_some() {
+                let diagnostic = UNUSED_NAME.diagnostic(binding.name.location, &[("name", &binding.name.data)]);
+                self.diagnostics.push(diagnostic.with_note(&binding.name, "declared here", ErrorLevel::Warning));
+            }
+        }
+
+        (self.output, self.diagnostics)
+    }
+}
+
+/// Run a file through every stage, the statements which parsed are run even if others have syntax errors
+pub fn run<'filedata>(walker: &FileWalker<'filedata>) -> Run<'filedata> {
+    let stream = match lex(&mut walker.clone()) {
+        Ok(stream) => stream,
+        Err(error) => return Run { statements: Vec::new(), output: Vec::new(), diagnostics: vec![Diagnostic::from_parsing_error(&error)] }
+    };
+
+    let (statements, errors) = parse(&stream);
+    let statements = statements.unwrap_or_default();
+
+    let mut interpreter = Interpreter::new();
+    interpreter.run(&statements);
+    let (output, diagnostics) = interpreter.finish();
+
+    Run {
+        statements,
+        output,
+        diagnostics: errors.iter().map(Diagnostic::from_parsing_error).chain(diagnostics).collect()
+    }
+}
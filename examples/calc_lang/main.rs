@@ -0,0 +1,79 @@
+//! Runs a file of the calculator language in `lang.rs`, printing its output to stdout and its diagnostics to stderr
+//!
+//! Usage: `cargo run --example calc_lang -- [--color=auto|always|never|json|github] [--pretty] [--explain CODE] [FILE]`, which runs
+//! `example.calc` if no file is given. `--pretty` prints the program in its canonical form instead of running it
+
+mod lang;
+
+use std::process::ExitCode;
+
+use compiler_utils::driver::emit::{DiagnosticEmitter, RenderTarget};
+use compiler_utils::driver::source::SourceFile;
+
+fn main() -> ExitCode {
+    let mut target = RenderTarget::auto();
+    let mut pretty = false;
+    let mut path = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if let Some(choice) = arg.strip_prefix("--color=") {
+            match RenderTarget::from_choice(choice) {
+                Ok(choice) => target = choice,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        else if arg == "--pretty" {
+            pretty = true;
+        }
+        else if arg == "--explain" {
+            let code = args.next().unwrap_or_default();
+            match lang::CODES.explain(&code) {
+                Some(explanation) => print!("{}", explanation),
+                None => eprintln!("no diagnostic has the code `{}`", code),
+            }
+            return ExitCode::SUCCESS;
+        }
+        else {
+            path = Some(arg);
+        }
+    }
+
+    let source = match path {
+        Some(path) => match SourceFile::load(&path) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("could not read `{}`: {}", path, error);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => SourceFile::from_bytes("example.calc", include_bytes!("example.calc")).unwrap()
+    };
+
+    let walker = source.walker();
+    let run = lang::run(&walker);
+
+    if pretty {
+        print!("{}", lang::pretty(&run.statements));
+    }
+    else {
+        for value in &run.output {
+            println!("{}", value);
+        }
+    }
+
+    let mut emitter = DiagnosticEmitter::new(target, std::io::stderr());
+    for diagnostic in &run.diagnostics {
+        emitter.emit(diagnostic, &walker).unwrap();
+    }
+
+    if emitter.error_count() > 0 {
+        ExitCode::FAILURE
+    }
+    else {
+        ExitCode::SUCCESS
+    }
+}
@@ -1,20 +1,13 @@
-use compiler_utils::*;
+use compiler_utils::prelude::*;
 
 fn digit<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
     one_of("0123456789")(walker)
 }
 
-fn alpha<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
-    take_if(|c| c.is_alphabetic(), "alphabetic")(walker)
-}
-
-fn alpha_numeric<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
-    take_if(|c| c.is_alphanumeric(), "alphanumeric")(walker)
-}
-
-fn ident_symbol<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
-    alt(alpha_numeric, one_of("_"))(walker)
-}
+const IDENTIFIER: IdentSpec = IdentSpec::new(
+    CharClass::Alphanumeric,
+    CharClass::Union(&[CharClass::Alphanumeric, CharClass::Chars("_")])
+).with_keywords(KeywordSet::new(&["fn", "return"]));
 
 fn ws_text<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<(), ParsingError<'filedata>> {
     map(opt(accepts_while(one_of("\r\n\t "))), |_| ())(walker)
@@ -51,17 +44,17 @@ fn number<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Span<'filedat
 }
 
 fn identifier<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
-    ws(accepts(
-        pair(alpha_numeric, accepts_while(ident_symbol))
-    ))(walker)
+    ws(map(identifier_with(IDENTIFIER), |identifier| identifier.span))(walker)
 }
 
+#[allow(dead_code)]
 #[derive(Debug)]
 enum Value<'filedata> {
     Identifier(Span<'filedata>),
     Number(Span<'filedata>)
 }
 
+#[allow(dead_code)]
 #[derive(Debug)]
 enum Instruction<'filedata> {
     Return(Value<'filedata>)
@@ -111,7 +104,7 @@ fn main() {
 
     let (name, tags) = funcdecl(&mut data).unwrap();
 
-    let settings = ErrorDisplaySettings{ colored: true };
+    let settings = ErrorDisplaySettings::default();
 
     let error_render = ErrorRender::new(ErrorLevel::Warning, &settings, "Pointing out the name", &name.location, vec![
         Note::new(&name, "This is the name", ErrorLevel::Warning),
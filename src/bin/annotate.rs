@@ -0,0 +1,107 @@
+//! Renders a diagnostic over a file from annotations given on the command line, for designing message layouts
+//!
+//! Usage: `annotate <file> <line:column:length:message>... [--level error|warning|info] [--message text] [--color auto|always|never|json|github]`
+//!
+//! Lines and columns count from 1, and lengths are in characters. The first annotation is the primary location, and is
+//! shown at the level of the diagnostic, the rest are shown as info notes. Colors are used when the output is a terminal, unless
+//! overridden by `--color` or the `NO_COLOR` and `CLICOLOR_FORCE` environment variables, and `--no-color` is kept as `--color never`
+
+use compiler_utils::driver::emit::{DiagnosticEmitter, RenderTarget};
+use compiler_utils::errors::Diagnostic;
+use compiler_utils::{ErrorLevel, FileWalker, Location, Span};
+
+/// An annotation given on the command line
+struct Annotation {
+    line: usize,
+    column: usize,
+    length: usize,
+    message: String
+}
+
+impl Annotation {
+    /// Parse an annotation of the form `line:column:length:message`
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut parts = text.splitn(4, ':');
+        let mut number = |name: &str| parts.next()
+            .and_then(|part| part.parse::<usize>().ok())
+            .filter(|value| *value > 0 || name == "length")
+            .ok_or_else(|| format!("invalid {} in annotation `{}`", name, text));
+
+        let line = number("line")?;
+        let column = number("column")?;
+        let length = number("length")?;
+        let message = parts.next().ok_or_else(|| format!("missing message in annotation `{}`", text))?.to_string();
+
+        Ok(Self { line, column, length, message })
+    }
+
+    /// Get the span of the file the annotation refers to
+    fn span<'filedata>(&self, walker: &FileWalker<'filedata>) -> Result<Span<'filedata>, String> {
+        let location = Location::from_components(self.column - 1, self.line - 1, walker.filename());
+        let start = walker.empty_span_at(&location)
+            .ok_or_else(|| format!("line {} column {} is not within the file", self.line, self.column))?;
+
+        let data = walker.all_data();
+        let offset = start.byte_range_in(data).start;
+        let length = data[offset..].char_indices().nth(self.length).map_or(data.len() - offset, |(index, _)| index);
+
+        Ok(Span::from_components(location, &data[offset..offset + length]))
+    }
+}
+
+fn run(args: Vec<String>) -> Result<String, String> {
+    let mut target = None;
+    let mut level = ErrorLevel::Error;
+    let mut message = None;
+    let mut path = None;
+    let mut annotations = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--no-color" => target = Some(RenderTarget::Plain),
+            "--color" => {
+                let choice = args.next().ok_or("expected a choice after `--color`")?;
+                target = Some(RenderTarget::from_choice_for(&choice, &std::io::stdout()).map_err(|e| e.to_string())?);
+            }
+            "--level" => level = match args.next().as_deref() {
+                Some("error") => ErrorLevel::Error,
+                Some("warning") => ErrorLevel::Warning,
+                Some("info") => ErrorLevel::Info,
+                _ => return Err("expected one of `error`, `warning` or `info` after `--level`".to_string())
+            },
+            "--message" => message = Some(args.next().ok_or("expected a message after `--message`")?),
+            _ if path.is_none() => path = Some(arg),
+            _ => annotations.push(Annotation::parse(&arg)?)
+        }
+    }
+
+    let path = path.ok_or("usage: annotate <file> <line:column:length:message>... [--level error|warning|info] [--message text] [--color auto|always|never|json|github]")?;
+    let data = std::fs::read_to_string(&path).map_err(|e| format!("could not read `{}`: {}", path, e))?;
+    let walker = FileWalker::from_data(&data, &path);
+
+    let primary = annotations.first().ok_or("expected at least one annotation")?;
+    let message = message.unwrap_or_else(|| primary.message.clone());
+
+    let spans = annotations.iter().map(|annotation| annotation.span(&walker)).collect::<Result<Vec<_>, _>>()?;
+    let diagnostic = annotations.iter().zip(&spans).enumerate().fold(
+        Diagnostic::new(level, message, spans[0].location),
+        |diagnostic, (i, (annotation, span))| diagnostic.with_note(span, &annotation.message, if i == 0 { level } else { ErrorLevel::Info }));
+
+    // The diagnostic is printed to stdout, so that is where colors are detected for
+    let target = target.unwrap_or_else(|| RenderTarget::auto_for(&std::io::stdout()));
+    let mut emitter = DiagnosticEmitter::new(target, Vec::new());
+    emitter.emit(&diagnostic, &walker).map_err(|e| e.to_string())?;
+
+    String::from_utf8(emitter.into_inner()).map_err(|e| e.to_string())
+}
+
+fn main() {
+    match run(std::env::args().skip(1).collect()) {
+        Ok(rendered) => print!("{}", rendered),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+/// The version of the layout of cache entries on disk, entries written with a different layout are invalidated
+pub const CACHE_FORMAT_VERSION: u32 = 1;
+
+/// Why a cached parse result could not be used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Invalidation {
+    /// The contents of the file have changed since the result was cached
+    ContentChanged,
+    /// The result was cached by a different version of the cache format or of the user's parse result
+    VersionChanged,
+    /// The cache entry could not be read as a parse result
+    Corrupt,
+    /// The entry was removed with `ParseCache::invalidate` or `ParseCache::clear`
+    Removed
+}
+
+/// A hook called when a cached parse result is invalidated
+pub type InvalidationHook = Box<dyn Fn(&str, Invalidation)>;
+
+/// A cache of parse results on disk, keyed by a name for each file and checked against a hash of its contents
+///
+/// Results are serialized with serde, so they must own their data rather than borrowing spans from the file
+pub struct ParseCache {
+    directory: PathBuf,
+    version: u32,
+    hooks: Vec<InvalidationHook>
+}
+
+#[derive(Serialize)]
+struct EntryRef<'a, T> {
+    format: u32,
+    version: u32,
+    hash: u64,
+    value: &'a T
+}
+
+/// The fields of an entry which say how it was written, read before the value so a changed layout is not mistaken for corruption
+#[derive(Deserialize)]
+struct Header {
+    format: u32,
+    version: u32
+}
+
+/// The rest of an entry, read once its header matches
+#[derive(Deserialize)]
+struct Entry<T> {
+    hash: u64,
+    value: T
+}
+
+/// Hash the contents of a file with 64 bit FNV-1a, which, unlike the standard library's hasher, is stable between runs and releases
+pub fn content_hash(data: &str) -> u64 {
+    data.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+impl ParseCache {
+    /// Construct a cache storing its entries in a directory, `version` is the version of the user's parse result, which should be changed whenever its layout changes
+    pub fn new(directory: impl Into<PathBuf>, version: u32) -> Self {
+        Self {
+            directory: directory.into(),
+            version,
+            hooks: Vec::new()
+        }
+    }
+
+    /// Add a hook called with the name of a file and the reason whenever its cached result is invalidated
+    pub fn with_invalidation_hook(mut self, hook: impl Fn(&str, Invalidation) + 'static) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    fn entry_path(&self, name: &str) -> PathBuf {
+        self.directory.join(format!("{:016x}.json", content_hash(name)))
+    }
+
+    fn invalidated(&self, name: &str, reason: Invalidation) {
+        for hook in &self.hooks {
+            hook(name, reason);
+        }
+    }
+
+    /// Get the cached parse result for a file, if the file has not changed since it was cached
+    pub fn get<T: DeserializeOwned>(&self, name: &str, data: &str) -> Option<T> {
+        let text = std::fs::read_to_string(self.entry_path(name)).ok()?;
+
+        let header: Header = match serde_json::from_str(&text) {
+            Ok(header) => header,
+            Err(_) => {
+                self.invalidated(name, Invalidation::Corrupt);
+                return None;
+            }
+        };
+
+        if header.format != CACHE_FORMAT_VERSION || header.version != self.version {
+            self.invalidated(name, Invalidation::VersionChanged);
+            return None;
+        }
+
+        let entry: Entry<T> = match serde_json::from_str(&text) {
+            Ok(entry) => entry,
+            Err(_) => {
+                self.invalidated(name, Invalidation::Corrupt);
+                return None;
+            }
+        };
+
+        if entry.hash != content_hash(data) {
+            self.invalidated(name, Invalidation::ContentChanged);
+            None
+        }
+        else {
+            Some(entry.value)
+        }
+    }
+
+    /// Store the parse result for a file
+    pub fn insert<T: Serialize>(&self, name: &str, data: &str, value: &T) -> std::io::Result<()> {
+        let entry = EntryRef {
+            format: CACHE_FORMAT_VERSION,
+            version: self.version,
+            hash: content_hash(data),
+            value
+        };
+
+        std::fs::create_dir_all(&self.directory)?;
+        std::fs::write(self.entry_path(name), serde_json::to_string(&entry)?)
+    }
+
+    /// Get the cached parse result for a file, or parse it and cache the result if it has changed
+    ///
+    /// Failing to write the cache is not an error, the result is just not cached
+    pub fn get_or_parse<'filedata, T: Serialize + DeserializeOwned, E>(&self, name: &str, data: &'filedata str, parse: impl FnOnce(&'filedata str) -> Result<T, E>) -> Result<T, E> {
+        if let Some(value) = self.get(name, data) {
+            return Ok(value);
+        }
+
+        let value = parse(data)?;
+        let _ = self.insert(name, data, &value);
+
+        Ok(value)
+    }
+
+    /// Remove the cached result for a file
+    pub fn invalidate(&self, name: &str) -> std::io::Result<()> {
+        match std::fs::remove_file(self.entry_path(name)) {
+            Ok(()) => {
+                self.invalidated(name, Invalidation::Removed);
+                Ok(())
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Remove every cached result
+    ///
+    /// Only the entry files the cache writes are removed, so other files in the directory are left alone
+    pub fn clear(&self) -> std::io::Result<()> {
+        let entries = match std::fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e)
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            if is_entry_path(&path) && path.is_file() {
+                std::fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Check if a path is named like the entries written by `ParseCache::entry_path`
+fn is_entry_path(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+
+    name.strip_suffix(".json").is_some_and(|hash| hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)))
+}
+
+impl std::fmt::Debug for ParseCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParseCache")
+            .field("directory", &self.directory)
+            .field("version", &self.version)
+            .field("hooks", &self.hooks.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{FileWalker, ParsingError, tag, take_while};
+    use super::{Invalidation, ParseCache};
+
+    fn parse_greeting(data: &str) -> Result<String, ParsingError<'_>> {
+        let mut walker = FileWalker::from_data(data, "input");
+        tag("Hello ")(&mut walker)?;
+
+        Ok(take_while(|c| c.is_alphabetic(), "name")(&mut walker)?.data.to_string())
+    }
+
+    #[test]
+    fn cache_round_trip() {
+        let directory = std::env::temp_dir().join(format!("compiler-utils-cache-{}", std::process::id()));
+        let invalidations = Rc::new(RefCell::new(Vec::new()));
+
+        let hook_invalidations = invalidations.clone();
+        let cache = ParseCache::new(&directory, 1)
+            .with_invalidation_hook(move |name, reason| hook_invalidations.borrow_mut().push((name.to_string(), reason)));
+
+        let mut parses = 0;
+        let mut parse = |data| {
+            parses += 1;
+            parse_greeting(data)
+        };
+
+        assert_eq!(cache.get_or_parse("greeting.txt", "Hello World", &mut parse).unwrap(), "World");
+        assert_eq!(cache.get_or_parse("greeting.txt", "Hello World", &mut parse).unwrap(), "World");
+        assert_eq!(cache.get_or_parse("greeting.txt", "Hello Mars", &mut parse).unwrap(), "Mars");
+        assert_eq!(parses, 2);
+
+        // Changing the version of the parse result invalidates everything cached by the previous version
+        let other_version = ParseCache::new(&directory, 2);
+        assert_eq!(other_version.get::<String>("greeting.txt", "Hello Mars"), None);
+
+        cache.invalidate("greeting.txt").unwrap();
+        assert_eq!(cache.get::<String>("greeting.txt", "Hello Mars"), None);
+
+        assert_eq!(*invalidations.borrow(), vec![
+            ("greeting.txt".to_string(), Invalidation::ContentChanged),
+            ("greeting.txt".to_string(), Invalidation::Removed),
+        ]);
+
+        // Clearing the cache leaves files it did not write
+        cache.insert("greeting.txt", "Hello Mars", &"Mars").unwrap();
+        std::fs::write(directory.join("notes.txt"), "not an entry").unwrap();
+        cache.clear().unwrap();
+        assert_eq!(cache.get::<String>("greeting.txt", "Hello Mars"), None);
+        assert!(directory.join("notes.txt").exists());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn cache_layout_change() {
+        let directory = std::env::temp_dir().join(format!("compiler-utils-cache-layout-{}", std::process::id()));
+        let invalidations = Rc::new(RefCell::new(Vec::new()));
+
+        ParseCache::new(&directory, 1).insert("numbers.txt", "1 2", &"1 2").unwrap();
+
+        // A new version whose result has a different layout reports the version, not a corrupt entry
+        let hook_invalidations = invalidations.clone();
+        let cache = ParseCache::new(&directory, 2)
+            .with_invalidation_hook(move |_, reason| hook_invalidations.borrow_mut().push(reason));
+        assert_eq!(cache.get::<Vec<u32>>("numbers.txt", "1 2"), None);
+
+        std::fs::write(directory.join(format!("{:016x}.json", super::content_hash("numbers.txt"))), "{\"format\": 1").unwrap();
+        assert_eq!(cache.get::<Vec<u32>>("numbers.txt", "1 2"), None);
+
+        assert_eq!(*invalidations.borrow(), vec![Invalidation::VersionChanged, Invalidation::Corrupt]);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,232 @@
+//! Builds generated code from pieces which remember the spans of the source they came from, so errors a downstream tool reports in the
+//! generated code can be translated back to the source, such as with `ErrorRender::with_source_map`
+
+use std::ops::Range;
+
+use crate::{LineIndex, Location, Span};
+
+/// A piece of generated code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Piece<'a, 'filedata> {
+    /// Text with no origin in the source, such as punctuation the generator adds
+    Text(&'a str),
+    /// The text of a span of the source, copied unchanged so positions within it map exactly
+    Original(Span<'filedata>),
+    /// Text generated from a span of the source, such as a renamed identifier, which maps to the whole span
+    Translated(&'a str, Span<'filedata>)
+}
+
+/// A range of the generated text which came from a span of the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping<'filedata> {
+    /// The range of bytes of the generated text
+    pub output: Range<usize>,
+    pub original: Span<'filedata>,
+    /// Whether the generated text is a copy of the original, so positions within it map exactly
+    pub exact: bool
+}
+
+/// Builds generated code piece by piece, recording where each piece came from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CodeBuilder<'filedata> {
+    text: String,
+    mappings: Vec<Mapping<'filedata>>
+}
+
+/// Generated code and the mappings from its text to the source it was generated from, in the order they appear in the text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedCode<'filedata> {
+    pub text: String,
+    pub mappings: Vec<Mapping<'filedata>>
+}
+
+impl<'a, 'filedata> From<&'a str> for Piece<'a, 'filedata> {
+    fn from(text: &'a str) -> Self {
+        Piece::Text(text)
+    }
+}
+
+impl<'filedata> From<Span<'filedata>> for Piece<'_, 'filedata> {
+    fn from(span: Span<'filedata>) -> Self {
+        Piece::Original(span)
+    }
+}
+
+impl<'filedata> CodeBuilder<'filedata> {
+    /// Construct a builder without any text
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the text generated so far
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Add a piece to the end of the generated text
+    pub fn push<'a>(&mut self, piece: impl Into<Piece<'a, 'filedata>>) -> &mut Self {
+        let (text, mapping) = match piece.into() {
+            Piece::Text(text) => (text, None),
+            Piece::Original(span) => (span.data, Some((span, true))),
+            Piece::Translated(text, span) => (text, Some((span, false)))
+        };
+
+        let start = self.text.len();
+        self.text.push_str(text);
+
+        if let Some((original, exact)) = mapping {
+            self.mappings.push(Mapping { output: start..self.text.len(), original, exact });
+        }
+
+        self
+    }
+
+    /// Add a template to the end of the generated text, where each `{}` is replaced by the next piece and `{{` and `}}` are braces
+    ///
+    /// Panics if the number of pieces does not match the number of `{}`s
+    pub fn push_template<'a>(&mut self, template: &str, pieces: &[Piece<'a, 'filedata>]) -> &mut Self {
+        let mut pieces = pieces.iter();
+        let mut rest = template;
+
+        while let Some(brace) = rest.find(['{', '}']) {
+            self.text.push_str(&rest[..brace]);
+            rest = &rest[brace..];
+
+            if rest.starts_with("{}") {
+                self.push(*pieces.next().expect("the template has more `{}`s than pieces"));
+            }
+            else if rest.starts_with("{{") || rest.starts_with("}}") {
+                self.text.push_str(&rest[..1]);
+            }
+            else {
+                panic!("unmatched `{}` in a template, braces are written `{{{{` and `}}}}`", &rest[..1]);
+            }
+            rest = &rest[2..];
+        }
+
+        self.text.push_str(rest);
+        assert!(pieces.next().is_none(), "the template has fewer `{{}}`s than pieces");
+
+        self
+    }
+
+    /// Add the code of another builder to the end of the generated text
+    pub fn append(&mut self, other: CodeBuilder<'filedata>) -> &mut Self {
+        let offset = self.text.len();
+        self.text.push_str(&other.text);
+        self.mappings.extend(other.mappings.into_iter().map(|mapping| Mapping {
+            output: mapping.output.start + offset..mapping.output.end + offset,
+            ..mapping
+        }));
+
+        self
+    }
+
+    /// Finish building, returning the text and its mappings
+    pub fn finish(self) -> GeneratedCode<'filedata> {
+        GeneratedCode { text: self.text, mappings: self.mappings }
+    }
+}
+
+impl<'filedata> GeneratedCode<'filedata> {
+    /// Translate a range of bytes of the generated text to the span of the source it came from, or None if it was not generated from the source
+    ///
+    /// The range is mapped by the piece its start is in. Within a copy of the source the range maps exactly, cut short at the end of the
+    /// copy, otherwise it maps to the whole span the piece was generated from
+    pub fn original_range(&self, range: Range<usize>) -> Option<Span<'filedata>> {
+        let index = self.mappings.partition_point(|mapping| mapping.output.end <= range.start);
+        let mapping = self.mappings.get(index).filter(|mapping| mapping.output.start <= range.start)?;
+
+        if !mapping.exact {
+            return Some(mapping.original);
+        }
+
+        let start = range.start - mapping.output.start;
+        let end = range.end.min(mapping.output.end).max(range.start) - mapping.output.start;
+        let data = mapping.original.data.get(start..end)?;
+
+        Some(Span::from_components(mapping.original.location.advanced_over(&mapping.original.data[..start]), data))
+    }
+
+    /// Translate a span of a walker over the generated text, for use with `ErrorRender::with_source_map`
+    pub fn original_span(&self, span: &Span) -> Option<Span<'filedata>> {
+        let range = LineIndex::from_data(&self.text, span.location.filename).compact(span)?.byte_range();
+        self.original_range(range)
+    }
+
+    /// Translate a location in the generated text, such as the line and column of an error reported by a downstream tool, to the span
+    /// of the source it came from
+    pub fn original_location(&self, location: &Location) -> Option<Span<'filedata>> {
+        let offset = LineIndex::from_data(&self.text, location.filename).pos_at(location)?.offset();
+        self.original_range(offset..offset)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::driver::codegen::{CodeBuilder, Piece};
+    use crate::{ErrorDisplaySettings, ErrorLevel, ErrorRender, FileWalker, Location, Note, Span, tag, take_while};
+
+    #[test]
+    fn generate_with_mappings() {
+        let mut walker = FileWalker::from_data("let x = 1 + y;\nprint x", "input.lang");
+        tag("let ")(&mut walker).unwrap();
+        let name = take_while(|c| c.is_ascii_alphabetic(), "a name")(&mut walker).unwrap();
+        tag(" = ")(&mut walker).unwrap();
+        let value = take_while(|c| c != ';', "an expression")(&mut walker).unwrap();
+        tag(";\n")(&mut walker).unwrap();
+        let print = tag("print")(&mut walker).unwrap();
+
+        let mut statement = CodeBuilder::new();
+        statement.push_template("var {} = ({});\n", &[name.into(), value.into()]);
+
+        let mut code = CodeBuilder::new();
+        code.push("// generated\n").append(statement);
+        code.push(Piece::Translated("console.log", print)).push_template("({{ {} }});\n", &["x".into()]);
+
+        let code = code.finish();
+        assert_eq!(code.text, "// generated\nvar x = (1 + y);\nconsole.log({ x });\n");
+        assert_eq!(code.mappings.iter().map(|mapping| &code.text[mapping.output.clone()]).collect::<Vec<_>>(), ["x", "1 + y", "console.log"]);
+
+        // Positions in copies of the source map exactly, generated text maps to its whole span, and added text maps to nothing
+        let y = code.original_location(&Location::from_components(13, 1, "out.js")).unwrap();
+        assert_eq!((y.location, y.data), (Location::from_components(12, 0, "input.lang"), ""));
+        assert_eq!(code.original_range(26..28).unwrap().data, "y");
+        assert_eq!(code.original_range(40..41), Some(print));
+        assert_eq!(code.original_range(0..5), None);
+        assert_eq!(code.original_location(&Location::from_components(0, 9, "out.js")), None);
+    }
+
+    #[test]
+    fn downstream_error_source_map() {
+        let data = "let x = 1 + y;";
+        let mut walker = FileWalker::from_data(data, "input.lang");
+        tag("let x = ")(&mut walker).unwrap();
+        let value = take_while(|c| c != ';', "an expression")(&mut walker).unwrap();
+
+        let mut code = CodeBuilder::new();
+        code.push_template("var x = ({});", &[value.into()]);
+        let code = code.finish();
+
+        // A downstream tool reports an unknown variable in the generated code, which is shown in the original source
+        let generated = FileWalker::from_data(&code.text, "out.js");
+        let y = Span::from_components(Location::from_components(13, 0, "out.js"), &code.text[13..14]);
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, "y is not defined", &y.location, vec![
+            Note::new(&y, "not defined", ErrorLevel::Error)
+        ], &generated).with_source_map(&walker, |span| code.original_span(span));
+
+        crate::assert_text_eq!(render.to_string(), "\
+error: y is not defined
+   --> column 13 line 1 in input.lang
+  1 |let x = 1 + y;
+    |            ^ not defined
+");
+    }
+
+    #[test]
+    #[should_panic(expected = "the template has more `{}`s than pieces")]
+    fn template_missing_piece() {
+        CodeBuilder::new().push_template("{} + {}", &["1".into()]);
+    }
+}
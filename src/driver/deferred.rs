@@ -0,0 +1,148 @@
+use std::cell::OnceCell;
+
+use crate::{ErrorKind, FileWalker, ParsingError, Span, tag, take_until_balanced};
+
+/// The body of an item which was skipped over while skimming a file, so it can be parsed once the declarations are known
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeferredBody<'filedata> {
+    /// The text between the delimiters of the body
+    pub span: Span<'filedata>,
+    pub close: Span<'filedata>
+}
+
+/// A deferred body which is parsed the first time it is needed, keeping the result for later uses
+#[derive(Debug, Clone)]
+pub struct LazyBody<'filedata, T> {
+    pub body: DeferredBody<'filedata>,
+    parsed: OnceCell<Result<T, ParsingError<'filedata>>>
+}
+
+impl<'filedata> DeferredBody<'filedata> {
+    /// Parse the whole of the body, errors refer to the locations in the original file
+    pub fn parse<T>(
+        &self,
+        parser: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>
+    ) -> Result<T, ParsingError<'filedata>> {
+        let mut walker = FileWalker::from_span(&self.span);
+        let value = parser(&mut walker)?;
+
+        match walker.current_string() {
+            "" => Ok(value),
+            rest => Err(ParsingError(walker.current_location(), ErrorKind::ExpectedEndOfInput(rest)))
+        }
+    }
+}
+
+impl<'filedata, T> LazyBody<'filedata, T> {
+    /// Construct a body which has not been parsed yet
+    pub fn new(body: DeferredBody<'filedata>) -> Self {
+        Self { body, parsed: OnceCell::new() }
+    }
+
+    /// Get the parsed body, parsing it if it has not been parsed yet
+    pub fn get_or_parse(
+        &self,
+        parser: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>
+    ) -> Result<&T, &ParsingError<'filedata>> {
+        self.parsed.get_or_init(|| self.body.parse(parser)).as_ref()
+    }
+
+    /// Check if the body has been parsed
+    pub fn is_parsed(&self) -> bool {
+        self.parsed.get().is_some()
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts a body between balanced delimiters without parsing it, for skimming the declarations of a file before parsing their bodies
+///
+/// Panics if either delimiter is empty, as `take_until_balanced` does
+pub fn deferred<'filedata>(
+    open: &'static str, close: &'static str, escape: Option<char>
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<DeferredBody<'filedata>, ParsingError<'filedata>> {
+    let body = take_until_balanced(open, close, escape);
+
+    move |walker: &mut FileWalker<'filedata>| {
+        let start = walker.get_marker();
+        tag(open)(walker)?;
+
+        match body(walker) {
+            Ok((span, close)) => Ok(DeferredBody { span, close }),
+            Err(e) => {
+                walker.pop_back(start);
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::driver::deferred::{LazyBody, deferred};
+    use crate::{ErrorKind, FileWalker, Location, ParsingError, Span, TrailingPolicy, list, opt, pair, tag, take_while};
+
+    fn name<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
+        take_while(|c: char| c.is_alphabetic(), "name")(walker)
+    }
+
+    fn whitespace<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
+        take_while(char::is_whitespace, "whitespace")(walker)
+    }
+
+    #[test]
+    fn skim_then_parse_bodies() {
+        let data = "fn first {\n  second(); second();\n}\nfn second {\n  first(;\n}";
+        let mut walker = FileWalker::from_data(data, "input");
+
+        // Skim the declarations, skipping over the bodies
+        let header = pair(tag("fn "), pair(name, pair(tag(" "), deferred("{", "}", None))));
+        let items = list(header, whitespace, TrailingPolicy::Forbidden)(&mut walker).unwrap();
+        assert_eq!(walker.current_string(), "");
+
+        let items: Vec<_> = items.items.into_iter().map(|(_, (name, (_, body)))| (name.data, LazyBody::new(body))).collect();
+        assert_eq!(items.iter().map(|(name, _)| *name).collect::<Vec<_>>(), vec!["first", "second"]);
+
+        // Bodies are parsed on demand, knowing every declared name
+        let calls = |walker: &mut FileWalker<'static>| {
+            let mut calls = Vec::new();
+            loop {
+                opt(whitespace)(walker)?;
+                if walker.current_string().is_empty() {
+                    return Ok(calls);
+                }
+
+                let callee = name(walker)?;
+                if !items.iter().any(|(name, _)| *name == callee.data) {
+                    return Err(ParsingError(callee.location, ErrorKind::ExpectedKind("a declared function")));
+                }
+                tag("();")(walker)?;
+                calls.push(callee.data);
+            }
+        };
+
+        let (_, first) = &items[0];
+        assert!(!first.is_parsed());
+        assert_eq!(first.get_or_parse(calls), Ok(&vec!["second", "second"]));
+        assert!(first.is_parsed());
+
+        // Errors point into the original file
+        let (_, second) = &items[1];
+        assert_eq!(second.get_or_parse(calls).unwrap_err().0, Location::from_components(8, 4, "input"));
+    }
+
+    #[test]
+    fn deferred_unterminated() {
+        let mut walker = FileWalker::from_data("{ { }", "input");
+        assert_eq!(
+            deferred("{", "}", None)(&mut walker),
+            Err(ParsingError(Location::from_components(1, 0, "input"), ErrorKind::Unterminated("}"))));
+        assert_eq!(walker.current_string(), "{ { }");
+    }
+
+    #[test]
+    #[should_panic(expected = "balanced delimiters cannot be empty")]
+    fn deferred_empty_delimiter() {
+        let _ = deferred::<'static>("{", "", None);
+    }
+}
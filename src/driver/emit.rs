@@ -0,0 +1,281 @@
+use std::io::{IsTerminal, Write};
+
+use crate::json::ToJson;
+use crate::{Diagnostic, ErrorDisplaySettings, ErrorLevel, FileWalker};
+
+/// How diagnostics are written, chosen from the environment and command line like rustc and clang
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RenderTarget {
+    /// Rendered snippets with terminal colors
+    Colored,
+    /// Rendered snippets without colors, for output which is piped or redirected
+    Plain,
+    /// One canonical JSON object per line, for tools
+    Json,
+    /// Workflow commands which GitHub Actions shows as annotations on the lines of a pull request
+    GitHub
+}
+
+/// The reasons a `--color` choice could not be used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownChoice(pub String);
+
+impl RenderTarget {
+    /// Choose colored output if stderr is a terminal and plain output otherwise, unless overridden by `NO_COLOR`, `CLICOLOR_FORCE` or `TERM=dumb`
+    pub fn auto() -> Self {
+        Self::auto_for(&std::io::stderr())
+    }
+
+    /// Choose a target as `auto` does, for diagnostics written to another stream such as stdout
+    pub fn auto_for(stream: &impl IsTerminal) -> Self {
+        Self::detect(|name| std::env::var(name).ok(), stream.is_terminal())
+    }
+
+    /// Choose a target from the environment variables and whether the output is a terminal
+    ///
+    /// A non-empty `NO_COLOR` disables colors, then a `CLICOLOR_FORCE` other than `0` enables them even when the output is not a terminal
+    pub fn detect(env: impl Fn(&str) -> Option<String>, is_terminal: bool) -> Self {
+        let set = |name: &str| env(name).filter(|value| !value.is_empty());
+
+        let forced = set("CLICOLOR_FORCE").is_some_and(|value| value != "0");
+        let terminal = is_terminal && env("TERM").as_deref() != Some("dumb");
+
+        if set("NO_COLOR").is_none() && (forced || terminal) {
+            RenderTarget::Colored
+        }
+        else {
+            RenderTarget::Plain
+        }
+    }
+
+    /// Choose a target from the value of a `--color` option, one of `auto`, `always`, `never`, `json` or `github`, for diagnostics written to stderr
+    pub fn from_choice(choice: &str) -> Result<Self, UnknownChoice> {
+        Self::from_choice_for(choice, &std::io::stderr())
+    }
+
+    /// Choose a target from the value of a `--color` option, where `auto` checks the stream the diagnostics are written to
+    pub fn from_choice_for(choice: &str, stream: &impl IsTerminal) -> Result<Self, UnknownChoice> {
+        match choice {
+            "auto" => Ok(Self::auto_for(stream)),
+            "always" => Ok(RenderTarget::Colored),
+            "never" => Ok(RenderTarget::Plain),
+            "json" => Ok(RenderTarget::Json),
+            "github" => Ok(RenderTarget::GitHub),
+            _ => Err(UnknownChoice(choice.to_string()))
+        }
+    }
+}
+
+/// Writes diagnostics in the form chosen by a `RenderTarget`, counting them by level
+#[derive(Debug)]
+pub struct DiagnosticEmitter<W: Write> {
+    target: RenderTarget,
+    settings: ErrorDisplaySettings,
+    out: W,
+    errors: usize,
+    warnings: usize
+}
+
+impl DiagnosticEmitter<std::io::Stderr> {
+    /// Construct an emitter writing to stderr, with the target detected by `RenderTarget::auto`
+    pub fn stderr() -> Self {
+        Self::new(RenderTarget::auto(), std::io::stderr())
+    }
+}
+
+impl<W: Write> DiagnosticEmitter<W> {
+    /// Construct an emitter writing to any output with the default display settings
+    pub fn new(target: RenderTarget, out: W) -> Self {
+        Self::with_settings(target, ErrorDisplaySettings::default(), out)
+    }
+
+    /// Construct an emitter with display settings, whose colors are replaced by the target's
+    pub fn with_settings(target: RenderTarget, settings: ErrorDisplaySettings, out: W) -> Self {
+        let settings = ErrorDisplaySettings { colored: target == RenderTarget::Colored, ..settings };
+
+        Self { target, settings, out, errors: 0, warnings: 0 }
+    }
+
+    /// Get the target diagnostics are written for
+    pub fn target(&self) -> RenderTarget {
+        self.target
+    }
+
+    /// Write a diagnostic, using a walker over the file it refers to for its snippet
+    pub fn emit(&mut self, diagnostic: &Diagnostic, walker: &FileWalker) -> std::io::Result<()> {
+        match diagnostic.level {
+            ErrorLevel::Error => self.errors += 1,
+            ErrorLevel::Warning => self.warnings += 1,
+            ErrorLevel::Info => {}
+        }
+
+        match self.target {
+            RenderTarget::Colored | RenderTarget::Plain => write!(self.out, "{}", diagnostic.render(&self.settings, walker)),
+            RenderTarget::Json => writeln!(self.out, "{}", diagnostic.to_json().to_compact()),
+            RenderTarget::GitHub => writeln!(self.out, "{}", github_annotation(diagnostic))
+        }
+    }
+
+    /// Get the number of errors which have been written
+    pub fn error_count(&self) -> usize {
+        self.errors
+    }
+
+    /// Get the number of warnings which have been written
+    pub fn warning_count(&self) -> usize {
+        self.warnings
+    }
+
+    /// Stop emitting, returning the output
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Write a diagnostic as a GitHub Actions workflow command, such as `::error file=src/main.rs,line=2,col=5::expected `;``
+///
+/// Lines and columns count from 1. The end of the annotation is taken from a note starting at the diagnostic's location, and the
+/// other notes are added to the message on lines of their own. The diagnostic's code is used as the title of the annotation
+pub fn github_annotation(diagnostic: &Diagnostic) -> String {
+    let command = match diagnostic.level {
+        ErrorLevel::Error => "error",
+        ErrorLevel::Warning => "warning",
+        ErrorLevel::Info => "notice",
+    };
+
+    let location = diagnostic.location;
+    let mut properties = vec![
+        ("file", location.filename.to_string()),
+        ("line", (location.line + 1).to_string()),
+        ("col", (location.column + 1).to_string()),
+    ];
+
+    let primary = diagnostic.notes.iter().find(|note| note.span.location == location && !note.synthetic);
+    if let Some(note) = primary.filter(|note| !note.span.data.is_empty()) {
+        let last = note.span.data.chars().last().unwrap();
+        let end = location.advanced_over(&note.span.data[..note.span.data.len() - last.len_utf8()]);
+        properties.push(("endLine", (end.line + 1).to_string()));
+        properties.push(("endColumn", (end.column + 1).to_string()));
+    }
+
+    if let Some(code) = diagnostic.code {
+        properties.push(("title", code.to_string()));
+    }
+
+    let mut message = diagnostic.message.clone();
+    for note in &diagnostic.notes {
+        message += &format!("\n{}:{}: {}", note.span.location.line + 1, note.span.location.column + 1, note.message);
+    }
+
+    let properties: Vec<String> = properties.into_iter().map(|(name, value)| format!("{}={}", name, escape_property(&value))).collect();
+    format!("::{} {}::{}", command, properties.join(","), escape_data(&message))
+}
+
+/// Escape the message of a workflow command, which ends at the end of the line
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Escape the value of a property of a workflow command, which also ends at a comma or colon
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+impl std::fmt::Display for UnknownChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown color choice `{}`, expected one of `auto`, `always`, `never`, `json` or `github`", self.0)
+    }
+}
+
+impl std::error::Error for UnknownChoice {}
+
+#[cfg(test)]
+mod test {
+    use crate::driver::emit::{DiagnosticEmitter, RenderTarget, UnknownChoice};
+    use crate::{Diagnostic, ErrorLevel, FileWalker, tag};
+
+    #[test]
+    fn render_target_detect() {
+        let env = |vars: &'static [(&'static str, &'static str)]| move |name: &str| vars.iter()
+            .find(|(var, _)| *var == name)
+            .map(|(_, value)| value.to_string());
+
+        assert_eq!(RenderTarget::detect(env(&[]), true), RenderTarget::Colored);
+        assert_eq!(RenderTarget::detect(env(&[]), false), RenderTarget::Plain);
+        assert_eq!(RenderTarget::detect(env(&[("NO_COLOR", "1")]), true), RenderTarget::Plain);
+        assert_eq!(RenderTarget::detect(env(&[("NO_COLOR", "")]), true), RenderTarget::Colored);
+        assert_eq!(RenderTarget::detect(env(&[("CLICOLOR_FORCE", "1")]), false), RenderTarget::Colored);
+        assert_eq!(RenderTarget::detect(env(&[("CLICOLOR_FORCE", "0")]), false), RenderTarget::Plain);
+        assert_eq!(RenderTarget::detect(env(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")]), false), RenderTarget::Plain);
+        assert_eq!(RenderTarget::detect(env(&[("TERM", "dumb")]), true), RenderTarget::Plain);
+
+        assert_eq!(RenderTarget::from_choice("json"), Ok(RenderTarget::Json));
+        assert_eq!(RenderTarget::from_choice("never"), Ok(RenderTarget::Plain));
+        assert_eq!(RenderTarget::from_choice("sometimes"), Err(UnknownChoice("sometimes".to_string())));
+
+        // `auto` checks the stream it is given, which is never a terminal for a file
+        let path = std::env::temp_dir().join(format!("compiler-utils-emit-{}", std::process::id()));
+        let file = std::fs::File::create(&path).unwrap();
+        let not_terminal = RenderTarget::detect(|name| std::env::var(name).ok(), false);
+        assert_eq!(RenderTarget::from_choice_for("auto", &file), Ok(not_terminal));
+        assert_eq!(RenderTarget::auto_for(&file), not_terminal);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn emitter_targets() {
+        let mut walker = FileWalker::from_data("let x = 1;", "input");
+        tag("let ")(&mut walker).unwrap();
+        let x = tag("x")(&mut walker).unwrap();
+
+        let diagnostic = Diagnostic::new(ErrorLevel::Warning, "unused variable", x.location).with_note(&x, "never read", ErrorLevel::Warning);
+
+        let mut emitter = DiagnosticEmitter::new(RenderTarget::Plain, Vec::new());
+        emitter.emit(&diagnostic, &walker).unwrap();
+        assert_eq!(emitter.warning_count(), 1);
+        assert_eq!(emitter.error_count(), 0);
+        assert_eq!(String::from_utf8(emitter.into_inner()).unwrap(), "\
+warning: unused variable
+   --> column 5 line 1 in input
+  1 |let x = 1;
+    |    ^ never read
+");
+
+        let mut emitter = DiagnosticEmitter::new(RenderTarget::Json, Vec::new());
+        emitter.emit(&diagnostic, &walker).unwrap();
+        emitter.emit(&Diagnostic::new(ErrorLevel::Error, "expected `;`", x.location), &walker).unwrap();
+        assert_eq!(emitter.error_count(), 1);
+
+        let output = String::from_utf8(emitter.into_inner()).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(r#"{"code":null,"level":"warning","location":{"column":4,"#));
+        assert!(lines[1].starts_with(r#"{"code":null,"level":"error","#));
+
+        let emitter = DiagnosticEmitter::new(RenderTarget::Colored, Vec::new());
+        assert!(emitter.settings.colored);
+    }
+
+    #[test]
+    fn github_annotations() {
+        let mut walker = FileWalker::from_data("let x = 1;\nlet möbius = x", "src/main, 2.rs");
+        tag("let x = 1;\nlet ")(&mut walker).unwrap();
+        let name = tag("möbius")(&mut walker).unwrap();
+        tag(" = ")(&mut walker).unwrap();
+        let x = tag("x")(&mut walker).unwrap();
+
+        let diagnostic = Diagnostic::new(ErrorLevel::Warning, "unused variable: 50% of variables", name.location)
+            .with_note(&name, "never read", ErrorLevel::Warning)
+            .with_note(&x, "the value comes from here", ErrorLevel::Info);
+
+        let mut emitter = DiagnosticEmitter::new(RenderTarget::GitHub, Vec::new());
+        emitter.emit(&diagnostic, &walker).unwrap();
+        emitter.emit(&Diagnostic::new(ErrorLevel::Info, "done", x.location).with_code("I0001"), &walker).unwrap();
+
+        crate::assert_text_eq!(String::from_utf8(emitter.into_inner()).unwrap(), "\
+::warning file=src/main%2C 2.rs,line=2,col=5,endLine=2,endColumn=10::unused variable: 50%25 of variables%0A2:5: never read%0A2:14: the value comes from here
+::notice file=src/main%2C 2.rs,line=2,col=14,title=I0001::done
+");
+        assert_eq!(RenderTarget::from_choice("github"), Ok(RenderTarget::GitHub));
+    }
+}
@@ -0,0 +1,191 @@
+use std::ops::Range;
+
+use crate::{Applicability, Diagnostic, FileWalker, LineIndex, Span};
+
+/// What `apply_fixes` did with a suggestion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FixStatus {
+    /// The replacement was made, or an identical one was already made for another diagnostic
+    Applied,
+    /// The suggestion was skipped because it is not `Applicability::MachineApplicable`
+    NotMachineApplicable,
+    /// The suggestion was skipped because it overlaps a fix which was already applied
+    Conflicting,
+    /// The suggestion was skipped because its span is not in the file being fixed
+    OutsideFile
+}
+
+/// A suggestion considered by `apply_fixes`, and what was done with it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixReport<'filedata> {
+    pub span: Span<'filedata>,
+    pub message: String,
+    pub replacement: String,
+    pub status: FixStatus
+}
+
+/// The fixed text of a file, along with a report of every suggestion in the order they were given
+///
+/// Displaying the outcome gives a summary of the changes, one line per suggestion
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixOutcome<'filedata> {
+    pub text: String,
+    pub fixes: Vec<FixReport<'filedata>>
+}
+
+impl<'filedata> FixOutcome<'filedata> {
+    /// Get the suggestions which were applied
+    pub fn applied(&self) -> impl Iterator<Item = &FixReport<'filedata>> {
+        self.fixes.iter().filter(|fix| fix.status == FixStatus::Applied)
+    }
+
+    /// Get the suggestions which were skipped
+    pub fn skipped(&self) -> impl Iterator<Item = &FixReport<'filedata>> {
+        self.fixes.iter().filter(|fix| fix.status != FixStatus::Applied)
+    }
+}
+
+/// Apply the machine applicable suggestions of the diagnostics to the walker's file, like `cargo fix`
+///
+/// Suggestions are considered in order, so when two overlap the first is applied and the second is reported as conflicting.
+/// Insertions at the same position conflict unless they insert the same text, since their order would be ambiguous
+pub fn apply_fixes<'filedata>(walker: &FileWalker<'filedata>, diagnostics: &[Diagnostic<'filedata>]) -> FixOutcome<'filedata> {
+    let index = LineIndex::from_walker(walker);
+    let mut accepted: Vec<(Range<usize>, usize)> = Vec::new();
+    let mut fixes: Vec<FixReport> = Vec::new();
+
+    for suggestion in diagnostics.iter().flat_map(|diagnostic| &diagnostic.suggestions) {
+        let range = index.compact_or_place(&suggestion.span).map(|span| span.byte_range());
+
+        let status = match range {
+            None => FixStatus::OutsideFile,
+            Some(_) if suggestion.applicability != Applicability::MachineApplicable => FixStatus::NotMachineApplicable,
+            Some(range) => {
+                let same = |(other, fix): &(Range<usize>, usize)| *other == range && fixes[*fix].replacement == suggestion.replacement;
+                let overlaps = |(other, _): &(Range<usize>, usize)| *other == range || (range.start < other.end && other.start < range.end);
+
+                if accepted.iter().any(same) {
+                    FixStatus::Applied
+                }
+                else if accepted.iter().any(overlaps) {
+                    FixStatus::Conflicting
+                }
+                else {
+                    accepted.push((range, fixes.len()));
+                    FixStatus::Applied
+                }
+            }
+        };
+
+        fixes.push(FixReport {
+            span: suggestion.span,
+            message: suggestion.message.clone(),
+            replacement: suggestion.replacement.clone(),
+            status
+        });
+    }
+
+    // Insertions sort before replacements starting at the same position, which they do not overlap
+    accepted.sort_by_key(|(range, _)| (range.start, range.end));
+
+    let data = walker.all_data();
+    let mut text = String::with_capacity(data.len());
+    let mut copied = 0;
+    for (range, fix) in accepted {
+        text.push_str(&data[copied..range.start]);
+        text.push_str(&fixes[fix].replacement);
+        copied = range.end;
+    }
+    text.push_str(&data[copied..]);
+
+    FixOutcome { text, fixes }
+}
+
+impl std::fmt::Display for FixStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FixStatus::Applied => write!(f, "applied"),
+            FixStatus::NotMachineApplicable => write!(f, "skipped, not machine applicable"),
+            FixStatus::Conflicting => write!(f, "skipped, overlaps an applied fix"),
+            FixStatus::OutsideFile => write!(f, "skipped, not in this file"),
+        }
+    }
+}
+
+impl<'filedata> std::fmt::Display for FixOutcome<'filedata> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "applied {} of {} suggested fixes", self.applied().count(), self.fixes.len())?;
+
+        for fix in &self.fixes {
+            writeln!(f, "  {}: {} ({})", fix.span.location, fix.message, fix.status)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::driver::fix::{FixStatus, apply_fixes};
+    use crate::{Applicability, Diagnostic, ErrorLevel, FileWalker, Span, tag};
+
+    #[test]
+    fn apply_machine_applicable_fixes() {
+        let mut walker = FileWalker::from_data("fun main() { retrun 1 }", "input");
+        let fun = tag("fun")(&mut walker).unwrap();
+        tag(" main() { ")(&mut walker).unwrap();
+        let retrun = tag("retrun")(&mut walker).unwrap();
+        let end = Span::from_components(walker.current_location(), "");
+        tag(" 1")(&mut walker).unwrap();
+        let one = Span::from_components(walker.current_location(), "");
+
+        let diagnostics = [
+            Diagnostic::new(ErrorLevel::Error, "unknown keyword `fun`", fun.location)
+                .with_suggestion(&fun, "did you mean `fn`?", "fn", Applicability::MachineApplicable),
+            Diagnostic::new(ErrorLevel::Error, "unknown keyword `retrun`", retrun.location)
+                .with_suggestion(&retrun, "did you mean `return`?", "return", Applicability::MachineApplicable)
+                .with_suggestion(&retrun, "remove the statement", "", Applicability::MaybeIncorrect),
+            Diagnostic::new(ErrorLevel::Error, "expected `;`", one.location)
+                .with_suggestion(&one, "add a semicolon", ";", Applicability::MachineApplicable),
+            // Repeated fixes are only made once, but other insertions at the same position conflict
+            Diagnostic::new(ErrorLevel::Error, "expected `;`", one.location)
+                .with_suggestion(&one, "add a semicolon", ";", Applicability::MachineApplicable)
+                .with_suggestion(&one, "add a comma", ",", Applicability::MachineApplicable),
+            Diagnostic::new(ErrorLevel::Warning, "misspelled keyword", retrun.location)
+                .with_suggestion(&retrun, "did you mean `returns`?", "returns", Applicability::MachineApplicable)
+                .with_suggestion(&end, "insert a space", " ", Applicability::MachineApplicable),
+        ];
+
+        let outcome = apply_fixes(&walker, &diagnostics);
+        assert_eq!(outcome.text, "fn main() { return  1; }");
+        assert_eq!(outcome.applied().count(), 5);
+        assert_eq!(outcome.skipped().map(|fix| fix.status).collect::<Vec<_>>(),
+            [FixStatus::NotMachineApplicable, FixStatus::Conflicting, FixStatus::Conflicting]);
+
+        crate::assert_text_eq!(outcome.to_string(), "\
+applied 5 of 8 suggested fixes
+  column 1 line 1 in input: did you mean `fn`? (applied)
+  column 14 line 1 in input: did you mean `return`? (applied)
+  column 14 line 1 in input: remove the statement (skipped, not machine applicable)
+  column 22 line 1 in input: add a semicolon (applied)
+  column 22 line 1 in input: add a semicolon (applied)
+  column 22 line 1 in input: add a comma (skipped, overlaps an applied fix)
+  column 14 line 1 in input: did you mean `returns`? (skipped, overlaps an applied fix)
+  column 20 line 1 in input: insert a space (applied)
+");
+    }
+
+    #[test]
+    fn apply_fixes_outside_file() {
+        let walker = FileWalker::from_data("x", "input");
+        let other = FileWalker::from_data("y", "other");
+        let y = Span::from_components(other.current_location(), other.all_data());
+
+        let diagnostics = [Diagnostic::new(ErrorLevel::Error, "unknown name", y.location)
+            .with_suggestion(&y, "rename it", "z", Applicability::MachineApplicable)];
+
+        let outcome = apply_fixes(&walker, &diagnostics);
+        assert_eq!(outcome.text, "x");
+        assert_eq!(outcome.fixes[0].status, FixStatus::OutsideFile);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{Diagnostic, ErrorLevel, FileWalker, Span};
+
+/// Lints for the layout of a source file rather than its meaning, each of which can be enabled separately
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HygieneLints {
+    /// Warn about indentation mixing tabs and spaces, within a line or between lines of the file
+    pub mixed_indentation: bool,
+    /// Warn about whitespace at the end of a line
+    pub trailing_whitespace: bool,
+    /// Warn about lines with more characters than this
+    pub max_line_length: Option<usize>
+}
+
+/// The character a line is indented with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Indentation {
+    Tabs,
+    Spaces
+}
+
+impl HygieneLints {
+    /// Every lint, with lines limited to 100 characters
+    pub const ALL: HygieneLints = HygieneLints { mixed_indentation: true, trailing_whitespace: true, max_line_length: Some(100) };
+
+    /// No lints, for enabling a subset of them
+    pub const NONE: HygieneLints = HygieneLints { mixed_indentation: false, trailing_whitespace: false, max_line_length: None };
+
+    /// Check the whole of the walker's file, returning a warning for each problem found in the order they appear
+    pub fn check<'filedata>(&self, walker: &FileWalker<'filedata>) -> Vec<Diagnostic<'filedata>> {
+        let mut diagnostics = Vec::new();
+        let mut file_indentation: Option<(Indentation, Span<'filedata>)> = None;
+
+        for line in FileWalker::from_data(walker.all_data(), walker.filename()).lines_spanned() {
+            let indent = sub_span(&line, 0, line.data.len() - line.data.trim_start_matches([' ', '\t']).len());
+
+            // Lines of only whitespace are blank, so they neither set the indentation nor break it
+            if self.mixed_indentation && !indent.data.is_empty() && !line.data.trim().is_empty() {
+                let indentation = if indent.data.starts_with('\t') { Indentation::Tabs } else { Indentation::Spaces };
+                let mixed = indent.data.contains(' ') && indent.data.contains('\t');
+
+                match file_indentation {
+                    _ if mixed => diagnostics.push(
+                        Diagnostic::new(ErrorLevel::Warning, "indentation mixes tabs and spaces", indent.location)
+                            .with_note(&indent, "mixed indentation", ErrorLevel::Warning)),
+                    Some((expected, first)) if expected != indentation => diagnostics.push(
+                        Diagnostic::new(ErrorLevel::Warning, format!("line is indented with {}, but the file is indented with {}", indentation, expected), indent.location)
+                            .with_note(&indent, "indented differently", ErrorLevel::Warning)
+                            .with_note(&first, "the first indented line sets the indentation", ErrorLevel::Info)),
+                    Some(_) => {}
+                    None => file_indentation = Some((indentation, indent))
+                }
+            }
+
+            let content = line.data.trim_end();
+            if self.trailing_whitespace && content.len() != line.data.len() && !content.is_empty() {
+                let trailing = sub_span(&line, content.len(), line.data.len());
+                diagnostics.push(
+                    Diagnostic::new(ErrorLevel::Warning, "trailing whitespace", trailing.location)
+                        .with_note(&trailing, "remove this whitespace", ErrorLevel::Warning));
+            }
+
+            if let Some(max_line_length) = self.max_line_length {
+                let length = line.char_len();
+
+                if let Some((overflow, _)) = line.data.char_indices().nth(max_line_length) {
+                    let overflow = sub_span(&line, overflow, line.data.len());
+                    diagnostics.push(
+                        Diagnostic::new(ErrorLevel::Warning, format!("line is {} characters long, the limit is {}", length, max_line_length), overflow.location)
+                            .with_note(&overflow, "past the limit", ErrorLevel::Warning));
+                }
+            }
+        }
+
+        diagnostics
+    }
+}
+
+/// Get the span of a range of bytes of a line
+fn sub_span<'filedata>(line: &Span<'filedata>, start: usize, end: usize) -> Span<'filedata> {
+    Span::from_components(line.location.advanced_over(&line.data[..start]), &line.data[start..end])
+}
+
+impl std::fmt::Display for Indentation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Indentation::Tabs => write!(f, "tabs"),
+            Indentation::Spaces => write!(f, "spaces"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::driver::hygiene::HygieneLints;
+    use crate::{DiagnosticBatch, ErrorDisplaySettings, FileWalker, Location};
+
+    fn render(lints: HygieneLints, data: &str) -> String {
+        let walker = FileWalker::from_data(data, "input");
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+        let diagnostics = lints.check(&walker);
+
+        DiagnosticBatch::new(&settings, &[&walker], &diagnostics).to_string()
+    }
+
+    #[test]
+    fn hygiene_lints() {
+        let data = "fn main() {\n    let x = 1; \n\tlet y = 2;\n  \tlet z = 3;\n}\n";
+
+        crate::assert_text_eq!(render(HygieneLints::ALL, data), "\
+warning: trailing whitespace
+   --> column 15 line 2 in input
+  1 |fn main() {
+  2 |    let x = 1;\x20
+    |              ^ remove this whitespace
+  3 |\tlet y = 2;
+
+warning: line is indented with tabs, but the file is indented with spaces
+   --> column 1 line 3 in input
+  1 |fn main() {
+  2 |    let x = 1;\x20
+    |^^^^ the first indented line sets the indentation
+  3 |\tlet y = 2;
+    |^ indented differently
+  4 |  \tlet z = 3;
+
+warning: indentation mixes tabs and spaces
+   --> column 1 line 4 in input
+  3 |\tlet y = 2;
+  4 |  \tlet z = 3;
+    |^^^ mixed indentation
+  5 |}
+");
+
+        // Lints can be enabled separately
+        let trailing = HygieneLints { trailing_whitespace: true, ..HygieneLints::NONE };
+        assert_eq!(trailing.check(&FileWalker::from_data(data, "input")).len(), 1);
+        assert!(HygieneLints::NONE.check(&FileWalker::from_data(data, "input")).is_empty());
+
+        // Carriage returns are not trailing whitespace, and blank lines are left alone
+        assert!(trailing.check(&FileWalker::from_data("a\r\n   \r\nb\r\n", "input")).is_empty());
+
+        // The first indented line with something on it sets the indentation, even after blank lines indented differently
+        let indentation = HygieneLints { mixed_indentation: true, ..HygieneLints::NONE };
+        let walker = FileWalker::from_data("{\n\t\n    a\n  \t \n    b\n\tc\n}\n", "input");
+        let diagnostics = indentation.check(&walker);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "line is indented with tabs, but the file is indented with spaces");
+        assert_eq!(diagnostics[0].notes[1].span.location, Location::from_components(0, 2, "input"));
+    }
+
+    #[test]
+    fn hygiene_line_length() {
+        let lints = HygieneLints { max_line_length: Some(8), ..HygieneLints::NONE };
+
+        crate::assert_text_eq!(render(lints, "let möbius = 1;\nlet x = 1;\nshort"), "\
+warning: line is 15 characters long, the limit is 8
+   --> column 9 line 1 in input
+  1 |let möbius = 1;
+    |        ^^^^^^^ past the limit
+  2 |let x = 1;
+
+warning: line is 10 characters long, the limit is 8
+   --> column 9 line 2 in input
+  1 |let möbius = 1;
+  2 |let x = 1;
+    |        ^^ past the limit
+  3 |short
+");
+    }
+}
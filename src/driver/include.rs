@@ -0,0 +1,263 @@
+//! Splices included files into the files which include them, for languages with textual inclusion such as `#include`
+//!
+//! The spliced text is parsed as one file, and the region of it each file was copied to is kept, so spans and diagnostics found in it
+//! can be mapped back to the files in the `SourceMap` they came from
+
+use std::sync::Arc;
+
+use crate::driver::source::{FileId, SourceError, SourceMap};
+use crate::parser::owned::OwnedLocation;
+use crate::{Diagnostic, FileWalker, Location, Span};
+
+/// A region of spliced text, copied from a range of bytes of a file in the source map
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    /// The offset of the region within the spliced text
+    pub start: usize,
+    pub file: FileId,
+    pub range: std::ops::Range<usize>
+}
+
+/// The text of a file with the files it includes spliced in place of the lines which include them, recursively
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spliced {
+    name: Arc<str>,
+    text: String,
+    regions: Vec<Region>
+}
+
+/// The reasons files could not be spliced, located at the line which included the file
+#[derive(Debug)]
+pub enum IncludeError {
+    /// An included file which is not in the source map could not be loaded from disk
+    Load {
+        name: String,
+        location: OwnedLocation,
+        error: SourceError
+    },
+    /// A file which includes itself, directly or through the files it includes
+    Cycle {
+        name: String,
+        location: OwnedLocation
+    }
+}
+
+impl Spliced {
+    /// Splice the files a file includes into it, where `directive` gives the name of the file a line includes, or None for other lines
+    ///
+    /// The whole of a line which includes a file is replaced by the file's data, while the line break after it is kept. Included files
+    /// are found by name in the source map, and loaded from disk into it if they are not there yet
+    pub fn splice(sources: &mut SourceMap, root: FileId, directive: impl Fn(&str) -> Option<&str>) -> Result<Self, IncludeError> {
+        let name = sources.get(root).expect("the root file is not in the source map").name.clone();
+        let mut spliced = Self { name, text: String::new(), regions: Vec::new() };
+        spliced.splice_file(sources, root, &directive, &mut vec![root])?;
+
+        Ok(spliced)
+    }
+
+    /// Append a file's data to the text, splicing the files it includes, with the stack of files including it ending with the file
+    fn splice_file(
+        &mut self, sources: &mut SourceMap, id: FileId, directive: &impl Fn(&str) -> Option<&str>, stack: &mut Vec<FileId>
+    ) -> Result<(), IncludeError> {
+        let data = sources.get(id).unwrap().data.clone();
+        let mut copied = 0;
+        let mut line_start = 0;
+
+        for line in data.split_inclusive('\n') {
+            let content = line.strip_suffix('\n').unwrap_or(line);
+            if let Some(name) = directive(content) {
+                self.copy(id, &data, copied..line_start);
+                copied = line_start + content.len();
+
+                let file = sources.get(id).unwrap();
+                let location = file.own_location(&Span::from_byte_range(file, line_start..line_start).unwrap().location);
+                let include = match sources.find(name) {
+                    Some(include) => include,
+                    None => sources.load(name).map_err(|error| IncludeError::Load { name: name.to_string(), location: location.clone(), error })?
+                };
+
+                if stack.contains(&include) {
+                    return Err(IncludeError::Cycle { name: name.to_string(), location });
+                }
+
+                stack.push(include);
+                self.splice_file(sources, include, directive, stack)?;
+                stack.pop();
+            }
+
+            line_start += line.len();
+        }
+
+        self.copy(id, &data, copied..data.len());
+        Ok(())
+    }
+
+    /// Append a range of a file's data to the text as a region of its own
+    fn copy(&mut self, file: FileId, data: &str, range: std::ops::Range<usize>) {
+        if !range.is_empty() {
+            self.regions.push(Region { start: self.text.len(), file, range: range.clone() });
+            self.text.push_str(&data[range]);
+        }
+    }
+
+    /// Get the spliced text
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Get the regions of the text, in order, each of which was copied from one file
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    /// Construct a walker over the spliced text, named after the root file, whose spans can be mapped back with `original_span`
+    pub fn walker(&self) -> FileWalker<'_> {
+        FileWalker::from_data(&self.text, &self.name)
+    }
+
+    /// Get the span of the file a span of the spliced text was copied from, or None if the span does not refer to the spliced text
+    ///
+    /// A span which runs past the end of the region it starts in, across a file boundary, is cut short at the end of the region
+    pub fn original_span<'a>(&self, sources: &'a SourceMap, span: &Span) -> Option<Span<'a>> {
+        let start = (span.data.as_ptr() as usize).checked_sub(self.text.as_ptr() as usize)?;
+        if start + span.data.len() > self.text.len() {
+            return None;
+        }
+
+        // An empty span at the end of a region is placed at the end of that region rather than the start of the next
+        let index = self.regions.partition_point(|region| region.start < start || (region.start == start && !span.data.is_empty())).max(1) - 1;
+        let region = self.regions.get(index)?;
+        let in_region = start.checked_sub(region.start)?;
+        let end = (in_region + span.data.len()).min(region.range.len());
+
+        Span::from_byte_range(sources.get(region.file)?, region.range.start + in_region..region.range.start + end)
+    }
+
+    /// Get the location in its original file of a location in the spliced text, or None if it is not within the spliced text
+    pub fn original_location<'a>(&self, sources: &'a SourceMap, location: &Location) -> Option<Location<'a>> {
+        self.original_span(sources, &self.walker().empty_span_at(location)?).map(|span| span.location)
+    }
+
+    /// Map the location and spans of a diagnostic found in the spliced text back to the files they came from, so it can be rendered
+    /// with walkers over the files of the source map
+    ///
+    /// Anything which cannot be mapped back, such as the location of inserted text, is left where it is
+    pub fn original_diagnostic<'a>(&'a self, sources: &'a SourceMap, diagnostic: &Diagnostic<'a>) -> Diagnostic<'a> {
+        let mut original = diagnostic.clone();
+        original.location = self.original_location(sources, &diagnostic.location).unwrap_or(diagnostic.location);
+
+        for note in &mut original.notes {
+            note.span = match note.synthetic {
+                true => Span::from_components(self.original_location(sources, &note.span.location).unwrap_or(note.span.location), note.span.data),
+                false => self.original_span(sources, &note.span).unwrap_or(note.span)
+            };
+        }
+
+        for suggestion in &mut original.suggestions {
+            suggestion.span = self.original_span(sources, &suggestion.span).unwrap_or(suggestion.span);
+        }
+
+        original
+    }
+}
+
+impl std::fmt::Display for IncludeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IncludeError::Load { name, location, error } => write!(f, "could not include `{}` at {}: {}", name, location, error),
+            IncludeError::Cycle { name, location } => write!(f, "`{}` is included by itself at {}", name, location),
+        }
+    }
+}
+
+impl std::error::Error for IncludeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IncludeError::Load { error, .. } => Some(error),
+            IncludeError::Cycle { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::driver::include::{IncludeError, Spliced};
+    use crate::driver::source::SourceMap;
+    use crate::parser::owned::OwnedLocation;
+    use crate::{Diagnostic, DiagnosticBatch, ErrorDisplaySettings, ErrorLevel, Location, Span, identifier, tag};
+
+    fn include(line: &str) -> Option<&str> {
+        line.strip_prefix("#include ")
+    }
+
+    #[test]
+    fn splice_includes() {
+        let mut sources = SourceMap::new();
+        let main = sources.add_data("main.c", "#include lib.h\nint main() { return f(g); }\n");
+        sources.add_data("lib.h", "int f(int);\n#include other.h\n");
+        let other = sources.add_data("other.h", "int g;");
+
+        let spliced = Spliced::splice(&mut sources, main, include).unwrap();
+        assert_eq!(spliced.text(), "int f(int);\nint g;\n\nint main() { return f(g); }\n");
+        assert_eq!(spliced.regions().iter().map(|region| (region.start, region.file.index())).collect::<Vec<_>>(),
+            [(0, 1), (12, 2), (18, 1), (19, 0)]);
+
+        // Spans of the spliced text map back to the file they were copied from
+        let mut walker = spliced.walker();
+        tag("int f(int);\nint ")(&mut walker).unwrap();
+        let g = identifier()(&mut walker).unwrap();
+        assert_eq!(g.location, Location::from_components(4, 1, "main.c"));
+
+        let original = spliced.original_span(&sources, &g).unwrap();
+        assert_eq!((original.location, original.data), (Location::from_components(4, 0, "other.h"), "g"));
+        assert_eq!(sources.file_of(&original), Some(other));
+        assert_eq!(spliced.original_location(&sources, &Location::from_components(15, 3, "main.c")), Some(Location::from_components(15, 1, "main.c")));
+
+        let declaration = Span::from_components(Location::from_components(0, 1, "main.c"), &spliced.text()[12..18]);
+        assert_eq!(spliced.original_span(&sources, &declaration).map(|span| (span.location, span.data)),
+            Some((Location::from_components(0, 0, "other.h"), "int g;")));
+        let end_of_lib = Span::from_components(Location::from_components(0, 1, "main.c"), &spliced.text()[12..12]);
+        assert_eq!(spliced.original_span(&sources, &end_of_lib).unwrap().location, Location::from_components(0, 1, "lib.h"));
+
+        // A span across a file boundary is cut short at the end of its region
+        let across = Span::from_components(Location::from_components(4, 1, "main.c"), &spliced.text()[16..22]);
+        assert_eq!(spliced.original_span(&sources, &across).unwrap().data, "g;");
+
+        // Diagnostics are rendered in the files their spans came from
+        let diagnostic = Diagnostic::new(ErrorLevel::Error, "`g` is not a function", g.location)
+            .with_note(&g, "declared as a variable", ErrorLevel::Info);
+        let diagnostics = [spliced.original_diagnostic(&sources, &diagnostic)];
+        let settings = ErrorDisplaySettings::default().with_colored(false);
+        let walkers = sources.walkers();
+        let walkers: Vec<_> = walkers.iter().collect();
+
+        crate::assert_text_eq!(DiagnosticBatch::new(&settings, &walkers, &diagnostics).to_string(), "\
+error: `g` is not a function
+   --> column 5 line 1 in other.h
+  1 |int g;
+    |    ^ declared as a variable
+");
+    }
+
+    #[test]
+    fn splice_errors() {
+        let mut sources = SourceMap::new();
+        let a = sources.add_data("a.h", "int a;\n#include b.h\n");
+        sources.add_data("b.h", "\n  #include a.h");
+
+        // Directives can be indented, but are still located at the start of their line
+        match Spliced::splice(&mut sources, a, |line| include(line.trim_start())).unwrap_err() {
+            IncludeError::Cycle { name, location } => {
+                assert_eq!(name, "a.h");
+                assert_eq!(location, OwnedLocation::from_components(0, 1, "b.h"));
+            }
+            error => panic!("unexpected error {}", error)
+        }
+
+        let mut sources = SourceMap::new();
+        let main = sources.add_data("main.c", "#include this file does not exist.h");
+        let error = Spliced::splice(&mut sources, main, include).unwrap_err();
+        assert!(matches!(error, IncludeError::Load { .. }));
+        assert!(error.to_string().starts_with("could not include `this file does not exist.h` at column 1 line 1 in main.c: could not read the file"));
+    }
+}
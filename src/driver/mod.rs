@@ -0,0 +1,163 @@
+pub mod source;
+pub mod deferred;
+pub mod emit;
+pub mod hygiene;
+pub mod fix;
+pub mod tokens;
+pub mod codegen;
+pub mod semantic;
+pub mod include;
+
+use std::path::Path;
+
+use source::SourceFile;
+
+use crate::{Diagnostic, ErrorDisplaySettings, ErrorKind, ErrorLevel, FileWalker, HeaderDisplay, Location, ParsingError};
+
+/// The diagnostics produced when a parse fails, rendered so they outlive the file data they refer to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedDiagnostics {
+    /// The diagnostics rendered with their snippets, ready to print
+    pub rendered: String,
+    /// The message of each diagnostic
+    pub messages: Vec<String>
+}
+
+impl RenderedDiagnostics {
+    /// Render a single diagnostic using a walker over the file it refers to
+    pub fn from_diagnostic(settings: &ErrorDisplaySettings, diagnostic: &Diagnostic, walker: &FileWalker) -> Self {
+        Self {
+            rendered: diagnostic.render(settings, walker).to_string(),
+            messages: vec![diagnostic.message.clone()]
+        }
+    }
+}
+
+impl std::fmt::Display for RenderedDiagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.rendered)
+    }
+}
+
+impl std::error::Error for RenderedDiagnostics {}
+
+/// Run a parser over the whole of some data, rendering a diagnostic if it fails or does not consume all of the input
+pub fn parse_str_with<'filedata, T>(
+    settings: &ErrorDisplaySettings,
+    filename: &'filedata str,
+    data: &'filedata str,
+    parser: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>
+) -> Result<T, RenderedDiagnostics> {
+    let walker = FileWalker::from_data(data, filename);
+    let mut parse_walker = walker.clone();
+
+    let error = match parser(&mut parse_walker) {
+        Ok(value) if parse_walker.current_string().is_empty() => return Ok(value),
+        Ok(_) => ParsingError(parse_walker.current_location(), ErrorKind::ExpectedEndOfInput(parse_walker.current_string())),
+        Err(error) => error
+    };
+
+    Err(RenderedDiagnostics::from_diagnostic(settings, &Diagnostic::from_parsing_error(&error), &walker))
+}
+
+/// Run a parser over the whole of some data, rendering a diagnostic with the default settings if it fails
+pub fn parse_str<'filedata, T>(
+    filename: &'filedata str,
+    data: &'filedata str,
+    parser: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>
+) -> Result<T, RenderedDiagnostics> {
+    parse_str_with(&ErrorDisplaySettings::default(), filename, data, parser)
+}
+
+/// Load a file, converting it to UTF-8 if it is in another encoding, and run a parser over the whole of it, rendering a diagnostic if it cannot be read or parsed
+///
+/// The file is dropped once parsing is done, so the parsed value cannot borrow from it, and the parser must be a function generic over the lifetime of the file
+pub fn parse_file_with<T>(
+    settings: &ErrorDisplaySettings,
+    path: impl AsRef<Path>,
+    parser: impl for<'filedata> Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>
+) -> Result<T, RenderedDiagnostics> {
+    let filename = path.as_ref().display().to_string();
+
+    let file = match SourceFile::load(path.as_ref()) {
+        Ok(file) => file,
+        Err(e) => {
+            let message = e.to_string();
+            let location = Location::from_components(0, 0, &filename);
+
+            return Err(RenderedDiagnostics {
+                rendered: format!("{}\n", HeaderDisplay::new(ErrorLevel::Error, settings, &message, &location)),
+                messages: vec![message]
+            });
+        }
+    };
+
+    parse_str_with(settings, &filename, &file.data, parser)
+}
+
+/// Load a file and run a parser over the whole of it, rendering a diagnostic with the default settings if it cannot be read or parsed
+pub fn parse_file<T>(
+    path: impl AsRef<Path>,
+    parser: impl for<'filedata> Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>
+) -> Result<T, RenderedDiagnostics> {
+    parse_file_with(&ErrorDisplaySettings::default(), path, parser)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::driver::{parse_file_with, parse_str_with};
+    use crate::{ErrorDisplaySettings, FileWalker, ParsingError, tag};
+
+    fn greeting_length<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<usize, ParsingError<'filedata>> {
+        Ok(tag("Hello")(walker)?.data.len())
+    }
+
+    #[test]
+    fn parse_str_diagnostics() {
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+
+        assert_eq!(parse_str_with(&settings, "input", "Hello", tag("Hello")).unwrap().data, "Hello");
+
+        let error = parse_str_with(&settings, "input", "Hello!", tag("Hello")).unwrap_err();
+        assert_eq!(error.messages, vec!["expected the end of the input, found `!`".to_string()]);
+        assert_eq!(error.to_string(), "\
+error: expected the end of the input, found `!`
+   --> column 6 line 1 in input
+  1 |Hello!
+    |     ^
+");
+
+        let error = parse_str_with(&settings, "input", "Help", tag("Hello")).unwrap_err();
+        assert_eq!(error.rendered, "\
+error: expected `Hello`, but the input diverges after `Hel`
+   --> column 4 line 1 in input
+  1 |Help
+    |   ^ input diverges here
+    |^^^ matched up to here
+");
+    }
+
+    #[test]
+    fn parse_file_owned() {
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+        let path = std::env::temp_dir().join(format!("compiler-utils-parse-file-{}.txt", std::process::id()));
+
+        std::fs::write(&path, "Hello").unwrap();
+        let value = parse_file_with(&settings, &path, greeting_length);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(value, Ok(5));
+
+        let error = parse_file_with(&settings, &path, greeting_length).unwrap_err();
+        assert!(error.messages[0].starts_with("could not read the file: "));
+        assert!(error.rendered.starts_with("error: could not read the file: "));
+
+        // Files in other encodings are converted, and invalid files are reported
+        std::fs::write(&path, b"\xFF\xFEH\0e\0l\0l\0o\0").unwrap();
+        assert_eq!(parse_file_with(&settings, &path, greeting_length), Ok(5));
+
+        std::fs::write(&path, b"Hello\xFF").unwrap();
+        let error = parse_file_with(&settings, &path, greeting_length).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(error.messages, vec!["file is not valid UTF-8 at byte 5".to_string()]);
+    }
+}
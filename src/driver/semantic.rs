@@ -0,0 +1,195 @@
+//! Encodes tokens as semantic tokens for language servers, in the form of the `data` array of a `semanticTokens/full` response
+//!
+//! Each token is five integers relative to the one before it: the change in line, the start character (relative to the previous
+//! token's start if they are on the same line), the length, the type and the bit set of modifiers. Characters are counted in UTF-16
+//! code units, which is the default position encoding of the protocol
+
+use crate::json::{Json, ToJson};
+use crate::parser::lexer::TokenStream;
+use crate::Span;
+
+/// The names of the token types and modifiers a language server supports, sent to the client when it starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SemanticLegend {
+    /// Token types, such as `keyword` and `variable`, which tokens refer to by index
+    pub token_types: &'static [&'static str],
+    /// Token modifiers, such as `declaration` and `readonly`, which tokens refer to by bit
+    pub token_modifiers: &'static [&'static str]
+}
+
+/// The type and modifiers of a token, as indices into a `SemanticLegend`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SemanticToken {
+    pub token_type: u32,
+    /// A bit set, where bit `n` is the modifier at index `n` of the legend
+    pub modifiers: u32
+}
+
+impl SemanticLegend {
+    /// Get a token of the type with the given name, or None if it is not in the legend
+    pub fn token(&self, token_type: &str) -> Option<SemanticToken> {
+        let index = self.token_types.iter().position(|name| *name == token_type)?;
+        Some(SemanticToken::new(index as u32))
+    }
+
+    /// Get the bit set of the modifiers with the given names, or None if any of them are not in the legend
+    pub fn modifiers(&self, modifiers: &[&str]) -> Option<u32> {
+        modifiers.iter().try_fold(0, |bits, modifier| {
+            let index = self.token_modifiers.iter().position(|name| name == modifier)?;
+            Some(bits | 1 << index)
+        })
+    }
+}
+
+impl SemanticToken {
+    /// Construct a token of a type without any modifiers
+    pub const fn new(token_type: u32) -> Self {
+        Self { token_type, modifiers: 0 }
+    }
+
+    /// Set the bit set of modifiers
+    pub const fn with_modifiers(self, modifiers: u32) -> Self {
+        Self { modifiers, ..self }
+    }
+}
+
+/// Encode the tokens of a stream, classifying each by its kind, or leaving it out if `classify` gives None
+pub fn encode_stream<'filedata, T>(stream: &TokenStream<'filedata, T>, classify: impl Fn(&T) -> Option<SemanticToken>) -> Vec<u32> {
+    let tokens = stream.tokens().iter().filter_map(|token| Some((token.span, classify(&token.value)?)));
+    encode(stream.data(), tokens)
+}
+
+/// Encode spans of a file's data as semantic tokens, the data must be the whole file so lines and characters are counted from its start
+///
+/// Tokens are sorted by position first. A token which overlaps the one before it is left out, since the protocol does not allow
+/// overlapping tokens, and a token over several lines is split into a token for each line
+pub fn encode<'filedata>(data: &str, tokens: impl IntoIterator<Item = (Span<'filedata>, SemanticToken)>) -> Vec<u32> {
+    let mut tokens: Vec<_> = tokens.into_iter().map(|(span, token)| (span.byte_range_in(data), token)).collect();
+    tokens.sort_by_key(|(range, _)| range.start);
+
+    let mut encoded = Vec::with_capacity(tokens.len() * 5);
+    let mut cursor = Cursor::default();
+    let mut previous = (0, 0);
+
+    for (range, token) in tokens {
+        if range.start < cursor.offset {
+            continue;
+        }
+        cursor.advance_over(&data[cursor.offset..range.start]);
+
+        for (i, text) in data[range.clone()].split('\n').enumerate() {
+            let text = text.strip_suffix('\r').unwrap_or(text);
+            let length = text.encode_utf16().count() as u32;
+            let (line, start) = if i == 0 { (cursor.line, cursor.character) } else { (cursor.line + i as u32, 0) };
+
+            if length > 0 {
+                let delta_start = if line == previous.0 { start - previous.1 } else { start };
+                encoded.extend([line - previous.0, delta_start, length, token.token_type, token.modifiers]);
+                previous = (line, start);
+            }
+        }
+        cursor.advance_over(&data[range]);
+    }
+
+    encoded
+}
+
+/// A position in a file, counting characters in UTF-16 code units
+#[derive(Debug, Default)]
+struct Cursor {
+    offset: usize,
+    line: u32,
+    character: u32
+}
+
+impl Cursor {
+    /// Move past the text, which must follow the cursor
+    fn advance_over(&mut self, text: &str) {
+        self.offset += text.len();
+
+        match text.rfind('\n') {
+            Some(index) => {
+                self.line += text.matches('\n').count() as u32;
+                self.character = text[index + 1..].encode_utf16().count() as u32;
+            }
+            None => self.character += text.encode_utf16().count() as u32
+        }
+    }
+}
+
+impl ToJson for SemanticLegend {
+    fn to_json(&self) -> Json {
+        Json::object([
+            ("tokenModifiers", Json::Array(self.token_modifiers.iter().map(|name| name.to_json()).collect())),
+            ("tokenTypes", Json::Array(self.token_types.iter().map(|name| name.to_json()).collect())),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::driver::semantic::{SemanticLegend, SemanticToken, encode, encode_stream};
+    use crate::json::ToJson;
+    use crate::parser::lexer::TokenStream;
+    use crate::{FileWalker, Location, ParsingError, Span, alt, map, one_of, take_while};
+
+    const LEGEND: SemanticLegend = SemanticLegend {
+        token_types: &["keyword", "variable", "number", "string"],
+        token_modifiers: &["declaration", "readonly"]
+    };
+
+    fn lex_token<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Option<&'static str>, ParsingError<'filedata>> {
+        alt(
+            alt(
+                map(take_while(char::is_whitespace, "whitespace"), |_| None),
+                map(take_while(|c| c.is_ascii_digit(), "a number"), |_| Some("number"))
+            ),
+            alt(
+                map(take_while(|c| c.is_alphabetic(), "a name"), |name| Some(if name.data == "let" { "keyword" } else { "variable" })),
+                map(one_of("=;"), |_| Some("punctuation"))
+            )
+        )(walker)
+    }
+
+    #[test]
+    fn encode_token_stream() {
+        let mut walker = FileWalker::from_data("let x = 1;\n  let yö = x;", "input");
+        let stream = TokenStream::lex(&mut walker, lex_token).unwrap();
+
+        // Punctuation is not in the legend, so it is left out
+        let encoded = encode_stream(&stream, |kind| LEGEND.token(kind));
+        assert_eq!(encoded, [
+            0, 0, 3, 0, 0,
+            0, 4, 1, 1, 0,
+            0, 4, 1, 2, 0,
+            1, 2, 3, 0, 0,
+            0, 4, 2, 1, 0,
+            0, 5, 1, 1, 0,
+        ]);
+
+        assert_eq!(LEGEND.modifiers(&["readonly", "declaration"]), Some(3));
+        assert_eq!(LEGEND.modifiers(&["static"]), None);
+        assert_eq!(LEGEND.to_json().to_compact(), r#"{"tokenModifiers":["declaration","readonly"],"tokenTypes":["keyword","variable","number","string"]}"#);
+    }
+
+    #[test]
+    fn encode_utf16_and_multi_line() {
+        // Characters outside the basic multilingual plane are two UTF-16 code units
+        let data = "s = \"😀\r\nab\"; t";
+        let string = Span::from_components(Location::from_components(4, 0, "input"), &data[4..14]);
+        let t = Span::from_components(Location::from_components(5, 1, "input"), &data[16..17]);
+        let s = Span::from_components(Location::from_components(0, 0, "input"), &data[0..1]);
+        let overlapping = Span::from_components(Location::from_components(4, 0, "input"), &data[4..5]);
+
+        let variable = SemanticToken::new(1).with_modifiers(LEGEND.modifiers(&["declaration"]).unwrap());
+        let encoded = encode(data, [(t, variable), (string, SemanticToken::new(3)), (s, variable), (overlapping, SemanticToken::new(3))]);
+
+        // A token over several lines is split at the line break, which is not part of either token
+        assert_eq!(encoded, [
+            0, 0, 1, 1, 1,
+            0, 4, 3, 3, 0,
+            1, 0, 3, 3, 0,
+            0, 5, 1, 1, 1,
+        ]);
+    }
+}
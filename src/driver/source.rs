@@ -0,0 +1,383 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::parser::owned::{OwnedLocation, OwnedSpan};
+use crate::{FileWalker, Location, Span};
+
+/// The encodings a source file can be read from, the data is always converted to UTF-8
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be
+}
+
+/// How the encoding of a source file was determined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Detection {
+    /// There was no byte order mark, and the file did not look like UTF-16, so it was assumed to be UTF-8
+    Assumed,
+    /// The file started with a byte order mark, which was removed from the data
+    ByteOrderMark,
+    /// There was no byte order mark, but the pattern of zero bytes suggested UTF-16
+    Guessed
+}
+
+/// The encoding a source file was read from, and how it was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EncodingReport {
+    pub encoding: Encoding,
+    pub detection: Detection
+}
+
+/// Data which is not valid in the encoding it was read as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EncodingError {
+    pub encoding: Encoding,
+    /// The offset of the first invalid byte of the file, including any byte order mark
+    pub offset: usize
+}
+
+/// The reasons a source file could not be loaded
+#[derive(Debug)]
+pub enum SourceError {
+    Io(std::io::Error),
+    Encoding(EncodingError)
+}
+
+/// The data of a file converted to UTF-8, with a report of the encoding it was read from
+///
+/// The name and data are shared, so cloning the file is cheap, and spans owned through it keep its data alive without copying it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    pub name: Arc<str>,
+    pub data: Arc<str>,
+    pub encoding: EncodingReport
+}
+
+/// Identifies a file within the `SourceMap` it was added to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(u32);
+
+/// The source files of a program, such as a file and those it includes, which spans and locations can be resolved to
+///
+/// Files cannot be added while a walker or span borrows the map, so every file should be loaded before parsing starts
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    files: Vec<SourceFile>
+}
+
+impl EncodingReport {
+    /// Check if the data had to be converted from another encoding
+    pub fn converted(&self) -> bool {
+        self.encoding != Encoding::Utf8
+    }
+}
+
+impl SourceFile {
+    /// Read a file from disk, detecting its encoding
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SourceError> {
+        let bytes = std::fs::read(path.as_ref()).map_err(SourceError::Io)?;
+        Self::from_bytes(path.as_ref().display().to_string(), &bytes).map_err(SourceError::Encoding)
+    }
+
+    /// Construct a file whose data is already UTF-8, such as one generated or embedded in the program
+    pub fn from_data(name: impl Into<Arc<str>>, data: impl Into<Arc<str>>) -> Self {
+        let encoding = EncodingReport { encoding: Encoding::Utf8, detection: Detection::Assumed };
+        Self { name: name.into(), data: data.into(), encoding }
+    }
+
+    /// Decode the data of a file, detecting its encoding from a byte order mark or the bytes themselves
+    pub fn from_bytes(name: impl Into<Arc<str>>, bytes: &[u8]) -> Result<Self, EncodingError> {
+        let (encoding, bom_length) = match bytes {
+            [0xEF, 0xBB, 0xBF, ..] => (EncodingReport { encoding: Encoding::Utf8, detection: Detection::ByteOrderMark }, 3),
+            [0xFF, 0xFE, ..] => (EncodingReport { encoding: Encoding::Utf16Le, detection: Detection::ByteOrderMark }, 2),
+            [0xFE, 0xFF, ..] => (EncodingReport { encoding: Encoding::Utf16Be, detection: Detection::ByteOrderMark }, 2),
+            _ => match guess_utf16(bytes) {
+                Some(encoding) => (EncodingReport { encoding, detection: Detection::Guessed }, 0),
+                None => (EncodingReport { encoding: Encoding::Utf8, detection: Detection::Assumed }, 0)
+            }
+        };
+
+        let data = decode(encoding.encoding, &bytes[bom_length..])
+            .map_err(|offset| EncodingError { encoding: encoding.encoding, offset: bom_length + offset })?;
+
+        Ok(Self { name: name.into(), data: data.into(), encoding })
+    }
+
+    /// Construct a walker over the file's data
+    pub fn walker(&self) -> FileWalker<'_> {
+        FileWalker::from_data(&self.data, &self.name)
+    }
+
+    /// Own a location in the file, sharing its name
+    pub fn own_location(&self, location: &Location) -> OwnedLocation {
+        OwnedLocation::from_components(location.column, location.line, self.name.clone())
+    }
+
+    /// Own a span of the file's data, sharing the data rather than copying it, or None if the span does not refer to the file's data
+    pub fn own_span(&self, span: &Span) -> Option<OwnedSpan> {
+        let range = span.to_byte_range(self)?;
+        Some(OwnedSpan::from_shared(self.own_location(&span.location), self.data.clone(), range))
+    }
+}
+
+impl FileId {
+    /// Get the position of the file in the order the files were added
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file, getting its id
+    pub fn add(&mut self, file: SourceFile) -> FileId {
+        let id = FileId(u32::try_from(self.files.len()).expect("too many files for a source map"));
+        self.files.push(file);
+        id
+    }
+
+    /// Add a file whose data is already UTF-8, such as one generated or embedded in the program
+    pub fn add_data(&mut self, name: impl Into<Arc<str>>, data: impl Into<Arc<str>>) -> FileId {
+        self.add(SourceFile::from_data(name, data))
+    }
+
+    /// Read a file from disk and add it, detecting its encoding
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<FileId, SourceError> {
+        SourceFile::load(path).map(|file| self.add(file))
+    }
+
+    /// Get a file by its id, or None if it was not added to this map
+    pub fn get(&self, id: FileId) -> Option<&SourceFile> {
+        self.files.get(id.index())
+    }
+
+    /// Get the id of the first file added with a name
+    pub fn find(&self, name: &str) -> Option<FileId> {
+        self.files.iter().position(|file| &*file.name == name).map(|index| FileId(index as u32))
+    }
+
+    /// Get every file with its id, in the order they were added
+    pub fn files(&self) -> impl Iterator<Item = (FileId, &SourceFile)> {
+        self.files.iter().enumerate().map(|(index, file)| (FileId(index as u32), file))
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Construct a walker over a file, or None if it was not added to this map
+    pub fn walker(&self, id: FileId) -> Option<FileWalker<'_>> {
+        self.get(id).map(SourceFile::walker)
+    }
+
+    /// Construct a walker over every file, in the order they were added, so a walker can be found by the index of its id
+    pub fn walkers(&self) -> Vec<FileWalker<'_>> {
+        self.files.iter().map(SourceFile::walker).collect()
+    }
+
+    /// Find the file a span is in from the data it refers to, or None if it is not from this map
+    ///
+    /// Empty spans have no data to find them by, so they are found by the filename of their location instead
+    pub fn file_of(&self, span: &Span) -> Option<FileId> {
+        if span.data.is_empty() {
+            return self.find(span.location.filename);
+        }
+
+        self.files().find(|(_, file)| file.walker().contains_span(span)).map(|(id, _)| id)
+    }
+
+    /// Own a span of one of the files, sharing the file's data, or None if it is not from this map
+    pub fn own_span(&self, span: &Span) -> Option<OwnedSpan> {
+        self.get(self.file_of(span)?)?.own_span(span)
+    }
+}
+
+/// Guess whether data without a byte order mark is UTF-16, which is likely when every other byte of mostly ASCII text is zero
+fn guess_utf16(bytes: &[u8]) -> Option<Encoding> {
+    if bytes.is_empty() || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let even_zeros = bytes.iter().step_by(2).filter(|b| **b == 0).count();
+    let odd_zeros = bytes.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+    let units = bytes.len() / 2;
+
+    match (even_zeros, odd_zeros) {
+        (0, odd) if odd * 2 >= units => Some(Encoding::Utf16Le),
+        (even, 0) if even * 2 >= units => Some(Encoding::Utf16Be),
+        _ => None
+    }
+}
+
+/// Decode data to UTF-8, or get the offset of the first invalid byte
+fn decode(encoding: Encoding, bytes: &[u8]) -> Result<String, usize> {
+    let unit: fn([u8; 2]) -> u16 = match encoding {
+        Encoding::Utf8 => return std::str::from_utf8(bytes).map(str::to_string).map_err(|e| e.valid_up_to()),
+        Encoding::Utf16Le => u16::from_le_bytes,
+        Encoding::Utf16Be => u16::from_be_bytes
+    };
+
+    let units = bytes.chunks(2).map(|pair| match pair {
+        [a, b] => Ok(unit([*a, *b])),
+        // A trailing odd byte cannot form a code unit
+        _ => Err(())
+    });
+
+    let mut data = String::with_capacity(bytes.len() / 2);
+    let mut offset = 0;
+    for c in char::decode_utf16(units.map_while(Result::ok)) {
+        match c {
+            Ok(c) => {
+                data.push(c);
+                offset += c.len_utf16() * 2;
+            }
+            Err(_) => return Err(offset)
+        }
+    }
+
+    if offset != bytes.len() {
+        return Err(offset);
+    }
+
+    Ok(data)
+}
+
+impl std::fmt::Display for Encoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Encoding::Utf8 => write!(f, "UTF-8"),
+            Encoding::Utf16Le => write!(f, "UTF-16LE"),
+            Encoding::Utf16Be => write!(f, "UTF-16BE"),
+        }
+    }
+}
+
+impl std::fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "file is not valid {} at byte {}", self.encoding, self.offset)
+    }
+}
+
+impl std::fmt::Display for SourceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SourceError::Io(e) => write!(f, "could not read the file: {}", e),
+            SourceError::Encoding(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for EncodingError {}
+
+impl std::error::Error for SourceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SourceError::Io(e) => Some(e),
+            SourceError::Encoding(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::driver::source::{Detection, Encoding, EncodingError, EncodingReport, SourceFile, SourceMap};
+    use crate::{Diagnostic, DiagnosticBatch, ErrorDisplaySettings, ErrorLevel, Location, Span};
+
+    fn utf16(text: &str, to_bytes: fn(u16) -> [u8; 2]) -> Vec<u8> {
+        text.encode_utf16().flat_map(to_bytes).collect()
+    }
+
+    #[test]
+    fn source_file_encodings() {
+        let file = SourceFile::from_bytes("input", "Möbius".as_bytes()).unwrap();
+        assert_eq!(&*file.data, "Möbius");
+        assert_eq!(file.encoding, EncodingReport { encoding: Encoding::Utf8, detection: Detection::Assumed });
+        assert!(!file.encoding.converted());
+
+        let file = SourceFile::from_bytes("input", b"\xEF\xBB\xBFfn main() {}").unwrap();
+        assert_eq!(&*file.data, "fn main() {}");
+        assert_eq!(file.encoding, EncodingReport { encoding: Encoding::Utf8, detection: Detection::ByteOrderMark });
+
+        let file = SourceFile::from_bytes("input", &[&[0xFF, 0xFE][..], &utf16("Möbius 😀", u16::to_le_bytes)].concat()).unwrap();
+        assert_eq!(&*file.data, "Möbius 😀");
+        assert_eq!(file.encoding, EncodingReport { encoding: Encoding::Utf16Le, detection: Detection::ByteOrderMark });
+        assert!(file.encoding.converted());
+
+        let file = SourceFile::from_bytes("input", &[&[0xFE, 0xFF][..], &utf16("Möbius", u16::to_be_bytes)].concat()).unwrap();
+        assert_eq!(&*file.data, "Möbius");
+        assert_eq!(file.encoding.encoding, Encoding::Utf16Be);
+
+        // Without a byte order mark, mostly ASCII UTF-16 is recognised by its zero bytes
+        let file = SourceFile::from_bytes("input", &utf16("let x = 1;", u16::to_le_bytes)).unwrap();
+        assert_eq!(&*file.data, "let x = 1;");
+        assert_eq!(file.encoding, EncodingReport { encoding: Encoding::Utf16Le, detection: Detection::Guessed });
+
+        let file = SourceFile::from_bytes("input", b"").unwrap();
+        assert_eq!(&*file.data, "");
+        assert_eq!(file.walker().current_string(), "");
+    }
+
+    #[test]
+    fn source_file_invalid() {
+        let error = SourceFile::from_bytes("input", b"let \xC3x = 1;").unwrap_err();
+        assert_eq!(error, EncodingError { encoding: Encoding::Utf8, offset: 4 });
+        assert_eq!(error.to_string(), "file is not valid UTF-8 at byte 4");
+
+        // Offsets count the byte order mark
+        let error = SourceFile::from_bytes("input", b"\xEF\xBB\xBFab\xFF").unwrap_err();
+        assert_eq!(error.offset, 5);
+
+        // An unpaired surrogate
+        let bytes = [&[0xFF, 0xFE][..], &utf16("ab", u16::to_le_bytes), &0xD800u16.to_le_bytes(), &utf16("c", u16::to_le_bytes)].concat();
+        assert_eq!(SourceFile::from_bytes("input", &bytes).unwrap_err(), EncodingError { encoding: Encoding::Utf16Le, offset: 6 });
+
+        // A trailing odd byte
+        let bytes = [&[0xFE, 0xFF][..], &utf16("ab", u16::to_be_bytes), &[0x00]].concat();
+        assert_eq!(SourceFile::from_bytes("input", &bytes).unwrap_err(), EncodingError { encoding: Encoding::Utf16Be, offset: 6 });
+    }
+
+    #[test]
+    fn source_map_files() {
+        let mut sources = SourceMap::new();
+        let main = sources.add_data("main.txt", "use lib;\nlet a = double(2);");
+        let lib = sources.add(SourceFile::from_bytes("lib.txt", "fn double(x) = x * 2;".as_bytes()).unwrap());
+        assert_eq!((main.index(), lib.index(), sources.len()), (0, 1, 2));
+        assert_eq!(sources.find("lib.txt"), Some(lib));
+        assert_eq!(&*sources.get(lib).unwrap().data, "fn double(x) = x * 2;");
+
+        // Spans are resolved to their file by their data, so equal text from elsewhere is not in any file
+        let call = Span::from_components(Location::from_components(8, 1, "main.txt"), &sources.get(main).unwrap().data[17..23]);
+        let definition = Span::from_components(Location::from_components(3, 0, "lib.txt"), &sources.get(lib).unwrap().data[3..9]);
+        assert_eq!((sources.file_of(&call), sources.file_of(&definition)), (Some(main), Some(lib)));
+        assert_eq!(sources.file_of(&Span::from_components(Location::from_components(3, 0, "lib.txt"), "double")), None);
+        assert_eq!(sources.file_of(&Span::from_components(Location::from_components(0, 5, "lib.txt"), "")), Some(lib));
+
+        // Diagnostics in one file can show notes in the others
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+        let diagnostics = [Diagnostic::new(ErrorLevel::Error, "wrong number of arguments", call.location)
+            .with_note(&call, "called with one argument", ErrorLevel::Error)
+            .with_note(&definition, "defined with one parameter", ErrorLevel::Info)];
+        let walkers = sources.walkers();
+        let walkers: Vec<_> = walkers.iter().collect();
+
+        crate::assert_text_eq!(DiagnosticBatch::new(&settings, &walkers, &diagnostics).to_string(), "\
+error: wrong number of arguments
+   --> column 9 line 2 in main.txt
+  1 |use lib;
+  2 |let a = double(2);
+    |        ^^^^^^ called with one argument
+   ::: column 4 line 1 in lib.txt
+  1 |fn double(x) = x * 2;
+    |   ^^^^^^ defined with one parameter
+");
+    }
+}
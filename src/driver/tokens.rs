@@ -0,0 +1,177 @@
+//! Dumps the tokens a lexer finds in a file, for debugging lexers and for `--emit=tokens` style options of drivers
+//!
+//! Any function which consumes one token from a walker can be dumped, so this works with hand written lexers as well as parsers. The
+//! file is lexed by `TokenStream::lex_partial`, keeping trivia as tokens so the dump shows everything the lexer consumed
+
+use crate::json::{Json, ToJson};
+use crate::parser::lexer::TokenStream;
+use crate::{FileWalker, ParsingError, Span};
+
+/// The most characters of a token shown in a dump, longer tokens are cut short with an ellipsis
+const PREVIEW_LENGTH: usize = 24;
+
+/// A token found while dumping a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DumpedToken<'filedata> {
+    pub kind: String,
+    pub span: Span<'filedata>,
+    /// Whether the token is trivia, such as whitespace and comments, which parsers skip
+    pub trivia: bool
+}
+
+/// The tokens of a file, in order, and the error which stopped the lexer if it did not reach the end of the file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenDump<'filedata> {
+    pub tokens: Vec<DumpedToken<'filedata>>,
+    pub error: Option<ParsingError<'filedata>>
+}
+
+impl<'filedata> TokenDump<'filedata> {
+    /// Lex the whole of the walker's file, calling `next` for each token, which gives the token's kind and whether it is trivia
+    ///
+    /// Each token's span is the input `next` consumed. Lexing stops at the first error, or at a token which consumes nothing since
+    /// the lexer would never reach the end of the file
+    pub fn lex<K: std::fmt::Display>(
+        walker: &FileWalker<'filedata>,
+        next: impl Fn(&mut FileWalker<'filedata>) -> Result<(K, bool), ParsingError<'filedata>>
+    ) -> Self {
+        let mut walker = FileWalker::from_data(walker.all_data(), walker.filename());
+        let (stream, error) = TokenStream::lex_partial(&mut walker, |walker| next(walker).map(Some));
+
+        let tokens = stream.tokens().iter()
+            .map(|token| DumpedToken { kind: token.value.0.to_string(), span: token.span, trivia: token.value.1 })
+            .collect();
+
+        Self { tokens, error }
+    }
+
+    /// Get the number of tokens which are not trivia
+    pub fn token_count(&self) -> usize {
+        self.tokens.iter().filter(|token| !token.trivia).count()
+    }
+
+    /// Get the number of tokens which are trivia
+    pub fn trivia_count(&self) -> usize {
+        self.tokens.iter().filter(|token| token.trivia).count()
+    }
+
+    /// Write the tokens as a table, one per line, with their locations counting from 1, followed by the counts of tokens and trivia
+    pub fn to_table(&self) -> String {
+        let location = |token: &DumpedToken| format!("{}:{}", token.span.location.line + 1, token.span.location.column + 1);
+        let location_width = self.tokens.iter().map(|token| location(token).len()).chain(["location".len()]).max().unwrap();
+        let kind_width = self.tokens.iter().map(|token| token.kind.chars().count()).chain(["kind".len()]).max().unwrap();
+
+        let mut table = format!("{:location_width$}  {:kind_width$}  trivia  text\n", "location", "kind");
+        for token in &self.tokens {
+            let trivia = if token.trivia { "yes" } else { "" };
+            table += &format!("{:location_width$}  {:kind_width$}  {:6}  {}\n", location(token), token.kind, trivia, preview(token.span.data));
+        }
+
+        let trivia_bytes: usize = self.tokens.iter().filter(|token| token.trivia).map(|token| token.span.data.len()).sum();
+        let total_bytes: usize = self.tokens.iter().map(|token| token.span.data.len()).sum();
+        table += &format!("{} tokens and {} trivia, trivia is {} of {} bytes\n", self.token_count(), self.trivia_count(), trivia_bytes, total_bytes);
+
+        if let Some(error) = &self.error {
+            table += &format!("stopped at {}: {}\n", error.0, error);
+        }
+
+        table
+    }
+}
+
+/// Quote the text of a token with its special characters escaped, cut short if it is long
+fn preview(text: &str) -> String {
+    let mut preview: String = text.chars().take(PREVIEW_LENGTH).collect();
+    if preview.len() < text.len() {
+        preview.push('…');
+    }
+
+    format!("{:?}", preview)
+}
+
+impl<'filedata> ToJson for DumpedToken<'filedata> {
+    fn to_json(&self) -> Json {
+        Json::object([
+            ("kind", self.kind.to_json()),
+            ("span", self.span.to_json()),
+            ("trivia", self.trivia.to_json()),
+        ])
+    }
+}
+
+impl<'filedata> ToJson for TokenDump<'filedata> {
+    fn to_json(&self) -> Json {
+        Json::object([
+            ("error", self.error.as_ref().map_or(Json::Null, |error| Json::String(error.to_string()))),
+            ("tokens", self.tokens.to_json()),
+            ("token_count", self.token_count().to_json()),
+            ("trivia_count", self.trivia_count().to_json()),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::driver::tokens::TokenDump;
+    use crate::json::ToJson;
+    use crate::{FileWalker, ParsingError, alt, map, one_of, tag, take_while};
+
+    fn token<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<(&'static str, bool), ParsingError<'filedata>> {
+        alt(
+            alt(
+                map(take_while(char::is_whitespace, "whitespace"), |_| ("whitespace", true)),
+                map(tag("let"), |_| ("keyword", false))
+            ),
+            alt(
+                map(take_while(|c| c.is_ascii_alphanumeric(), "a word"), |_| ("word", false)),
+                map(one_of("=;"), |_| ("punctuation", false))
+            )
+        )(walker)
+    }
+
+    #[test]
+    fn token_table() {
+        let walker = FileWalker::from_data("let x = \"a\";\n  letter", "input");
+        let dump = TokenDump::lex(&walker, token);
+
+        crate::assert_text_eq!(dump.to_table(), r#"location  kind         trivia  text
+1:1       keyword              "let"
+1:4       whitespace   yes     " "
+1:5       word                 "x"
+1:6       whitespace   yes     " "
+1:7       punctuation          "="
+1:8       whitespace   yes     " "
+3 tokens and 3 trivia, trivia is 3 of 8 bytes
+stopped at column 9 line 1 in input: expected one of =;
+"#);
+
+        // Keywords which are the start of a longer word are a common mistake the dump makes visible
+        let walker = FileWalker::from_data("letter\n\t ", "input");
+        let dump = TokenDump::lex(&walker, token);
+        assert_eq!(dump.tokens.iter().map(|token| (token.kind.as_str(), token.span.data)).collect::<Vec<_>>(),
+            [("keyword", "let"), ("word", "ter"), ("whitespace", "\n\t ")]);
+        assert_eq!(dump.error, None);
+    }
+
+    #[test]
+    fn token_dump_json() {
+        let walker = FileWalker::from_data("x;", "input");
+        let dump = TokenDump::lex(&walker, token);
+
+        assert_eq!(dump.to_json().to_compact(), concat!(
+            r#"{"error":null,"token_count":2,"tokens":["#,
+            r#"{"kind":"word","span":{"data":"x","location":{"column":0,"filename":"input","line":0}},"trivia":false},"#,
+            r#"{"kind":"punctuation","span":{"data":";","location":{"column":1,"filename":"input","line":0}},"trivia":false}"#,
+            r#"],"trivia_count":0}"#));
+
+        // A lexer which consumes nothing is stopped rather than looping forever
+        let dump = TokenDump::lex(&walker, |_: &mut FileWalker| Ok(("nothing", false)));
+        assert_eq!(dump.error.unwrap().to_string(), "expected a token which consumes input");
+    }
+
+    #[test]
+    fn token_preview() {
+        assert_eq!(super::preview("a\tb\""), r#""a\tb\"""#);
+        assert_eq!(super::preview(&"x".repeat(30)), format!("\"{}…\"", "x".repeat(24)));
+    }
+}
@@ -0,0 +1,372 @@
+use crate::json::{Json, ToJson};
+use crate::{Diagnostic, ErrorLevel, Location};
+
+/// A kind of diagnostic a language reports, declared once so its code, level and message are the same in every output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiagnosticCode {
+    /// The code shown to users, such as `E0001`, which should never be reused for another kind of diagnostic
+    pub code: &'static str,
+    /// A short name in kebab case, such as `unknown-name`, for options which refer to diagnostics by name
+    pub name: &'static str,
+    /// The level the diagnostic is reported at by default
+    pub level: ErrorLevel,
+    /// The message, where a placeholder like `{name}` is replaced by the argument with that name
+    pub message: &'static str,
+    /// A longer description of the diagnostic and how to fix it, shown by `CodeRegistry::explain`
+    pub explanation: &'static str
+}
+
+/// Every diagnostic code of a language, looked up by code or name
+///
+/// Constructing a registry in a `static` or `const` checks at compile time that no code or name is repeated, see `diagnostic_codes!`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeRegistry {
+    codes: &'static [DiagnosticCode]
+}
+
+impl DiagnosticCode {
+    /// Fill in the placeholders of the message from the arguments, leaving placeholders without an argument as they are
+    pub fn message(&self, args: &[(&str, &dyn std::fmt::Display)]) -> String {
+        let mut message = String::with_capacity(self.message.len());
+        let mut rest = self.message;
+
+        while let Some(start) = rest.find('{') {
+            message.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let argument = rest.find('}').and_then(|end| Some((end, args.iter().find(|(name, _)| *name == &rest[1..end])?)));
+            match argument {
+                Some((end, (_, value))) => {
+                    message += &value.to_string();
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    message.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+
+        message.push_str(rest);
+        message
+    }
+
+    /// Construct a diagnostic of this kind at a location, with its default level, code and message
+    pub fn diagnostic<'filedata>(&self, location: Location<'filedata>, args: &[(&str, &dyn std::fmt::Display)]) -> Diagnostic<'filedata> {
+        Diagnostic::new(self.level, self.message(args), location).with_code(self.code)
+    }
+}
+
+/// Compare strings in a constant, which cannot call `PartialEq::eq`
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+
+    true
+}
+
+/// The SARIF name of a level, which calls informational results notes
+fn sarif_level(level: ErrorLevel) -> Json {
+    match level {
+        ErrorLevel::Error => "error",
+        ErrorLevel::Warning => "warning",
+        ErrorLevel::Info => "note",
+    }.to_json()
+}
+
+/// A SARIF location, with lines and columns counted from 1
+fn sarif_location(start: Location, end: Location, message: Option<&str>) -> Json {
+    let physical = Json::object([
+        ("artifactLocation", Json::object([("uri", start.filename.to_json())])),
+        ("region", Json::object([
+            ("endColumn", (end.column + 1).to_json()),
+            ("endLine", (end.line + 1).to_json()),
+            ("startColumn", (start.column + 1).to_json()),
+            ("startLine", (start.line + 1).to_json()),
+        ])),
+    ]);
+
+    match message {
+        Some(message) => Json::object([("message", Json::object([("text", message.to_json())])), ("physicalLocation", physical)]),
+        None => Json::object([("physicalLocation", physical)])
+    }
+}
+
+impl CodeRegistry {
+    /// Construct a registry of codes, panicking if a code or name is repeated
+    pub const fn new(codes: &'static [DiagnosticCode]) -> Self {
+        let mut i = 0;
+        while i < codes.len() {
+            let mut j = i + 1;
+            while j < codes.len() {
+                assert!(!str_eq(codes[i].code, codes[j].code), "a diagnostic code is declared more than once");
+                assert!(!str_eq(codes[i].name, codes[j].name), "a diagnostic name is declared more than once");
+                j += 1;
+            }
+            i += 1;
+        }
+
+        Self { codes }
+    }
+
+    /// Get every code, in the order they were declared
+    pub fn codes(&self) -> &'static [DiagnosticCode] {
+        self.codes
+    }
+
+    /// Look up a code, such as `E0001`
+    pub fn get(&self, code: &str) -> Option<&'static DiagnosticCode> {
+        self.codes.iter().find(|declared| declared.code == code)
+    }
+
+    /// Look up a code by its name, such as `unknown-name`
+    pub fn by_name(&self, name: &str) -> Option<&'static DiagnosticCode> {
+        self.codes.iter().find(|declared| declared.name == name)
+    }
+
+    /// Look up the code of a diagnostic, or None if it has no code or the code is not in the registry
+    pub fn of(&self, diagnostic: &Diagnostic) -> Option<&'static DiagnosticCode> {
+        self.get(diagnostic.code?)
+    }
+
+    /// Describe a code for an `--explain` option like rustc's, or None if the code is not in the registry
+    pub fn explain(&self, code: &str) -> Option<String> {
+        let declared = self.get(code)?;
+        let level = match declared.level {
+            ErrorLevel::Error => "error",
+            ErrorLevel::Warning => "warning",
+            ErrorLevel::Info => "info",
+        };
+
+        Some(format!("{} ({}, {} by default): {}\n\n{}\n", declared.code, declared.name, level, declared.message, declared.explanation.trim()))
+    }
+
+    /// Write diagnostics as a SARIF 2.1.0 log from a tool, for code scanning services such as GitHub's
+    ///
+    /// Every code in the registry is listed as a rule of the tool, and each result refers to the rule of its code by `ruleId` and
+    /// `ruleIndex`. A diagnostic's notes are its related locations
+    pub fn to_sarif(&self, tool: &str, diagnostics: &[Diagnostic]) -> Json {
+        let rules = self.codes.iter().map(|declared| Json::object([
+            ("defaultConfiguration", Json::object([("level", sarif_level(declared.level))])),
+            ("fullDescription", Json::object([("text", declared.explanation.trim().to_json())])),
+            ("id", declared.code.to_json()),
+            ("name", declared.name.to_json()),
+            ("shortDescription", Json::object([("text", declared.message.to_json())])),
+        ])).collect();
+
+        let results = diagnostics.iter().map(|diagnostic| {
+            let mut fields = vec![
+                ("level", sarif_level(diagnostic.level)),
+                ("locations", Json::Array(vec![sarif_location(diagnostic.location, diagnostic.location, None)])),
+                ("message", Json::object([("text", diagnostic.message.to_json())])),
+                ("relatedLocations", Json::Array(diagnostic.notes.iter().map(|note| {
+                    let end = if note.synthetic { note.span.location } else { note.span.end_location() };
+                    sarif_location(note.span.location, end, Some(&note.message))
+                }).collect())),
+            ];
+
+            if let Some(code) = diagnostic.code {
+                fields.push(("ruleId", code.to_json()));
+                if let Some(index) = self.codes.iter().position(|declared| declared.code == code) {
+                    fields.push(("ruleIndex", index.to_json()));
+                }
+            }
+
+            Json::object(fields)
+        }).collect();
+
+        Json::object([
+            ("$schema", "https://json.schemastore.org/sarif-2.1.0.json".to_json()),
+            ("runs", Json::Array(vec![Json::object([
+                ("results", Json::Array(results)),
+                ("tool", Json::object([("driver", Json::object([("name", tool.to_json()), ("rules", Json::Array(rules))]))])),
+            ])])),
+            ("version", "2.1.0".to_json()),
+        ])
+    }
+}
+
+impl ToJson for DiagnosticCode {
+    fn to_json(&self) -> Json {
+        Json::object([
+            ("code", self.code.to_json()),
+            ("explanation", self.explanation.to_json()),
+            ("level", self.level.to_json()),
+            ("message", self.message.to_json()),
+            ("name", self.name.to_json()),
+        ])
+    }
+}
+
+impl ToJson for CodeRegistry {
+    fn to_json(&self) -> Json {
+        Json::Array(self.codes.iter().map(ToJson::to_json).collect())
+    }
+}
+
+/// Declare the diagnostic codes of a language as constants, along with a `CodeRegistry` of all of them
+///
+/// Each code is written as `UNKNOWN_NAME { code: "E0001", name: "unknown-name", level: Error, message: "...", explanation: "..." }`.
+/// The registry is checked when the crate is compiled, so repeating a code or name is a compile error
+#[macro_export]
+macro_rules! diagnostic_codes {
+    (
+        $registry_vis:vis static $registry:ident;
+
+        $(
+            $(#[$attr:meta])*
+            $vis:vis $constant:ident {
+                code: $code:literal, name: $name:literal, level: $level:ident,
+                message: $message:literal,
+                explanation: $explanation:expr $(,)?
+            }
+        )*
+    ) => {
+        $(
+            $(#[$attr])*
+            $vis const $constant: $crate::errors::codes::DiagnosticCode = $crate::errors::codes::DiagnosticCode {
+                code: $code,
+                name: $name,
+                level: $crate::ErrorLevel::$level,
+                message: $message,
+                explanation: $explanation
+            };
+        )*
+
+        $registry_vis static $registry: $crate::errors::codes::CodeRegistry = $crate::errors::codes::CodeRegistry::new(&[$($constant),*]);
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::errors::codes::{CodeRegistry, DiagnosticCode};
+    use crate::json::{Json, ToJson};
+    use crate::{Diagnostic, DiagnosticBatch, ErrorDisplaySettings, ErrorLevel, FileWalker, LineIndex, tag};
+
+    crate::diagnostic_codes! {
+        static CODES;
+
+        UNKNOWN_NAME {
+            code: "E0001", name: "unknown-name", level: Error,
+            message: "unknown name `{name}`",
+            explanation: "
+A name was used which is not declared in scope.
+
+Declare the name with `let` before using it."
+        }
+
+        /// Names are only warned about once per scope
+        UNUSED_NAME {
+            code: "W0001", name: "unused-name", level: Warning,
+            message: "`{name}` is declared but never used in {scope}",
+            explanation: "A name was declared but never read."
+        }
+    }
+
+    #[test]
+    fn diagnostic_codes() {
+        assert_eq!(CODES.codes(), [UNKNOWN_NAME, UNUSED_NAME]);
+        assert_eq!(CODES.get("W0001"), Some(&UNUSED_NAME));
+        assert_eq!(CODES.by_name("unknown-name"), Some(&UNKNOWN_NAME));
+        assert_eq!(CODES.get("E0002"), None);
+
+        // Placeholders without an argument are left alone
+        assert_eq!(UNUSED_NAME.message(&[("name", &"x"), ("scope", &"main")]), "`x` is declared but never used in main");
+        assert_eq!(UNUSED_NAME.message(&[("name", &"{scope}")]), "`{scope}` is declared but never used in {scope}");
+        assert_eq!(UNKNOWN_NAME.message(&[]), "unknown name `{name}`");
+
+        crate::assert_text_eq!(CODES.explain("E0001").unwrap(), "\
+E0001 (unknown-name, error by default): unknown name `{name}`
+
+A name was used which is not declared in scope.
+
+Declare the name with `let` before using it.
+");
+        assert_eq!(CODES.explain("E0002"), None);
+    }
+
+    #[test]
+    fn coded_diagnostics() {
+        let mut walker = FileWalker::from_data("let x = y;", "input");
+        tag("let x = ")(&mut walker).unwrap();
+        let y = tag("y")(&mut walker).unwrap();
+
+        let diagnostic = UNKNOWN_NAME.diagnostic(y.location, &[("name", &y.data)]).with_note(&y, "not declared", ErrorLevel::Error);
+        assert_eq!(CODES.of(&diagnostic), Some(&UNKNOWN_NAME));
+
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+        crate::assert_text_eq!(diagnostic.render(&settings, &walker).to_string(), "\
+error[E0001]: unknown name `y`
+   --> column 9 line 1 in input
+  1 |let x = y;
+    |        ^ not declared
+");
+        assert!(DiagnosticBatch::new(&settings, &[], std::slice::from_ref(&diagnostic)).to_string().starts_with("error[E0001]: unknown name `y`\n"));
+
+        // Codes are kept when diagnostics are compacted and sent between threads
+        let index = LineIndex::from_walker(&walker);
+        let compact = diagnostic.compact(&index).unwrap();
+        assert_eq!(compact.expand(&index).unwrap().code, Some("E0001"));
+
+        assert!(diagnostic.to_json().to_compact().starts_with(r#"{"code":"E0001","#));
+        assert_eq!(CODES.to_json().to_compact().matches("\"code\"").count(), 2);
+    }
+
+    #[test]
+    fn sarif_output() {
+        let mut walker = FileWalker::from_data("let x = y;", "input");
+        tag("let ")(&mut walker).unwrap();
+        let x = tag("x")(&mut walker).unwrap();
+        tag(" = ")(&mut walker).unwrap();
+        let y = tag("y")(&mut walker).unwrap();
+
+        let diagnostics = [
+            UNUSED_NAME.diagnostic(x.location, &[("name", &x.data), ("scope", &"main")]).with_note(&x, "declared here", ErrorLevel::Info),
+            Diagnostic::new(ErrorLevel::Error, "no code", y.location),
+        ];
+        let sarif = CODES.to_sarif("calc", &diagnostics);
+        let Json::Object(log) = &sarif else { panic!("expected an object") };
+        let Json::Array(runs) = &log["runs"] else { panic!("expected an array of runs") };
+        let Json::Object(run) = &runs[0] else { panic!("expected a run object") };
+        let Json::Array(results) = &run["results"] else { panic!("expected an array of results") };
+
+        assert_eq!(log["version"].to_compact(), r#""2.1.0""#);
+        assert_eq!(run["tool"].to_compact().matches(r#""id""#).count(), 2);
+        assert!(run["tool"].to_compact().contains(concat!(
+            r#"{"defaultConfiguration":{"level":"warning"},"fullDescription":{"text":"A name was declared but never read."},"#,
+            r#""id":"W0001","name":"unused-name","shortDescription":{"text":"`{name}` is declared but never used in {scope}"}}"#
+        )));
+
+        // Results refer to their rule by code and by its index in the rules, and columns are counted from 1
+        assert_eq!(results[0].to_compact(), concat!(
+            r#"{"level":"warning","locations":[{"physicalLocation":{"artifactLocation":{"uri":"input"},"#,
+            r#""region":{"endColumn":5,"endLine":1,"startColumn":5,"startLine":1}}}],"#,
+            r#""message":{"text":"`x` is declared but never used in main"},"#,
+            r#""relatedLocations":[{"message":{"text":"declared here"},"physicalLocation":{"artifactLocation":{"uri":"input"},"#,
+            r#""region":{"endColumn":6,"endLine":1,"startColumn":5,"startLine":1}}}],"ruleId":"W0001","ruleIndex":1}"#
+        ));
+
+        // A diagnostic without a code is reported without a rule
+        assert!(!results[1].to_compact().contains("rule"));
+        assert!(results[1].to_compact().contains(r#""level":"error""#));
+    }
+
+    #[test]
+    #[should_panic(expected = "a diagnostic code is declared more than once")]
+    fn repeated_code() {
+        const CODE: DiagnosticCode = DiagnosticCode { code: "E0001", name: "a", level: ErrorLevel::Error, message: "", explanation: "" };
+        static REPEATED: [DiagnosticCode; 2] = [CODE, DiagnosticCode { name: "b", ..CODE }];
+
+        let _ = CodeRegistry::new(&REPEATED);
+    }
+}
@@ -0,0 +1,589 @@
+use crate::{CompactSpan, ErrorDisplaySettings, ErrorLevel, ErrorRender, FileWalker, HeaderDisplay, LineIndex, Location, Note, ParsingError, Pos, Span, SyntheticSpan};
+use crate::errors::display::LineCache;
+
+use std::borrow::Cow;
+
+/// A note attached to a `Diagnostic`, which owns its message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiagnosticNote<'filedata> {
+    pub span: Span<'filedata>,
+    pub message: String,
+    pub level: ErrorLevel,
+    /// Whether the span refers to text which was inserted rather than appearing in the file
+    pub synthetic: bool
+}
+
+/// An error message and its notes, which owns its messages so it can be collected and rendered later
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic<'filedata> {
+    pub level: ErrorLevel,
+    pub message: String,
+    pub location: Location<'filedata>,
+    pub notes: Vec<DiagnosticNote<'filedata>>,
+    pub suggestions: Vec<Suggestion<'filedata>>,
+    /// The code identifying the kind of diagnostic, such as `E0001`, see `errors::codes`
+    pub code: Option<&'static str>
+}
+
+/// How likely a `Suggestion` is to be correct, only machine applicable suggestions are applied without asking
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Applicability {
+    /// The replacement is definitely what was intended, so it can be applied by a tool
+    MachineApplicable,
+    /// The replacement may not be what was intended, so it should be reviewed
+    MaybeIncorrect,
+    /// The replacement contains placeholders which must be filled in
+    HasPlaceholders
+}
+
+/// A replacement for the text of a span which would fix a `Diagnostic`, an empty span inserts the replacement at its location
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion<'filedata> {
+    pub span: Span<'filedata>,
+    pub message: String,
+    pub replacement: String,
+    pub applicability: Applicability
+}
+
+/// A note of a `CompactDiagnostic`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactNote {
+    pub span: CompactSpan,
+    pub message: String,
+    pub level: ErrorLevel,
+    /// The inserted text of a synthetic note, which does not appear in the file so is placed by an empty span
+    pub synthetic: Option<String>
+}
+
+/// A suggestion of a `CompactDiagnostic`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactSuggestion {
+    pub span: CompactSpan,
+    pub message: String,
+    pub replacement: String,
+    pub applicability: Applicability
+}
+
+/// A `Diagnostic` which stores positions as byte offsets rather than borrowing the file, so it can be sent to another thread
+///
+/// Diagnostics are compacted and expanded using a `LineIndex` over the file they refer to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactDiagnostic {
+    pub level: ErrorLevel,
+    pub message: String,
+    pub location: Pos,
+    pub notes: Vec<CompactNote>,
+    pub suggestions: Vec<CompactSuggestion>,
+    pub code: Option<&'static str>
+}
+
+/// Renders many diagnostics at once, sorted by their position in the files they refer to
+#[derive(Debug, Clone)]
+pub struct DiagnosticBatch<'filedata, 'a> {
+    settings: &'a ErrorDisplaySettings,
+    walkers: &'a [&'a FileWalker<'filedata>],
+    /// Diagnostics which were merged by `merge_repeats` are owned by the batch
+    diagnostics: Vec<Cow<'a, Diagnostic<'filedata>>>
+}
+
+impl<'filedata> Diagnostic<'filedata> {
+    /// Construct a new diagnostic without any notes
+    pub fn new(level: ErrorLevel, message: impl Into<String>, location: Location<'filedata>) -> Self {
+        Self {
+            level,
+            message: message.into(),
+            location,
+            notes: Vec::new(),
+            suggestions: Vec::new(),
+            code: None
+        }
+    }
+
+    /// Set the code identifying the kind of diagnostic, which is shown after its level like `error[E0001]`
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Add a note to the diagnostic
+    pub fn with_note(mut self, span: &Span<'filedata>, message: impl Into<String>, level: ErrorLevel) -> Self {
+        self.notes.push(DiagnosticNote {
+            span: *span,
+            message: message.into(),
+            level,
+            synthetic: false
+        });
+        self
+    }
+
+    /// Add a note for each span, message and level, such as one for every definition of a name which is defined more than once
+    pub fn with_notes<M: Into<String>>(mut self, notes: impl IntoIterator<Item = (Span<'filedata>, M, ErrorLevel)>) -> Self {
+        self.notes.extend(notes.into_iter().map(|(span, message, level)| DiagnosticNote {
+            span,
+            message: message.into(),
+            level,
+            synthetic: false
+        }));
+        self
+    }
+
+    /// Add a note on text which was automatically inserted into the file
+    pub fn with_synthetic_note(mut self, span: &SyntheticSpan<'filedata>, message: impl Into<String>, level: ErrorLevel) -> Self {
+        self.notes.push(DiagnosticNote {
+            span: span.as_span(),
+            message: message.into(),
+            level,
+            synthetic: true
+        });
+        self
+    }
+
+    /// Add a suggested replacement for the text of a span, which is shown as a note
+    pub fn with_suggestion(mut self, span: &Span<'filedata>, message: impl Into<String>, replacement: impl Into<String>, applicability: Applicability) -> Self {
+        self.suggestions.push(Suggestion {
+            span: *span,
+            message: message.into(),
+            replacement: replacement.into(),
+            applicability
+        });
+
+        self
+    }
+
+    /// Construct an error diagnostic describing a `ParsingError`, including the notes for the spans it references
+    ///
+    /// Errors which reference no spans, such as an expected tag, get an error note at their location so the line is still shown
+    pub fn from_parsing_error(error: &ParsingError<'filedata>) -> Self {
+        let notes = error.notes();
+        let diagnostic = Self::new(ErrorLevel::Error, error.to_string(), error.0);
+
+        if notes.is_empty() {
+            return diagnostic.with_note(&Span::from_components(error.0, ""), "", ErrorLevel::Error);
+        }
+
+        notes.iter().fold(diagnostic, |diagnostic, note| diagnostic.with_note(note.span(), note.text(), note.error_level()))
+    }
+
+    /// Get the notes of the diagnostic in the form used by `ErrorRender`, followed by an info note for each suggestion
+    pub fn render_notes(&self) -> Vec<Note<'filedata, '_>> {
+        self.notes.iter().map(|note| if note.synthetic {
+            Note::synthetic(&SyntheticSpan::from_components(note.span.location, note.span.data), &note.message, note.level)
+        }
+        else {
+            Note::new(&note.span, &note.message, note.level)
+        }).chain(self.suggestions.iter().map(|suggestion| Note::new(&suggestion.span, &suggestion.message, ErrorLevel::Info))).collect()
+    }
+
+    /// Compact the diagnostic using a `LineIndex` over the file it refers to, or None if any of its spans are in another file
+    pub fn compact(&self, index: &LineIndex<'filedata>) -> Option<CompactDiagnostic> {
+        let notes = self.notes.iter().map(|note| {
+            // Synthetic text does not appear in the file, so it is placed by its location
+            let span = if note.synthetic {
+                CompactSpan::from_components(index.pos_at(&note.span.location)?, 0)
+            }
+            else {
+                index.compact_or_place(&note.span)?
+            };
+
+            Some(CompactNote {
+                span,
+                message: note.message.clone(),
+                level: note.level,
+                synthetic: note.synthetic.then(|| note.span.data.to_string())
+            })
+        }).collect::<Option<_>>()?;
+
+        let suggestions = self.suggestions.iter().map(|suggestion| Some(CompactSuggestion {
+            span: index.compact_or_place(&suggestion.span)?,
+            message: suggestion.message.clone(),
+            replacement: suggestion.replacement.clone(),
+            applicability: suggestion.applicability
+        })).collect::<Option<_>>()?;
+
+        Some(CompactDiagnostic {
+            level: self.level,
+            message: self.message.clone(),
+            location: index.pos_at(&self.location)?,
+            notes,
+            suggestions,
+            code: self.code
+        })
+    }
+
+    /// Construct an `ErrorRender` to display the diagnostic, using a walker over the file it refers to
+    pub fn render<'a>(&'a self, settings: &'a ErrorDisplaySettings, walker: &'a FileWalker<'filedata>) -> ErrorRender<'filedata, 'a> {
+        ErrorRender::new(self.level, settings, &self.message, &self.location, self.render_notes(), walker).with_code(self.code)
+    }
+}
+
+impl CompactDiagnostic {
+    /// Expand the diagnostic using a `LineIndex` over the file it was compacted with, or None if its positions do not lie within that file
+    ///
+    /// The text of synthetic notes is borrowed from the compact diagnostic
+    pub fn expand<'a>(&'a self, index: &LineIndex<'a>) -> Option<Diagnostic<'a>> {
+        let mut diagnostic = Diagnostic::new(self.level, self.message.clone(), index.location(self.location)?);
+        diagnostic.code = self.code;
+
+        for note in &self.notes {
+            diagnostic = match &note.synthetic {
+                Some(text) => {
+                    let span = SyntheticSpan::from_components(index.location(note.span.start)?, text);
+                    diagnostic.with_synthetic_note(&span, note.message.clone(), note.level)
+                }
+                None => diagnostic.with_note(&index.span(note.span)?, note.message.clone(), note.level)
+            };
+        }
+
+        for suggestion in &self.suggestions {
+            diagnostic = diagnostic.with_suggestion(&index.span(suggestion.span)?, suggestion.message.clone(), suggestion.replacement.clone(), suggestion.applicability);
+        }
+
+        Some(diagnostic)
+    }
+}
+
+impl<'filedata> From<ParsingError<'filedata>> for Diagnostic<'filedata> {
+    fn from(error: ParsingError<'filedata>) -> Self {
+        Self::from_parsing_error(&error)
+    }
+}
+
+impl<'filedata, 'a> DiagnosticBatch<'filedata, 'a> {
+    /// Construct a new batch from the walkers over the files referenced by the diagnostics, and the diagnostics themselves
+    pub fn new(settings: &'a ErrorDisplaySettings, walkers: &'a [&'a FileWalker<'filedata>], diagnostics: &'a [Diagnostic<'filedata>]) -> Self {
+        let mut diagnostics: Vec<_> = diagnostics.iter().map(Cow::Borrowed).collect();
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.location.filename, diagnostic.location.line, diagnostic.location.column));
+
+        Self {
+            settings,
+            walkers,
+            diagnostics
+        }
+    }
+
+    /// Combine diagnostics with the same level and message at the same location, such as an error repeated for every instantiation of a template
+    ///
+    /// The policy is given every set of repeated diagnostics, and returns the text of a note summarizing them to merge them into the first,
+    /// or None to keep them separate
+    pub fn merge_repeats(mut self, policy: impl Fn(&[&Diagnostic<'filedata>]) -> Option<String>) -> Self {
+        let mut merged = Vec::with_capacity(self.diagnostics.len());
+        let mut rest = &self.diagnostics[..];
+
+        // The diagnostics are sorted by location, so repeats are found among the diagnostics at the same location
+        while let Some(first) = rest.first() {
+            let length = rest.iter().take_while(|diagnostic| diagnostic.location == first.location).count();
+            let (at_location, remaining) = rest.split_at(length);
+            rest = remaining;
+
+            let mut handled = vec![false; at_location.len()];
+            for i in 0..at_location.len() {
+                if handled[i] {
+                    continue;
+                }
+
+                let repeats: Vec<usize> = (i..at_location.len())
+                    .filter(|j| at_location[*j].level == at_location[i].level && at_location[*j].message == at_location[i].message)
+                    .collect();
+                let diagnostics: Vec<&Diagnostic> = repeats.iter().map(|j| at_location[*j].as_ref()).collect();
+
+                let summary = if repeats.len() > 1 { policy(&diagnostics) } else { None };
+                match summary {
+                    Some(summary) => {
+                        let first = at_location[i].as_ref();
+                        let location = Span::from_components(first.location, "");
+                        merged.push(Cow::Owned(first.clone().with_note(&location, summary, ErrorLevel::Info)));
+                    }
+                    None => merged.extend(repeats.iter().map(|j| at_location[*j].clone()))
+                }
+
+                repeats.iter().for_each(|j| handled[*j] = true);
+            }
+        }
+
+        self.diagnostics = merged;
+        self
+    }
+
+    /// Get the diagnostics to render, grouping adjacent diagnostics with the same level and message on the same line
+    fn groups(&self) -> Vec<&[Cow<'a, Diagnostic<'filedata>>]> {
+        let mut groups = Vec::new();
+        let mut rest = &self.diagnostics[..];
+
+        while let Some(first) = rest.first() {
+            let length = rest.iter().take_while(|diagnostic|
+                diagnostic.location.filename == first.location.filename &&
+                diagnostic.location.line == first.location.line &&
+                diagnostic.level == first.level &&
+                diagnostic.message == first.message).count();
+
+            let (group, remaining) = rest.split_at(length);
+            groups.push(group);
+            rest = remaining;
+        }
+
+        groups
+    }
+}
+
+impl<'filedata, 'a> std::fmt::Display for DiagnosticBatch<'filedata, 'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Every diagnostic is rendered with the same settings, so the lines they share are only formatted once
+        let line_cache = LineCache::default();
+
+        for (i, group) in self.groups().into_iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+
+            let first = &group[0];
+            let walker = self.walkers.iter().find(|walker| walker.filename() == first.location.filename);
+
+            match walker {
+                Some(walker) => {
+                    let notes = group.iter().flat_map(|diagnostic| diagnostic.render_notes()).collect();
+                    write!(f, "{}", ErrorRender::new(first.level, self.settings, &first.message, &first.location, notes, walker)
+                        .with_code(first.code)
+                        .with_other_files(self.walkers)
+                        .with_line_cache(&line_cache))?;
+                }
+                // Without the file, there is no snippet to show
+                None => writeln!(f, "{}", HeaderDisplay::new(first.level, self.settings, &first.message, &first.location).with_code(first.code))?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Applicability, CompactSpan, Diagnostic, DiagnosticBatch, ErrorDisplaySettings, ErrorLevel, FileWalker, LineIndex, Location, Pos, Span, SyntheticSpan, tag};
+
+    #[test]
+    fn from_parsing_error() {
+        let mut walker = FileWalker::from_data("High beams", "input");
+        let error = tag("Highway")(&mut walker).unwrap_err();
+
+        let diagnostic = Diagnostic::from(error.clone());
+        assert_eq!(diagnostic.level, ErrorLevel::Error);
+        assert_eq!(diagnostic.message, error.to_string());
+        assert_eq!(diagnostic.location, error.0);
+        assert_eq!(diagnostic.notes.len(), 2);
+        assert_eq!(diagnostic.notes[0].span.data, "High");
+    }
+
+    #[test]
+    fn suggestion_render() {
+        let mut walker = FileWalker::from_data("fun main() {}", "input");
+        let fun = tag("fun")(&mut walker).unwrap();
+
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+        let diagnostic = Diagnostic::new(ErrorLevel::Error, "unknown keyword `fun`", fun.location)
+            .with_note(&fun, "not a keyword", ErrorLevel::Error)
+            .with_suggestion(&fun, "did you mean `fn`?", "fn", Applicability::MachineApplicable);
+
+        crate::assert_text_eq!(diagnostic.render(&settings, &walker).to_string(), "\
+error: unknown keyword `fun`
+   --> column 1 line 1 in input
+  1 |fun main() {}
+    |^^^ not a keyword
+    |^^^ did you mean `fn`?
+");
+    }
+
+    #[test]
+    fn compact_round_trip() {
+        let data = "let x = 1\nlet y = 2;";
+        let mut walker = FileWalker::from_data(data, "input");
+        let index = LineIndex::from_walker(&walker);
+
+        tag("let ")(&mut walker).unwrap();
+        let x = tag("x")(&mut walker).unwrap();
+        tag(" = 1")(&mut walker).unwrap();
+        let end = walker.current_location();
+
+        let diagnostic = Diagnostic::new(ErrorLevel::Error, "expected `;`", end)
+            .with_note(&x, "in the declaration of `x`", ErrorLevel::Info)
+            .with_note(&Span::from_components(end, ""), "expected here", ErrorLevel::Error)
+            .with_synthetic_note(&SyntheticSpan::from_components(end, ";"), "inserted", ErrorLevel::Info)
+            .with_suggestion(&Span::from_components(end, ""), "add a semicolon", ";", Applicability::MachineApplicable)
+            .with_suggestion(&x, "rename the variable", "_x", Applicability::MaybeIncorrect);
+
+        let compact = diagnostic.compact(&index).unwrap();
+        assert_eq!(compact.location, Pos(9));
+        assert_eq!(compact.notes[0].span, CompactSpan::from_components(Pos(4), 1));
+        assert_eq!(compact.notes[1].span, CompactSpan::from_components(Pos(9), 0));
+        assert_eq!(compact.notes[2].synthetic.as_deref(), Some(";"));
+        assert_eq!(compact.suggestions[0].span, CompactSpan::from_components(Pos(9), 0));
+        assert_eq!(compact.suggestions[1].span, CompactSpan::from_components(Pos(4), 1));
+
+        // The compact diagnostic does not borrow the file, so it can be expanded after being sent to another thread
+        let compact = std::thread::spawn(move || compact).join().unwrap();
+        let expanded = compact.expand(&index).unwrap();
+        assert_eq!(expanded, diagnostic);
+        assert_eq!(expanded.notes[0].span.byte_range_in(data), 4..5);
+
+        // Spans in another file cannot be compacted
+        let other = LineIndex::from_data("let x = 1", "other");
+        assert_eq!(diagnostic.compact(&other), None);
+    }
+
+    #[test]
+    fn batch_render() {
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+
+        let first_data = "let a = 1;\nlet b = 2;";
+        let second_data = "fn main() {}";
+        let first = FileWalker::from_data(first_data, "first.txt");
+        let second = FileWalker::from_data(second_data, "second.txt");
+
+        let a = Span::from_components(Location::from_components(4, 0, "first.txt"), &first_data[4..5]);
+        let b = Span::from_components(Location::from_components(4, 1, "first.txt"), &first_data[15..16]);
+        let main = Span::from_components(Location::from_components(3, 0, "second.txt"), &second_data[3..7]);
+
+        let diagnostics = vec![
+            Diagnostic::new(ErrorLevel::Error, "unused function", main.location).with_note(&main, "never called", ErrorLevel::Error),
+            Diagnostic::new(ErrorLevel::Warning, "unused variable", b.location).with_note(&b, "`b` is never read", ErrorLevel::Warning),
+            Diagnostic::new(ErrorLevel::Warning, "unused variable", a.location).with_note(&a, "`a` is never read", ErrorLevel::Warning),
+            Diagnostic::new(ErrorLevel::Info, "not rendered with a snippet", Location::from_components(0, 0, "third.txt")),
+        ];
+
+        let walkers = [&second, &first];
+        let batch = DiagnosticBatch::new(&settings, &walkers, &diagnostics);
+
+        assert_eq!(batch.to_string(), "\
+warning: unused variable
+   --> column 5 line 1 in first.txt
+  1 |let a = 1;
+    |    ^ `a` is never read
+  2 |let b = 2;
+
+warning: unused variable
+   --> column 5 line 2 in first.txt
+  1 |let a = 1;
+  2 |let b = 2;
+    |    ^ `b` is never read
+
+error: unused function
+   --> column 4 line 1 in second.txt
+  1 |fn main() {}
+    |   ^^^^ never called
+
+info: not rendered with a snippet
+   --> column 1 line 1 in third.txt
+");
+    }
+
+    #[test]
+    fn batch_merges_same_line() {
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+
+        let data = "let a = b + c;";
+        let walker = FileWalker::from_data(data, "input.txt");
+
+        let b = Span::from_components(Location::from_components(8, 0, "input.txt"), &data[8..9]);
+        let c = Span::from_components(Location::from_components(12, 0, "input.txt"), &data[12..13]);
+
+        let diagnostics = vec![
+            Diagnostic::new(ErrorLevel::Error, "unknown variable", c.location).with_note(&c, "not found", ErrorLevel::Error),
+            Diagnostic::new(ErrorLevel::Error, "unknown variable", b.location).with_note(&b, "not found", ErrorLevel::Error),
+        ];
+
+        let walkers = [&walker];
+        let batch = DiagnosticBatch::new(&settings, &walkers, &diagnostics);
+
+        assert_eq!(batch.to_string(), "\
+error: unknown variable
+   --> column 9 line 1 in input.txt
+  1 |let a = b + c;
+    |            ^ not found
+    |        ^ not found
+");
+    }
+
+    #[test]
+    fn synthetic_note_render() {
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+
+        let data = "let a = 1\nlet b = 2;";
+        let mut walker = FileWalker::from_data(data, "input.txt");
+        tag("let a = 1")(&mut walker).unwrap();
+
+        let semicolon = walker.synthesize(";");
+        assert_eq!(semicolon.location, Location::from_components(9, 0, "input.txt"));
+
+        let diagnostic = Diagnostic::new(ErrorLevel::Warning, "missing semicolon", semicolon.location)
+            .with_synthetic_note(&semicolon, "", ErrorLevel::Warning)
+            .with_synthetic_note(&semicolon, "consider adding it", ErrorLevel::Info);
+
+        assert_eq!(diagnostic.render(&settings, &walker).to_string(), "\
+warning: missing semicolon
+   --> column 10 line 1 in input.txt
+  1 |let a = 1
+    |         + automatically inserted `;`
+    |         + automatically inserted `;`, consider adding it
+  2 |let b = 2;
+");
+    }
+
+    #[test]
+    fn notes_from_iterator() {
+        let data = "let x = 1;\nlet y = x;\nlet x = y;";
+        let walker = FileWalker::from_data(data, "input");
+        let definitions: Vec<Span> = [(0, 4), (2, 24)].into_iter()
+            .map(|(line, offset)| Span::from_components(Location::from_components(4, line, "input"), &data[offset..offset + 1]))
+            .collect();
+
+        // The messages are built while iterating, without keeping the strings alive elsewhere
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+        let diagnostic = Diagnostic::new(ErrorLevel::Error, "`x` is defined more than once", definitions[1].location)
+            .with_notes(definitions.iter().enumerate().map(|(i, span)| (*span, format!("definition {}", i + 1), ErrorLevel::Info)));
+
+        crate::assert_text_eq!(diagnostic.render(&settings, &walker).to_string(), "\
+error: `x` is defined more than once
+   --> column 5 line 3 in input
+  1 |let x = 1;
+    |    ^ definition 1
+  2 |let y = x;
+  3 |let x = y;
+    |    ^ definition 2
+");
+    }
+
+    #[test]
+    fn batch_merge_repeats() {
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+
+        let data = "fn double<T>(x: T) -> T { x * 2 }";
+        let walker = FileWalker::from_data(data, "input.txt");
+        let product = Span::from_components(Location::from_components(26, 0, "input.txt"), &data[26..31]);
+
+        let instantiation = |ty: &str| Diagnostic::new(ErrorLevel::Error, "cannot multiply by an integer", product.location)
+            .with_note(&product, format!("with T = {}", ty), ErrorLevel::Error);
+        let diagnostics = vec![
+            instantiation("u32"),
+            Diagnostic::new(ErrorLevel::Warning, "unused result", product.location),
+            instantiation("String"),
+            instantiation("bool"),
+        ];
+
+        let walkers = [&walker];
+        let batch = DiagnosticBatch::new(&settings, &walkers, &diagnostics)
+            .merge_repeats(|repeats| Some(format!("occurred {} times, e.g. {}", repeats.len(), repeats[0].notes[0].message)));
+
+        crate::assert_text_eq!(batch.to_string(), "\
+error: cannot multiply by an integer
+   --> column 27 line 1 in input.txt
+  1 |fn double<T>(x: T) -> T { x * 2 }
+    |                          ^^^^^ with T = u32
+    |                          ^ occurred 3 times, e.g. with T = u32
+
+warning: unused result
+   --> column 27 line 1 in input.txt
+");
+
+        // Without merging, the repeats are only grouped as diagnostics on the same line, keeping every note
+        let batch = DiagnosticBatch::new(&settings, &walkers, &diagnostics).merge_repeats(|_| None);
+        assert_eq!(batch.to_string().matches("with T = ").count(), 3);
+    }
+}
@@ -1,22 +1,293 @@
-use std::str::Lines;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use crate::{Location, Span, FileWalker, ErrorLevel};
+use crate::{Location, Span, SyntheticSpan, FileWalker, ErrorLevel, SpannedLines};
+use crate::json::{Json, ToJson};
 
 const CLEAR: &str = "\x1b[0m";
 const RED: &str = "\x1b[31m";
 const YELLOW: &str = "\x1b[33m";
 const CYAN: &str = "\x1b[36m";
 const WHITE: &str = "\x1b[37m";
+const GREEN: &str = "\x1b[32m";
+const BLUE: &str = "\x1b[34m";
+const MAGENTA: &str = "\x1b[35m";
+
+/// Shown in place of the line a note is on when the file ends before it, such as a note on an empty file
+const END_OF_FILE: &str = "<end of file>";
+
+/// Shown after the underline of a note which reaches past the end of its line, such as a note on a line break
+const END_OF_LINE: &str = "<eol>";
+
+
+/// How the lines of a snippet are numbered in its gutter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineNumbers {
+    /// Number lines by their line in the file
+    #[default]
+    Absolute,
+    /// Number lines from 1 at the first line of the snippet, for snippets embedded where the file's lines are meaningless
+    Relative,
+    /// Do not display the gutter at all
+    Hidden
+}
+
+/// The character a note underlines its span with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Underline {
+    /// `^`, or `+` for notes on inserted text
+    #[default]
+    Primary,
+    /// `-`, for notes which give context to a primary note
+    Secondary,
+    Custom(char)
+}
 
+/// A color a note can be displayed in instead of the color of its level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Red,
+    Yellow,
+    Green,
+    Cyan,
+    Blue,
+    Magenta,
+    White
+}
 
+impl Color {
+    /// Get the escape code which switches the terminal to the color
+    fn code(&self) -> &'static str {
+        match self {
+            Color::Red => RED,
+            Color::Yellow => YELLOW,
+            Color::Green => GREEN,
+            Color::Cyan => CYAN,
+            Color::Blue => BLUE,
+            Color::Magenta => MAGENTA,
+            Color::White => WHITE,
+        }
+    }
+}
+
+/// How errors are rendered, constructed from the default settings with the `with_*` methods so new settings can be added
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub struct ErrorDisplaySettings {
-    pub colored: bool
+    /// Color the output with terminal escape codes
+    pub colored: bool,
+    /// The maximum number of notes to render for a single error, the remaining notes are summarized in a footer
+    pub max_notes: Option<usize>,
+    /// Align notes using the display width of the text (with the `unicode-width` feature) rather than counting characters
+    pub display_width: bool,
+    /// The maximum number of characters of a line to display, longer lines are shown as a window around the notes on them
+    pub max_line_width: Option<usize>,
+    /// How lines are numbered in the gutter of a snippet
+    pub line_numbers: LineNumbers,
+    /// Show extra context after the snippet, such as the chain of rules being parsed when the error occurred
+    pub verbose: bool
 }
 
 impl std::default::Default for ErrorDisplaySettings {
     fn default() -> Self {
-        Self { colored: true }
+        Self { colored: true, max_notes: None, display_width: true, max_line_width: None, line_numbers: LineNumbers::Absolute, verbose: false }
+    }
+}
+
+impl ErrorDisplaySettings {
+    /// Set whether the output is colored
+    pub fn with_colored(mut self, colored: bool) -> Self {
+        self.colored = colored;
+        self
+    }
+
+    /// Set the maximum number of notes to render for a single error
+    pub fn with_max_notes(mut self, max_notes: Option<usize>) -> Self {
+        self.max_notes = max_notes;
+        self
+    }
+
+    /// Set whether notes are aligned using the display width of the text
+    pub fn with_display_width(mut self, display_width: bool) -> Self {
+        self.display_width = display_width;
+        self
+    }
+
+    /// Set the maximum number of characters of a line to display
+    pub fn with_max_line_width(mut self, max_line_width: Option<usize>) -> Self {
+        self.max_line_width = max_line_width;
+        self
+    }
+
+    /// Set how lines are numbered in the gutter of a snippet
+    pub fn with_line_numbers(mut self, line_numbers: LineNumbers) -> Self {
+        self.line_numbers = line_numbers;
+        self
+    }
+
+    /// Set whether extra context is shown after the snippet
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Get the number of columns text takes up when displayed
+    pub fn text_width(&self, text: &str) -> usize {
+        #[cfg(feature = "unicode-width")]
+        if self.display_width {
+            return unicode_width::UnicodeWidthStr::width(text);
+        }
+
+        text.chars().count()
+    }
+
+    /// Get the number of columns taken up by the text of a line before a given character column, or the column itself if the line is not known
+    fn prefix_width(&self, line: Option<&str>, column: usize) -> usize {
+        match line {
+            Some(line) => {
+                let end = line.char_indices().nth(column).map_or(line.len(), |(index, _)| index);
+                self.text_width(&line[..end]) + column.saturating_sub(line.chars().count())
+            }
+            None => column
+        }
+    }
+
+    /// Get the first character of a line to display so that the given column is visible, if lines are limited in length
+    pub fn window_start(&self, line: &str, column: usize) -> usize {
+        match self.max_line_width {
+            Some(width) => {
+                let length = line.chars().count();
+
+                if length <= width {
+                    0
+                }
+                else {
+                    column.saturating_sub(width / 3).min(length - width)
+                }
+            }
+            None => 0
+        }
+    }
+
+    /// Get the first character of a line to display so that the columns from `start` up to `end` are visible, or None if they do not
+    /// fit in one window, in which case the line is shown in full
+    pub fn window_spanning(&self, line: &str, start: usize, end: usize) -> Option<usize> {
+        let window_start = self.window_start(line, start);
+
+        match self.max_line_width {
+            Some(width) if end.saturating_sub(start) > width => None,
+            // The window is moved right until the end is visible, which keeps the start visible as they fit within its width
+            Some(width) => Some(window_start.max(end.min(line.chars().count()).saturating_sub(width))),
+            None => Some(window_start)
+        }
+    }
+
+    /// Get the gutter displayed before lines which are not lines of the file
+    fn empty_gutter(&self) -> &'static str {
+        match self.line_numbers {
+            LineNumbers::Hidden => "",
+            _ => "    |"
+        }
+    }
+
+    /// Get the indentation of lines listed after the snippet, which lines up with the gutter when it is shown
+    fn footer_indent(&self) -> &'static str {
+        match self.line_numbers {
+            LineNumbers::Hidden => "",
+            _ => "    "
+        }
+    }
+
+    /// Get the escape code of the color a note is displayed in, or nothing if colors are disabled
+    fn note_color(&self, note: &Note) -> &'static str {
+        if !self.colored {
+            return "";
+        }
+
+        match (note.color, note.error_level) {
+            (Some(color), _) => color.code(),
+            (None, ErrorLevel::Error) => RED,
+            (None, ErrorLevel::Warning) => YELLOW,
+            (None, ErrorLevel::Info) => CYAN,
+        }
+    }
+
+    /// Write the line under a line of the file which underlines the spans of notes sharing the label of the first, after the margin
+    /// which brackets spans over several lines
+    fn write_note_line(&self, f: &mut std::fmt::Formatter<'_>, margin: &str, line: Option<&str>, window_start: Option<usize>, notes: &[&Note]) -> std::fmt::Result {
+        let clear: &str = if self.colored { CLEAR } else { "" };
+        let cyan: &str = if self.colored { CYAN } else { "" };
+
+        let note = notes[0];
+        let color = self.note_color(note);
+
+        // Columns before the window are not displayed, but replaced by an ellipsis
+        let (line, ellipsis) = match window_start {
+            Some(window_start) if window_start > 0 => {
+                (line.map(|line| &line[line.char_indices().nth(window_start).map_or(line.len(), |(index, _)| index)..]), 3)
+            }
+            _ => (line, 0)
+        };
+
+        let mut spans: Vec<_> = notes.iter().map(|note| (&note.span, note.synthetic)).collect();
+        spans.sort_by_key(|(span, _)| span.location.column);
+
+        write!(f, "{cyan}{}{}", self.empty_gutter(), margin)?;
+        let marker = note.marker();
+        let mut written = 0;
+        for (i, (span, synthetic)) in spans.iter().enumerate() {
+            let mut column = span.location.column.saturating_sub(window_start.unwrap_or(0));
+
+            // Empty spans, such as the end of the file, are still marked with a single caret
+            let mut underline = self.text_width(span.data).max(1);
+
+            // Spans reaching past the end of the line, such as a line break, would underline nothing, so they are cut short at the end of
+            // the line and marked as reaching it. The text of a synthetic span is not in the line, so only its position is checked
+            let reach = if *synthetic { 0 } else { span.data.chars().count() };
+            let line_end = line.map(|line| (line, line.chars().count()))
+                .filter(|(_, length)| column > *length || (reach > 0 && column + reach > *length));
+            if let Some((line, length)) = line_end {
+                column = column.min(length);
+                if !synthetic {
+                    let start = line.char_indices().nth(column).map_or(line.len(), |(index, _)| index);
+                    underline = self.text_width(&line[start..]).max(1);
+                }
+            }
+
+            // A line shown in full has no window for the underline to be cut short at
+            if let (Some(width), Some(_)) = (self.max_line_width, window_start) {
+                underline = underline.min(width.saturating_sub(column).max(1));
+            }
+
+            let length = (ellipsis + self.prefix_width(line, column)).max(written);
+            write!(f, "{:1$}", "", length - written)?;
+            if i == 0 {
+                write!(f, "{color}")?;
+            }
+
+            for _ in 0..underline {
+                write!(f, "{}", marker)?;
+            }
+            written = length + underline;
+
+            if line_end.is_some() {
+                write!(f, "{}", END_OF_LINE)?;
+                written += END_OF_LINE.len();
+            }
+        }
+
+        if note.synthetic {
+            write!(f, " automatically inserted `{}`", note.span.data)?;
+            if !note.note.is_empty() {
+                write!(f, ", {}", note.note)?;
+            }
+            write!(f, "{clear}")
+        }
+        else if note.note.is_empty() {
+            write!(f, "{clear}")
+        }
+        else {
+            write!(f, " {}{clear}", note.note)
+        }
     }
 }
 
@@ -25,48 +296,391 @@ pub struct ErrorRender<'filedata, 'a> {
     level: ErrorLevel,
     settings: &'a ErrorDisplaySettings,
     message: &'a str,
-    primary_location: &'a Location<'filedata>,
+    primary_location: Location<'filedata>,
     notes: Vec<Note<'filedata, 'a>>,
-    walker: &'a FileWalker<'filedata>
+    /// Notes on spans which are not within the walker's file, which cannot be shown in the snippet
+    foreign_notes: Vec<Note<'filedata, 'a>>,
+    /// Snippets of the other files which notes are in, rendered after the snippet of the walker's file
+    other_files: Vec<ErrorRender<'filedata, 'a>>,
+    hidden_notes: usize,
+    walker: &'a FileWalker<'filedata>,
+    line_cache: Option<&'a LineCache>,
+    /// The rules being parsed when the error occurred, from the outermost, shown in verbose mode
+    rule_chain: &'a [&'static str],
+    /// The constructs being parsed when the error occurred, from the outermost, see `ParsingError::contexts`
+    context_chain: &'a [&'static str],
+    code: Option<&'a str>
+}
+
+/// The address of a line's data, its line number, the start of its window and the first line of its snippet
+type LineKey = (usize, usize, Option<usize>, usize);
+
+/// Formatted lines of snippets, shared between the renders of a batch so lines touched by many diagnostics are only formatted once
+///
+/// Every render using the cache must use the same settings, as they are not part of the key
+#[derive(Debug, Default)]
+pub(crate) struct LineCache {
+    lines: RefCell<HashMap<LineKey, Rc<str>>>
+}
+
+impl LineCache {
+    /// Get the formatted line, formatting it if it has not been seen yet
+    fn get_or_format(&self, line: &LineDisplay) -> Rc<str> {
+        // Lines are identified by the data they refer to, which is distinct for every line of every file being rendered.
+        // The first line of the snippet only changes the output when lines are numbered relative to it
+        let first_line = if line.settings.line_numbers == LineNumbers::Relative { line.first_line } else { 0 };
+        let key = (line.line_span.data.as_ptr() as usize, line.line_span.location.line, line.window_start, first_line);
+
+        self.lines.borrow_mut().entry(key).or_insert_with(|| line.to_string().into()).clone()
+    }
+
+    /// Get the number of distinct lines which have been formatted
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.lines.borrow().len()
+    }
 }
 
 impl<'filedata, 'a> ErrorRender<'filedata, 'a> {
     pub fn new(level: ErrorLevel, settings: &'a ErrorDisplaySettings, message: &'a str, primary_location: &'a Location<'filedata>, mut notes: Vec<Note<'filedata, 'a>>, walker: &'a FileWalker<'filedata>) -> Self {
-        // Now we need to rely on the notes being in sorted order, so we will need to do that first
-        notes.sort_by(|a, b| match a.span.location.line.cmp(&b.span.location.line) {
-            std::cmp::Ordering::Equal => b.span.location.column.cmp(&a.span.location.column),
-            default => default
-        });
-        
+        // If there are too many notes, keep the most severe ones, and of those, the ones nearest the primary location
+        let mut hidden_notes = 0;
+        if let Some(max_notes) = settings.max_notes {
+            if notes.len() > max_notes {
+                notes.sort_by_key(|note| (
+                    note.error_level.severity_rank(),
+                    note.span.location.line.abs_diff(primary_location.line),
+                    note.span.location.column.abs_diff(primary_location.column)
+                ));
+                hidden_notes = notes.len() - max_notes;
+                notes.truncate(max_notes);
+            }
+        }
+
+        // Notes with several spans are split into a note for each span, which are rendered together where they share a line
+        let notes: Vec<_> = notes.into_iter().enumerate().flat_map(|(group, note)| {
+            let group = if note.others.is_empty() { None } else { Some(group) };
+
+            std::iter::once(note.span).chain(note.others.iter().copied())
+                .map(move |span| Note { span, others: &[], group, ..note })
+        }).collect();
+
+        let (notes, foreign_notes) = Self::place_notes(notes, walker);
+
         Self {
             level,
             settings,
             message,
-            primary_location,
+            primary_location: *primary_location,
             notes,
-            walker
+            foreign_notes,
+            other_files: Vec::new(),
+            hidden_notes,
+            walker,
+            line_cache: None,
+            rule_chain: &[],
+            context_chain: &[],
+            code: None
+        }
+    }
+
+    /// Split notes into those within the walker's file, sorted for rendering, and those from other files
+    fn place_notes(notes: Vec<Note<'filedata, 'a>>, walker: &FileWalker<'filedata>) -> (Vec<Note<'filedata, 'a>>, Vec<Note<'filedata, 'a>>) {
+        // Spans from other files cannot be found in the walker's file, so they are listed separately rather than rendered from the wrong data
+        // Empty spans have no data to find them by, so they are placed by their location if it is in the walker's file
+        let (mut notes, foreign_notes): (Vec<_>, Vec<_>) = notes.into_iter().map(|note| {
+            if note.synthetic || !note.span.data.is_empty() || note.span.location.filename != walker.filename() || walker.contains_span(&note.span) {
+                return note;
+            }
+
+            match walker.empty_span_at(&note.span.location) {
+                Some(span) => Note { span, ..note },
+                None => note
+            }
+        }).partition(|note| if note.synthetic {
+            note.span.location.filename == walker.filename() && walker.empty_span_at(&note.span.location).is_some()
         }
+        else {
+            walker.contains_span(&note.span)
+        });
+
+        // Now we need to rely on the notes being in sorted order, so we will need to do that first
+        notes.sort_by(|a, b| match a.span.location.line.cmp(&b.span.location.line) {
+            std::cmp::Ordering::Equal => b.span.location.column.cmp(&a.span.location.column),
+            default => default
+        });
+
+        (notes, foreign_notes)
     }
+
+    /// Render the error from the original text a parsed buffer was produced from, such as a file before preprocessing
+    ///
+    /// Spans of the parsed text are resolved through the mapping, which returns the span of the original text they came from, or None if they have no origin.
+    /// Locations without a span, such as the primary location, are mapped as empty spans
+    pub fn with_source_map(mut self, original: &'a FileWalker<'filedata>, map: impl Fn(&Span<'filedata>) -> Option<Span<'filedata>>) -> Self {
+        let map_location = |location: &Location<'filedata>| self.walker.empty_span_at(location)
+            .and_then(|span| map(&span))
+            .map(|span| span.location);
+
+        let notes = self.notes.iter().chain(self.foreign_notes.iter()).map(|note| {
+            let span = if note.synthetic {
+                map_location(&note.span.location).map(|location| Span::from_components(location, note.span.data))
+            }
+            else {
+                map(&note.span)
+            };
+
+            Note { span: span.unwrap_or(note.span), ..*note }
+        }).collect();
+
+        if let Some(location) = map_location(&self.primary_location) {
+            self.primary_location = location;
+        }
+
+        (self.notes, self.foreign_notes) = Self::place_notes(notes, original);
+        self.walker = original;
+        self
+    }
+
+    /// Show the rules being parsed when the error occurred, from the outermost, as a chain after the snippet in verbose mode
+    pub fn with_rule_chain(mut self, rules: &'a [&'static str]) -> Self {
+        self.rule_chain = rules;
+        self
+    }
+
+    /// Show the constructs being parsed when the error occurred, from the outermost, as a chain after the snippet
+    ///
+    /// Unlike the rule chain, the context chain is always shown since the grammar chose to label these constructs
+    pub fn with_context_chain(mut self, contexts: &'a [&'static str]) -> Self {
+        self.context_chain = contexts;
+        self
+    }
+
+    /// Show the code identifying the kind of diagnostic after its level, like `error[E0001]`
+    pub fn with_code(mut self, code: Option<&'a str>) -> Self {
+        self.code = code;
+        self
+    }
+
+    /// Render the notes on spans in other files as snippets of those files, rather than listing their locations
+    ///
+    /// Each file with notes is shown after the snippet of the walker's file, in the order of the walkers. Notes in none of the files are still listed
+    pub fn with_other_files(mut self, walkers: &'a [&'a FileWalker<'filedata>]) -> Self {
+        for walker in walkers {
+            let (notes, foreign_notes) = Self::place_notes(std::mem::take(&mut self.foreign_notes), walker);
+            self.foreign_notes = foreign_notes;
+
+            let Some(first) = notes.iter().min_by_key(|note| (note.span.location.line, note.span.location.column)) else {
+                continue;
+            };
+
+            self.other_files.push(ErrorRender {
+                level: self.level,
+                settings: self.settings,
+                message: self.message,
+                primary_location: first.span.location,
+                notes,
+                foreign_notes: Vec::new(),
+                other_files: Vec::new(),
+                hidden_notes: 0,
+                walker,
+                line_cache: self.line_cache,
+                rule_chain: &[],
+                context_chain: &[],
+                code: None
+            });
+        }
+
+        self
+    }
+
+    /// Reuse the formatted lines of other renders with the same settings
+    pub(crate) fn with_line_cache(mut self, line_cache: &'a LineCache) -> Self {
+        self.line_cache = Some(line_cache);
+        for other in &mut self.other_files {
+            other.line_cache = Some(line_cache);
+        }
+        self
+    }
+
+    /// Get the lines of the snippet in the order they are displayed, with the notes on each
+    fn snippet_lines(&self) -> Vec<SnippetLine<'_, 'filedata, 'a>> {
+        let mut lines = Vec::new();
+        let mut next_line_needed = 0;
+        let mut first_line = None;
+
+        // Group the notes by line once, so each line is only looked up rather than searched for among every note
+        let mut notes_by_line: HashMap<usize, Vec<&Note>> = HashMap::new();
+        for note in &self.notes {
+            notes_by_line.entry(note.span.location.line).or_default().push(note);
+        }
+
+        let mut note_lines: Vec<_> = notes_by_line.keys().copied().collect();
+        note_lines.sort_unstable();
+
+        // Every note on a line shares the same region, so only one region is rendered per line with notes on it
+        for note_line in note_lines {
+            let note = notes_by_line[&note_line][0];
+
+            // Inserted text is not part of the file, so the region is found from where it was inserted
+            let region_span = if note.synthetic {
+                self.walker.empty_span_at(&note.span.location).unwrap()
+            }
+            else {
+                note.span
+            };
+
+            let region = match note.is_bracketed() {
+                true => RegionRender::covering(self.settings, &region_span, self.walker, 1),
+                false => RegionRender::new(self.settings, &region_span, self.walker, 1)
+            };
+
+            for line in region {
+                if line.line_span.location.line < next_line_needed { continue; }
+
+                let line_notes = notes_by_line.get(&line.line_span.location.line).cloned().unwrap_or_default();
+
+                // Keep the notes on the line visible if it is too long to show in full, or the note being rendered if there are none
+                let start = line_notes.iter().map(|other| other.span.location.column).min().unwrap_or(note.span.location.column);
+                let end = line_notes.iter().map(|other| other.end_column_on_line()).max().unwrap_or(note.span.location.column + 1);
+                let first_line = *first_line.get_or_insert(line.line_span.location.line);
+                let line = LineDisplay { settings: self.settings, ..line }.with_window_spanning(start, end).with_first_line(first_line);
+
+                next_line_needed = line.line_span.location.line + 1;
+                lines.push(SnippetLine { line, notes: line_notes, end_of_file: false });
+            }
+
+            // The file ends before the line of the note, such as a note at the end of an empty file or after a trailing newline
+            if note_line >= next_line_needed {
+                let location = Location::from_components(0, note_line, self.walker.filename());
+                let line = LineDisplay {
+                    line_span: Span::from_components(location, END_OF_FILE),
+                    settings: self.settings,
+                    window_start: Some(0),
+                    first_line: *first_line.get_or_insert(note_line)
+                };
+
+                next_line_needed = note_line + 1;
+                lines.push(SnippetLine { line, notes: notes_by_line[&note_line].clone(), end_of_file: true });
+            }
+        }
+
+        lines
+    }
+
+    /// Get the content of the render as plain data, rather than formatted text
+    pub fn to_debug_tree(&self) -> DebugTree<'_> {
+        let lines = self.snippet_lines().into_iter().map(|SnippetLine { line, notes, end_of_file }| DebugTreeLine {
+            line: line.line_span.location.line,
+            text: if end_of_file { None } else { Some(line.line_span.data) },
+            notes: MultiNoteDisplay::from_line_notes(self.settings, notes).rows().iter().map(|notes| debug_tree_note(notes)).collect()
+        }).collect();
+
+        DebugTree {
+            level: self.level,
+            message: self.message,
+            location: self.primary_location,
+            lines,
+            foreign_notes: self.other_files.iter().flat_map(|other| &other.notes).chain(&self.foreign_notes).map(|note| debug_tree_note(&[note])).collect(),
+            hidden_notes: self.hidden_notes
+        }
+    }
+}
+
+/// The content of an `ErrorRender` as plain data, for testing the renderer or laying out diagnostics in another interface
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugTree<'a> {
+    pub level: ErrorLevel,
+    pub message: &'a str,
+    pub location: Location<'a>,
+    /// The lines of the snippet, in the order they are displayed
+    pub lines: Vec<DebugTreeLine<'a>>,
+    /// Notes on spans in other files, which are not shown in the snippet
+    pub foreign_notes: Vec<DebugTreeNote<'a>>,
+    /// The number of notes left out because of `max_notes`
+    pub hidden_notes: usize
+}
+
+/// A line of the snippet of an `ErrorRender`, and the notes displayed under it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugTreeLine<'a> {
+    pub line: usize,
+    /// The text of the line, or None if the file ends before it
+    pub text: Option<&'a str>,
+    pub notes: Vec<DebugTreeNote<'a>>
+}
+
+/// A note of an `ErrorRender`, with every span it labels on a line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugTreeNote<'a> {
+    pub spans: Vec<Span<'a>>,
+    pub text: &'a str,
+    pub level: ErrorLevel,
+    pub synthetic: bool,
+    pub underline: Underline,
+    pub color: Option<Color>
+}
+
+/// A line of the snippet of an `ErrorRender`, with the notes displayed under it
+struct SnippetLine<'r, 'filedata, 'a> {
+    line: LineDisplay<'r, 'r>,
+    notes: Vec<&'r Note<'filedata, 'a>>,
+    /// Whether the line is shown in place of a line after the end of the file
+    end_of_file: bool
+}
+
+/// Describe the notes displayed on a row, which are the spans of a single note
+fn debug_tree_note<'a>(notes: &[&'a Note<'a, 'a>]) -> DebugTreeNote<'a> {
+    DebugTreeNote {
+        spans: notes.iter().map(|note| note.span).collect(),
+        text: notes[0].note,
+        level: notes[0].error_level,
+        synthetic: notes[0].synthetic,
+        underline: notes[0].underline,
+        color: notes[0].color
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderDisplay<'filedata, 'a> {
+    level: ErrorLevel,
+    settings: &'a ErrorDisplaySettings,
+    message: &'a str,
+    location: &'a Location<'filedata>,
+    /// The code identifying the kind of diagnostic, shown after the level like `error[E0001]`
+    code: Option<&'a str>
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct LineDisplay<'filedata, 'a> {
     pub line_span: Span<'filedata>,
     settings: &'a ErrorDisplaySettings,
+    /// The first character of the line which is displayed if it is too long to show in full, or None to show it in full anyway
+    window_start: Option<usize>,
+    /// The line of the file the snippet starts on, for numbering lines relative to the snippet
+    first_line: usize
 }
 
 #[derive(Debug, Clone)]
 pub struct RegionRender<'filedata, 'a> {
     settings: &'a ErrorDisplaySettings,
     pub location: Location<'filedata>,
-    lines: Lines<'filedata>
+    lines: SpannedLines<'filedata>
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Note<'filedata, 'a> {
-    span: &'a Span<'filedata>,
+    span: Span<'filedata>,
     note: &'a str,
-    error_level: ErrorLevel
+    error_level: ErrorLevel,
+    synthetic: bool,
+    /// Further spans labelled by the same note
+    others: &'a [Span<'filedata>],
+    /// The note that this one was split from, if it was one of the spans of a note with several
+    group: Option<usize>,
+    underline: Underline,
+    /// Displayed instead of the color of the note's level
+    color: Option<Color>
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -74,7 +688,12 @@ pub struct NoteDisplay<'filedata, 'a> {
     pub span: &'a Span<'filedata>,
     settings: &'a ErrorDisplaySettings,
     note: &'a str,
-    color: ErrorLevel
+    color: ErrorLevel,
+    line: Option<&'a str>,
+    window_start: Option<usize>,
+    synthetic: bool,
+    underline: Underline,
+    color_override: Option<Color>
 }
 
 
@@ -82,29 +701,188 @@ pub struct NoteDisplay<'filedata, 'a> {
 pub struct MultiNoteDisplay<'filedata, 'a> {
     notes: Vec<&'a Note<'filedata, 'a>>,
     settings: &'a ErrorDisplaySettings,
+    line: Option<&'a str>,
+    window_start: Option<usize>
 }
 
 impl<'filedata, 'a> MultiNoteDisplay<'filedata, 'a> {
     pub fn new(settings: &'a ErrorDisplaySettings, notes: &'a [Note<'filedata, 'a>], line: usize) -> Self {
-        let mut notes: Vec<_> = notes.iter().filter(|v| v.span.location.line == line).collect();
-        notes.sort_by(|a, b| b.span.location.column.cmp(&a.span.location.column));
+        Self::from_line_notes(settings, notes.iter().filter(|v| v.span.location.line == line).collect())
+    }
+
+    /// Construct a display of notes which are already known to be on the same line
+    pub fn from_line_notes(settings: &'a ErrorDisplaySettings, mut notes: Vec<&'a Note<'filedata, 'a>>) -> Self {
+        notes.sort_by_key(|v| std::cmp::Reverse(v.span.location.column));
 
         Self {
             settings,
-            notes
+            notes,
+            line: None,
+            window_start: Some(0)
+        }
+    }
+
+    /// Get the notes displayed on each row, the spans of a note with several share a single row
+    fn rows(&self) -> Vec<Vec<&'a Note<'filedata, 'a>>> {
+        let mut written_groups = Vec::new();
+        let mut rows = Vec::new();
+
+        for note in &self.notes {
+            match note.group {
+                Some(group) if written_groups.contains(&group) => {}
+                Some(group) => {
+                    written_groups.push(group);
+                    rows.push(self.notes.iter().copied().filter(|other| other.group == Some(group)).collect());
+                }
+                None => rows.push(vec![*note])
+            }
         }
+
+        rows
+    }
+
+    /// Provide the text of the line the notes are on, so they can be aligned by display width
+    pub fn with_line(mut self, line: &'a str) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    /// Align the notes with a line which is only displayed from the given character onwards, or shown in full if it is None
+    pub fn with_window_start(mut self, window_start: Option<usize>) -> Self {
+        self.window_start = window_start;
+        self
     }
 }
 
 
+impl<'filedata, 'a> HeaderDisplay<'filedata, 'a> {
+    pub fn new(level: ErrorLevel, settings: &'a ErrorDisplaySettings, message: &'a str, location: &'a Location<'filedata>) -> Self {
+        Self {
+            level,
+            settings,
+            message,
+            location,
+            code: None
+        }
+    }
+
+    /// Show the code identifying the kind of diagnostic after its level
+    pub fn with_code(mut self, code: Option<&'a str>) -> Self {
+        self.code = code;
+        self
+    }
+}
+
 impl<'filedata, 'a> Note<'filedata, 'a> {
-    pub fn new(span: &'a Span<'filedata>, text: &'a str, error_level: ErrorLevel) -> Self {
+    pub fn new(span: &Span<'filedata>, text: &'a str, error_level: ErrorLevel) -> Self {
         Self {
-            span,
+            span: *span,
+            note: text,
+            error_level,
+            synthetic: false,
+            others: &[],
+            group: None,
+            underline: Underline::Primary,
+            color: None
+        }
+    }
+
+    /// Construct a note which labels several spans at once, panics if there are no spans
+    pub fn multi(spans: &'a [Span<'filedata>], text: &'a str, error_level: ErrorLevel) -> Self {
+        Self {
+            span: spans[0],
+            note: text,
+            error_level,
+            synthetic: false,
+            others: &spans[1..],
+            group: None,
+            underline: Underline::Primary,
+            color: None
+        }
+    }
+
+    /// Construct a note on text which was inserted rather than appearing in the file, displayed as being automatically inserted
+    pub fn synthetic(span: &SyntheticSpan<'filedata>, text: &'a str, error_level: ErrorLevel) -> Self {
+        Self {
+            span: span.as_span(),
             note: text,
-            error_level
+            error_level,
+            synthetic: true,
+            others: &[],
+            group: None,
+            underline: Underline::Primary,
+            color: None
+        }
+    }
+
+    /// Get the span the note refers to
+    pub fn span(&self) -> &Span<'filedata> {
+        &self.span
+    }
+
+    /// Get the text of the note
+    pub fn text(&self) -> &'a str {
+        self.note
+    }
+
+    /// Get the level the note is displayed at
+    pub fn error_level(&self) -> ErrorLevel {
+        self.error_level
+    }
+
+    /// Get the spans labelled by the note after the first
+    pub fn other_spans(&self) -> &'a [Span<'filedata>] {
+        self.others
+    }
+
+    /// Check if the note refers to inserted text rather than the data of the file
+    pub fn is_synthetic(&self) -> bool {
+        self.synthetic
+    }
+
+    /// Get the character the note's span is underlined with, inserted text is marked differently as it does not appear in the line above
+    fn marker(&self) -> char {
+        match self.underline {
+            Underline::Primary if self.synthetic => '+',
+            Underline::Primary => '^',
+            Underline::Secondary => '-',
+            Underline::Custom(c) => c
         }
     }
+
+    /// Get the column after the last character the note marks on its first line, which is at least one past its start
+    fn end_column_on_line(&self) -> usize {
+        let marked = match self.synthetic {
+            true => 1,
+            false => self.span.data.split('\n').next().unwrap_or("").chars().count().max(1)
+        };
+
+        self.span.location.column + marked
+    }
+
+    /// Check if the note is drawn as a bracket in the margin, which is the case for spans whose text continues onto a later line
+    ///
+    /// Trailing whitespace and line breaks are not counted, so a span of a single line ending with a line break is drawn on that line
+    fn is_bracketed(&self) -> bool {
+        !self.synthetic && self.span.data.trim_end().contains('\n')
+    }
+
+    /// Get the location just after the last character a bracket around the note ends at, which ignores trailing whitespace
+    fn bracket_end(&self) -> Location<'filedata> {
+        self.span.trim_end().end_location()
+    }
+
+    /// Underline the note's span with a different character
+    pub fn with_underline(mut self, underline: Underline) -> Self {
+        self.underline = underline;
+        self
+    }
+
+    /// Display the note in a color other than the color of its level
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = Some(color);
+        self
+    }
 }
 
 impl<'filedata, 'a> NoteDisplay<'filedata, 'a> {
@@ -113,28 +891,77 @@ impl<'filedata, 'a> NoteDisplay<'filedata, 'a> {
             span,
             settings,
             note,
-            color
+            color,
+            line: None,
+            window_start: Some(0),
+            synthetic: false,
+            underline: Underline::Primary,
+            color_override: None
         }
     }
 
-    pub fn from_note(settings: &'a ErrorDisplaySettings, note: &Note<'filedata, 'a>) -> Self {
+    pub fn from_note(settings: &'a ErrorDisplaySettings, note: &'a Note<'filedata, 'a>) -> Self {
         Self {
-            span: note.span,
+            span: &note.span,
             settings,
             note: note.note,
-            color: note.error_level
+            color: note.error_level,
+            line: None,
+            window_start: Some(0),
+            synthetic: note.synthetic,
+            underline: note.underline,
+            color_override: note.color
         }
     }
+
+    /// Provide the text of the line the note is on, so it can be aligned by display width
+    pub fn with_line(mut self, line: &'a str) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    /// Align the note with a line which is only displayed from the given character onwards, or shown in full if it is None
+    pub fn with_window_start(mut self, window_start: Option<usize>) -> Self {
+        self.window_start = window_start;
+        self
+    }
+
+    /// Underline the span with a different character
+    pub fn with_underline(mut self, underline: Underline) -> Self {
+        self.underline = underline;
+        self
+    }
+
+    /// Display the note in a color other than the color of its level
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color_override = Some(color);
+        self
+    }
 }
 
-impl<'filedata, 'a: 'filedata> RegionRender<'filedata, 'a> {
-    pub fn new(settings: &'a ErrorDisplaySettings, span: &'a Span<'filedata>, walker: &'a FileWalker<'filedata>, width: usize) -> Self {
+impl<'filedata, 'a> RegionRender<'filedata, 'a> {
+    pub fn new(settings: &'a ErrorDisplaySettings, span: &Span, walker: &FileWalker<'filedata>, width: usize) -> Self {
         let region_span = walker.expand_span(span, width);
 
         Self {
             settings,
             location: region_span.location,
-            lines: region_span.data.lines(),
+            lines: FileWalker::from_span(&region_span).lines_spanned(),
+        }
+    }
+
+    /// Construct a render of every line a span covers, with `width` lines around its first and last lines, for spans over several lines
+    pub fn covering(settings: &'a ErrorDisplaySettings, span: &Span, walker: &FileWalker<'filedata>, width: usize) -> Self {
+        let end = Span::from_components(span.end_location(), &span.data[span.data.len()..]);
+        let (first, last) = (walker.expand_span(span, width), walker.expand_span(&end, width));
+
+        let data = walker.all_data();
+        let region_span = Span::from_components(first.location, &data[first.byte_range_in(data).start..last.byte_range_in(data).end]);
+
+        Self {
+            settings,
+            location: region_span.location,
+            lines: FileWalker::from_span(&region_span).lines_spanned(),
         }
     }
 }
@@ -143,90 +970,113 @@ impl<'filedata, 'a> std::iter::Iterator for RegionRender<'filedata, 'a> {
     type Item = LineDisplay<'filedata, 'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let this_location = self.location;
-        self.location.line += 1;
-        self.location.column = 0;
-
-        self.lines.next().map(|line| 
+        self.lines.next().map(|line_span|
             LineDisplay{
-                line_span: Span::from_components(this_location, line),
-                settings: self.settings
+                line_span,
+                settings: self.settings,
+                window_start: Some(0),
+                first_line: 0
             })
     }
 }
 
-impl<'filedata, 'a> std::fmt::Display for LineDisplay<'filedata, 'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let clear: &str = if self.settings.colored { CLEAR } else { "" };
-        let cyan: &str = if self.settings.colored { CYAN } else { "" };
+impl<'filedata, 'a> LineDisplay<'filedata, 'a> {
+    /// Display the line through a window which keeps the given column visible, if lines are limited in length
+    pub fn with_window_around(mut self, column: usize) -> Self {
+        self.window_start = Some(self.settings.window_start(self.line_span.data, column));
+        self
+    }
 
-        write!(f, "{cyan}{:3} |{clear}{}", self.line_span.location.line + 1, self.line_span.data)?;
+    /// Display the line through a window which keeps the columns from `start` up to `end` visible, or in full if they do not fit in one
+    pub fn with_window_spanning(mut self, start: usize, end: usize) -> Self {
+        self.window_start = self.settings.window_spanning(self.line_span.data, start, end);
+        self
+    }
 
-        Ok(())
+    /// Number the line relative to a snippet starting at the given line of the file, if lines are numbered relatively
+    pub fn with_first_line(mut self, first_line: usize) -> Self {
+        self.first_line = first_line;
+        self
+    }
+
+    /// Get the first character of the line which is displayed, or None if it is shown in full
+    pub fn window_start(&self) -> Option<usize> {
+        self.window_start
     }
 }
 
-impl<'filedata, 'a> std::fmt::Display for NoteDisplay<'filedata, 'a> {
+impl<'filedata, 'a> std::fmt::Display for LineDisplay<'filedata, 'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_with_margin(f, "")
+    }
+}
+
+impl LineDisplay<'_, '_> {
+    /// Write the line with a margin between the gutter and the text, which brackets spans over several lines
+    fn write_with_margin(&self, f: &mut std::fmt::Formatter<'_>, margin: &str) -> std::fmt::Result {
         let clear: &str = if self.settings.colored { CLEAR } else { "" };
         let cyan: &str = if self.settings.colored { CYAN } else { "" };
-        let red: &str = if self.settings.colored { RED } else { "" };
-        let yellow: &str = if self.settings.colored { YELLOW } else { "" };
 
-        let color = match self.color {
-            ErrorLevel::Error => red,
-            ErrorLevel::Warning => yellow,
-            ErrorLevel::Info => cyan,
-        };
+        match self.settings.line_numbers {
+            LineNumbers::Absolute => write!(f, "{cyan}{:3} |{clear}", self.line_span.location.line + 1)?,
+            LineNumbers::Relative => write!(f, "{cyan}{:3} |{clear}", self.line_span.location.line.saturating_sub(self.first_line) + 1)?,
+            LineNumbers::Hidden => {}
+        }
+        write!(f, "{}", margin)?;
 
-        let length = self.span.location.column;
+        match (self.settings.max_line_width, self.window_start) {
+            (Some(width), Some(window_start)) if self.line_span.data.chars().count() > width => {
+                if window_start > 0 {
+                    write!(f, "{cyan}...{clear}")?;
+                }
 
-        write!(f, "{cyan}    |{:1$}{color}", "", length)?;
+                let mut remaining = self.line_span.data.chars().skip(window_start);
+                for c in remaining.by_ref().take(width) {
+                    write!(f, "{}", c)?;
+                }
 
-        for _ in 0..self.span.data.chars().count() {
-            write!(f, "^")?;
+                if remaining.next().is_some() {
+                    write!(f, "{cyan}...{clear}")?;
+                }
+            }
+            _ => write!(f, "{}", self.line_span.data)?
         }
 
-        write!(f, " {}{clear}", self.note)?;
-
         Ok(())
     }
 }
 
-impl<'filedata, 'a> std::fmt::Display for MultiNoteDisplay<'filedata, 'a> {
+impl<'filedata, 'a> std::fmt::Display for NoteDisplay<'filedata, 'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let clear: &str = if self.settings.colored { CLEAR } else { "" };
-        let cyan: &str = if self.settings.colored { CYAN } else { "" };
-        let red: &str = if self.settings.colored { RED } else { "" };
-        let yellow: &str = if self.settings.colored { YELLOW } else { "" };
+        let note = Note {
+            span: *self.span,
+            note: self.note,
+            error_level: self.color,
+            synthetic: self.synthetic,
+            others: &[],
+            group: None,
+            underline: self.underline,
+            color: self.color_override
+        };
+        self.settings.write_note_line(f, "", self.line, self.window_start, &[&note])
+    }
+}
 
-        for (i, note) in self.notes.iter().enumerate() {
+impl<'filedata, 'a> std::fmt::Display for MultiNoteDisplay<'filedata, 'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, notes) in self.rows().iter().enumerate() {
             if i != 0 {
                 writeln!(f)?;
             }
 
-            let color = match note.error_level {
-                ErrorLevel::Error => red,
-                ErrorLevel::Warning => yellow,
-                ErrorLevel::Info => cyan,
-            };
-    
-            let length = note.span.location.column;
-    
-            write!(f, "{cyan}    |{:1$}{color}", "", length)?;
-    
-            for _ in 0..note.span.data.chars().count() {
-                write!(f, "^")?;
-            }
-    
-            write!(f, " {}{clear}", note.note)?;
+            self.settings.write_note_line(f, "", self.line, self.window_start, notes)?;
         }
 
         Ok(())
     }
 }
 
-impl<'filedata, 'a> std::fmt::Display for ErrorRender<'filedata, 'a> {
+impl<'filedata, 'a> std::fmt::Display for HeaderDisplay<'filedata, 'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let clear: &str = if self.settings.colored { CLEAR } else { "" };
         let cyan: &str = if self.settings.colored { CYAN } else { "" };
@@ -235,42 +1085,128 @@ impl<'filedata, 'a> std::fmt::Display for ErrorRender<'filedata, 'a> {
         let white: &str = if self.settings.colored { WHITE } else { "" };
 
         match self.level {
-            ErrorLevel::Error => write!(f, "{red}error{white}: "),
-            ErrorLevel::Warning => write!(f, "{yellow}warning{white}: "),
-            ErrorLevel::Info => write!(f, "{cyan}info{white}: "),
+            ErrorLevel::Error => write!(f, "{red}error"),
+            ErrorLevel::Warning => write!(f, "{yellow}warning"),
+            ErrorLevel::Info => write!(f, "{cyan}info"),
         }?;
 
+        if let Some(code) = self.code {
+            write!(f, "[{}]", code)?;
+        }
+        write!(f, "{white}: ")?;
+
         writeln!(f, "{}{cyan}", self.message)?;
-        writeln!(f, "   --> {clear}{}", self.primary_location)?;
+        write!(f, "   --> {clear}{}", self.location)?;
 
-        let mut next_line_needed = 0;
+        Ok(())
+    }
+}
 
-        // We rely here on the notes being sorted, this is done by having the only way to construct this object be by sorting the notes
-        for note in &self.notes {
-            let current_renderer = RegionRender::new(self.settings, note.span, self.walker, 1);
+/// The lines of the snippet of an `ErrorRender` and the notes under them, without the header or footers
+struct SnippetDisplay<'r, 'filedata, 'a>(&'r ErrorRender<'filedata, 'a>);
 
-            for line in current_renderer {
-                if line.line_span.location.line < next_line_needed { continue; }
-                writeln!(f, "{}", line)?;
-                next_line_needed = line.line_span.location.line + 1;
+impl std::fmt::Display for SnippetDisplay<'_, '_, '_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let render = self.0;
+
+        // Spans over several lines are bracketed in a margin with a column for each, the outermost first
+        let mut candidates: Vec<&Note> = render.notes.iter().filter(|note| note.is_bracketed()).collect();
+        candidates.sort_by_key(|note| (note.span.location.line, std::cmp::Reverse(note.bracket_end().line)));
+
+        // The connectors of brackets which overlap without nesting would cross each other's columns, so any bracket which would is
+        // left out and drawn on its first line like a span of a single line
+        let mut brackets: Vec<&Note> = Vec::new();
+        for note in candidates {
+            let (start, end) = (note.span.location.line, note.bracket_end().line);
+            if brackets.iter().all(|outer| outer.bracket_end().line < start || outer.bracket_end().line >= end) {
+                brackets.push(note);
+            }
+        }
+
+        if !brackets.is_empty() {
+            return self.write_bracketed(f, &brackets);
+        }
 
-                let mut line_note = None;
-
-                for note in &self.notes {
-                    if note.span.location.line == line.line_span.location.line {
-                        if line_note.is_none() {
-                            line_note = Some(note);
-                        }
-                        else {
-                            line_note = None;
-                            writeln!(f, "{}", MultiNoteDisplay::new(self.settings, &self.notes, note.span.location.line))?;
-                            break;
-                        }
-                    }
+        for SnippetLine { line, notes: line_notes, .. } in render.snippet_lines() {
+            match render.line_cache {
+                Some(cache) => writeln!(f, "{}", cache.get_or_format(&line))?,
+                None => writeln!(f, "{}", line)?
+            }
+
+            match line_notes[..] {
+                [] => {}
+                [note] => writeln!(f, "{}", NoteDisplay::from_note(render.settings, note).with_line(line.line_span.data).with_window_start(line.window_start()))?,
+                _ => writeln!(f, "{}", MultiNoteDisplay::from_line_notes(render.settings, line_notes).with_line(line.line_span.data).with_window_start(line.window_start()))?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SnippetDisplay<'_, '_, '_> {
+    /// Write the snippet with spans over several lines drawn like rustc, as a bracket in the margin from the start of the span on its
+    /// first line to the end of the span on its last line, which is labelled
+    fn write_bracketed(&self, f: &mut std::fmt::Formatter<'_>, brackets: &[&Note]) -> std::fmt::Result {
+        let render = self.0;
+        let settings = render.settings;
+        let clear: &str = if settings.colored { CLEAR } else { "" };
+        let cyan: &str = if settings.colored { CYAN } else { "" };
+
+        let mut open = vec![false; brackets.len()];
+        let margin = |open: &[bool], upto: usize| -> String {
+            let mut margin = String::from(" ");
+            for (bracket, open) in brackets.iter().zip(open).take(upto) {
+                match open {
+                    true => margin += &format!("{}|{clear} ", settings.note_color(bracket)),
+                    false => margin += "  "
+                }
+            }
+            margin
+        };
+
+        for SnippetLine { line, notes: line_notes, .. } in render.snippet_lines() {
+            let number = line.line_span.location.line;
+            line.write_with_margin(f, &margin(&open, brackets.len()))?;
+            writeln!(f)?;
+
+            let text = line.line_span.data;
+            let window_start = line.window_start().unwrap_or(0);
+            let write_connector = |f: &mut std::fmt::Formatter<'_>, open: &[bool], i: usize, column: usize, end: bool| {
+                let note = brackets[i];
+                let (shown, ellipsis) = match window_start {
+                    0 => (text, 0),
+                    _ => (&text[text.char_indices().nth(window_start).map_or(text.len(), |(index, _)| index)..], 3)
+                };
+                let width = ellipsis + settings.prefix_width(Some(shown), column.saturating_sub(window_start));
+
+                let color = settings.note_color(note);
+                write!(f, "{cyan}{}{clear}{}{color}", settings.empty_gutter(), margin(open, i))?;
+                write!(f, "{}{}{}", if end { '|' } else { ' ' }, "_".repeat(2 * (brackets.len() - i) - 1 + width), note.marker())?;
+                match end && !note.note.is_empty() {
+                    true => writeln!(f, " {}{clear}", note.note),
+                    false => writeln!(f, "{clear}")
                 }
+            };
+
+            // The start of each bracket is connected to the first character of its span, directly under the line
+            for (i, note) in brackets.iter().enumerate().filter(|(_, note)| note.span.location.line == number) {
+                write_connector(f, &open, i, note.span.location.column, false)?;
+                open[i] = true;
+            }
+
+            let line_notes: Vec<_> = line_notes.into_iter().filter(|note| !brackets.iter().any(|bracket| std::ptr::eq(*bracket, *note))).collect();
+            for notes in MultiNoteDisplay::from_line_notes(settings, line_notes).rows() {
+                settings.write_note_line(f, &margin(&open, brackets.len()), Some(text), line.window_start(), &notes)?;
+                writeln!(f)?;
+            }
 
-                if let Some(note) = line_note {
-                    writeln!(f, "{}", NoteDisplay::from_note(self.settings,note))?;
+            // Inner brackets end first, each connected to the last character of its span
+            for (i, note) in brackets.iter().enumerate().rev() {
+                let end = note.bracket_end();
+                if end.line == number {
+                    write_connector(f, &open, i, end.column - 1, true)?;
+                    open[i] = false;
                 }
             }
         }
@@ -279,6 +1215,101 @@ impl<'filedata, 'a> std::fmt::Display for ErrorRender<'filedata, 'a> {
     }
 }
 
+/// The range of a note as its file and the line and column it starts and ends at, an inserted span ends where it starts
+fn range_json(note: &Note) -> Json {
+    let position = |location: Location| Json::object([("column", location.column.to_json()), ("line", location.line.to_json())]);
+    let end = if note.synthetic { note.span.location } else { note.span.end_location() };
+
+    Json::object([
+        ("end", position(end)),
+        ("filename", note.span.location.filename.to_json()),
+        ("start", position(note.span.location)),
+    ])
+}
+
+/// The render as structured data for editors and CI tools, with the range of every note, including those in other files
+///
+/// Notes with several spans are listed once for each span, sharing a group number
+impl<'filedata, 'a> ToJson for ErrorRender<'filedata, 'a> {
+    fn to_json(&self) -> Json {
+        let notes = self.notes.iter().chain(self.other_files.iter().flat_map(|other| &other.notes)).chain(&self.foreign_notes).map(|note| Json::object([
+            ("group", note.group.to_json()),
+            ("level", note.error_level.to_json()),
+            ("message", note.note.to_json()),
+            ("range", range_json(note)),
+            ("synthetic", note.synthetic.to_json()),
+        ])).collect();
+
+        Json::object([
+            ("code", self.code.map_or(Json::Null, |code| code.to_json())),
+            ("contexts", Json::Array(self.context_chain.iter().map(|context| context.to_json()).collect())),
+            ("hidden_notes", self.hidden_notes.to_json()),
+            ("level", self.level.to_json()),
+            ("location", self.primary_location.to_json()),
+            ("message", self.message.to_json()),
+            ("notes", Json::Array(notes)),
+        ])
+    }
+}
+
+/// Render the lines around a span with the span underlined and labelled, without colors or a header, for embedding in log messages and panics
+///
+/// The `codeframe!` macro formats the label in place
+pub fn code_frame(walker: &FileWalker, span: &Span, label: &str) -> String {
+    let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+    let render = ErrorRender::new(ErrorLevel::Error, &settings, "", &span.location, vec![Note::new(span, label, ErrorLevel::Error)], walker);
+
+    SnippetDisplay(&render).to_string()
+}
+
+/// Render a code frame of a span with a label written like `format!`, as in `codeframe!(walker, span, "`{}` is never read", name)`
+///
+/// The label can be left out, and the walker can be any expression giving a `FileWalker` over the span's file, such as `source.walker()`
+#[macro_export]
+macro_rules! codeframe {
+    ($walker:expr, $span:expr) => {
+        $crate::errors::display::code_frame(&$walker, &$span, "")
+    };
+    ($walker:expr, $span:expr, $($label:tt)+) => {
+        $crate::errors::display::code_frame(&$walker, &$span, &format!($($label)+))
+    };
+}
+
+impl<'filedata, 'a> std::fmt::Display for ErrorRender<'filedata, 'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let clear: &str = if self.settings.colored { CLEAR } else { "" };
+        let cyan: &str = if self.settings.colored { CYAN } else { "" };
+
+        writeln!(f, "{}", HeaderDisplay::new(self.level, self.settings, self.message, &self.primary_location).with_code(self.code))?;
+        write!(f, "{}", SnippetDisplay(self))?;
+
+        for other in &self.other_files {
+            writeln!(f, "{cyan}   ::: {clear}{}", other.primary_location)?;
+            write!(f, "{}", SnippetDisplay(other))?;
+        }
+
+        for note in &self.foreign_notes {
+            writeln!(f, "{cyan}{}= {clear}<span from other file> at {}: {}", self.settings.footer_indent(), note.span.location, note.note)?;
+        }
+
+        match self.hidden_notes {
+            0 => {}
+            1 => writeln!(f, "{cyan}    ...{clear} and 1 more note")?,
+            hidden => writeln!(f, "{cyan}    ...{clear} and {} more notes", hidden)?,
+        }
+
+        let rule_chain = if self.settings.verbose { self.rule_chain } else { &[] };
+        for chain in [self.context_chain, rule_chain] {
+            for (depth, rule) in chain.iter().enumerate() {
+                let prefix = if depth == 0 { "=" } else { " " };
+                writeln!(f, "{cyan}    {prefix} {clear}{:indent$}while parsing {}", "", rule, indent = depth * 2)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -286,7 +1317,8 @@ mod test {
     #[test]
     fn line_display_no_color() {
         let settings = ErrorDisplaySettings {
-            colored: false
+            colored: false,
+            ..Default::default()
         };
 
         let line_display = LineDisplay {
@@ -299,6 +1331,8 @@ mod test {
                 data: "Hello World!",
             },
             settings: &settings,
+            window_start: Some(0),
+            first_line: 0,
         };
 
         assert_eq!(line_display.to_string(), " 42 |Hello World!");
@@ -307,7 +1341,8 @@ mod test {
     #[test]
     fn line_display_color() {
         let settings = ErrorDisplaySettings {
-            colored: true
+            colored: true,
+            ..Default::default()
         };
 
         let line_display = LineDisplay {
@@ -320,6 +1355,8 @@ mod test {
                 data: "Hello World!",
             },
             settings: &settings,
+            window_start: Some(0),
+            first_line: 0,
         };
 
         assert_eq!(line_display.to_string(), format!("{CYAN} 42 |{CLEAR}Hello World!"));
@@ -340,30 +1377,627 @@ mod test {
         let mut region_render0 = RegionRender::new(&settings, &inner_span, &walker, 0);
 
         assert_eq!(region_render0.next(), Some(LineDisplay { line_span: Span { location: 
-            Location { column: 0, line: 2, filename: "input.txt" }, data: "GHI" }, settings: &settings }));
+            Location { column: 0, line: 2, filename: "input.txt" }, data: "GHI" }, settings: &settings, window_start: Some(0), first_line: 0 }));
         assert_eq!(region_render0.next(), None);
 
 
         let mut region_render1 = RegionRender::new(&settings, &inner_span, &walker, 1);
 
         assert_eq!(region_render1.next(), Some(LineDisplay { line_span: Span { location: 
-            Location { column: 0, line: 1, filename: "input.txt" }, data: " DEF" }, settings: &settings }));
+            Location { column: 0, line: 1, filename: "input.txt" }, data: " DEF" }, settings: &settings, window_start: Some(0), first_line: 0 }));
         assert_eq!(region_render1.next(), Some(LineDisplay { line_span: Span { location: 
-            Location { column: 0, line: 2, filename: "input.txt" }, data: "GHI" }, settings: &settings }));
+            Location { column: 0, line: 2, filename: "input.txt" }, data: "GHI" }, settings: &settings, window_start: Some(0), first_line: 0 }));
         assert_eq!(region_render1.next(), Some(LineDisplay { line_span: Span { location: 
-            Location { column: 0, line: 3, filename: "input.txt" }, data: " JKL" }, settings: &settings }));
+            Location { column: 0, line: 3, filename: "input.txt" }, data: " JKL" }, settings: &settings, window_start: Some(0), first_line: 0 }));
         assert_eq!(region_render1.next(), None);
 
         let mut region_render2 = RegionRender::new(&settings, &inner_span, &walker, 2);
 
         assert_eq!(region_render2.next(), Some(LineDisplay { line_span: Span { location: 
-            Location { column: 0, line: 0, filename: "input.txt" }, data: "ABC" }, settings: &settings }));
+            Location { column: 0, line: 0, filename: "input.txt" }, data: "ABC" }, settings: &settings, window_start: Some(0), first_line: 0 }));
         assert_eq!(region_render2.next(), Some(LineDisplay { line_span: Span { location: 
-            Location { column: 0, line: 1, filename: "input.txt" }, data: " DEF" }, settings: &settings }));
+            Location { column: 0, line: 1, filename: "input.txt" }, data: " DEF" }, settings: &settings, window_start: Some(0), first_line: 0 }));
         assert_eq!(region_render2.next(), Some(LineDisplay { line_span: Span { location: 
-            Location { column: 0, line: 2, filename: "input.txt" }, data: "GHI" }, settings: &settings }));
+            Location { column: 0, line: 2, filename: "input.txt" }, data: "GHI" }, settings: &settings, window_start: Some(0), first_line: 0 }));
         assert_eq!(region_render2.next(), Some(LineDisplay { line_span: Span { location: 
-            Location { column: 0, line: 3, filename: "input.txt" }, data: " JKL" }, settings: &settings }));
+            Location { column: 0, line: 3, filename: "input.txt" }, data: " JKL" }, settings: &settings, window_start: Some(0), first_line: 0 }));
         assert_eq!(region_render2.next(), None);
     }
+
+    #[test]
+    fn error_render_truncated_notes() {
+        let settings = ErrorDisplaySettings::default().with_colored(false).with_max_notes(Some(2));
+
+        let input = "ABC\nDEF\nGHI\nJKL\nMNO";
+        let walker = FileWalker::from_data(input, "input.txt");
+
+        let spans = [
+            Span::from_components(Location::from_components(0, 0, "input.txt"), &input[0..1]),
+            Span::from_components(Location::from_components(1, 2, "input.txt"), &input[9..10]),
+            Span::from_components(Location::from_components(2, 3, "input.txt"), &input[14..15]),
+            Span::from_components(Location::from_components(0, 4, "input.txt"), &input[16..17]),
+        ];
+
+        let notes = vec![
+            Note::new(&spans[0], "far info", ErrorLevel::Info),
+            Note::new(&spans[1], "near info", ErrorLevel::Info),
+            Note::new(&spans[2], "near warning", ErrorLevel::Warning),
+            Note::new(&spans[3], "far error", ErrorLevel::Error),
+        ];
+
+        let primary = spans[1].location;
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, "Too many notes", &primary, notes, &walker);
+
+        assert_eq!(render.to_string(), "\
+error: Too many notes
+   --> column 2 line 3 in input.txt
+  3 |GHI
+  4 |JKL
+    |  ^ near warning
+  5 |MNO
+    |^ far error
+    ... and 2 more notes
+");
+    }
+
+    #[test]
+    fn note_display_char_count() {
+        let settings = ErrorDisplaySettings::default().with_colored(false).with_display_width(false);
+
+        let line = "let 日本 = 語;";
+        let span = Span::from_components(Location::from_components(9, 0, "input.txt"), &line[13..16]);
+
+        assert_eq!(NoteDisplay::new(&span, &settings, "here", ErrorLevel::Error).with_line(line).to_string(), "    |         ^ here");
+    }
+
+    #[test]
+    fn note_underline_and_color() {
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+
+        let data = "let x: u8 = \"one\";";
+        let walker = FileWalker::from_data(data, "input.txt");
+        let annotation = Span::from_components(Location::from_components(7, 0, "input.txt"), &data[7..9]);
+        let value = Span::from_components(Location::from_components(12, 0, "input.txt"), &data[12..17]);
+
+        let notes = vec![
+            Note::new(&value, "expected `u8`, found `&str`", ErrorLevel::Error),
+            Note::new(&annotation, "expected due to this", ErrorLevel::Info).with_underline(Underline::Secondary),
+        ];
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, "mismatched types", &value.location, notes, &walker);
+
+        assert_eq!(render.to_string(), "\
+error: mismatched types
+   --> column 13 line 1 in input.txt
+  1 |let x: u8 = \"one\";
+    |            ^^^^^ expected `u8`, found `&str`
+    |       -- expected due to this
+");
+
+        // A colour replaces the colour of the level, and custom underlines are used as is
+        let settings = ErrorDisplaySettings { colored: true, ..Default::default() };
+        let display = NoteDisplay::new(&value, &settings, "here", ErrorLevel::Error)
+            .with_underline(Underline::Custom('~'))
+            .with_color(Color::Green);
+
+        assert_eq!(display.to_string(), format!("{CYAN}    |{:12}{GREEN}~~~~~ here{CLEAR}", ""));
+    }
+
+    #[test]
+    fn error_render_debug_tree() {
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+
+        let data = "let a = 1;\nlet b = a + a;\n";
+        let walker = FileWalker::from_data(data, "input.txt");
+        let uses = [
+            Span::from_components(Location::from_components(8, 1, "input.txt"), &data[19..20]),
+            Span::from_components(Location::from_components(12, 1, "input.txt"), &data[23..24]),
+        ];
+        let end = Span::from_components(Location::from_components(0, 2, "input.txt"), "");
+
+        let notes = vec![
+            Note::multi(&uses, "used here", ErrorLevel::Info),
+            Note::new(&end, "expected `}`", ErrorLevel::Error).with_underline(Underline::Secondary),
+        ];
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, "unclosed block", &end.location, notes, &walker);
+
+        let note = |spans: Vec<Span<'static>>, text, level, underline| DebugTreeNote { spans, text, level, synthetic: false, underline, color: None };
+        assert_eq!(render.to_debug_tree(), DebugTree {
+            level: ErrorLevel::Error,
+            message: "unclosed block",
+            location: end.location,
+            lines: vec![
+                DebugTreeLine { line: 0, text: Some("let a = 1;"), notes: vec![] },
+                DebugTreeLine { line: 1, text: Some("let b = a + a;"), notes: vec![note(vec![uses[1], uses[0]], "used here", ErrorLevel::Info, Underline::Primary)] },
+                DebugTreeLine { line: 2, text: None, notes: vec![note(vec![Span::from_components(end.location, &data[26..])], "expected `}`", ErrorLevel::Error, Underline::Secondary)] },
+            ],
+            foreign_notes: vec![],
+            hidden_notes: 0
+        });
+    }
+
+    #[cfg(feature = "unicode-width")]
+    #[test]
+    fn note_display_unicode_width() {
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+
+        let line = "let 日本 = 語;";
+        let span = Span::from_components(Location::from_components(9, 0, "input.txt"), &line[13..16]);
+
+        assert_eq!(NoteDisplay::new(&span, &settings, "here", ErrorLevel::Error).with_line(line).to_string(), "    |           ^^ here");
+
+        // Without the line, the column is all there is to go on
+        assert_eq!(NoteDisplay::new(&span, &settings, "here", ErrorLevel::Error).to_string(), "    |         ^^ here");
+    }
+
+    #[test]
+    fn error_render_windowed_line() {
+        let settings = ErrorDisplaySettings::default().with_colored(false).with_max_line_width(Some(20));
+
+        let input = "short\nvar a=1;var b=2;var c=3;var d=4;var e=5;var f=6;";
+        let walker = FileWalker::from_data(input, "input.txt");
+
+        let span = Span::from_components(Location::from_components(28, 1, "input.txt"), &input[34..35]);
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, "Windowed", &span.location, vec![
+            Note::new(&span, "this one", ErrorLevel::Error)
+        ], &walker);
+
+        assert_eq!(render.to_string(), "\
+error: Windowed
+   --> column 29 line 2 in input.txt
+  1 |short
+  2 |...3;var d=4;var e=5;va...
+    |         ^ this one
+");
+
+        // Notes far apart on the line are all kept within the window
+        let first = Span::from_components(Location::from_components(14, 1, "input.txt"), &input[20..21]);
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, "Windowed", &span.location, vec![
+            Note::new(&first, "first", ErrorLevel::Info),
+            Note::new(&span, "this one", ErrorLevel::Error)
+        ], &walker);
+
+        crate::assert_text_eq!(render.to_string(), "\
+error: Windowed
+   --> column 29 line 2 in input.txt
+  1 |short
+  2 |...ar b=2;var c=3;var d...
+    |                      ^ this one
+    |        ^ first
+");
+
+        // Or if they do not fit in one window, the line is shown in full
+        let far = Span::from_components(Location::from_components(2, 1, "input.txt"), &input[8..9]);
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, "Windowed", &span.location, vec![
+            Note::new(&far, "far", ErrorLevel::Info),
+            Note::new(&span, "this one", ErrorLevel::Error)
+        ], &walker);
+
+        crate::assert_text_eq!(render.to_string(), "\
+error: Windowed
+   --> column 29 line 2 in input.txt
+  1 |short
+  2 |var a=1;var b=2;var c=3;var d=4;var e=5;var f=6;
+    |                            ^ this one
+    |  ^ far
+");
+
+        // Without a limit the whole line is shown
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, "Windowed", &span.location, vec![
+            Note::new(&span, "this one", ErrorLevel::Error)
+        ], &walker);
+
+        assert_eq!(render.to_string(), "\
+error: Windowed
+   --> column 29 line 2 in input.txt
+  1 |short
+  2 |var a=1;var b=2;var c=3;var d=4;var e=5;var f=6;
+    |                            ^ this one
+");
+    }
+
+    #[test]
+    fn error_render_end_of_line() {
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+
+        let input = "let a = 1\r\nlet b = 2;";
+        let walker = FileWalker::from_data(input, "input.txt");
+
+        // A line break is not shown, so a note on it is drawn at the end of the line and marked
+        let line_break = Span::from_components(Location::from_components(9, 0, "input.txt"), &input[9..11]);
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, "expected `;`", &line_break.location, vec![
+            Note::new(&line_break, "found the end of the line", ErrorLevel::Error)
+        ], &walker);
+
+        crate::assert_text_eq!(render.to_string(), "\
+error: expected `;`
+   --> column 10 line 1 in input.txt
+  1 |let a = 1
+    |         ^<eol> found the end of the line
+  2 |let b = 2;
+");
+
+        // Spans which continue onto the next line are bracketed from their start to their end, and the other notes are moved along
+        let value = Span::from_components(Location::from_components(8, 0, "input.txt"), &input[8..14]);
+        let end = Span::from_components(Location::from_components(9, 0, "input.txt"), &input[9..9]);
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, "expected `;`", &end.location, vec![
+            Note::new(&value, "statement continues here", ErrorLevel::Info),
+            Note::new(&end, "add `;` here", ErrorLevel::Error),
+        ], &walker);
+
+        crate::assert_text_eq!(render.to_string(), "\
+error: expected `;`
+   --> column 10 line 1 in input.txt
+  1 |   let a = 1
+    |  _________^
+    | |          ^ add `;` here
+  2 | | let b = 2;
+    | |___^ statement continues here
+");
+
+        // Lines between the first and last are shown inside the bracket
+        let input = "fn main() {\n    f(1,\n      2);\n}";
+        let walker = FileWalker::from_data(input, "input.txt");
+        let call = Span::from_components(Location::from_components(4, 1, "input.txt"), &input[16..29]);
+        assert_eq!(call.end_location(), Location::from_components(8, 2, "input.txt"));
+
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, "wrong number of arguments", &call.location, vec![
+            Note::new(&call, "expected 1 argument", ErrorLevel::Error)
+        ], &walker);
+
+        crate::assert_text_eq!(render.to_string(), "\
+error: wrong number of arguments
+   --> column 5 line 2 in input.txt
+  1 |   fn main() {
+  2 |       f(1,
+    |  _____^
+  3 | |       2);
+    | |________^ expected 1 argument
+  4 |   }
+");
+    }
+
+    #[test]
+    fn error_render_multi_line_brackets() {
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+
+        let input = "fn main() {\n    if x {\n        f();\n    }\n    g();\n}\n";
+        let walker = FileWalker::from_data(input, "input.txt");
+        let body = Span::from_components(Location::from_components(10, 0, "input.txt"), &input[10..52]);
+        let branch = Span::from_components(Location::from_components(4, 1, "input.txt"), &input[16..41]);
+        let call = Span::from_components(Location::from_components(4, 4, "input.txt"), &input[46..49]);
+
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, "unreachable code", &call.location, vec![
+            Note::new(&body, "in this function", ErrorLevel::Info).with_underline(Underline::Secondary),
+            Note::new(&branch, "this always returns", ErrorLevel::Info),
+            Note::new(&call, "never called", ErrorLevel::Error),
+        ], &walker);
+
+        crate::assert_text_eq!(render.to_string(), "\
+error: unreachable code
+   --> column 5 line 5 in input.txt
+  1 |     fn main() {
+    |  _____________-
+  2 | |       if x {
+    | |  _____^
+  3 | | |         f();
+  4 | | |     }
+    | | |_____^ this always returns
+  5 | |       g();
+    | |       ^^^ never called
+  6 | |   }
+    | |___- in this function
+");
+    }
+
+    #[test]
+    fn error_render_bracket_layout() {
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+
+        // A line break ending the span does not stop it being bracketed, and the bracket ends on the last character before it
+        let input = "fn main() {\n    f(1,\n      2);\n}";
+        let walker = FileWalker::from_data(input, "input.txt");
+        let call = Span::from_components(Location::from_components(4, 1, "input.txt"), &input[16..31]);
+        assert_eq!(call.data, "f(1,\n      2);\n");
+
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, "wrong number of arguments", &call.location, vec![
+            Note::new(&call, "expected 1 argument", ErrorLevel::Error)
+        ], &walker);
+
+        crate::assert_text_eq!(render.to_string(), "\
+error: wrong number of arguments
+   --> column 5 line 2 in input.txt
+  1 |   fn main() {
+  2 |       f(1,
+    |  _____^
+  3 | |       2);
+    | |_________^ expected 1 argument
+  4 |   }
+");
+
+        // Brackets which overlap without one inside the other would cross, so the later one is drawn on its first line instead
+        let input = "let x = (1 +\n  [2) * 3,\n  4];";
+        let walker = FileWalker::from_data(input, "input.txt");
+        let parens = Span::from_components(Location::from_components(8, 0, "input.txt"), &input[8..18]);
+        let brackets = Span::from_components(Location::from_components(2, 1, "input.txt"), &input[15..28]);
+        assert_eq!((parens.data, brackets.data), ("(1 +\n  [2)", "[2) * 3,\n  4]"));
+
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, "mismatched delimiters", &brackets.location, vec![
+            Note::new(&parens, "parenthesised here", ErrorLevel::Info),
+            Note::new(&brackets, "bracketed here", ErrorLevel::Error),
+        ], &walker);
+
+        crate::assert_text_eq!(render.to_string(), "\
+error: mismatched delimiters
+   --> column 3 line 2 in input.txt
+  1 |   let x = (1 +
+    |  _________^
+  2 | |   [2) * 3,
+    | |   ^^^^^^^^<eol> bracketed here
+    | |_____^ parenthesised here
+  3 |     4];
+");
+    }
+
+    #[test]
+    fn error_render_multi_span_note() {
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+
+        let input = "match x {\n    0 => 1, 1 => \"one\",\n    _ => true\n}";
+        let walker = FileWalker::from_data(input, "input.txt");
+
+        let spans = [
+            Span::from_components(Location::from_components(9, 1, "input.txt"), &input[19..20]),
+            Span::from_components(Location::from_components(17, 1, "input.txt"), &input[27..32]),
+            Span::from_components(Location::from_components(9, 2, "input.txt"), &input[43..47]),
+        ];
+        let scrutinee = Span::from_components(Location::from_components(6, 0, "input.txt"), &input[6..7]);
+
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, "Mismatched arms", &spans[0].location, vec![
+            Note::multi(&spans, "all of the arms return different types", ErrorLevel::Error),
+            Note::new(&scrutinee, "matched here", ErrorLevel::Info),
+        ], &walker);
+
+        assert_eq!(render.to_string(), "\
+error: Mismatched arms
+   --> column 10 line 2 in input.txt
+  1 |match x {
+    |      ^ matched here
+  2 |    0 => 1, 1 => \"one\",
+    |         ^       ^^^^^ all of the arms return different types
+  3 |    _ => true
+    |         ^^^^ all of the arms return different types
+  4 |}
+");
+    }
+
+    #[test]
+    fn error_render_line_numbers() {
+        let input = "a\nb\nc\nlet x = y;\nd";
+        let walker = FileWalker::from_data(input, "input.txt");
+        let span = Span::from_components(Location::from_components(8, 3, "input.txt"), &input[14..15]);
+
+        let render = |line_numbers| {
+            let settings = ErrorDisplaySettings::default().with_colored(false).with_line_numbers(line_numbers);
+            ErrorRender::new(ErrorLevel::Error, &settings, "Unknown variable", &span.location, vec![
+                Note::new(&span, "not found", ErrorLevel::Error)
+            ], &walker).to_string()
+        };
+
+        assert_eq!(render(LineNumbers::Relative), "\
+error: Unknown variable
+   --> column 9 line 4 in input.txt
+  1 |c
+  2 |let x = y;
+    |        ^ not found
+  3 |d
+");
+
+        assert_eq!(render(LineNumbers::Hidden), "\
+error: Unknown variable
+   --> column 9 line 4 in input.txt
+c
+let x = y;
+        ^ not found
+d
+");
+    }
+
+    #[test]
+    fn error_render_foreign_note() {
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+
+        let input = "let a = b;";
+        let other = "fn b() {}";
+        let walker = FileWalker::from_data(input, "input.txt");
+
+        let b = Span::from_components(Location::from_components(8, 0, "input.txt"), &input[8..9]);
+        let definition = Span::from_components(Location::from_components(3, 0, "other.txt"), &other[3..4]);
+
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, "Not a value", &b.location, vec![
+            Note::new(&b, "used as a value here", ErrorLevel::Error),
+            Note::new(&definition, "defined as a function here", ErrorLevel::Info),
+        ], &walker);
+
+        assert_eq!(render.to_string(), "\
+error: Not a value
+   --> column 9 line 1 in input.txt
+  1 |let a = b;
+    |        ^ used as a value here
+    = <span from other file> at column 4 line 1 in other.txt: defined as a function here
+");
+
+        // Without the gutter the footer is not indented, and inserted text in another file is listed there rather than placed at its column
+        let hidden = settings.clone().with_line_numbers(LineNumbers::Hidden);
+        let inserted = SyntheticSpan::from_components(Location::from_components(9, 0, "other.txt"), ";");
+        let hidden_render = ErrorRender::new(ErrorLevel::Error, &hidden, "Not a value", &b.location, vec![
+            Note::new(&b, "used as a value here", ErrorLevel::Error),
+            Note::synthetic(&inserted, "inserted", ErrorLevel::Info),
+        ], &walker);
+        assert_eq!(hidden_render.to_string(), "\
+error: Not a value
+   --> column 9 line 1 in input.txt
+let a = b;
+        ^ used as a value here
+= <span from other file> at column 10 line 1 in other.txt: inserted
+");
+
+        // With the other file, the note is shown in a snippet of its own
+        let other_walker = FileWalker::from_data(other, "other.txt");
+        let others = [&other_walker];
+        let render = render.with_other_files(&others);
+        assert_eq!(render.to_string(), "\
+error: Not a value
+   --> column 9 line 1 in input.txt
+  1 |let a = b;
+    |        ^ used as a value here
+   ::: column 4 line 1 in other.txt
+  1 |fn b() {}
+    |   ^ defined as a function here
+");
+        assert_eq!(render.to_debug_tree().foreign_notes.len(), 1);
+
+        // Notes in other files keep their own file in the structured form
+        assert_eq!(render.with_code(Some("E0001")).to_json().to_compact(), concat!(
+            r#"{"code":"E0001","contexts":[],"hidden_notes":0,"level":"error","location":{"column":8,"filename":"input.txt","line":0},"#,
+            r#""message":"Not a value","notes":[{"group":null,"level":"error","message":"used as a value here","#,
+            r#""range":{"end":{"column":9,"line":0},"filename":"input.txt","start":{"column":8,"line":0}},"synthetic":false},"#,
+            r#"{"group":null,"level":"info","message":"defined as a function here","#,
+            r#""range":{"end":{"column":4,"line":0},"filename":"other.txt","start":{"column":3,"line":0}},"synthetic":false}]}"#));
+    }
+
+    #[test]
+    fn error_render_json_ranges() {
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+
+        let input = "let s = \"a\nb\";\nlet t = s + s;";
+        let walker = FileWalker::from_data(input, "input.txt");
+        let string = Span::from_components(Location::from_components(8, 0, "input.txt"), &input[8..13]);
+        let uses = [
+            Span::from_components(Location::from_components(8, 2, "input.txt"), &input[23..24]),
+            Span::from_components(Location::from_components(12, 2, "input.txt"), &input[27..28]),
+        ];
+        let semicolon = SyntheticSpan::from_components(Location::from_components(14, 2, "input.txt"), ";");
+
+        let render = ErrorRender::new(ErrorLevel::Warning, &settings, "strings are added", &uses[0].location, vec![
+            Note::new(&string, "spans two lines", ErrorLevel::Info),
+            Note::multi(&uses, "added here", ErrorLevel::Warning),
+            Note::synthetic(&semicolon, "inserted", ErrorLevel::Info),
+        ], &walker).with_context_chain(&["statement"]);
+
+        let json = render.to_json();
+        let Json::Object(fields) = &json else { panic!("expected an object") };
+        let Json::Array(notes) = &fields["notes"] else { panic!("expected an array of notes") };
+        let field = |name: &'static str| move |note: &Json| match note {
+            Json::Object(fields) => fields[name].to_compact(),
+            _ => panic!("expected a note object")
+        };
+
+        // Notes are in the order they are shown, the spans of a note share a group, and inserted text ends where it starts
+        assert_eq!(notes.iter().map(field("range")).collect::<Vec<_>>(), [
+            r#"{"end":{"column":2,"line":1},"filename":"input.txt","start":{"column":8,"line":0}}"#,
+            r#"{"end":{"column":14,"line":2},"filename":"input.txt","start":{"column":14,"line":2}}"#,
+            r#"{"end":{"column":13,"line":2},"filename":"input.txt","start":{"column":12,"line":2}}"#,
+            r#"{"end":{"column":9,"line":2},"filename":"input.txt","start":{"column":8,"line":2}}"#,
+        ]);
+        assert_eq!(notes.iter().map(field("group")).collect::<Vec<_>>(), ["null", "null", "1", "1"]);
+        assert_eq!(fields["contexts"].to_compact(), r#"["statement"]"#);
+    }
+
+    #[test]
+    fn error_render_source_map() {
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+
+        // The parsed text has had a macro expanded from the original
+        let original = "x = SQUARE(y)\nz = 1";
+        let expanded = "x = y * y\nz = 1";
+        let original_walker = FileWalker::from_data(original, "input.txt");
+        let expanded_walker = FileWalker::from_data(expanded, "input.txt");
+
+        let y = Span::from_components(Location::from_components(4, 0, "input.txt"), &expanded[4..5]);
+        let z = Span::from_components(Location::from_components(0, 1, "input.txt"), &expanded[10..11]);
+
+        let map = |span: &Span<'static>| {
+            let start = span.data.as_ptr() as usize - expanded.as_ptr() as usize;
+            match start {
+                // The expansion maps back to the whole invocation
+                0..=3 => None,
+                4..=9 => Some(Span::from_components(Location::from_components(4, 0, "input.txt"), &original[4..13])),
+                _ => {
+                    let start = start + 4;
+                    Some(Span::from_components(Location::from_components(start - 14, 1, "input.txt"), &original[start..start + span.data.len()]))
+                }
+            }
+        };
+
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, "Unknown variable", &y.location, vec![
+            Note::new(&y, "not found", ErrorLevel::Error),
+            Note::new(&z, "next statement", ErrorLevel::Info),
+        ], &expanded_walker).with_source_map(&original_walker, map);
+
+        assert_eq!(render.to_string(), "\
+error: Unknown variable
+   --> column 5 line 1 in input.txt
+  1 |x = SQUARE(y)
+    |    ^^^^^^^^^ not found
+  2 |z = 1
+    |^ next statement
+");
+    }
+
+    #[test]
+    fn error_render_many_notes() {
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+
+        let input = "x;".repeat(2000);
+        let walker = FileWalker::from_data(&input, "input.txt");
+
+        let spans: Vec<_> = (0..2000).map(|i| Span::from_components(Location::from_components(i * 2, 0, "input.txt"), &input[i * 2..i * 2 + 1])).collect();
+        let notes = spans.iter().map(|span| Note::new(span, "unused", ErrorLevel::Warning)).collect();
+
+        let render = ErrorRender::new(ErrorLevel::Warning, &settings, "Unused values", &spans[0].location, notes, &walker).to_string();
+
+        // The line is shown once, with a row for every note on it
+        assert_eq!(render.lines().count(), 2 + 1 + 2000);
+        assert_eq!(render.lines().nth(3), Some(format!("    |{:3998}^ unused", "").as_str()));
+    }
+
+    #[test]
+    fn error_render_line_cache() {
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+
+        let data = "let a = 1;\nlet b = m!(a);\nlet c = 3;";
+        let walker = FileWalker::from_data(data, "input.txt");
+        let call = Span::from_components(Location::from_components(8, 1, "input.txt"), &data[19..25]);
+        let argument = Span::from_components(Location::from_components(11, 1, "input.txt"), &data[22..23]);
+
+        let cache = LineCache::default();
+        let notes = [(call, "expanded here"), (argument, "used here"), (call, "expanded here")];
+        for (span, text) in notes {
+            let uncached = ErrorRender::new(ErrorLevel::Error, &settings, "bad macro", &span.location, vec![Note::new(&span, text, ErrorLevel::Error)], &walker).to_string();
+            let cached = ErrorRender::new(ErrorLevel::Error, &settings, "bad macro", &span.location, vec![Note::new(&span, text, ErrorLevel::Error)], &walker)
+                .with_line_cache(&cache)
+                .to_string();
+
+            assert_eq!(cached, uncached);
+        }
+
+        // Only the three lines around the macro were formatted
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn code_frame_macro() {
+        let data = "let a = 1;\nlet b = a +;\nlet c = 3;";
+        let walker = FileWalker::from_data(data, "input.txt");
+        let plus = Span::from_components(Location::from_components(10, 1, "input.txt"), &data[21..22]);
+
+        crate::assert_text_eq!(codeframe!(walker, plus, "expected an operand after `{}`", plus.data), "  1 |let a = 1;
+  2 |let b = a +;
+    |          ^ expected an operand after `+`
+  3 |let c = 3;
+");
+
+        assert_eq!(codeframe!(walker, plus), code_frame(&walker, &plus, ""));
+    }
 }
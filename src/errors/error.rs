@@ -4,3 +4,14 @@ pub enum ErrorLevel {
     Warning,
     Info
 }
+
+impl ErrorLevel {
+    /// Rank the level by severity, with the most severe level ranked lowest
+    pub fn severity_rank(&self) -> u8 {
+        match self {
+            ErrorLevel::Error => 0,
+            ErrorLevel::Warning => 1,
+            ErrorLevel::Info => 2,
+        }
+    }
+}
@@ -1,5 +1,8 @@
 pub mod display;
 pub mod error;
+pub mod diagnostic;
+pub mod codes;
 
 pub use display::*;
-pub use error::*;
\ No newline at end of file
+pub use error::*;
+pub use diagnostic::*;
\ No newline at end of file
@@ -0,0 +1,270 @@
+//! A canonical JSON form of spans and diagnostics, for snapshot tests and golden files
+//!
+//! The output is written by hand rather than through serde, so it is byte for byte stable: object keys are sorted, numbers are
+//! only ever integers, strings escape only what JSON requires, and the compact form contains no whitespace
+
+use std::collections::BTreeMap;
+
+use crate::{Applicability, Diagnostic, DiagnosticNote, ErrorLevel, Location, Span, Suggestion, SyntheticSpan};
+
+/// A JSON value, objects keep their keys sorted so they are always written in the same order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    String(String),
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>)
+}
+
+/// Types with a canonical JSON form
+pub trait ToJson {
+    fn to_json(&self) -> Json;
+}
+
+impl Json {
+    /// Construct an object from its fields, in any order
+    pub fn object<'a>(fields: impl IntoIterator<Item = (&'a str, Json)>) -> Self {
+        Json::Object(fields.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+    }
+
+    /// Write the value without any whitespace
+    pub fn to_compact(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, None, 0);
+        out
+    }
+
+    /// Write the value with each array element and object field on its own line, indented by two spaces
+    pub fn to_pretty(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, Some(2), 0);
+        out
+    }
+
+    fn write(&self, out: &mut String, indent: Option<usize>, depth: usize) {
+        // Puts the next element on its own line when pretty printing
+        let newline = |out: &mut String, depth: usize| if let Some(indent) = indent {
+            out.push('\n');
+            out.extend(std::iter::repeat_n(' ', indent * depth));
+        };
+        let colon = if indent.is_some() { ": " } else { ":" };
+
+        match self {
+            Json::Null => out.push_str("null"),
+            Json::Bool(value) => out.push_str(if *value { "true" } else { "false" }),
+            Json::Integer(value) => out.push_str(&value.to_string()),
+            Json::String(value) => write_string(out, value),
+            Json::Array(values) if values.is_empty() => out.push_str("[]"),
+            Json::Array(values) => {
+                out.push('[');
+                for (i, value) in values.iter().enumerate() {
+                    if i != 0 {
+                        out.push(',');
+                    }
+                    newline(out, depth + 1);
+                    value.write(out, indent, depth + 1);
+                }
+                newline(out, depth);
+                out.push(']');
+            }
+            Json::Object(fields) if fields.is_empty() => out.push_str("{}"),
+            Json::Object(fields) => {
+                out.push('{');
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i != 0 {
+                        out.push(',');
+                    }
+                    newline(out, depth + 1);
+                    write_string(out, key);
+                    out.push_str(colon);
+                    value.write(out, indent, depth + 1);
+                }
+                newline(out, depth);
+                out.push('}');
+            }
+        }
+    }
+}
+
+/// Write a string literal, escaping only quotes, backslashes and control characters
+fn write_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+impl std::fmt::Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            write!(f, "{}", self.to_pretty())
+        }
+        else {
+            write!(f, "{}", self.to_compact())
+        }
+    }
+}
+
+impl ToJson for usize {
+    fn to_json(&self) -> Json {
+        Json::Integer(i64::try_from(*self).expect("integer is too large for canonical JSON"))
+    }
+}
+
+impl ToJson for bool {
+    fn to_json(&self) -> Json {
+        Json::Bool(*self)
+    }
+}
+
+impl ToJson for str {
+    fn to_json(&self) -> Json {
+        Json::String(self.to_string())
+    }
+}
+
+impl<T: ToJson> ToJson for [T] {
+    fn to_json(&self) -> Json {
+        Json::Array(self.iter().map(ToJson::to_json).collect())
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn to_json(&self) -> Json {
+        self.as_ref().map_or(Json::Null, ToJson::to_json)
+    }
+}
+
+impl ToJson for ErrorLevel {
+    fn to_json(&self) -> Json {
+        match self {
+            ErrorLevel::Error => "error",
+            ErrorLevel::Warning => "warning",
+            ErrorLevel::Info => "info",
+        }.to_json()
+    }
+}
+
+impl ToJson for Applicability {
+    fn to_json(&self) -> Json {
+        match self {
+            Applicability::MachineApplicable => "machine-applicable",
+            Applicability::MaybeIncorrect => "maybe-incorrect",
+            Applicability::HasPlaceholders => "has-placeholders",
+        }.to_json()
+    }
+}
+
+impl<'filedata> ToJson for Location<'filedata> {
+    fn to_json(&self) -> Json {
+        Json::object([
+            ("column", self.column.to_json()),
+            ("filename", self.filename.to_json()),
+            ("line", self.line.to_json()),
+        ])
+    }
+}
+
+impl<'filedata> ToJson for Span<'filedata> {
+    fn to_json(&self) -> Json {
+        Json::object([
+            ("data", self.data.to_json()),
+            ("location", self.location.to_json()),
+        ])
+    }
+}
+
+impl<'filedata> ToJson for SyntheticSpan<'filedata> {
+    fn to_json(&self) -> Json {
+        self.as_span().to_json()
+    }
+}
+
+impl<'filedata> ToJson for DiagnosticNote<'filedata> {
+    fn to_json(&self) -> Json {
+        Json::object([
+            ("level", self.level.to_json()),
+            ("message", self.message.to_json()),
+            ("span", self.span.to_json()),
+            ("synthetic", self.synthetic.to_json()),
+        ])
+    }
+}
+
+impl<'filedata> ToJson for Suggestion<'filedata> {
+    fn to_json(&self) -> Json {
+        Json::object([
+            ("applicability", self.applicability.to_json()),
+            ("message", self.message.to_json()),
+            ("replacement", self.replacement.to_json()),
+            ("span", self.span.to_json()),
+        ])
+    }
+}
+
+impl<'filedata> ToJson for Diagnostic<'filedata> {
+    fn to_json(&self) -> Json {
+        Json::object([
+            ("code", self.code.map_or(Json::Null, |code| code.to_json())),
+            ("level", self.level.to_json()),
+            ("location", self.location.to_json()),
+            ("message", self.message.to_json()),
+            ("notes", self.notes.to_json()),
+            ("suggestions", self.suggestions.to_json()),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::json::{Json, ToJson};
+    use crate::{Diagnostic, ErrorLevel, FileWalker, tag};
+
+    #[test]
+    fn canonical_json() {
+        let mut walker = FileWalker::from_data("let \"ö\" = 1;", "input.txt");
+        tag("let ")(&mut walker).unwrap();
+        let name = tag("\"ö\"")(&mut walker).unwrap();
+
+        assert_eq!(name.to_json().to_compact(), r#"{"data":"\"ö\"","location":{"column":4,"filename":"input.txt","line":0}}"#);
+
+        let diagnostic = Diagnostic::new(ErrorLevel::Error, "expected an identifier", name.location)
+            .with_note(&name, "a string\n", ErrorLevel::Info);
+
+        assert_eq!(diagnostic.to_json().to_compact(), concat!(
+            r#"{"code":null,"level":"error","location":{"column":4,"filename":"input.txt","line":0},"message":"expected an identifier","#,
+            r#""notes":[{"level":"info","message":"a string\n","span":{"data":"\"ö\"","location":{"column":4,"filename":"input.txt","line":0}},"#,
+            r#""synthetic":false}],"suggestions":[]}"#));
+
+        assert_eq!(format!("{:#}", Diagnostic::new(ErrorLevel::Warning, "unused", name.location).with_code("W0001").to_json()), r#"{
+  "code": "W0001",
+  "level": "warning",
+  "location": {
+    "column": 4,
+    "filename": "input.txt",
+    "line": 0
+  },
+  "message": "unused",
+  "notes": [],
+  "suggestions": []
+}"#);
+    }
+
+    #[test]
+    fn canonical_json_escapes() {
+        assert_eq!("tab\there \u{1} \u{7f} \\".to_json().to_compact(), "\"tab\\there \\u0001 \u{7f} \\\\\"");
+        assert_eq!(Json::object([("b", Json::Null), ("a", Json::Array(vec![]))]).to_compact(), r#"{"a":[],"b":null}"#);
+        assert_eq!(Some(3usize).to_json(), Json::Integer(3));
+    }
+}
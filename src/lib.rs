@@ -1,5 +1,48 @@
+//! Utilities for writing compilers, parser combinators over a `FileWalker` and rendering of diagnostics
+//!
+//! The items re-exported at the root of the crate are frozen, so existing `use compiler_utils::*` imports keep working,
+//! new subsystems are only exported from their own modules. Prefer `use compiler_utils::prelude::*` for the commonly used items
+
 pub mod errors;
 pub mod parser;
+pub mod testing;
+pub mod driver;
+pub mod prelude;
+pub mod json;
+#[cfg(feature = "cache")]
+pub mod cache;
+
+// The names exported at the root before the exports were frozen, later items are only exported from their own modules
+pub use errors::{display, error};
+pub use errors::{ErrorDisplaySettings, ErrorLevel, ErrorRender, LineDisplay, MultiNoteDisplay, Note, NoteDisplay, RegionRender};
+pub use parser::{location, parsers, walker};
+pub use parser::{combinators, leaves, r#trait};
+pub use parser::{ErrorKind, FileLocationMarker, FileWalker, Location, ParsingError, Span};
+pub use parser::{accepts, accepts_while, alt, but_not, map, one_of, opt, pair, tag, take_if, take_while, triple};
+
+// Within the crate every item is still reachable from the root
+pub(crate) use errors::*;
+pub(crate) use parser::*;
+
+// Parsing results are sent from worker threads to be reported elsewhere, so this fails to compile if any of these types stop being `Send + Sync`
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
 
-pub use errors::*;
-pub use parser::*;
\ No newline at end of file
+    assert_send_sync::<FileWalker>();
+    assert_send_sync::<Location>();
+    assert_send_sync::<Span>();
+    assert_send_sync::<SyntheticSpan>();
+    assert_send_sync::<ParsingError>();
+    assert_send_sync::<Diagnostic>();
+    assert_send_sync::<DiagnosticBatch>();
+    assert_send_sync::<ErrorDisplaySettings>();
+    assert_send_sync::<LineIndex>();
+    assert_send_sync::<CompactSpan>();
+    assert_send_sync::<CompactDiagnostic>();
+    assert_send_sync::<driver::source::SourceFile>();
+    assert_send_sync::<parser::intern::Interner>();
+    assert_send_sync::<parser::events::EventLog>();
+    assert_send_sync::<parser::lexer::TokenStream<()>>();
+    assert_send_sync::<parser::owned::OwnedSpan>();
+    assert_send_sync::<parser::state::StatefulWalker<()>>();
+};
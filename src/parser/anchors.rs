@@ -0,0 +1,223 @@
+use crate::{CompactSpan, LineIndex, Pos, Span};
+
+/// A replacement of a range of a file's data with new text
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Edit {
+    pub span: CompactSpan,
+    pub replacement: String
+}
+
+/// Identifies a span registered with `Anchors`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AnchorId(u32);
+
+/// Where an anchored span is after the edits applied since it was registered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnchorState {
+    /// No edit has touched the span, it refers to the same text, possibly moved
+    Intact(CompactSpan),
+    /// An edit changed part of the span, it covers the text which replaced it
+    Modified(CompactSpan),
+    /// An edit replaced all of the span
+    Deleted
+}
+
+/// Tracks spans of a file through edits, so positions such as breakpoints can be kept across reparses
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Anchors {
+    anchors: Vec<Option<AnchorState>>
+}
+
+impl Edit {
+    /// Construct an edit replacing the text of a span
+    pub fn replace(span: CompactSpan, replacement: impl Into<String>) -> Self {
+        Self { span, replacement: replacement.into() }
+    }
+
+    /// Construct an edit inserting text at a position
+    pub fn insert(pos: Pos, text: impl Into<String>) -> Self {
+        Self::replace(CompactSpan::from_components(pos, 0), text)
+    }
+
+    /// Construct an edit removing the text of a span
+    pub fn delete(span: CompactSpan) -> Self {
+        Self::replace(span, "")
+    }
+
+    /// Get the data of a file after the edit, panics if the span is not on character boundaries within the data
+    pub fn apply(&self, data: &str) -> String {
+        let range = self.span.byte_range();
+        assert!(data.get(range.clone()).is_some(), "edit does not refer to the file's data");
+
+        format!("{}{}{}", &data[..range.start], self.replacement, &data[range.end..])
+    }
+
+    /// Get the new position of text which started at `pos` before the edit, for a position outside of the edited span
+    fn shifted(&self, pos: Pos) -> Pos {
+        Pos(pos.0 - self.span.len + self.replacement.len() as u32)
+    }
+
+    /// Get the state of a span after the edit
+    fn update(&self, span: CompactSpan) -> AnchorState {
+        let edit_start = self.span.start;
+        let edit_end = self.span.end();
+
+        if span.end() <= edit_start {
+            // Entirely before the edit, an empty span at the position of an insertion stays before it
+            AnchorState::Intact(span)
+        }
+        else if span.start >= edit_end {
+            AnchorState::Intact(CompactSpan::from_components(self.shifted(span.start), span.len))
+        }
+        else if self.span.len > 0 && edit_start <= span.start && span.end() <= edit_end {
+            AnchorState::Deleted
+        }
+        else {
+            let start = span.start.min(edit_start);
+            let end = if span.end() > edit_end {
+                self.shifted(span.end())
+            }
+            else {
+                Pos(edit_start.0 + self.replacement.len() as u32)
+            };
+
+            AnchorState::Modified(CompactSpan::from_components(start, end.0 - start.0))
+        }
+    }
+}
+
+impl AnchorState {
+    /// Get the current span of the anchor, or None if it was deleted
+    pub fn span(&self) -> Option<CompactSpan> {
+        match self {
+            AnchorState::Intact(span) | AnchorState::Modified(span) => Some(*span),
+            AnchorState::Deleted => None
+        }
+    }
+}
+
+impl Anchors {
+    /// Construct an empty set of anchors
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a span, getting the id to look it up by after edits
+    pub fn anchor(&mut self, span: CompactSpan) -> AnchorId {
+        self.anchors.push(Some(AnchorState::Intact(span)));
+        AnchorId(self.anchors.len() as u32 - 1)
+    }
+
+    /// Register a `Span`, or None if the span does not refer to the data of the index's file
+    pub fn anchor_span(&mut self, index: &LineIndex, span: &Span) -> Option<AnchorId> {
+        Some(self.anchor(index.compact(span)?))
+    }
+
+    /// Get the state of an anchor, or None if it was removed
+    pub fn get(&self, id: AnchorId) -> Option<AnchorState> {
+        self.anchors.get(id.0 as usize).copied().flatten()
+    }
+
+    /// Stop tracking an anchor
+    pub fn remove(&mut self, id: AnchorId) {
+        if let Some(anchor) = self.anchors.get_mut(id.0 as usize) {
+            *anchor = None;
+        }
+    }
+
+    /// Update every anchor for an edit to the file, returning the anchors which the edit modified or deleted
+    pub fn apply(&mut self, edit: &Edit) -> Vec<(AnchorId, AnchorState)> {
+        let mut changed = Vec::new();
+
+        for (i, anchor) in self.anchors.iter_mut().enumerate() {
+            let Some(state) = anchor else { continue };
+            let Some(span) = state.span() else { continue };
+
+            let updated = match (edit.update(span), *state) {
+                (AnchorState::Intact(span), AnchorState::Modified(_)) => AnchorState::Modified(span),
+                (updated, _) => updated
+            };
+
+            if !matches!(edit.update(span), AnchorState::Intact(_)) {
+                changed.push((AnchorId(i as u32), updated));
+            }
+
+            *state = updated;
+        }
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{CompactSpan, FileWalker, LineIndex, Pos, tag};
+    use crate::parser::anchors::{AnchorState, Anchors, Edit};
+
+    #[test]
+    fn anchors_through_edits() {
+        let data = "let a = 1;\nlet b = 2;\nlet c = 3;";
+        let mut walker = FileWalker::from_data(data, "input");
+        let index = LineIndex::from_walker(&walker);
+
+        let mut anchors = Anchors::new();
+        let a = anchors.anchor_span(&index, &tag("let a = 1;")(&mut walker).unwrap()).unwrap();
+        tag("\n")(&mut walker).unwrap();
+        let b = anchors.anchor_span(&index, &tag("let b = 2;")(&mut walker).unwrap()).unwrap();
+        tag("\n")(&mut walker).unwrap();
+        let c = anchors.anchor_span(&index, &tag("let c = 3;")(&mut walker).unwrap()).unwrap();
+
+        // Changing the value of `b` modifies it and moves `c`
+        let edit = Edit::replace(CompactSpan::from_components(Pos(19), 1), "20");
+        let data = edit.apply(data);
+        assert_eq!(data, "let a = 1;\nlet b = 20;\nlet c = 3;");
+        assert_eq!(anchors.apply(&edit), vec![(b, AnchorState::Modified(CompactSpan::from_components(Pos(11), 11)))]);
+        assert_eq!(anchors.get(a), Some(AnchorState::Intact(CompactSpan::from_components(Pos(0), 10))));
+        assert_eq!(anchors.get(c), Some(AnchorState::Intact(CompactSpan::from_components(Pos(23), 10))));
+        assert_eq!(&data[anchors.get(c).unwrap().span().unwrap().byte_range()], "let c = 3;");
+
+        // An insertion at the start of a span moves it rather than modifying it
+        let edit = Edit::insert(Pos(0), "// Header\n");
+        let data = edit.apply(&data);
+        assert_eq!(anchors.apply(&edit), vec![]);
+        assert_eq!(&data[anchors.get(a).unwrap().span().unwrap().byte_range()], "let a = 1;");
+
+        // Removing the line of `b` deletes it, it stays modified
+        let edit = Edit::delete(CompactSpan::from_components(Pos(21), 12));
+        let data = edit.apply(&data);
+        assert_eq!(data, "// Header\nlet a = 1;\nlet c = 3;");
+        assert_eq!(anchors.apply(&edit), vec![(b, AnchorState::Deleted)]);
+        assert_eq!(anchors.get(b), Some(AnchorState::Deleted));
+        assert_eq!(&data[anchors.get(c).unwrap().span().unwrap().byte_range()], "let c = 3;");
+
+        // Removed anchors are no longer tracked
+        anchors.remove(c);
+        assert_eq!(anchors.get(c), None);
+        assert_eq!(anchors.apply(&Edit::delete(CompactSpan::from_components(Pos(21), 10))), vec![]);
+    }
+
+    #[test]
+    fn anchor_partial_overlap() {
+        let mut anchors = Anchors::new();
+        let word = anchors.anchor(CompactSpan::from_components(Pos(4), 6));
+
+        // Replacing text overlapping the start of the span extends the span over the replacement
+        let edit = Edit::replace(CompactSpan::from_components(Pos(2), 4), "X");
+        assert_eq!(anchors.apply(&edit), vec![(word, AnchorState::Modified(CompactSpan::from_components(Pos(2), 5)))]);
+
+        // And overlapping the end of the span
+        let edit = Edit::replace(CompactSpan::from_components(Pos(5), 4), "YZ");
+        assert_eq!(anchors.apply(&edit), vec![(word, AnchorState::Modified(CompactSpan::from_components(Pos(2), 5)))]);
+
+        // An insertion inside of the span modifies it
+        let empty = anchors.anchor(CompactSpan::from_components(Pos(20), 0));
+        let edit = Edit::insert(Pos(3), "__");
+        assert_eq!(anchors.apply(&edit), vec![(word, AnchorState::Modified(CompactSpan::from_components(Pos(2), 7)))]);
+        assert_eq!(anchors.get(empty), Some(AnchorState::Intact(CompactSpan::from_components(Pos(22), 0))));
+
+        // An empty span at the position of an insertion stays before the inserted text
+        let edit = Edit::insert(Pos(22), "abc");
+        assert_eq!(anchors.apply(&edit), vec![]);
+        assert_eq!(anchors.get(empty), Some(AnchorState::Intact(CompactSpan::from_components(Pos(22), 0))));
+    }
+}
@@ -0,0 +1,220 @@
+use crate::{FileWalker, Location, Span};
+
+/// A compact position within a file, stored as a byte offset into the file data
+///
+/// Positions can be converted back to a full `Location` using a `LineIndex` over the same file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Pos(pub u32);
+
+/// A compact span within a file, stored as a starting byte offset and a length in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct CompactSpan {
+    pub start: Pos,
+    pub len: u32
+}
+
+/// An index of the start of every line in a file, for converting compact positions to lines and columns on demand
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex<'filedata> {
+    all_data: &'filedata str,
+    filename: &'filedata str,
+    line_starts: Vec<u32>
+}
+
+impl Pos {
+    /// Get the byte offset this position refers to
+    pub fn offset(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl CompactSpan {
+    /// Construct a compact span from its components
+    pub fn from_components(start: Pos, len: u32) -> Self {
+        Self { start, len }
+    }
+
+    /// Get the position just after the end of the span
+    pub fn end(&self) -> Pos {
+        Pos(self.start.0 + self.len)
+    }
+
+    /// Get the range of bytes the span covers
+    pub fn byte_range(&self) -> std::ops::Range<usize> {
+        self.start.offset()..self.end().offset()
+    }
+}
+
+impl<'filedata> LineIndex<'filedata> {
+    /// Construct a new `LineIndex` from a name and data, panics if the data is too large to be addressed by a `Pos`
+    pub fn from_data(data: &'filedata str, filename: &'filedata str) -> Self {
+        // The end of the data is a position too, so it must fit even if no line starts near it
+        assert!(u32::try_from(data.len()).is_ok(), "file is too large for compact positions");
+
+        let line_starts = std::iter::once(0)
+            .chain(data.match_indices('\n').map(|(index, _)| index + 1))
+            .map(|start| u32::try_from(start).expect("file is too large for compact positions"))
+            .collect();
+
+        Self {
+            all_data: data,
+            filename,
+            line_starts
+        }
+    }
+
+    /// Construct a new `LineIndex` over the same file as a `FileWalker`
+    pub fn from_walker(walker: &FileWalker<'filedata>) -> Self {
+        Self::from_data(walker.all_data(), walker.filename())
+    }
+
+    /// Get the number of lines in the file
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Get the position of the start of a line, or None if the line does not exist
+    pub fn line_start(&self, line: usize) -> Option<Pos> {
+        self.line_starts.get(line).map(|start| Pos(*start))
+    }
+
+    /// Get the location of a position, or None if the position is not at a character boundary within the file
+    pub fn location(&self, pos: Pos) -> Option<Location<'filedata>> {
+        let offset = pos.offset();
+        if !self.all_data.is_char_boundary(offset) {
+            return None;
+        }
+
+        let line = self.line_starts.partition_point(|start| *start as usize <= offset) - 1;
+        let column = self.all_data[self.line_starts[line] as usize..offset].chars().count();
+
+        Some(Location::from_components(column, line, self.filename))
+    }
+
+    /// Expand a compact span into a full `Span`, or None if it does not lie on character boundaries within the file
+    pub fn span(&self, span: CompactSpan) -> Option<Span<'filedata>> {
+        let location = self.location(span.start)?;
+        let data = self.all_data.get(span.byte_range())?;
+
+        Some(Span::from_components(location, data))
+    }
+
+    /// Get the span covering a range of bytes of the file, or None if the range does not lie on character boundaries within the file
+    pub fn span_at(&self, range: std::ops::Range<usize>) -> Option<Span<'filedata>> {
+        let start = u32::try_from(range.start).ok()?;
+        let len = u32::try_from(range.end.checked_sub(range.start)?).ok()?;
+
+        self.span(CompactSpan::from_components(Pos(start), len))
+    }
+
+    /// Get the position of the start of a span, or None if the span does not refer to this file's data
+    pub fn pos_of(&self, span: &Span) -> Option<Pos> {
+        let start = self.all_data.as_ptr() as usize;
+        let span_start = span.data.as_ptr() as usize;
+
+        if span_start < start || span_start + span.data.len() > start + self.all_data.len() {
+            return None;
+        }
+
+        u32::try_from(span_start - start).ok().map(Pos)
+    }
+
+    /// Get the position of a location in this file, or None if the location is in another file or past the end of its line
+    pub fn pos_at(&self, location: &Location) -> Option<Pos> {
+        if location.filename != self.filename {
+            return None;
+        }
+
+        let start = *self.line_starts.get(location.line)? as usize;
+        let end = self.line_starts.get(location.line + 1).map_or(self.all_data.len(), |end| *end as usize);
+        let line = &self.all_data[start..end];
+
+        // The end of the line is only a column of its own on the last line, otherwise it is the start of the next line
+        let end_column = Some(line.len()).filter(|_| !line.ends_with('\n'));
+        let offset = line.char_indices().map(|(index, _)| index).chain(end_column).nth(location.column)?;
+
+        u32::try_from(start + offset).ok().map(Pos)
+    }
+
+    /// Compress a `Span` into a `CompactSpan`, or None if the span does not refer to this file's data
+    pub fn compact(&self, span: &Span) -> Option<CompactSpan> {
+        Some(CompactSpan::from_components(self.pos_of(span)?, u32::try_from(span.data.len()).ok()?))
+    }
+
+    /// Compress a `Span` like `compact`, except empty spans which may not refer to the file's data are placed by their location instead
+    pub fn compact_or_place(&self, span: &Span) -> Option<CompactSpan> {
+        if span.data.is_empty() {
+            Some(CompactSpan::from_components(self.pos_at(&span.location)?, 0))
+        }
+        else {
+            self.compact(span)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{CompactSpan, FileWalker, LineIndex, Location, Pos, Span, tag};
+
+    #[test]
+    fn line_index_location() {
+        let data = "Mö\nbi\r\nus";
+        let index = LineIndex::from_data(data, "hello.txt");
+
+        assert_eq!(index.line_count(), 3);
+        assert_eq!(index.line_start(1), Some(Pos(4)));
+        assert_eq!(index.line_start(3), None);
+
+        // Every character boundary should agree with the walker
+        let mut walker = FileWalker::from_data(data, "hello.txt");
+        loop {
+            assert_eq!(index.location(walker.current_pos()), Some(walker.current_location()));
+            if walker.step().is_none() {
+                break;
+            }
+        }
+
+        // But not inside of a character
+        assert_eq!(index.location(Pos(2)), None);
+    }
+
+    #[test]
+    fn line_index_round_trip() {
+        let data = "fn main() {\n    return 4;\n}";
+        let mut walker = FileWalker::from_data(data, "input");
+        let index = LineIndex::from_walker(&walker);
+
+        tag("fn main() {\n    ")(&mut walker).unwrap();
+        let span = tag("return")(&mut walker).unwrap();
+
+        let compact = index.compact(&span).unwrap();
+        assert_eq!(compact, CompactSpan::from_components(Pos(16), 6));
+        assert_eq!(index.span(compact), Some(span));
+        assert_eq!(index.span(compact).unwrap().location, Location::from_components(4, 1, "input"));
+
+        // Spans from other data cannot be compressed
+        assert_eq!(index.compact(&Span::from_components(Location::from_components(0, 0, "input"), "return")), None);
+
+        assert_eq!(index.span_at(16..22), Some(span));
+        assert_eq!(index.span_at(16..100), None);
+    }
+
+    #[test]
+    fn line_index_pos_at() {
+        let data = "Mö\nbi\r\nus";
+        let index = LineIndex::from_data(data, "hello.txt");
+
+        // Every character boundary should agree with the walker
+        let mut walker = FileWalker::from_data(data, "hello.txt");
+        loop {
+            assert_eq!(index.pos_at(&walker.current_location()), Some(walker.current_pos()));
+            if walker.step().is_none() {
+                break;
+            }
+        }
+
+        assert_eq!(index.pos_at(&Location::from_components(3, 0, "hello.txt")), None);
+        assert_eq!(index.pos_at(&Location::from_components(0, 3, "hello.txt")), None);
+        assert_eq!(index.pos_at(&Location::from_components(0, 0, "other.txt")), None);
+    }
+}
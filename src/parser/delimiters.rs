@@ -0,0 +1,164 @@
+use crate::{FileWalker, Pos, Span, tag};
+
+/// Quoted text, such as a string literal, which delimiters are not matched inside of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quoted {
+    pub open: &'static str,
+    pub close: &'static str,
+    /// A character which causes the character after it to be skipped, such as `\` in `"\""`
+    pub escape: Option<char>
+}
+
+/// The delimiters of a language, and the trivia which is skipped when matching them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DelimiterSpec {
+    /// Pairs of opening and closing delimiters
+    pub pairs: &'static [(&'static str, &'static str)],
+    /// Quoted text, which is matched from quote to quote
+    pub quoted: &'static [Quoted],
+    /// Comments which run to the end of the line
+    pub line_comments: &'static [&'static str],
+    /// Comments with an opening and closing delimiter, which do not nest
+    pub block_comments: &'static [(&'static str, &'static str)]
+}
+
+impl DelimiterSpec {
+    /// Brackets, strings and comments as in C
+    pub const C_LIKE: Self = Self {
+        pairs: &[("(", ")"), ("[", "]"), ("{", "}")],
+        quoted: &[
+            Quoted { open: "\"", close: "\"", escape: Some('\\') },
+            Quoted { open: "'", close: "'", escape: Some('\\') },
+        ],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")]
+    };
+}
+
+/// Step a walker forward over a number of bytes of its remaining input
+fn skip_bytes(walker: &mut FileWalker, length: usize) {
+    let end = walker.current_pos().offset() + length;
+    while walker.current_pos().offset() < end && walker.step().is_some() {}
+}
+
+/// Find the partner of the delimiter or quote which starts at a position in a file, or None if there is no delimiter there or it is unmatched
+///
+/// The file is scanned from its start, so delimiters within quoted text and comments are never matched
+pub fn find_matching_delimiter<'filedata>(walker: &FileWalker<'filedata>, pos: Pos, spec: &DelimiterSpec) -> Option<Span<'filedata>> {
+    let target = pos.offset();
+    let mut walker = FileWalker::from_data(walker.all_data(), walker.filename());
+
+    // The open delimiters, and the depth of the delimiter at the target once it has been found
+    let mut stack: Vec<(usize, Span<'filedata>)> = Vec::new();
+    let mut target_depth = None;
+
+    loop {
+        let at = walker.current_pos().offset();
+        let rest = walker.current_string();
+
+        if rest.is_empty() || (at > target && target_depth.is_none()) {
+            return None;
+        }
+
+        if spec.line_comments.iter().any(|open| rest.starts_with(open)) {
+            skip_bytes(&mut walker, rest.find('\n').unwrap_or(rest.len()));
+        }
+        else if let Some((open, close)) = spec.block_comments.iter().find(|(open, _)| rest.starts_with(open)) {
+            skip_bytes(&mut walker, rest[open.len()..].find(close).map_or(rest.len(), |index| open.len() + index + close.len()));
+        }
+        else if let Some(quoted) = spec.quoted.iter().find(|quoted| rest.starts_with(quoted.open)) {
+            let open_span = tag(quoted.open)(&mut walker).ok()?;
+
+            loop {
+                let close_at = walker.current_pos().offset();
+                if walker.current_string().starts_with(quoted.close) {
+                    let close_span = tag(quoted.close)(&mut walker).ok()?;
+
+                    if at == target {
+                        return Some(close_span);
+                    }
+                    if close_at == target {
+                        return Some(open_span);
+                    }
+                    break;
+                }
+
+                match walker.step() {
+                    Some(c) if Some(c) == quoted.escape => { walker.step(); }
+                    Some(_) => {}
+                    None => return None
+                }
+            }
+        }
+        else if let Some(pair) = spec.pairs.iter().position(|(open, _)| rest.starts_with(open)) {
+            stack.push((pair, tag(spec.pairs[pair].0)(&mut walker).ok()?));
+
+            if at == target {
+                target_depth = Some(stack.len());
+            }
+        }
+        else if let Some(pair) = spec.pairs.iter().position(|(_, close)| rest.starts_with(close)) {
+            let close_span = tag(spec.pairs[pair].1)(&mut walker).ok()?;
+
+            // A closing delimiter which does not match the innermost open delimiter is skipped
+            match stack.last() {
+                Some((open, _)) if *open == pair => {
+                    let (_, open_span) = stack.pop()?;
+
+                    if target_depth == Some(stack.len() + 1) {
+                        return Some(close_span);
+                    }
+                    if at == target {
+                        return Some(open_span);
+                    }
+                }
+                _ if at == target => return None,
+                _ => {}
+            }
+        }
+        else {
+            walker.step();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FileWalker, Location, Pos, Span};
+    use crate::parser::delimiters::{DelimiterSpec, find_matching_delimiter};
+
+    #[test]
+    fn matching_delimiters() {
+        let data = "f(a[1], \")\", /* ( */ g(b))\n// }\n{ '\\'' }";
+        let walker = FileWalker::from_data(data, "input");
+        let find = |offset: usize| find_matching_delimiter(&walker, Pos(offset as u32), &DelimiterSpec::C_LIKE);
+
+        // The outer call, skipping the string and the comment
+        assert_eq!(find(1), Some(Span::from_components(Location::from_components(25, 0, "input"), &data[25..26])));
+        assert_eq!(find(25), Some(Span::from_components(Location::from_components(1, 0, "input"), &data[1..2])));
+
+        assert_eq!(find(3).map(|span| span.location.column), Some(5));
+        assert_eq!(find(22).map(|span| span.location.column), Some(24));
+
+        // Quotes match each other
+        assert_eq!(find(8).map(|span| span.location.column), Some(10));
+        assert_eq!(find(10).map(|span| span.location.column), Some(8));
+
+        // The braces on the last line, ignoring the escaped quote
+        assert_eq!(find(32), Some(Span::from_components(Location::from_components(7, 2, "input"), &data[39..40])));
+        assert_eq!(find(34).map(|span| span.location.column), Some(5));
+
+        // Not a delimiter, or inside of a string or comment
+        assert_eq!(find(0), None);
+        assert_eq!(find(9), None);
+        assert_eq!(find(16), None);
+        assert_eq!(find(30), None);
+    }
+
+    #[test]
+    fn unmatched_delimiters() {
+        let walker = FileWalker::from_data("(a]", "input");
+        assert_eq!(find_matching_delimiter(&walker, Pos(0), &DelimiterSpec::C_LIKE), None);
+        assert_eq!(find_matching_delimiter(&walker, Pos(2), &DelimiterSpec::C_LIKE), None);
+    }
+}
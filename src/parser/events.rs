@@ -0,0 +1,154 @@
+use std::sync::Mutex;
+
+use crate::{FileWalker, Location, ParsingError, Span};
+
+/// An event emitted by a labelled rule while it is parsed, see `emit`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseEvent<'filedata> {
+    /// The rule started parsing at the location
+    Begin { rule: &'static str, location: Location<'filedata> },
+    /// The rule accepted the span, which covers the events since its `Begin`
+    End { rule: &'static str, span: Span<'filedata> },
+    /// The rule failed, so the events since its `Begin` were for input which may be parsed again by another alternative
+    Abandon { rule: &'static str, location: Location<'filedata> }
+}
+
+/// Receives the events of labelled rules as they are parsed, for consumers such as indexers and highlighters which do not need the
+/// parsed values
+///
+/// Any `Fn(ParseEvent)` is a sink, so a sink can count, filter or forward events without keeping them
+pub trait EventSink<'filedata> {
+    /// Receive the next event
+    fn event(&self, event: ParseEvent<'filedata>);
+}
+
+impl<'filedata, F: Fn(ParseEvent<'filedata>)> EventSink<'filedata> for F {
+    fn event(&self, event: ParseEvent<'filedata>) {
+        self(event)
+    }
+}
+
+/// A sink which keeps every event in order, for testing grammars and for consumers which want the whole stream
+#[derive(Debug, Default)]
+pub struct EventLog<'filedata> {
+    events: Mutex<Vec<ParseEvent<'filedata>>>
+}
+
+impl<'filedata> EventLog<'filedata> {
+    /// Construct a log without any events
+    pub const fn new() -> Self {
+        Self { events: Mutex::new(Vec::new()) }
+    }
+
+    /// Take the events received so far, leaving the log empty
+    pub fn take(&self) -> Vec<ParseEvent<'filedata>> {
+        std::mem::take(&mut *self.events.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl<'filedata> EventSink<'filedata> for EventLog<'filedata> {
+    fn event(&self, event: ParseEvent<'filedata>) {
+        self.events.lock().unwrap_or_else(|e| e.into_inner()).push(event);
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Labels a rule of the grammar, sending a `Begin` event to the sink when it starts and an `End` event with its span when it succeeds
+///
+/// Events are sent while parsing rather than collected, so memory does not grow with the input. When the rule fails an `Abandon` event
+/// is sent instead, since its input may be parsed again by another alternative
+pub fn emit<'filedata, 'a, T>(
+    sink: &'a impl EventSink<'filedata>,
+    rule: &'static str,
+    combinator: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>> + 'a,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>> + 'a {
+    move |walker: &mut FileWalker<'filedata>| {
+        let start = walker.get_marker();
+        sink.event(ParseEvent::Begin { rule, location: walker.current_location() });
+
+        match combinator(walker) {
+            Ok(value) => {
+                sink.event(ParseEvent::End { rule, span: walker.span_from_marker_to_here(start).unwrap() });
+                Ok(value)
+            }
+            Err(e) => {
+                walker.pop_back(start);
+                sink.event(ParseEvent::Abandon { rule, location: walker.current_location() });
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use crate::parser::events::{EventLog, EventSink, ParseEvent, emit};
+    use crate::{FileWalker, Location, ParsingError, alt, many0, map, one_of, pair, tag, take_while};
+
+    fn value<'filedata>(sink: &impl EventSink<'filedata>, walker: &mut FileWalker<'filedata>) -> Result<(), ParsingError<'filedata>> {
+        alt(
+            emit(sink, "number", map(take_while(|c| c.is_ascii_digit(), "a number"), |_| ())),
+            emit(sink, "list", |walker: &mut FileWalker<'filedata>| {
+                tag("[")(walker)?;
+                while one_of("]")(walker).is_err() {
+                    value(sink, walker)?;
+                    many0(tag(" "))(walker)?;
+                }
+                Ok(())
+            })
+        )(walker)
+    }
+
+    #[test]
+    fn event_stream() {
+        let log = EventLog::new();
+        let mut walker = FileWalker::from_data("[1 [23]]", "input");
+        value(&log, &mut walker).unwrap();
+
+        let events: Vec<String> = log.take().iter().map(|event| match event {
+            ParseEvent::Begin { rule, location } => format!("begin {} {}", rule, location.column),
+            ParseEvent::End { rule, span } => format!("end {} {:?}", rule, span.data),
+            ParseEvent::Abandon { rule, location } => format!("abandon {} {}", rule, location.column),
+        }).collect();
+
+        assert_eq!(events, [
+            "begin number 0", "abandon number 0",
+            "begin list 0",
+            "begin number 1", "end number \"1\"",
+            "begin number 3", "abandon number 3",
+            "begin list 3",
+            "begin number 4", "end number \"23\"",
+            "end list \"[23]\"",
+            "end list \"[1 [23]]\"",
+        ]);
+        assert!(log.take().is_empty());
+    }
+
+    #[test]
+    fn event_sink_closure() {
+        // A sink which keeps only the deepest nesting seen uses constant memory however long the input is
+        let (depth, deepest) = (Cell::new(0), Cell::new(0));
+        let sink = |event: ParseEvent| match event {
+            ParseEvent::Begin { rule: "list", .. } => {
+                depth.set(depth.get() + 1);
+                deepest.set(deepest.get().max(depth.get()));
+            }
+            ParseEvent::End { rule: "list", .. } | ParseEvent::Abandon { rule: "list", .. } => depth.set(depth.get() - 1),
+            _ => {}
+        };
+
+        let data = format!("{}{}", "[".repeat(50), "]".repeat(50));
+        value(&sink, &mut FileWalker::from_data(&data, "input")).unwrap();
+        assert_eq!((depth.get(), deepest.get()), (0, 50));
+
+        // A failing rule is abandoned at its start, with the walker moved back
+        let log = EventLog::new();
+        let mut walker = FileWalker::from_data("ab", "input");
+        assert!(emit(&log, "pair", pair(tag("a"), tag("c")))(&mut walker).is_err());
+        assert_eq!(walker.current_string(), "ab");
+        assert_eq!(log.take().last(), Some(&ParseEvent::Abandon { rule: "pair", location: Location::from_components(0, 0, "input") }));
+    }
+}
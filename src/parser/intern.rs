@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// The number of shards an `Interner` splits its strings between, so threads interning different strings rarely wait for each other
+const SHARDS: usize = 16;
+
+/// An interned string, which is compared and hashed as a number rather than by its text
+///
+/// Symbols are only meaningful to the `Interner` which created them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+/// A hasher for keys which are already hashes, so the map does not hash them again
+#[derive(Debug, Default)]
+struct Prehashed(u64);
+
+/// The strings of one shard, looked up by their hash
+#[derive(Debug, Default)]
+struct Shard {
+    /// The symbols of the strings with each hash, which only holds more than one symbol for a collision
+    by_hash: HashMap<u64, Vec<Symbol>, BuildHasherDefault<Prehashed>>,
+    strings: Vec<Arc<str>>
+}
+
+/// Interns strings as `Symbol`s, and can be shared between threads parsing files in parallel
+///
+/// Each string is hashed once with a fast non-cryptographic hash, which chooses the shard it is stored in, so only threads interning
+/// strings in the same shard contend for a lock. The predefined strings of `with_predefined` are numbered in order from zero, so their
+/// symbols are the same in every run and can be written as constants, other symbols depend on the order strings are first interned
+#[derive(Debug)]
+pub struct Interner {
+    predefined: Vec<Arc<str>>,
+    shards: [Mutex<Shard>; SHARDS]
+}
+
+impl Symbol {
+    /// Construct a symbol from its number, for writing the symbols of predefined strings as constants
+    pub const fn from_u32(number: u32) -> Self {
+        Self(number)
+    }
+
+    /// Get the number of the symbol
+    pub fn as_u32(&self) -> u32 {
+        self.0
+    }
+}
+
+impl Hasher for Prehashed {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, _: &[u8]) {
+        unreachable!("only hashes are used as keys")
+    }
+
+    fn write_u64(&mut self, hash: u64) {
+        self.0 = hash;
+    }
+}
+
+/// Hash a string in the style of `FxHash`, one word at a time
+fn hash(text: &str) -> u64 {
+    const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+    let add = |hash: u64, word: u64| (hash.rotate_left(5) ^ word).wrapping_mul(SEED);
+
+    let mut chunks = text.as_bytes().chunks_exact(8);
+    let mut hash = chunks.by_ref().fold(0, |hash, chunk| add(hash, u64::from_le_bytes(chunk.try_into().unwrap())));
+
+    let mut rest = [0; 8];
+    rest[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+    hash = add(hash, u64::from_le_bytes(rest));
+
+    // The length separates strings which differ only by trailing zero bytes
+    add(hash, text.len() as u64)
+}
+
+/// Get the shard a string is stored in from its hash
+fn shard_of(hash: u64) -> usize {
+    // The low bits choose the bucket within the shard's map, so the high bits choose the shard
+    (hash >> (u64::BITS - SHARDS.trailing_zeros())) as usize
+}
+
+impl Interner {
+    /// Construct an interner without any strings
+    pub fn new() -> Self {
+        Self::with_predefined(&[])
+    }
+
+    /// Construct an interner where each of the strings, such as the keywords of a language, has the symbol numbered by its index
+    ///
+    /// Panics if a string is repeated
+    pub fn with_predefined(strings: &[&str]) -> Self {
+        let interner = Self {
+            predefined: strings.iter().map(|text| Arc::from(*text)).collect(),
+            shards: std::array::from_fn(|_| Mutex::new(Shard::default()))
+        };
+
+        for (i, text) in strings.iter().enumerate() {
+            let hash = hash(text);
+            let mut shard = interner.shard(shard_of(hash));
+
+            assert!(interner.find(&shard, hash, text).is_none(), "`{}` is predefined more than once", text);
+            shard.by_hash.entry(hash).or_default().push(Symbol(i as u32));
+        }
+
+        interner
+    }
+
+    fn shard(&self, index: usize) -> std::sync::MutexGuard<'_, Shard> {
+        self.shards[index].lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn find(&self, shard: &Shard, hash: u64, text: &str) -> Option<Symbol> {
+        shard.by_hash.get(&hash)?.iter().copied().find(|symbol| self.text_in(shard, *symbol).as_deref() == Some(text))
+    }
+
+    fn text_in(&self, shard: &Shard, symbol: Symbol) -> Option<Arc<str>> {
+        match (symbol.0 as usize).checked_sub(self.predefined.len()) {
+            None => self.predefined.get(symbol.0 as usize).cloned(),
+            Some(number) => shard.strings.get(number / SHARDS).cloned()
+        }
+    }
+
+    /// Get the symbol of a string, interning it if it has not been seen before
+    pub fn intern(&self, text: &str) -> Symbol {
+        let hash = hash(text);
+        let shard_index = shard_of(hash);
+        let mut shard = self.shard(shard_index);
+
+        if let Some(symbol) = self.find(&shard, hash, text) {
+            return symbol;
+        }
+
+        // Symbols record their shard in their lowest bits, so they can be resolved by locking only that shard
+        let number = shard.strings.len() * SHARDS + shard_index + self.predefined.len();
+        let symbol = Symbol(u32::try_from(number).expect("too many interned strings"));
+
+        shard.strings.push(Arc::from(text));
+        shard.by_hash.entry(hash).or_default().push(symbol);
+
+        symbol
+    }
+
+    /// Get the symbol of a string without interning it, or None if it has not been interned
+    pub fn get(&self, text: &str) -> Option<Symbol> {
+        let hash = hash(text);
+        self.find(&self.shard(shard_of(hash)), hash, text)
+    }
+
+    /// Get the text of a symbol, or None if it was not created by this interner
+    ///
+    /// Predefined symbols are resolved without taking a lock
+    pub fn resolve(&self, symbol: Symbol) -> Option<Arc<str>> {
+        let number = (symbol.0 as usize).checked_sub(self.predefined.len());
+
+        match number {
+            None => self.predefined.get(symbol.0 as usize).cloned(),
+            Some(number) => self.shard(number % SHARDS).strings.get(number / SHARDS).cloned()
+        }
+    }
+
+    /// Get the number of strings which have been interned, including the predefined strings
+    pub fn len(&self) -> usize {
+        self.predefined.len() + (0..SHARDS).map(|index| self.shard(index).strings.len()).sum::<usize>()
+    }
+
+    /// Check if no strings have been interned
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::intern::{Interner, Symbol, hash};
+
+    #[test]
+    fn intern_and_resolve() {
+        const FN: Symbol = Symbol::from_u32(0);
+        const LET: Symbol = Symbol::from_u32(1);
+
+        let interner = Interner::with_predefined(&["fn", "let"]);
+        assert_eq!(interner.intern("let"), LET);
+        assert_eq!(interner.get("fn"), Some(FN));
+        assert_eq!(interner.resolve(FN).as_deref(), Some("fn"));
+
+        let x = interner.intern("x");
+        assert_eq!(interner.intern("x"), x);
+        assert_ne!(interner.intern("y"), x);
+        assert_eq!(interner.resolve(x).as_deref(), Some("x"));
+        assert_eq!(interner.get("z"), None);
+        assert_eq!(interner.resolve(Symbol(1000)), None);
+        assert_eq!(interner.len(), 4);
+
+        // Strings which differ only by trailing zero bytes still hash differently
+        assert_ne!(hash("a"), hash("a\0"));
+        assert_ne!(interner.intern("a"), interner.intern("a\0"));
+        assert_eq!(interner.intern(""), interner.intern(""));
+    }
+
+    #[test]
+    #[should_panic(expected = "`fn` is predefined more than once")]
+    fn repeated_predefined() {
+        let _ = Interner::with_predefined(&["fn", "let", "fn"]);
+    }
+
+    #[test]
+    fn intern_from_threads() {
+        let interner = Interner::with_predefined(&["fn"]);
+        let words: Vec<String> = (0..500).map(|i| format!("word{}", i)).collect();
+
+        // Every thread interns every word, in a different order, and they must all agree on the symbols
+        let symbols: Vec<Vec<Symbol>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4).map(|offset| {
+                let (interner, words) = (&interner, &words);
+                scope.spawn(move || {
+                    let mut symbols = vec![Symbol(0); words.len()];
+                    for i in (0..words.len()).map(|i| (i * 7 + offset * 131) % words.len()) {
+                        symbols[i] = interner.intern(&words[i]);
+                    }
+                    symbols
+                })
+            }).collect();
+
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+
+        assert!(symbols.iter().all(|other| *other == symbols[0]));
+        assert_eq!(interner.len(), 501);
+        for (word, symbol) in words.iter().zip(&symbols[0]) {
+            assert_eq!(interner.resolve(*symbol).as_deref(), Some(word.as_str()));
+        }
+    }
+}
@@ -0,0 +1,1123 @@
+//! Parsing over a stream of tokens rather than characters, for compilers with a separate lexing pass
+//!
+//! A lexer is any function which consumes one token from a `FileWalker`, and the tokens keep their spans so errors found while parsing
+//! them are reported at the text they came from
+//!
+//! Languages with string interpolation or embedded sub-languages lex differently depending on where they are, which a modal lexer
+//! tracks with a `ModeStack`. The rules of a modal lexer take the stack along with the walker, and `push_mode`, `pop_mode` and
+//! `in_mode` build them from ordinary lexer rules
+//!
+//! Editors relex a file on every edit, which an `IncrementalLexer` keeps cheap by restarting from a checkpoint of the lexer's modes
+//! before the edit, and stopping once they are the same as they were at a checkpoint after it
+
+use crate::parser::state::Snapshot;
+use crate::{CompactSpan, Diagnostic, ErrorKind, FileWalker, LineIndex, Location, ParsingError, Pos, Span, Spanned};
+
+/// The tokens of a file, in order, with the trivia between them removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenStream<'filedata, T> {
+    tokens: Vec<Spanned<'filedata, T>>,
+    data: &'filedata str,
+    /// The location after the last token, where missing tokens are reported
+    end: Location<'filedata>,
+    /// The offset of the end location within the data
+    end_offset: usize
+}
+
+/// A position within a `TokenStream`, which token parsers advance past the tokens they accept in place of a `FileWalker`
+///
+/// The position is an index into the stream's tokens, so markers, forks and backtracking never touch the text, while the spans of what
+/// was parsed still cover the text the tokens came from
+#[derive(Debug)]
+pub struct TokenWalker<'stream, 'filedata, T> {
+    stream: &'stream TokenStream<'filedata, T>,
+    index: usize
+}
+
+/// A kind of token which has a name to use in errors, so token parsers can report what they expected and found by it
+pub trait TokenKind {
+    /// Get the name of the kind, such as `identifier` for a kind whose text varies, or the text quoted with backticks for a kind such
+    /// as a comma which is always the same
+    fn name(&self) -> &'static str;
+}
+
+/// A marker for the position of a token within a stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TokenMarker {
+    index: usize
+}
+
+/// The modes of a modal lexer, such as inside a string or inside an expression interpolated into one, the innermost of which is current
+///
+/// The base mode is never popped, so a stray closing token cannot leave the lexer without a mode
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ModeStack<M> {
+    modes: Vec<M>
+}
+
+/// A lexer over text which is edited, relexing only from the checkpoint before each edit until the lexer is in the same state as it
+/// was at a checkpoint after it, then keeping the tokens it found before the edit from there
+///
+/// Checkpoints hold the modes at the first token boundary on or after every `lines_per_checkpoint` lines. Tokens are assumed to depend
+/// on no more of the text than the character after them, so lexers which look further ahead may be left with stale tokens
+#[derive(Debug)]
+pub struct IncrementalLexer<T, M, L> {
+    filename: String,
+    text: String,
+    lexer: L,
+    lines_per_checkpoint: usize,
+    tokens: Vec<(CompactSpan, T)>,
+    checkpoints: Vec<Checkpoint<M>>,
+    /// The offset and message of the error lexing stopped at
+    error: Option<(usize, String)>
+}
+
+/// The state of an `IncrementalLexer` at a token boundary, which lexing can restart from
+#[derive(Debug, Clone)]
+struct Checkpoint<M> {
+    offset: usize,
+    line: usize,
+    column: usize,
+    /// The index of the first token after the checkpoint
+    token: usize,
+    modes: ModeStack<M>
+}
+
+/// What an `IncrementalLexer` knew before an edit, from the checkpoint before the edit on, to resynchronize with after it
+struct Relex<T, M> {
+    /// The end of the edited text before and after the edit
+    old_end: usize,
+    new_end: usize,
+    removed_lines: usize,
+    added_lines: usize,
+    tokens: Vec<(CompactSpan, T)>,
+    checkpoints: Vec<Checkpoint<M>>,
+    error: Option<(usize, String)>
+}
+
+impl<'filedata, T> TokenStream<'filedata, T> {
+    /// Lex the rest of the walker's input, calling `next` for each token, which gives the token's kind or None if it is trivia
+    ///
+    /// Each token's span is the input `next` consumed. Lexing stops at the first error, or at a token which consumes nothing since
+    /// the lexer would never reach the end of the file
+    pub fn lex(
+        walker: &mut FileWalker<'filedata>,
+        next: impl Fn(&mut FileWalker<'filedata>) -> Result<Option<T>, ParsingError<'filedata>>
+    ) -> Result<Self, ParsingError<'filedata>> {
+        match Self::lex_partial(walker, next) {
+            (stream, None) => Ok(stream),
+            (_, Some(error)) => Err(error)
+        }
+    }
+
+    /// Lex like `lex`, but keep the tokens found before an error along with it, for tools which show how far the lexer got
+    ///
+    /// The walker is left at the start of the token which could not be lexed, and the end of the stream is there too
+    pub fn lex_partial(
+        walker: &mut FileWalker<'filedata>,
+        next: impl Fn(&mut FileWalker<'filedata>) -> Result<Option<T>, ParsingError<'filedata>>
+    ) -> (Self, Option<ParsingError<'filedata>>) {
+        Self::lex_with(walker, &mut ModeStack::new(()), |walker, _| next(walker), |_, error| Err(error))
+    }
+
+    /// Lex the rest of the walker's input like `lex`, but never stop at an error, for editors which need every token of a file even
+    /// while it is being edited
+    ///
+    /// Input which cannot be lexed becomes a token made by `error`, covering the character `next` failed at and those after it up to the
+    /// next boundary, such as whitespace, where lexing resumes. The error of each error token is returned as a diagnostic
+    pub fn lex_recovering(
+        walker: &mut FileWalker<'filedata>,
+        next: impl Fn(&mut FileWalker<'filedata>) -> Result<Option<T>, ParsingError<'filedata>>,
+        is_boundary: impl Fn(char) -> bool,
+        error: impl Fn(&ParsingError<'filedata>) -> T
+    ) -> (Self, Vec<Diagnostic<'filedata>>) {
+        let mut diagnostics = Vec::new();
+        let (stream, _) = Self::lex_with(walker, &mut ModeStack::new(()), |walker, _| next(walker), |walker, e| {
+            walker.step();
+            while walker.current_string().chars().next().is_some_and(|c| !is_boundary(c)) {
+                walker.step();
+            }
+
+            let token = error(&e);
+            diagnostics.push(Diagnostic::from_parsing_error(&e));
+            Ok(Some(token))
+        });
+
+        (stream, diagnostics)
+    }
+
+    /// Lex like `lex`, passing the modes to each call of `next`, which can change them, such as with the rules built by `push_mode`
+    ///
+    /// The modes are left as they were at the end of the input, so modes which are still pushed there are unterminated constructs
+    pub fn lex_modal<M>(
+        walker: &mut FileWalker<'filedata>,
+        modes: &mut ModeStack<M>,
+        next: impl Fn(&mut FileWalker<'filedata>, &mut ModeStack<M>) -> Result<Option<T>, ParsingError<'filedata>>
+    ) -> Result<Self, ParsingError<'filedata>> {
+        match Self::lex_with(walker, modes, next, |_, error| Err(error)) {
+            (stream, None) => Ok(stream),
+            (_, Some(error)) => Err(error)
+        }
+    }
+
+    /// Lex the rest of the walker's input, which every way of lexing a stream is built on
+    ///
+    /// When `next` fails, or accepts a token which consumes nothing, the walker is moved back to the start of the token and `recover`
+    /// is given the error, which either consumes some input as a token to keep lexing, or gives back an error to stop at
+    fn lex_with<M>(
+        walker: &mut FileWalker<'filedata>,
+        modes: &mut ModeStack<M>,
+        next: impl Fn(&mut FileWalker<'filedata>, &mut ModeStack<M>) -> Result<Option<T>, ParsingError<'filedata>>,
+        mut recover: impl FnMut(&mut FileWalker<'filedata>, ParsingError<'filedata>) -> Result<Option<T>, ParsingError<'filedata>>
+    ) -> (Self, Option<ParsingError<'filedata>>) {
+        let mut tokens = Vec::new();
+        let mut error = None;
+
+        while !walker.current_string().is_empty() {
+            match Self::lex_one(walker, modes, &next, &mut recover) {
+                Ok(Some(token)) => tokens.push(token),
+                Ok(None) => {}
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+
+        let end_offset = walker.all_data().len() - walker.current_string().len();
+        (Self { tokens, data: walker.all_data(), end: walker.current_location(), end_offset }, error)
+    }
+
+    /// Lex the token at the walker, which is None if it is trivia, see `lex_with` for how errors are recovered from
+    fn lex_one<M>(
+        walker: &mut FileWalker<'filedata>,
+        modes: &mut ModeStack<M>,
+        next: impl Fn(&mut FileWalker<'filedata>, &mut ModeStack<M>) -> Result<Option<T>, ParsingError<'filedata>>,
+        mut recover: impl FnMut(&mut FileWalker<'filedata>, ParsingError<'filedata>) -> Result<Option<T>, ParsingError<'filedata>>
+    ) -> Result<Option<Spanned<'filedata, T>>, ParsingError<'filedata>> {
+        let start = walker.get_marker();
+        let kind = next(walker, modes).and_then(|kind| match walker.span_from_marker_to_here(start).unwrap() {
+            span if span.data.is_empty() => Err(ParsingError(span.location, ErrorKind::ExpectedKind("a token which consumes input"))),
+            _ => Ok(kind)
+        });
+
+        let kind = kind.or_else(|e| {
+            walker.pop_back(start);
+            recover(walker, e)
+        })?;
+
+        Ok(kind.map(|kind| Spanned::new(walker.span_from_marker_to_here(start).unwrap(), kind)))
+    }
+
+    /// Get the tokens of the stream
+    pub fn tokens(&self) -> &[Spanned<'filedata, T>] {
+        &self.tokens
+    }
+
+    /// Get the number of tokens in the stream
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// Check if the stream has no tokens
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Get the data the tokens were lexed from
+    pub fn data(&self) -> &'filedata str {
+        self.data
+    }
+
+    /// Get the location after the last token
+    pub fn end_location(&self) -> Location<'filedata> {
+        self.end
+    }
+
+    /// Get the tokens which lie within a span of the stream's data, such as the inside of a balanced group, as a stream of their own
+    ///
+    /// The tokens keep their spans, so a parser run over the slice reports errors at their places in the file, and the slice ends at the
+    /// end of the span. Tokens which run past either end of the span are left out, and None is returned if the span is not in the data
+    pub fn slice(&self, span: &Span<'filedata>) -> Option<Self> where T: Clone {
+        let start = (span.data.as_ptr() as usize).checked_sub(self.data.as_ptr() as usize)?;
+        let range = Some(start..start + span.data.len()).filter(|range| range.end <= self.data.len())?;
+
+        let tokens = self.tokens.iter()
+            .filter(|token| {
+                let token_range = token.span.byte_range_in(self.data);
+                token_range.start >= range.start && token_range.end <= range.end
+            })
+            .cloned()
+            .collect();
+
+        Some(Self { tokens, data: self.data, end: span.end_location(), end_offset: range.end })
+    }
+
+    /// Remove the newline tokens which are not significant, for grammars where newlines end statements only in some places, such as
+    /// after a name or a closing bracket but not inside brackets
+    ///
+    /// The lexer keeps newlines as tokens, which `is_newline` picks out, and `significant` decides each one from the tokens kept before
+    /// it and the tokens after it, so it can look at its neighbours or count the brackets it is inside
+    pub fn filter_newlines(
+        &mut self,
+        is_newline: impl Fn(&T) -> bool,
+        significant: impl Fn(&[Spanned<'filedata, T>], &[Spanned<'filedata, T>]) -> bool
+    ) {
+        let mut tokens = std::mem::take(&mut self.tokens).into_iter();
+        while let Some(token) = tokens.next() {
+            if !is_newline(&token.value) || significant(&self.tokens, tokens.as_slice()) {
+                self.tokens.push(token);
+            }
+        }
+    }
+
+    /// Construct a walker at the first token of the stream
+    pub fn walker(&self) -> TokenWalker<'_, 'filedata, T> {
+        TokenWalker { stream: self, index: 0 }
+    }
+}
+
+impl<M> ModeStack<M> {
+    /// Construct a stack holding only the base mode
+    pub fn new(base: M) -> Self {
+        Self { modes: vec![base] }
+    }
+
+    /// Get the innermost mode
+    pub fn current(&self) -> &M {
+        self.modes.last().unwrap()
+    }
+
+    /// Enter a mode, which is current until it is popped
+    pub fn push(&mut self, mode: M) {
+        self.modes.push(mode);
+    }
+
+    /// Leave the current mode, returning it, or return None and stay in the base mode if no modes were pushed
+    pub fn pop(&mut self) -> Option<M> {
+        if self.modes.len() > 1 {
+            self.modes.pop()
+        }
+        else {
+            None
+        }
+    }
+
+    /// Get the number of modes pushed above the base mode
+    pub fn depth(&self) -> usize {
+        self.modes.len() - 1
+    }
+}
+
+impl<T, M, L> IncrementalLexer<T, M, L>
+where
+    M: Clone + PartialEq,
+    L: for<'filedata> Fn(&mut FileWalker<'filedata>, &mut ModeStack<M>) -> Result<Option<T>, ParsingError<'filedata>>
+{
+    /// Lex the whole of a text, starting in the base mode, panics if `lines_per_checkpoint` is zero
+    pub fn new(filename: impl Into<String>, text: impl Into<String>, base: M, lines_per_checkpoint: usize, lexer: L) -> Self {
+        assert!(lines_per_checkpoint > 0, "checkpoints must be at least one line apart");
+
+        let start = Checkpoint { offset: 0, line: 0, column: 0, token: 0, modes: ModeStack::new(base) };
+        let mut lexer = Self {
+            filename: filename.into(),
+            text: text.into(),
+            lexer,
+            lines_per_checkpoint,
+            tokens: Vec::new(),
+            checkpoints: vec![start],
+            error: None
+        };
+
+        lexer.relex(None);
+        lexer
+    }
+
+    /// Replace a range of bytes of the text, relexing the tokens around it, and return the range of tokens which were relexed
+    ///
+    /// Panics if the range does not lie on character boundaries of the text, like `String::replace_range`
+    pub fn edit(&mut self, range: std::ops::Range<usize>, replacement: &str) -> std::ops::Range<usize> {
+        let removed_lines = self.text[range.clone()].matches('\n').count();
+        self.text.replace_range(range.clone(), replacement);
+
+        // The token before the edit may have been cut short by what followed it, so lexing restarts at a checkpoint before its start
+        let restart = self.checkpoints.partition_point(|checkpoint| checkpoint.offset < range.start).max(1) - 1;
+        let checkpoints = self.checkpoints.split_off(restart + 1);
+        let tokens = self.tokens.split_off(self.checkpoints[restart].token);
+        let error = self.error.take();
+
+        self.relex(Some(Relex {
+            old_end: range.end,
+            new_end: range.start + replacement.len(),
+            removed_lines,
+            added_lines: replacement.matches('\n').count(),
+            tokens,
+            checkpoints,
+            error
+        }))
+    }
+
+    /// Lex from the last checkpoint, until the end of the text or until the state matches a checkpoint from before an edit
+    fn relex(&mut self, mut edit: Option<Relex<T, M>>) -> std::ops::Range<usize> {
+        let start = self.checkpoints.last().unwrap().clone();
+        let data = &self.text[start.offset..];
+        let mut walker = FileWalker::from_span(&Span::from_components(Location::from_components(start.column, start.line, &self.filename), data));
+        let mut modes = start.modes;
+        let mut next_line = (start.line / self.lines_per_checkpoint + 1) * self.lines_per_checkpoint;
+        self.error = None;
+
+        loop {
+            let offset = start.offset + data.len() - walker.current_string().len();
+            let location = walker.current_location();
+
+            if location.line >= next_line {
+                next_line = (location.line / self.lines_per_checkpoint + 1) * self.lines_per_checkpoint;
+
+                if let Some(edit) = edit.as_mut().filter(|edit| offset >= edit.new_end) {
+                    let old_offset = offset - edit.new_end + edit.old_end;
+                    let old_line = location.line + edit.removed_lines - edit.added_lines;
+                    let matching = edit.checkpoints.iter().position(|checkpoint| {
+                        checkpoint.offset == old_offset && checkpoint.line == old_line && checkpoint.modes == modes
+                    });
+
+                    if let Some(index) = matching {
+                        let relexed = start.token..self.tokens.len();
+                        let old_token = edit.checkpoints[index].token;
+                        let shift = |old: usize| old - edit.old_end + edit.new_end;
+
+                        self.tokens.extend(edit.tokens.drain(old_token - start.token..).map(|(span, token)| {
+                            let start = Pos(u32::try_from(shift(span.start.offset())).expect("file is too large for compact positions"));
+                            (CompactSpan::from_components(start, span.len), token)
+                        }));
+                        self.checkpoints.extend(edit.checkpoints.drain(index..).map(|checkpoint| Checkpoint {
+                            offset: shift(checkpoint.offset),
+                            line: checkpoint.line + edit.added_lines - edit.removed_lines,
+                            token: checkpoint.token - old_token + relexed.end,
+                            ..checkpoint
+                        }));
+                        self.error = edit.error.take().map(|(offset, message)| (shift(offset), message));
+
+                        return relexed;
+                    }
+                }
+
+                self.checkpoints.push(Checkpoint { offset, line: location.line, column: location.column, token: self.tokens.len(), modes: modes.clone() });
+            }
+
+            if walker.current_string().is_empty() {
+                break;
+            }
+
+            match TokenStream::lex_one(&mut walker, &mut modes, &self.lexer, |_, error| Err(error)) {
+                Ok(Some(token)) => {
+                    let range = token.span.byte_range_in(&self.text);
+                    let span = CompactSpan::from_components(
+                        Pos(u32::try_from(range.start).expect("file is too large for compact positions")),
+                        u32::try_from(range.len()).expect("file is too large for compact positions"));
+                    self.tokens.push((span, token.value));
+                }
+                Ok(None) => {}
+                Err(error) => {
+                    self.error = Some((offset, error.to_string()));
+                    break;
+                }
+            }
+        }
+
+        start.token..self.tokens.len()
+    }
+
+    /// Get the text being lexed
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Get the tokens of the text with their compact spans
+    pub fn tokens(&self) -> &[(CompactSpan, T)] {
+        &self.tokens
+    }
+
+    /// Get the number of checkpoints, including the one at the start of the text
+    pub fn checkpoint_count(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    /// Get the location and message of the error lexing stopped at, if it did not reach the end of the text
+    pub fn error(&self) -> Option<(Location<'_>, &str)> {
+        self.error.as_ref().map(|(offset, message)| {
+            (Location::from_components(0, 0, &self.filename).advanced_over(&self.text[..*offset]), message.as_str())
+        })
+    }
+
+    /// Get the tokens as a `TokenStream` over the current text, for parsing them, which ends at the error if there was one
+    pub fn stream(&self) -> TokenStream<'_, T> where T: Clone {
+        let index = LineIndex::from_data(&self.text, &self.filename);
+        let tokens = self.tokens.iter().map(|(span, token)| Spanned::new(index.span(*span).unwrap(), token.clone())).collect();
+        let end_offset = self.error.as_ref().map_or(self.text.len(), |(offset, _)| *offset);
+        let end = Location::from_components(0, 0, &self.filename).advanced_over(&self.text[..end_offset]);
+
+        TokenStream { tokens, data: &self.text, end, end_offset }
+    }
+}
+
+impl<'stream, 'filedata, T> TokenWalker<'stream, 'filedata, T> {
+    /// Get the location of the current token, or of the end of the stream if every token has been consumed
+    pub fn current_location(&self) -> Location<'filedata> {
+        self.peek().map_or(self.stream.end, |token| token.span.location)
+    }
+
+    /// Get the current token without consuming it
+    pub fn peek(&self) -> Option<&'stream Spanned<'filedata, T>> {
+        self.stream.tokens.get(self.index)
+    }
+
+    /// Get the tokens which have not been consumed
+    pub fn current_tokens(&self) -> &'stream [Spanned<'filedata, T>] {
+        &self.stream.tokens[self.index..]
+    }
+
+    /// Step forward by one token if possible, return the token stepped over, otherwise return None
+    pub fn step(&mut self) -> Option<&'stream Spanned<'filedata, T>> {
+        let token = self.peek()?;
+        self.index += 1;
+
+        Some(token)
+    }
+
+    /// Get the position of the current token as a `TokenMarker`
+    pub fn get_marker(&self) -> TokenMarker {
+        TokenMarker { index: self.index }
+    }
+
+    /// Return to a previous token in the stream (using a `TokenMarker`) and return true, if the marker is past the end of the stream,
+    /// return false and do not move
+    pub fn pop_back(&mut self, marker: TokenMarker) -> bool {
+        if marker.index <= self.stream.tokens.len() {
+            self.index = marker.index;
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    /// Construct an independent copy of this walker at the same position, for parsing speculatively without disturbing this one
+    pub fn fork(&self) -> Self {
+        self.clone()
+    }
+
+    /// Move this walker to the position reached by a fork of it and return true, if the fork is walking a different stream, return
+    /// false and do not move
+    pub fn join(&mut self, fork: &TokenWalker<'stream, 'filedata, T>) -> bool {
+        if std::ptr::eq(self.stream, fork.stream) {
+            self.pop_back(fork.get_marker())
+        }
+        else {
+            false
+        }
+    }
+
+    /// Get the span of the file covered by the tokens from a marker to the current token (not including it), including the trivia
+    /// between them, returns None if the marker is after the current token
+    pub fn span_from_marker_to_here(&self, marker: TokenMarker) -> Option<Span<'filedata>> {
+        let consumed = self.stream.tokens.get(marker.index..self.index)?;
+
+        match (consumed.first(), consumed.last()) {
+            (Some(first), Some(last)) => {
+                let start = first.span.byte_range_in(self.stream.data).start;
+                let end = last.span.byte_range_in(self.stream.data).end;
+
+                Some(Span::from_components(first.span.location, &self.stream.data[start..end]))
+            }
+            _ => {
+                let start = self.peek().map_or(self.stream.end_offset, |token| token.span.byte_range_in(self.stream.data).start);
+                Some(Span::from_components(self.current_location(), &self.stream.data[start..start]))
+            }
+        }
+    }
+}
+
+impl<T> Clone for TokenWalker<'_, '_, T> {
+    fn clone(&self) -> Self {
+        Self { stream: self.stream, index: self.index }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts a single token of a kind, returning its span
+pub fn token<'filedata, T: PartialEq>(
+    kind: T, expected: &'static str
+) -> impl Fn(&mut TokenWalker<'_, 'filedata, T>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
+    move |walker: &mut TokenWalker<'_, 'filedata, T>| {
+        match walker.peek() {
+            Some(token) if token.value == kind => {
+                walker.step();
+                Ok(token.span)
+            }
+            Some(token) => Err(ParsingError(token.span.location, ErrorKind::UnexpectedToken { expected: vec![expected], found_kind: None, found: token.span.data })),
+            None => Err(ParsingError(walker.current_location(), ErrorKind::UnexpectedEndOfFile(expected)))
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts a single token whose kind matches a predicate, such as any identifier, returning a copy of it
+pub fn token_if<'filedata, T: Clone>(
+    f: impl Fn(&T) -> bool, expected: &'static str
+) -> impl Fn(&mut TokenWalker<'_, 'filedata, T>) -> Result<Spanned<'filedata, T>, ParsingError<'filedata>> {
+    move |walker: &mut TokenWalker<'_, 'filedata, T>| {
+        match walker.peek() {
+            Some(token) if f(&token.value) => {
+                walker.step();
+                Ok(token.clone())
+            }
+            Some(token) => Err(ParsingError(token.span.location, ErrorKind::UnexpectedToken { expected: vec![expected], found_kind: None, found: token.span.data })),
+            None => Err(ParsingError(walker.current_location(), ErrorKind::UnexpectedEndOfFile(expected)))
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts a single token of any of the kinds, returning a copy of it, errors name the kinds expected and the kind of token found,
+/// such as ``expected `,` or `)`, found identifier `foo` ``
+pub fn token_of<'filedata, T: TokenKind + PartialEq + Clone>(
+    kinds: &'static [T]
+) -> impl Fn(&mut TokenWalker<'_, 'filedata, T>) -> Result<Spanned<'filedata, T>, ParsingError<'filedata>> {
+    move |walker: &mut TokenWalker<'_, 'filedata, T>| {
+        let expected = || kinds.iter().map(T::name).collect();
+
+        match walker.peek() {
+            Some(token) if kinds.contains(&token.value) => {
+                walker.step();
+                Ok(token.clone())
+            }
+            Some(token) => Err(ParsingError(token.span.location, ErrorKind::UnexpectedToken {
+                expected: expected(), found_kind: Some(token.value.name()), found: token.span.data
+            })),
+            None => Err(ParsingError(walker.current_location(), ErrorKind::UnexpectedToken { expected: expected(), found_kind: None, found: "" }))
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts a group of tokens between an opening token and the closing token which balances it, returning the tokens inside as a
+/// stream of their own, which ends at the closing token
+///
+/// The group can be parsed later by a different grammar, such as the body of an attribute or macro, with errors still reported at
+/// the places of its tokens in the file
+pub fn token_group<'filedata, T: PartialEq + Clone>(
+    open: T, close: T, expected: &'static str
+) -> impl Fn(&mut TokenWalker<'_, 'filedata, T>) -> Result<TokenStream<'filedata, T>, ParsingError<'filedata>> {
+    move |walker: &mut TokenWalker<'_, 'filedata, T>| {
+        let stream = walker.stream;
+        let start = walker.get_marker();
+        token(open.clone(), expected)(walker)?;
+
+        let inside = walker.index;
+        let mut depth = 0usize;
+        while let Some(token) = walker.step() {
+            if token.value == open {
+                depth += 1;
+            }
+            else if token.value == close && depth > 0 {
+                depth -= 1;
+            }
+            else if token.value == close {
+                let end_offset = token.span.byte_range_in(stream.data).start;
+                let tokens = stream.tokens[inside..walker.index - 1].to_vec();
+
+                return Ok(TokenStream { tokens, data: stream.data, end: token.span.location, end_offset });
+            }
+        }
+
+        walker.pop_back(start);
+        Err(ParsingError(stream.end, ErrorKind::UnexpectedEndOfFile(expected)))
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Lifts a lexer rule into a rule of a modal lexer which leaves the modes as they are
+pub fn modal<'filedata, T, M>(
+    rule: impl Fn(&mut FileWalker<'filedata>) -> Result<Option<T>, ParsingError<'filedata>>
+) -> impl Fn(&mut FileWalker<'filedata>, &mut ModeStack<M>) -> Result<Option<T>, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>, _: &mut ModeStack<M>| rule(walker)
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Enters a mode after the lexer rule accepts a token, such as the string mode after an opening quote
+pub fn push_mode<'filedata, T, M: Clone>(
+    rule: impl Fn(&mut FileWalker<'filedata>) -> Result<Option<T>, ParsingError<'filedata>>, mode: M
+) -> impl Fn(&mut FileWalker<'filedata>, &mut ModeStack<M>) -> Result<Option<T>, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>, modes: &mut ModeStack<M>| {
+        let token = rule(walker)?;
+        modes.push(mode.clone());
+
+        Ok(token)
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Leaves the current mode after the lexer rule accepts a token, such as the string mode at a closing quote
+pub fn pop_mode<'filedata, T, M>(
+    rule: impl Fn(&mut FileWalker<'filedata>) -> Result<Option<T>, ParsingError<'filedata>>
+) -> impl Fn(&mut FileWalker<'filedata>, &mut ModeStack<M>) -> Result<Option<T>, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>, modes: &mut ModeStack<M>| {
+        let token = rule(walker)?;
+        modes.pop();
+
+        Ok(token)
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Applies a rule of a modal lexer only when a mode is current, failing without consuming anything in any other mode
+pub fn in_mode<'filedata, T, M: PartialEq>(
+    mode: M,
+    rule: impl Fn(&mut FileWalker<'filedata>, &mut ModeStack<M>) -> Result<Option<T>, ParsingError<'filedata>>
+) -> impl Fn(&mut FileWalker<'filedata>, &mut ModeStack<M>) -> Result<Option<T>, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>, modes: &mut ModeStack<M>| {
+        if *modes.current() != mode {
+            return Err(ParsingError(walker.current_location(), ErrorKind::ExpectedKind("a token of the current mode")));
+        }
+
+        rule(walker, modes)
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Tries the first rule of a modal lexer, then the second if it fails, returning the error which got further like `alt`
+///
+/// The modes are restored after a rule which fails, so modes entered by a rule before it failed are not seen by the next
+pub fn modal_alt<'filedata, T, M: Clone>(
+    first: impl Fn(&mut FileWalker<'filedata>, &mut ModeStack<M>) -> Result<Option<T>, ParsingError<'filedata>>,
+    second: impl Fn(&mut FileWalker<'filedata>, &mut ModeStack<M>) -> Result<Option<T>, ParsingError<'filedata>>
+) -> impl Fn(&mut FileWalker<'filedata>, &mut ModeStack<M>) -> Result<Option<T>, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>, modes: &mut ModeStack<M>| {
+        let saved = modes.snapshot();
+
+        let first_error = match first(walker, modes) {
+            Ok(token) => return Ok(token),
+            Err(e) => e
+        };
+
+        modes.restore(saved.clone());
+        if !first_error.is_recoverable() {
+            return Err(first_error);
+        }
+
+        second(walker, modes).map_err(|second_error| {
+            modes.restore(saved);
+            first_error.furthest(second_error)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::lexer::{IncrementalLexer, ModeStack, TokenKind, TokenStream, TokenWalker, in_mode, modal, modal_alt, pop_mode, push_mode, token, token_group, token_if,
+        token_of};
+    use crate::{ErrorKind, FileWalker, Location, ParsingError, Span, alt, map, one_of, tag, take_while};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Token {
+        Number,
+        Name,
+        Plus,
+        Star
+    }
+
+    impl TokenKind for Token {
+        fn name(&self) -> &'static str {
+            match self {
+                Token::Number => "number",
+                Token::Name => "name",
+                Token::Plus => "`+`",
+                Token::Star => "`*`"
+            }
+        }
+    }
+
+    fn lex_token<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Option<Token>, ParsingError<'filedata>> {
+        alt(
+            alt(
+                map(take_while(char::is_whitespace, "whitespace"), |_| None),
+                map(take_while(|c| c.is_ascii_digit(), "a number"), |_| Some(Token::Number))
+            ),
+            alt(
+                map(take_while(|c| c.is_ascii_alphabetic(), "a name"), |_| Some(Token::Name)),
+                map(one_of("+*"), |op| Some(if op.data == "+" { Token::Plus } else { Token::Star }))
+            )
+        )(walker)
+    }
+
+    /// Parses `operand (+ operand)*`, where an operand is `term (* term)*`, returning the span of the whole sum
+    fn sum<'filedata>(walker: &mut TokenWalker<'_, 'filedata, Token>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
+        let start = walker.get_marker();
+        let term = token_if(|kind| matches!(kind, Token::Number | Token::Name), "a number or name");
+
+        loop {
+            term(walker)?;
+            while token(Token::Star, "`*`")(walker).is_ok() {
+                term(walker)?;
+            }
+
+            if token(Token::Plus, "`+`")(walker).is_err() {
+                return Ok(walker.span_from_marker_to_here(start).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn lex_and_parse_tokens() {
+        let mut walker = FileWalker::from_data("2 * x\n  + 10", "input");
+        let stream = TokenStream::lex(&mut walker, lex_token).unwrap();
+
+        assert_eq!(stream.tokens().iter().map(|token| (token.value, token.span.data)).collect::<Vec<_>>(), [
+            (Token::Number, "2"), (Token::Star, "*"), (Token::Name, "x"), (Token::Plus, "+"), (Token::Number, "10")
+        ]);
+        assert_eq!(stream.tokens()[4].span.location, Location::from_components(4, 1, "input"));
+        assert_eq!(stream.end_location(), Location::from_components(6, 1, "input"));
+
+        // The span of a rule covers the text of its tokens, including the trivia between them
+        let mut tokens = stream.walker();
+        assert_eq!(sum(&mut tokens).unwrap().data, "2 * x\n  + 10");
+        assert!(tokens.current_tokens().is_empty());
+    }
+
+    #[test]
+    fn token_errors() {
+        let mut walker = FileWalker::from_data("x + * 1 +", "input");
+        let stream = TokenStream::lex(&mut walker, lex_token).unwrap();
+
+        // Errors are reported at the token which was not expected, and missing tokens at the end of the file
+        let mut tokens = stream.walker();
+        let error = sum(&mut tokens).unwrap_err();
+        assert_eq!(error, ParsingError(Location::from_components(4, 0, "input"), ErrorKind::UnexpectedToken {
+            expected: vec!["a number or name"], found_kind: None, found: "*"
+        }));
+        assert_eq!(error.to_string(), "expected a number or name, found `*`");
+
+        tokens.step();
+        assert_eq!(sum(&mut tokens), Err(ParsingError(Location::from_components(9, 0, "input"), ErrorKind::UnexpectedEndOfFile("a number or name"))));
+
+        // Forks move independently until they are joined
+        let mut tokens = stream.walker();
+        let start = tokens.get_marker();
+        let mut fork = tokens.fork();
+        fork.step();
+        assert_eq!(tokens.peek().map(|token| token.span.data), Some("x"));
+        assert!(tokens.join(&fork));
+        assert_eq!(tokens.peek().map(|token| token.span.data), Some("+"));
+        assert_eq!(tokens.span_from_marker_to_here(tokens.get_marker()).map(|span| (span.location.column, span.data)), Some((2, "")));
+        assert!(tokens.pop_back(start));
+        assert_eq!(tokens.span_from_marker_to_here(fork.get_marker()), None);
+
+        // Lexing stops at the first character no token matches, which partial lexing reports with the tokens before it
+        let mut walker = FileWalker::from_data("1 - 2", "input");
+        let error = TokenStream::lex(&mut walker, lex_token).unwrap_err();
+        assert_eq!(error.0, Location::from_components(2, 0, "input"));
+
+        let mut walker = FileWalker::from_data("1 - 2", "input");
+        let (stream, partial_error) = TokenStream::lex_partial(&mut walker, lex_token);
+        assert_eq!(partial_error, Some(error));
+        assert_eq!(stream.tokens().iter().map(|token| token.span.data).collect::<Vec<_>>(), ["1"]);
+        assert_eq!((stream.end_location(), walker.current_string()), (Location::from_components(2, 0, "input"), "- 2"));
+    }
+
+    #[test]
+    fn token_kind_names() {
+        let mut walker = FileWalker::from_data("x + 1", "input");
+        let stream = TokenStream::lex(&mut walker, lex_token).unwrap();
+        let mut tokens = stream.walker();
+        let operator = token_of(&[Token::Plus, Token::Star]);
+
+        // Kinds whose text varies are named before it, and those named by their text are not
+        let error = operator(&mut tokens).unwrap_err();
+        assert_eq!(error.to_string(), "expected `+` or `*`, found name `x`");
+        assert_eq!(error.1.expectation().unwrap(), "`+` or `*`");
+        tokens.step();
+        assert_eq!(operator(&mut tokens).unwrap().span.data, "+");
+        assert_eq!(token_of(&[Token::Star, Token::Name, Token::Plus])(&mut tokens).unwrap_err().to_string(),
+            "expected `*`, name or `+`, found number `1`");
+        assert_eq!(token_of(&[Token::Number])(&mut tokens).unwrap().value, Token::Number);
+
+        let error = operator(&mut tokens).unwrap_err();
+        assert_eq!(error, ParsingError(Location::from_components(5, 0, "input"), ErrorKind::UnexpectedToken {
+            expected: vec!["`+`", "`*`"], found_kind: None, found: ""
+        }));
+        assert_eq!(error.to_string(), "expected `+` or `*`, found the end of the input");
+        assert_eq!(error.notes()[0].text(), "the tokens end here");
+
+        let mut tokens = stream.walker();
+        tokens.step();
+        assert_eq!(token_of(&[Token::Star])(&mut tokens).unwrap_err().to_string(), "expected `*`, found `+`");
+    }
+
+    #[test]
+    fn significant_newlines() {
+        let lex_line = |walker: &mut FileWalker<'static>| alt(
+            alt(
+                map(take_while(|c| c == ' ', "spaces"), |_| None),
+                map(tag("\n"), |_| Some('\n'))
+            ),
+            map(one_of("ab+()"), |c| c.data.chars().next())
+        )(walker);
+
+        let mut walker = FileWalker::from_data("a +\nb\n(a\n+ b)\n\nb\n", "input");
+        let mut stream = TokenStream::lex(&mut walker, lex_line).unwrap();
+
+        // Like Go, a newline ends a statement after a name or closing bracket, but not inside brackets or after another newline
+        stream.filter_newlines(|c| *c == '\n', |before, _| {
+            let depth = before.iter().map(|token| match token.value { '(' => 1, ')' => -1, _ => 0 }).sum::<i32>();
+            depth == 0 && matches!(before.last().map(|token| token.value), Some('a' | 'b' | ')'))
+        });
+
+        assert_eq!(stream.tokens().iter().map(|token| token.value).collect::<String>(), "a+b\n(a+b)\nb\n");
+        assert_eq!(stream.tokens()[3].span.location, Location::from_components(1, 1, "input"));
+
+        // The tokens after a newline are given too, so a newline before an operator can continue the statement
+        let mut walker = FileWalker::from_data("a\n+ b\na", "input");
+        let mut stream = TokenStream::lex(&mut walker, lex_line).unwrap();
+        stream.filter_newlines(|c| *c == '\n', |_, after| after.first().is_some_and(|token| token.value != '+'));
+        assert_eq!(stream.tokens().iter().map(|token| token.value).collect::<String>(), "a+b\na");
+    }
+
+    #[test]
+    fn token_slices() {
+        let lex_char = |walker: &mut FileWalker<'static>| alt(
+            map(take_while(|c| c == ' ', "spaces"), |_| None),
+            map(one_of("ab+()"), |c| c.data.chars().next())
+        )(walker);
+
+        let mut walker = FileWalker::from_data("a (b + (a) b) + b", "input");
+        let stream = TokenStream::lex(&mut walker, lex_char).unwrap();
+        let mut tokens = stream.walker();
+        tokens.step();
+
+        // The group stops at the bracket which balances the opening one, and can be parsed later with locations preserved
+        let group = token_group('(', ')', "a group")(&mut tokens).unwrap();
+        assert_eq!(group.tokens().iter().map(|token| token.value).collect::<String>(), "b+(a)b");
+        assert_eq!(group.end_location(), Location::from_components(12, 0, "input"));
+        assert_eq!(tokens.peek().map(|token| token.value), Some('+'));
+
+        let mut inner = group.walker();
+        let start = inner.get_marker();
+        inner.step();
+        assert_eq!(token('a', "`a`")(&mut inner), Err(ParsingError(Location::from_components(5, 0, "input"), ErrorKind::UnexpectedToken {
+            expected: vec!["`a`"], found_kind: None, found: "+"
+        })));
+        while inner.step().is_some() {}
+        assert_eq!(inner.span_from_marker_to_here(start).unwrap().data, "b + (a) b");
+        assert_eq!(inner.span_from_marker_to_here(inner.get_marker()).map(|span| (span.location.column, span.data)), Some((12, "")));
+
+        // Slicing by a span leaves out the tokens which run past it
+        let slice = stream.slice(&Span::from_components(Location::from_components(3, 0, "input"), &stream.data()[3..8])).unwrap();
+        assert_eq!(slice.tokens().iter().map(|token| token.value).collect::<String>(), "b+(");
+        assert_eq!(slice.end_location(), Location::from_components(8, 0, "input"));
+        assert!(stream.slice(&Span::from_components(Location::from_components(3, 0, "input"), "b + (")).is_none());
+
+        // An unbalanced group is reported at the end of the stream, without consuming anything
+        let mut walker = FileWalker::from_data("(a (b)", "input");
+        let stream = TokenStream::lex(&mut walker, lex_char).unwrap();
+        let mut tokens = stream.walker();
+        assert_eq!(token_group('(', ')', "a group")(&mut tokens),
+            Err(ParsingError(Location::from_components(6, 0, "input"), ErrorKind::UnexpectedEndOfFile("a group"))));
+        assert_eq!(tokens.get_marker(), stream.walker().get_marker());
+    }
+
+    #[test]
+    fn lex_recovering_errors() {
+        // Tokens are kept as Some, so the error tokens are None
+        let mut walker = FileWalker::from_data("2 - x?y * 3 #\n+ 1", "input");
+        let (stream, diagnostics) = TokenStream::lex_recovering(&mut walker, |walker| lex_token(walker).map(|token| token.map(Some)),
+            char::is_whitespace, |_| None);
+
+        // Each error token runs up to the next whitespace, and the tokens after it are still lexed
+        assert_eq!(stream.tokens().iter().map(|token| (token.value, token.span.data)).collect::<Vec<_>>(), [
+            (Some(Token::Number), "2"), (None, "-"), (Some(Token::Name), "x"), (None, "?y"), (Some(Token::Star), "*"),
+            (Some(Token::Number), "3"), (None, "#"), (Some(Token::Plus), "+"), (Some(Token::Number), "1")
+        ]);
+        assert_eq!(stream.end_location(), Location::from_components(3, 1, "input"));
+        assert_eq!(diagnostics.iter().map(|diagnostic| (diagnostic.location.column, diagnostic.location.line)).collect::<Vec<_>>(),
+            [(2, 0), (5, 0), (12, 0)]);
+        assert_eq!(diagnostics[0].message, "expected one of +*");
+
+        // A lexer which consumes nothing is recovered from too, rather than looping forever
+        let mut walker = FileWalker::from_data("ab", "input");
+        let (stream, diagnostics) = TokenStream::lex_recovering(&mut walker, |_| Ok(Some(())), |_| true, |_| ());
+        assert_eq!((stream.len(), diagnostics.len()), (2, 2));
+        assert_eq!(diagnostics[0].message, "expected a token which consumes input");
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Mode {
+        Code,
+        String,
+        Interpolation
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Piece {
+        Quote,
+        Text,
+        Open,
+        Close,
+        Name,
+        Plus
+    }
+
+    /// Lexes code with strings which interpolate expressions between `${` and `}`, which may contain strings of their own
+    fn lex_piece<'filedata>(walker: &mut FileWalker<'filedata>, modes: &mut ModeStack<Mode>) -> Result<Option<Piece>, ParsingError<'filedata>> {
+        let string = modal_alt(
+            modal_alt(
+                pop_mode(map(tag("\""), |_| Some(Piece::Quote))),
+                push_mode(map(tag("${"), |_| Some(Piece::Open)), Mode::Interpolation)
+            ),
+            modal(map(take_while(|c| c != '"' && c != '$', "text"), |_| Some(Piece::Text)))
+        );
+
+        let code = modal_alt(
+            modal_alt(
+                push_mode(map(tag("\""), |_| Some(Piece::Quote)), Mode::String),
+                in_mode(Mode::Interpolation, pop_mode(map(tag("}"), |_| Some(Piece::Close))))
+            ),
+            modal(alt(
+                map(take_while(char::is_whitespace, "whitespace"), |_| None),
+                alt(
+                    map(take_while(|c| c.is_ascii_alphabetic(), "a name"), |_| Some(Piece::Name)),
+                    map(tag("+"), |_| Some(Piece::Plus))
+                )
+            ))
+        );
+
+        modal_alt(in_mode(Mode::String, string), code)(walker, modes)
+    }
+
+    #[test]
+    fn lex_modes() {
+        let mut walker = FileWalker::from_data("a + \"x ${b + \"y\"} z\"", "input");
+        let mut modes = ModeStack::new(Mode::Code);
+        let stream = TokenStream::lex_modal(&mut walker, &mut modes, lex_piece).unwrap();
+
+        // Spaces are text inside strings, but trivia in the code interpolated into them
+        assert_eq!(stream.tokens().iter().map(|token| (token.value, token.span.data)).collect::<Vec<_>>(), [
+            (Piece::Name, "a"), (Piece::Plus, "+"), (Piece::Quote, "\""), (Piece::Text, "x "), (Piece::Open, "${"), (Piece::Name, "b"),
+            (Piece::Plus, "+"), (Piece::Quote, "\""), (Piece::Text, "y"), (Piece::Quote, "\""), (Piece::Close, "}"), (Piece::Text, " z"),
+            (Piece::Quote, "\"")
+        ]);
+        assert_eq!((modes.current(), modes.depth()), (&Mode::Code, 0));
+
+        // Modes still pushed at the end of the input are left for the caller to report
+        let mut walker = FileWalker::from_data("\"x ${b", "input");
+        let mut modes = ModeStack::new(Mode::Code);
+        assert_eq!(TokenStream::lex_modal(&mut walker, &mut modes, lex_piece).unwrap().len(), 4);
+        assert_eq!((modes.current(), modes.depth()), (&Mode::Interpolation, 2));
+        assert_eq!(modes.pop(), Some(Mode::Interpolation));
+        assert_eq!(modes.pop(), Some(Mode::String));
+        assert_eq!(modes.pop(), None);
+        assert_eq!(modes.current(), &Mode::Code);
+
+        // A closing brace only means something inside an interpolation
+        let mut walker = FileWalker::from_data("a }", "input");
+        let error = TokenStream::lex_modal(&mut walker, &mut ModeStack::new(Mode::Code), lex_piece).unwrap_err();
+        assert_eq!(error.0, Location::from_components(2, 0, "input"));
+    }
+
+    /// Enters a string before checking for its opening quote, so it fails with the string mode pushed when there is no quote
+    fn eager_string<'filedata>(walker: &mut FileWalker<'filedata>, modes: &mut ModeStack<Mode>) -> Result<Option<Piece>, ParsingError<'filedata>> {
+        modes.push(Mode::String);
+        tag("\"")(walker).map(|_| Some(Piece::Quote))
+    }
+
+    #[test]
+    fn modal_alt_restores_modes() {
+        let mut modes = ModeStack::new(Mode::Code);
+        let rule = modal_alt(eager_string, in_mode(Mode::Code, modal(map(tag("+"), |_| Some(Piece::Plus)))));
+
+        // The mode entered by the failed rule is undone before the next is tried, and after both fail
+        assert_eq!(rule(&mut FileWalker::from_data("+", "input"), &mut modes), Ok(Some(Piece::Plus)));
+        assert_eq!(modes.depth(), 0);
+        assert!(rule(&mut FileWalker::from_data("x", "input"), &mut modes).is_err());
+        assert_eq!(modes.depth(), 0);
+
+        assert_eq!(rule(&mut FileWalker::from_data("\"", "input"), &mut modes), Ok(Some(Piece::Quote)));
+        assert_eq!(modes.current(), &Mode::String);
+    }
+
+    /// Lexes words, and strings which may span several lines, into single characters standing for their kinds
+    fn lex_quoted<'filedata>(walker: &mut FileWalker<'filedata>, modes: &mut ModeStack<Mode>) -> Result<Option<char>, ParsingError<'filedata>> {
+        let string = modal_alt(
+            pop_mode(map(tag("\""), |_| Some('"'))),
+            modal(map(take_while(|c| c != '"', "text"), |_| Some('s')))
+        );
+        let code = modal_alt(
+            push_mode(map(tag("\""), |_| Some('"')), Mode::String),
+            modal(alt(
+                map(take_while(char::is_whitespace, "whitespace"), |_| None),
+                map(take_while(|c| c.is_ascii_alphabetic(), "a word"), |_| Some('w'))
+            ))
+        );
+
+        modal_alt(in_mode(Mode::String, string), code)(walker, modes)
+    }
+
+    /// Check an edited lexer found the same tokens, checkpoints and error as lexing its text from scratch
+    fn assert_relexed<L>(lexer: &IncrementalLexer<char, Mode, L>)
+    where
+        L: for<'filedata> Fn(&mut FileWalker<'filedata>, &mut ModeStack<Mode>) -> Result<Option<char>, ParsingError<'filedata>>
+    {
+        let fresh = IncrementalLexer::new("input", lexer.text(), Mode::Code, 2, lex_quoted);
+        assert_eq!(lexer.stream(), fresh.stream());
+        assert_eq!(lexer.checkpoint_count(), fresh.checkpoint_count());
+        assert_eq!(lexer.error(), fresh.error());
+    }
+
+    #[test]
+    fn incremental_lexing() {
+        let mut lexer = IncrementalLexer::new("input", "ab cd\n".repeat(10), Mode::Code, 2, lex_quoted);
+        assert_eq!((lexer.tokens().len(), lexer.checkpoint_count()), (20, 6));
+
+        // An edit within a word is relexed up to the next checkpoint, where the lexer is in the same state as before
+        assert_eq!(lexer.edit(7..8, "xyz"), 0..4);
+        assert_eq!(lexer.stream().tokens()[2].span.data, "axyz");
+        assert_eq!(lexer.stream().tokens()[19].span.location, Location::from_components(3, 9, "input"));
+        assert_relexed(&lexer);
+
+        // Opening a string changes the state of every line after it, so the rest of the text is relexed
+        assert_eq!(lexer.edit(3..3, "\""), 0..3);
+        assert_eq!(lexer.stream().tokens().iter().map(|token| token.value).collect::<String>(), "w\"s");
+        assert_relexed(&lexer);
+
+        // Closing it again has no checkpoints from before to match either
+        assert_eq!(lexer.edit(3..4, ""), 0..20);
+        assert_eq!(lexer.text(), format!("ab cd\naxyz cd\n{}", "ab cd\n".repeat(8)));
+        assert_relexed(&lexer);
+    }
+
+    #[test]
+    fn incremental_lexing_errors() {
+        let mut lexer = IncrementalLexer::new("input", format!("{}a?b\nab\n", "ab cd\n".repeat(6)), Mode::Code, 2, lex_quoted);
+        assert_eq!(lexer.error(), Some((Location::from_components(1, 6, "input"), "expected a word")));
+        assert_eq!(lexer.stream().end_location(), Location::from_components(1, 6, "input"));
+
+        // Lines added before the error move it once lexing has resynchronized
+        assert_eq!(lexer.edit(0..0, "\n\n"), 0..4);
+        assert_eq!(lexer.error(), Some((Location::from_components(1, 8, "input"), "expected a word")));
+        assert_relexed(&lexer);
+
+        // Removing the error lexes to the end of the text
+        let error = lexer.text().find('?').unwrap();
+        assert_eq!(lexer.edit(error..error + 1, " "), 12..15);
+        assert_eq!(lexer.error(), None);
+        assert_relexed(&lexer);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::driver::source::SourceFile;
+
 /// Holds the location of a token within a file
 /// 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -8,13 +10,45 @@ pub struct Location<'name> {
 }
 
 
+/// The number of characters of a span's data shown by its compact `Debug` output
+const DEBUG_PREVIEW_LENGTH: usize = 32;
+
 /// Refers to a particular length of data within a file
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///
+/// The `Debug` output is compact, `{file}:{line}:{column} "{data}"` with the data truncated, use `{:#?}` for the full struct
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct Span<'filedata> {
     pub location: Location<'filedata>,
     pub data: &'filedata str
 }
 
+/// Refers to text which does not appear in a file, but was inserted at a location within it, such as a token added while desugaring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyntheticSpan<'filedata> {
+    pub location: Location<'filedata>,
+    pub data: &'filedata str
+}
+
+/// A value parsed from a span of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Spanned<'filedata, T> {
+    pub span: Span<'filedata>,
+    pub value: T
+}
+
+/// Get the line and column after a character, this is the only place the rules for advancing over text are defined
+///
+/// Columns count characters, and only `\n` starts a new line, so the `\r` of a `\r\n` is counted as a column of the line it ends
+#[inline]
+pub(crate) fn advance_position(line: usize, column: usize, c: char) -> (usize, usize) {
+    if c == '\n' {
+        (line + 1, 0)
+    }
+    else {
+        (line, column + 1)
+    }
+}
+
 impl<'name> Location<'name> {
     /// Construct a location from its components
     pub fn from_components(column: usize, line: usize, filename: &'name str) -> Self {
@@ -22,6 +56,13 @@ impl<'name> Location<'name> {
             column, line, filename
         }
     }
+
+    /// Get the location after some text starting at this location, exactly as a `FileWalker` would advance over it
+    pub fn advanced_over(&self, text: &str) -> Self {
+        let (line, column) = text.chars().fold((self.line, self.column), |(line, column), c| advance_position(line, column, c));
+
+        Self::from_components(column, line, self.filename)
+    }
 }
 
 impl<'name> std::cmp::PartialOrd for Location<'name> {
@@ -52,10 +93,282 @@ impl <'filedata> Span<'filedata> {
             location, data
         }
     }
+
+    /// Get the range of bytes of a file's data the span covers, panics if the span does not refer to the file's data
+    pub fn byte_range_in(&self, file: &str) -> std::ops::Range<usize> {
+        let file_start = file.as_ptr() as usize;
+        let start = self.data.as_ptr() as usize;
+
+        assert!(start >= file_start && start + self.data.len() <= file_start + file.len(), "span does not refer to the file's data");
+
+        start - file_start..start - file_start + self.data.len()
+    }
+
+    /// Get the span covering a range of bytes of a file, such as a regex match, or None if the range does not lie on character boundaries within it
+    ///
+    /// The location is found by scanning the file up to the start of the range, use `LineIndex::span_at` to convert many ranges
+    pub fn from_byte_range(file: &'filedata SourceFile, range: std::ops::Range<usize>) -> Option<Self> {
+        let data = file.data.get(range.clone())?;
+        let location = Location::from_components(0, 0, &file.name).advanced_over(&file.data[..range.start]);
+
+        Some(Self::from_components(location, data))
+    }
+
+    /// Get the range of bytes of a file the span covers, or None if the span does not refer to the file's data
+    pub fn to_byte_range(&self, file: &SourceFile) -> Option<std::ops::Range<usize>> {
+        let file_start = file.data.as_ptr() as usize;
+        let start = (self.data.as_ptr() as usize).checked_sub(file_start)?;
+
+        Some(start..start + self.data.len()).filter(|range| range.end <= file.data.len())
+    }
+
+    /// Get the location just after the last character of the span, which is on a later line if the span contains a line break
+    ///
+    /// Spans only store where they start, which keeps errors holding them small, so the end is found by walking the span's text
+    pub fn end_location(&self) -> Location<'filedata> {
+        self.location.advanced_over(self.data)
+    }
+
+    /// Shrink the span to remove leading whitespace, moving its location past the removed text
+    pub fn trim_start(&self) -> Self {
+        let trimmed = self.data.trim_start();
+        let removed = &self.data[..self.data.len() - trimmed.len()];
+
+        Self::from_components(self.location.advanced_over(removed), trimmed)
+    }
+
+    /// Shrink the span to remove trailing whitespace
+    pub fn trim_end(&self) -> Self {
+        Self::from_components(self.location, self.data.trim_end())
+    }
+
+    /// Shrink the span to remove leading and trailing whitespace
+    pub fn trim(&self) -> Self {
+        self.trim_start().trim_end()
+    }
+
+    /// Get the part of the span from one character to another, counting from the start of the span, or None if the range is out of
+    /// bounds or reversed
+    pub fn slice(&self, range: std::ops::Range<usize>) -> Option<Self> {
+        let start = self.char_offset(range.start)?;
+        let end = self.char_offset(range.end).filter(|end| *end >= start)?;
+
+        Some(Self::from_components(self.location.advanced_over(&self.data[..start]), &self.data[start..end]))
+    }
+
+    /// Get the first `n` characters of the span, or the whole span if it is shorter
+    pub fn first_n(&self, n: usize) -> Self {
+        let end = self.char_offset(n).unwrap_or(self.data.len());
+        Self::from_components(self.location, &self.data[..end])
+    }
+
+    /// Get the last `n` characters of the span, or the whole span if it is shorter
+    pub fn last_n(&self, n: usize) -> Self {
+        let start = match n {
+            0 => self.data.len(),
+            _ => self.data.char_indices().nth_back(n - 1).map_or(0, |(index, _)| index)
+        };
+
+        Self::from_components(self.location.advanced_over(&self.data[..start]), &self.data[start..])
+    }
+
+    /// Get the byte offset of a character of the data, which may be the end of the data
+    fn char_offset(&self, chars: usize) -> Option<usize> {
+        self.data.char_indices().map(|(index, _)| index).chain(std::iter::once(self.data.len())).nth(chars)
+    }
+
+    /// Get the number of characters in the span
+    pub fn char_len(&self) -> usize {
+        self.data.chars().count()
+    }
+
+    /// Get the number of UTF-16 code units in the span, as used for positions by the language server protocol
+    pub fn utf16_len(&self) -> usize {
+        self.data.chars().map(char::len_utf16).sum()
+    }
+
+    /// Get the first `max_chars` characters of the span's data, followed by `...` if the data is longer
+    pub fn preview(&self, max_chars: usize) -> String {
+        match self.data.char_indices().nth(max_chars) {
+            Some((index, _)) => format!("{}...", &self.data[..index]),
+            None => self.data.to_string()
+        }
+    }
+}
+
+impl <'filedata> std::fmt::Debug for Span<'filedata> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            f.debug_struct("Span")
+                .field("location", &self.location)
+                .field("data", &self.data)
+                .finish()
+        }
+        else {
+            write!(f, "{}:{}:{} {:?}", self.location.filename, self.location.line + 1, self.location.column + 1, self.preview(DEBUG_PREVIEW_LENGTH))
+        }
+    }
 }
 
 impl <'filedata> std::fmt::Display for Span<'filedata> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.data)
     }
-}
\ No newline at end of file
+}
+
+impl <'filedata> SyntheticSpan<'filedata> {
+    /// Construct a new synthetic span from the location it was inserted at and the inserted text
+    pub fn from_components(location: Location<'filedata>, data: &'filedata str) -> Self {
+        Self {
+            location, data
+        }
+    }
+
+    /// Get the span of the inserted text, which does not refer to the data of the file
+    pub fn as_span(&self) -> Span<'filedata> {
+        Span::from_components(self.location, self.data)
+    }
+}
+
+impl <'filedata> std::fmt::Display for SyntheticSpan<'filedata> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.data)
+    }
+}
+
+impl<'filedata, T> Spanned<'filedata, T> {
+    /// Construct a value from the span it was parsed from
+    pub fn new(span: Span<'filedata>, value: T) -> Self {
+        Self { span, value }
+    }
+
+    /// Transform the value, keeping the span it was parsed from
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Spanned<'filedata, U> {
+        Spanned::new(self.span, f(self.value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Location, Span};
+    use crate::driver::source::SourceFile;
+
+    #[test]
+    fn location_advanced_over() {
+        let start = Location::from_components(3, 1, "input");
+
+        assert_eq!(start.advanced_over(""), start);
+        assert_eq!(start.advanced_over("Möbius"), Location::from_components(9, 1, "input"));
+        assert_eq!(start.advanced_over("a\r\nbc\n\nö"), Location::from_components(1, 4, "input"));
+
+        // Make sure it agrees with the walker at every character
+        let data = "fn\r\n  möbius() {\n\t😀\n}";
+        let mut walker = crate::FileWalker::from_data(data, "input");
+        let origin = walker.current_location();
+        loop {
+            let consumed = &data[..data.len() - walker.current_string().len()];
+            assert_eq!(origin.advanced_over(consumed), walker.current_location());
+            if walker.step().is_none() {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn span_preview() {
+        let span = Span::from_components(Location::from_components(0, 0, "input"), "Möbius strip");
+
+        assert_eq!(span.preview(6), "Möbius...");
+        assert_eq!(span.preview(12), "Möbius strip");
+        assert_eq!(span.preview(20), "Möbius strip");
+    }
+
+    #[test]
+    fn span_lengths() {
+        let file = "let ö = \"😀\";";
+        let span = Span::from_components(Location::from_components(4, 0, "input"), &file[4..15]);
+
+        assert_eq!(span.data, "ö = \"😀\"");
+        assert_eq!(span.byte_range_in(file), 4..15);
+        assert_eq!(span.data.len(), 11);
+        assert_eq!(span.char_len(), 7);
+        assert_eq!(span.utf16_len(), 8);
+    }
+
+    #[test]
+    #[should_panic(expected = "span does not refer to the file's data")]
+    fn span_byte_range_other_file() {
+        let span = Span::from_components(Location::from_components(0, 0, "input"), "elsewhere");
+        span.byte_range_in("some other file");
+    }
+
+    #[test]
+    fn span_byte_ranges() {
+        let file = SourceFile::from_bytes("input", "fn möbius() {\n    return 4;\n}".as_bytes()).unwrap();
+
+        let span = Span::from_byte_range(&file, 19..25).unwrap();
+        assert_eq!(span, Span::from_components(Location::from_components(4, 1, "input"), "return"));
+        assert_eq!(span.to_byte_range(&file), Some(19..25));
+
+        // The location counts characters, not bytes
+        assert_eq!(Span::from_byte_range(&file, 10..12).unwrap().location, Location::from_components(9, 0, "input"));
+
+        // Ranges must be within the file and on character boundaries
+        assert_eq!(Span::from_byte_range(&file, 4..5), None);
+        assert_eq!(Span::from_byte_range(&file, 30..40), None);
+        assert_eq!(Span::from_components(Location::from_components(0, 0, "input"), "return").to_byte_range(&file), None);
+    }
+
+    #[test]
+    fn span_trim() {
+        let file = "let x =  \n\t value  \n;";
+        let span = Span::from_components(Location::from_components(7, 0, "input"), &file[7..]);
+
+        let trimmed = span.trim();
+        assert_eq!(trimmed, Span::from_components(Location::from_components(2, 1, "input"), "value  \n;"));
+        assert_eq!(trimmed.byte_range_in(file), 12..21);
+
+        let value = Span::from_components(Location::from_components(7, 0, "input"), &file[7..20]);
+        assert_eq!(value.trim(), Span::from_components(Location::from_components(2, 1, "input"), "value"));
+        assert_eq!(value.trim_end(), Span::from_components(Location::from_components(7, 0, "input"), "  \n\t value"));
+
+        // Trimming a span of only whitespace leaves an empty span at its end
+        let blank = Span::from_components(Location::from_components(7, 0, "input"), &file[7..12]);
+        assert_eq!(blank.trim_start(), Span::from_components(Location::from_components(2, 1, "input"), ""));
+    }
+
+    #[test]
+    fn span_slices() {
+        let file = "x = möbius(\n  a) + 😀b";
+        let span = Span::from_components(Location::from_components(4, 0, "input"), &file[4..]);
+
+        // Locations are recomputed across line breaks and multibyte characters
+        assert_eq!(span.slice(8..11), Some(Span::from_components(Location::from_components(0, 1, "input"), "  a")));
+        assert_eq!(span.slice(1..3), Some(Span::from_components(Location::from_components(5, 0, "input"), "öb")));
+        assert_eq!(span.slice(13..16), Some(Span::from_components(Location::from_components(5, 1, "input"), "+ 😀")));
+        assert_eq!(span.slice(17..17), Some(Span::from_components(Location::from_components(9, 1, "input"), "")));
+        assert_eq!(span.slice(17..18), None);
+        assert_eq!(span.slice(std::ops::Range { start: 3, end: 2 }), None);
+
+        assert_eq!(span.first_n(1), Span::from_components(Location::from_components(4, 0, "input"), "m"));
+        assert_eq!(span.first_n(40), span);
+        assert_eq!(span.last_n(2), Span::from_components(Location::from_components(7, 1, "input"), "😀b"));
+        assert_eq!(span.last_n(40), span);
+        assert_eq!(span.last_n(0), Span::from_components(span.end_location(), ""));
+    }
+
+    #[test]
+    fn span_debug() {
+        let span = Span::from_components(Location::from_components(4, 2, "input.txt"), "fn main() {\n    println!(\"Hello, world!\");\n}");
+
+        assert_eq!(format!("{:?}", span), r#"input.txt:3:5 "fn main() {\n    println!(\"Hello,...""#);
+        assert_eq!(format!("{:#?}", span), r#"Span {
+    location: Location {
+        column: 4,
+        line: 2,
+        filename: "input.txt",
+    },
+    data: "fn main() {\n    println!(\"Hello, world!\");\n}",
+}"#);
+    }
+}
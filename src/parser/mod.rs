@@ -1,7 +1,18 @@
 pub mod parsers;
 pub mod location;
 pub mod walker;
+pub mod compact;
+pub mod anchors;
+pub mod delimiters;
+pub mod rules;
+pub mod intern;
+pub mod events;
+pub mod lexer;
+pub mod pratt;
+pub mod owned;
+pub mod state;
 
 pub use parsers::*;
 pub use location::*;
 pub use walker::*;
+pub use compact::*;
\ No newline at end of file
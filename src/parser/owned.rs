@@ -0,0 +1,188 @@
+use std::sync::Arc;
+
+use crate::driver::source::SourceFile;
+use crate::{Location, Span};
+
+/// A location which owns its filename, so it can be kept after the file's data is dropped
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OwnedLocation {
+    pub column: usize,
+    pub line: usize,
+    pub filename: Arc<str>
+}
+
+/// A span which shares ownership of the data it refers to, so syntax trees holding it can outlive the walker they were parsed with
+///
+/// Spans owned through a `SourceFile` or `SourceMap` keep the whole file's data alive, and borrow back as spans of a walker over it,
+/// so they can still be rendered in diagnostics. Spans converted on their own only copy their text
+#[derive(Clone)]
+pub struct OwnedSpan {
+    pub location: OwnedLocation,
+    data: Arc<str>,
+    range: std::ops::Range<usize>
+}
+
+impl OwnedLocation {
+    /// Construct a location from its components
+    pub fn from_components(column: usize, line: usize, filename: impl Into<Arc<str>>) -> Self {
+        Self { column, line, filename: filename.into() }
+    }
+
+    /// Borrow the location as a `Location`
+    pub fn as_location(&self) -> Location<'_> {
+        Location::from_components(self.column, self.line, &self.filename)
+    }
+}
+
+impl OwnedSpan {
+    /// Construct a span of shared data from the range of bytes it covers, panics if the range is not within the data
+    pub(crate) fn from_shared(location: OwnedLocation, data: Arc<str>, range: std::ops::Range<usize>) -> Self {
+        assert!(data.get(range.clone()).is_some(), "span does not lie within the shared data");
+        Self { location, data, range }
+    }
+
+    /// Get the text the span refers to
+    pub fn data(&self) -> &str {
+        &self.data[self.range.clone()]
+    }
+
+    /// Borrow the span as a `Span`, which refers to the data of the shared file if it was owned through one
+    pub fn as_span(&self) -> Span<'_> {
+        Span::from_components(self.location.as_location(), self.data())
+    }
+
+    /// Check if the span shares the data of a file, rather than holding a copy of its text
+    pub fn is_in(&self, file: &SourceFile) -> bool {
+        Arc::ptr_eq(&self.data, &file.data)
+    }
+}
+
+impl From<Location<'_>> for OwnedLocation {
+    fn from(location: Location<'_>) -> Self {
+        Self::from_components(location.column, location.line, location.filename)
+    }
+}
+
+impl<'a> From<&'a OwnedLocation> for Location<'a> {
+    fn from(location: &'a OwnedLocation) -> Self {
+        location.as_location()
+    }
+}
+
+impl From<Span<'_>> for OwnedSpan {
+    fn from(span: Span<'_>) -> Self {
+        Self { location: span.location.into(), data: span.data.into(), range: 0..span.data.len() }
+    }
+}
+
+impl<'a> From<&'a OwnedSpan> for Span<'a> {
+    fn from(span: &'a OwnedSpan) -> Self {
+        span.as_span()
+    }
+}
+
+impl PartialEq for OwnedSpan {
+    fn eq(&self, other: &Self) -> bool {
+        self.location == other.location && self.data() == other.data()
+    }
+}
+
+impl Eq for OwnedSpan {}
+
+impl std::hash::Hash for OwnedSpan {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.location.hash(state);
+        self.data().hash(state);
+    }
+}
+
+impl std::fmt::Debug for OwnedSpan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&self.as_span(), f)
+    }
+}
+
+impl std::fmt::Display for OwnedLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_location())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::driver::source::{SourceFile, SourceMap};
+    use crate::parser::owned::{OwnedLocation, OwnedSpan};
+    use crate::{ErrorDisplaySettings, ErrorLevel, ErrorRender, Location, Note, Span, identifier, tag};
+
+    /// Parse the names of a file, keeping them after the walker and the borrowed data are gone
+    fn names(file: &SourceFile) -> Vec<OwnedSpan> {
+        let mut walker = file.walker();
+        let mut names = Vec::new();
+        while let Ok(name) = identifier()(&mut walker) {
+            names.push(file.own_span(&name).unwrap());
+            let _ = tag(" ")(&mut walker);
+        }
+        names
+    }
+
+    #[test]
+    fn owned_spans_outlive_the_walker() {
+        let file = SourceFile::from_data("input.txt", String::from("alpha beta gamma"));
+        let spans = names(&file);
+
+        assert_eq!(spans.iter().map(OwnedSpan::data).collect::<Vec<_>>(), ["alpha", "beta", "gamma"]);
+        assert_eq!(spans[1].location, OwnedLocation::from_components(6, 0, "input.txt"));
+        assert_eq!(spans[1].location.to_string(), "column 7 line 1 in input.txt");
+        assert!(spans.iter().all(|span| span.is_in(&file)));
+
+        // Shared spans borrow back as spans of the file's walker, so they render from it
+        let walker = file.walker();
+        let span = Span::from(&spans[2]);
+        assert!(walker.contains_span(&span));
+        assert_eq!(format!("{:?}", spans[2]), format!("{:?}", span));
+
+        let settings = ErrorDisplaySettings::default().with_colored(false);
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, "unknown name", &span.location, vec![Note::new(&span, "not declared", ErrorLevel::Error)], &walker);
+        assert!(render.to_string().ends_with("  1 |alpha beta gamma\n    |           ^^^^^ not declared\n"));
+    }
+
+    #[test]
+    fn owned_span_conversions() {
+        let data = String::from("let x = 1;");
+        let span = Span::from_components(Location::from_components(4, 0, "input.txt"), &data[4..5]);
+
+        // Converting without a shared file copies only the text, and still compares equal
+        let copied = OwnedSpan::from(span);
+        let file = SourceFile::from_data("input.txt", data.as_str());
+        assert!(!copied.is_in(&file));
+        assert_eq!(Span::from(&copied), span);
+        assert_eq!(Location::from(&copied.location), span.location);
+        assert_eq!(OwnedLocation::from(span.location), copied.location);
+        drop(data);
+        assert_eq!(copied.data(), "x");
+
+        let shared = file.own_span(&file.walker().empty_span_at(&Location::from_components(4, 0, "input.txt")).unwrap()).unwrap();
+        assert_eq!(shared.location, copied.location);
+        assert_eq!(shared.data(), "");
+
+        // Spans of other data cannot share the file
+        assert_eq!(file.own_span(&Span::from_components(Location::from_components(4, 0, "input.txt"), "x")), None);
+    }
+
+    #[test]
+    fn owned_spans_of_source_map() {
+        let mut sources = SourceMap::new();
+        sources.add_data("main.txt", "use lib;");
+        let lib = sources.add_data("lib.txt", "fn double(x) = x * 2;");
+
+        let walker = sources.walker(lib).unwrap();
+        let span = Span::from_components(Location::from_components(3, 0, "lib.txt"), &walker.all_data()[3..9]);
+        let owned = sources.own_span(&span).unwrap();
+
+        // The span shares the data of the file it was found in
+        assert!(owned.is_in(sources.get(lib).unwrap()));
+        assert_eq!(owned.location.to_string(), "column 4 line 1 in lib.txt");
+        assert_eq!(owned.data(), "double");
+        assert_eq!(sources.own_span(&Span::from_components(Location::from_components(3, 0, "lib.txt"), "double")), None);
+    }
+}
@@ -1,6 +1,94 @@
-use crate::{ErrorKind, FileWalker, ParsingError, Span};
+use crate::{ErrorKind, FileWalker, ParsingError, Span, Spanned};
+
+/// Whether a list accepted by `list` may, or must, end with a separator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrailingPolicy {
+    Allowed,
+    /// Every item, including the last, must be followed by a separator, as with `;` after statements
+    Required,
+    Forbidden
+}
+
+/// The items of a list accepted by `list`, and the spans of the separators between them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct List<'filedata, T> {
+    pub items: Vec<T>,
+    /// The separators after each item, including any trailing separator
+    pub separators: Vec<Span<'filedata>>
+}
+
+/// A collection repeating combinators such as `many0_into` push their values into, always in the order they were parsed
+///
+/// With the `small-vec` feature, `SmallItems` keeps short lists such as arguments or generic parameters off the heap
+pub trait Collection<T> {
+    /// Construct an empty collection with room for at least `capacity` values
+    fn with_capacity(capacity: usize) -> Self;
+
+    /// Add a value after the values already in the collection
+    fn push(&mut self, value: T);
+
+    /// Get the number of values in the collection
+    fn len(&self) -> usize;
+
+    /// Check if the collection has no values
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A collection which holds up to four values without allocating
+#[cfg(feature = "small-vec")]
+pub type SmallItems<T> = smallvec::SmallVec<[T; 4]>;
+
+/// The result of a parser which keeps whatever it built before failing, for editors which need a tree of a file with errors
+///
+/// The value is None only if nothing useful could be built, in which case there is at least one error.
+/// A value with errors is partial, and the errors are in the order they were found
+pub type PartialResult<'filedata, T> = (Option<T>, Vec<ParsingError<'filedata>>);
+
+impl<T> Collection<T> for Vec<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        Vec::with_capacity(capacity)
+    }
+
+    fn push(&mut self, value: T) {
+        Vec::push(self, value)
+    }
+
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+}
+
+#[cfg(feature = "small-vec")]
+impl<A: smallvec::Array> Collection<A::Item> for smallvec::SmallVec<A> {
+    fn with_capacity(capacity: usize) -> Self {
+        smallvec::SmallVec::with_capacity(capacity)
+    }
+
+    fn push(&mut self, value: A::Item) {
+        smallvec::SmallVec::push(self, value)
+    }
+
+    fn len(&self) -> usize {
+        smallvec::SmallVec::len(self)
+    }
+}
+
+impl<'filedata, T> List<'filedata, T> {
+    /// Get the separator after the last item, if there is one
+    pub fn trailing(&self) -> Option<&Span<'filedata>> {
+        if !self.items.is_empty() && self.separators.len() == self.items.len() {
+            self.separators.last()
+        }
+        else {
+            None
+        }
+    }
+}
 
 #[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
 pub fn map<'filedata, Input, Output>(
     combinator: impl Fn(&mut FileWalker<'filedata>) -> Result<Input, ParsingError<'filedata>>,
     f: impl Fn(Input) -> Output,
@@ -12,6 +100,7 @@ pub fn map<'filedata, Input, Output>(
 }
 
 #[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
 pub fn pair<'filedata, A, B>(
     first: impl Fn(&mut FileWalker<'filedata>) -> Result<A, ParsingError<'filedata>>,
     second: impl Fn(&mut FileWalker<'filedata>) -> Result<B, ParsingError<'filedata>>,
@@ -32,6 +121,7 @@ pub fn pair<'filedata, A, B>(
 }
 
 #[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
 pub fn triple<'filedata, A, B, C>(
     first: impl Fn(&mut FileWalker<'filedata>) -> Result<A, ParsingError<'filedata>>,
     second: impl Fn(&mut FileWalker<'filedata>) -> Result<B, ParsingError<'filedata>>,
@@ -61,27 +151,272 @@ pub fn triple<'filedata, A, B, C>(
 }
 
 #[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts a value between an opening and closing parser, such as an expression in parentheses, keeping only the value
+pub fn delimited<'filedata, A, B, C>(
+    open: impl Fn(&mut FileWalker<'filedata>) -> Result<A, ParsingError<'filedata>>,
+    inner: impl Fn(&mut FileWalker<'filedata>) -> Result<B, ParsingError<'filedata>>,
+    close: impl Fn(&mut FileWalker<'filedata>) -> Result<C, ParsingError<'filedata>>,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<B, ParsingError<'filedata>> {
+    map(triple(open, inner, close), |(_, value, _)| value)
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts a value after a prefix, such as a type after `:`, keeping only the value
+pub fn preceded<'filedata, A, B>(
+    prefix: impl Fn(&mut FileWalker<'filedata>) -> Result<A, ParsingError<'filedata>>,
+    inner: impl Fn(&mut FileWalker<'filedata>) -> Result<B, ParsingError<'filedata>>,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<B, ParsingError<'filedata>> {
+    map(pair(prefix, inner), |(_, value)| value)
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts a value followed by a suffix, such as a statement before `;`, keeping only the value
+pub fn terminated<'filedata, A, B>(
+    inner: impl Fn(&mut FileWalker<'filedata>) -> Result<A, ParsingError<'filedata>>,
+    suffix: impl Fn(&mut FileWalker<'filedata>) -> Result<B, ParsingError<'filedata>>,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<A, ParsingError<'filedata>> {
+    map(pair(inner, suffix), |(value, _)| value)
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
 pub fn opt<'filedata, A>(
     first: impl Fn(&mut FileWalker<'filedata>) -> Result<A, ParsingError<'filedata>>,
 ) -> impl Fn(&mut FileWalker<'filedata>) -> Result<Option<A>, ParsingError<'filedata>> {
-    move |walker: &mut FileWalker<'filedata>| Ok(first(walker).ok())
+    move |walker: &mut FileWalker<'filedata>| {
+        match first(walker) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if !e.is_recoverable() => Err(e),
+            Err(_) => Ok(None)
+        }
+    }
 }
 
 #[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Tries the first parser, then the second if it fails
+///
+/// If both fail, the error of whichever got further into the input is returned, preferring the second's at the same location.
+/// An unrecoverable error from the first, see `cut`, is returned without trying the second
 pub fn alt<'filedata, A>(
     first: impl Fn(&mut FileWalker<'filedata>) -> Result<A, ParsingError<'filedata>>,
     second: impl Fn(&mut FileWalker<'filedata>) -> Result<A, ParsingError<'filedata>>,
 ) -> impl Fn(&mut FileWalker<'filedata>) -> Result<A, ParsingError<'filedata>> {
     move |walker: &mut FileWalker<'filedata>| {
-        if let Ok(value) = first(walker) {
-            Ok(value)
-        } else {
-            second(walker)
+        match first(walker) {
+            Ok(value) => Ok(value),
+            Err(first_error) if !first_error.is_recoverable() => Err(first_error),
+            Err(first_error) => second(walker).map_err(|second_error| first_error.furthest(second_error))
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Commits to the construct being parsed, so an error from the parser is unrecoverable and is reported rather than backtracked over
+///
+/// Once the `fn` keyword is seen, `pair(tag("fn"), cut(function_rest))` stops `alt`, `opt`, `choice` and the repeating combinators
+/// from trying something else when the rest of the function is wrong
+pub fn cut<'filedata, T>(
+    parser: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        parser(walker).map_err(|error| match error.is_recoverable() {
+            true => ParsingError(error.0, ErrorKind::Unrecoverable(Box::new(error))),
+            false => error
+        })
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Labels the construct a parser accepts, such as `"function declaration"`, so its errors carry the chain of constructs being parsed
+///
+/// The error keeps its location and message, the labels are found with `ParsingError::contexts`
+pub fn context<'filedata, T>(
+    label: &'static str,
+    parser: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        parser(walker).map_err(|error| ParsingError(error.0, ErrorKind::InContext(label, Box::new(error))))
+    }
+}
+
+/// Parsers which can be tried in order by `choice`, implemented for tuples of up to 16 parsers and for arrays and vectors of parsers,
+/// such as a `Vec` of boxed parsers
+///
+/// Calling `choose` directly gives the error of every alternative, for inspecting the ones `ParsingError::deepest` did not choose
+pub trait Choice<'filedata, T> {
+    /// Try each parser in order, returning the value of the first to succeed, or the errors of every parser in order if none do
+    ///
+    /// An unrecoverable error stops the search, and is returned alone
+    fn choose(&self, walker: &mut FileWalker<'filedata>) -> Result<T, Vec<ParsingError<'filedata>>>;
+}
+
+macro_rules! impl_choice {
+    ($($parser:ident $index:tt),+) => {
+        impl<'filedata, T, $($parser: Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>),+> Choice<'filedata, T> for ($($parser,)+) {
+            fn choose(&self, walker: &mut FileWalker<'filedata>) -> Result<T, Vec<ParsingError<'filedata>>> {
+                let mut errors = Vec::new();
+                $(
+                    match (self.$index)(walker) {
+                        Ok(value) => return Ok(value),
+                        Err(e) if !e.is_recoverable() => return Err(vec![e]),
+                        Err(e) => errors.push(e)
+                    }
+                )+
+                Err(errors)
+            }
+        }
+    };
+}
+
+impl_choice!(A 0);
+impl_choice!(A 0, B 1);
+impl_choice!(A 0, B 1, C 2);
+impl_choice!(A 0, B 1, C 2, D 3);
+impl_choice!(A 0, B 1, C 2, D 3, E 4);
+impl_choice!(A 0, B 1, C 2, D 3, E 4, F 5);
+impl_choice!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_choice!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+impl_choice!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+impl_choice!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+impl_choice!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+impl_choice!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+impl_choice!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12);
+impl_choice!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13);
+impl_choice!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13, O 14);
+impl_choice!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13, O 14, P 15);
+
+impl<'filedata, T, P: Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>> Choice<'filedata, T> for [P] {
+    fn choose(&self, walker: &mut FileWalker<'filedata>) -> Result<T, Vec<ParsingError<'filedata>>> {
+        let mut errors = Vec::with_capacity(self.len());
+
+        for parser in self {
+            match parser(walker) {
+                Ok(value) => return Ok(value),
+                Err(e) if !e.is_recoverable() => return Err(vec![e]),
+                Err(e) => errors.push(e)
+            }
+        }
+
+        Err(errors)
+    }
+}
+
+impl<'filedata, T, P: Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>, const N: usize> Choice<'filedata, T> for [P; N] {
+    fn choose(&self, walker: &mut FileWalker<'filedata>) -> Result<T, Vec<ParsingError<'filedata>>> {
+        self[..].choose(walker)
+    }
+}
+
+impl<'filedata, T, P: Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>> Choice<'filedata, T> for Vec<P> {
+    fn choose(&self, walker: &mut FileWalker<'filedata>) -> Result<T, Vec<ParsingError<'filedata>>> {
+        self[..].choose(walker)
+    }
+}
+
+/// Combine the errors of the alternatives of a `choice` into one at the furthest location any of them reached
+///
+/// If every error there is an expectation they are listed together, otherwise the last of them is kept as `alt` would
+fn merge_alternatives<'filedata>(errors: Vec<ParsingError<'filedata>>) -> Option<ParsingError<'filedata>> {
+    let location = errors.iter().map(|error| error.0).reduce(|furthest, location| match location.partial_cmp(&furthest) {
+        Some(std::cmp::Ordering::Greater) => location,
+        _ => furthest
+    })?;
+
+    let mut kinds: Vec<ErrorKind<'filedata>> = Vec::new();
+    for error in errors.into_iter().filter(|error| error.0 == location) {
+        let alternatives = match error.1 {
+            ErrorKind::ExpectedAlternatives(alternatives) => alternatives,
+            kind => vec![kind]
+        };
+
+        for kind in alternatives {
+            if !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
+        }
+    }
+
+    if kinds.len() > 1 && kinds.iter().all(|kind| kind.expectation().is_some()) {
+        Some(ParsingError(location, ErrorKind::ExpectedAlternatives(kinds)))
+    }
+    else {
+        kinds.pop().map(|kind| ParsingError(location, kind))
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Tries any number of parsers in order, returning the value of the first to succeed, as a flat alternative to nested `alt`s
+///
+/// If none succeed, the error lists everything expected by the alternatives which got furthest, such as ``expected one of `(`, a number``
+pub fn choice<'filedata, T>(
+    alternatives: impl Choice<'filedata, T>,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        alternatives.choose(walker).map_err(|errors| {
+            merge_alternatives(errors).unwrap_or_else(|| ParsingError(walker.current_location(), ErrorKind::ExpectedKind("one of no alternatives")))
+        })
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Behaves like `alt`, but always runs both parsers and fails if they both accept input of different lengths, for finding ambiguities while designing a grammar
+pub fn alt_checked<'filedata, A>(
+    first: impl Fn(&mut FileWalker<'filedata>) -> Result<A, ParsingError<'filedata>>,
+    second: impl Fn(&mut FileWalker<'filedata>) -> Result<A, ParsingError<'filedata>>,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<A, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        let start = walker.get_marker();
+
+        let first_result = match first(walker) {
+            Err(e) if !e.is_recoverable() => return Err(e),
+            result => result.map(|value| (value, walker.get_marker()))
+        };
+        walker.pop_back(start);
+        let second_result = second(walker).map(|value| (value, walker.get_marker()));
+
+        match (first_result, second_result) {
+            (Ok((value, first_end)), Ok((_, second_end))) => {
+                if first_end == second_end {
+                    return Ok(value);
+                }
+
+                walker.pop_back(first_end);
+                let first_span = walker.span_from_marker_to_here(start).unwrap();
+                walker.pop_back(second_end);
+                let second_span = walker.span_from_marker_to_here(start).unwrap();
+                walker.pop_back(start);
+
+                Err(ParsingError(
+                    first_span.location,
+                    ErrorKind::AmbiguousAlternatives {
+                        first: first_span.data,
+                        second: second_span.data,
+                    },
+                ))
+            }
+            (Ok((value, first_end)), Err(_)) => {
+                walker.pop_back(first_end);
+                Ok(value)
+            }
+            (Err(_), Ok((value, _))) => Ok(value),
+            (Err(first_error), Err(second_error)) => {
+                walker.pop_back(start);
+                Err(first_error.furthest(second_error))
+            }
         }
     }
 }
 
 #[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
 /// Accepts input that satisfies the first parser, but not the second, returns the result of the first
 pub fn but_not<'filedata, A, B>(
     first: impl Fn(&mut FileWalker<'filedata>) -> Result<A, ParsingError<'filedata>>,
@@ -112,6 +447,7 @@ pub fn but_not<'filedata, A, B>(
 }
 
 #[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
 /// Returns the span of anything that accepts the wrapped parser
 pub fn accepts<'filedata, T>(
     combinator: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
@@ -124,6 +460,7 @@ pub fn accepts<'filedata, T>(
 }
 
 #[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
 /// Returns the span of anything that accepts any count of the wrapped parser
 pub fn accepts_while<'filedata, T>(
     combinator: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
@@ -131,16 +468,515 @@ pub fn accepts_while<'filedata, T>(
     move |walker: &mut FileWalker<'filedata>| {
         let start = walker.get_marker();
         combinator(walker)?;
-        while combinator(walker).is_ok() {}
-        Ok(walker.span_from_marker_to_here(start).unwrap())
+        loop {
+            match combinator(walker) {
+                Ok(_) => {}
+                Err(e) if !e.is_recoverable() => {
+                    walker.pop_back(start);
+                    return Err(e);
+                }
+                Err(_) => return Ok(walker.span_from_marker_to_here(start).unwrap())
+            }
+        }
+    }
+}
+
+/// Apply a parser as many times as it succeeds, moving the walker back to before the attempt which failed
+///
+/// A success which consumes nothing also ends the repetition, as it would succeed forever
+fn repeat<'filedata, T>(
+    walker: &mut FileWalker<'filedata>,
+    combinator: &impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+    values: &mut impl Collection<T>,
+) -> ParsingError<'filedata> {
+    loop {
+        let before = walker.get_marker();
+
+        match combinator(walker) {
+            Ok(_) if walker.get_marker() == before => return ParsingError(walker.current_location(), ErrorKind::ExpectedKind("input to be consumed")),
+            Ok(value) => values.push(value),
+            Err(e) => {
+                walker.pop_back(before);
+                return e;
+            }
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Finds every non-overlapping match of the wrapped parser in the rest of the input, such as for a lint or for highlighting every
+/// occurrence of a name, returning each match with its span
+///
+/// The text between matches is skipped, so this never fails and always consumes the rest of the input, see `FileWalker::find_iter`
+pub fn find_all<'filedata, T>(
+    combinator: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<Vec<Spanned<'filedata, T>>, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        let mut search = walker.find_iter(&combinator);
+        let matches = search.by_ref().collect();
+        walker.join(search.walker());
+
+        Ok(matches)
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts the wrapped parser any number of times, including none, returning each of its values
+pub fn many0<'filedata, T>(
+    combinator: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<Vec<T>, ParsingError<'filedata>> {
+    many0_into(combinator, 0)
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts the wrapped parser any number of times like `many0`, collecting the values into a collection with room for `capacity` of them
+pub fn many0_into<'filedata, T, C: Collection<T>>(
+    combinator: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+    capacity: usize,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<C, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        let start = walker.get_marker();
+        let mut values = C::with_capacity(capacity);
+        let error = repeat(walker, &combinator, &mut values);
+
+        if !error.is_recoverable() {
+            walker.pop_back(start);
+            return Err(error);
+        }
+
+        Ok(values)
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts the wrapped parser at least once, returning each of its values, or the error of the first attempt
+pub fn many1<'filedata, T>(
+    combinator: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<Vec<T>, ParsingError<'filedata>> {
+    many1_into(combinator, 0)
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts the wrapped parser at least once like `many1`, collecting the values into a collection with room for `capacity` of them
+pub fn many1_into<'filedata, T, C: Collection<T>>(
+    combinator: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+    capacity: usize,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<C, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        let start = walker.get_marker();
+        let mut values = C::with_capacity(capacity);
+        let error = repeat(walker, &combinator, &mut values);
+
+        if values.is_empty() || !error.is_recoverable() {
+            walker.pop_back(start);
+            Err(error)
+        }
+        else {
+            Ok(values)
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts the wrapped parser until the terminator is accepted, returning the values and the terminator's value
+///
+/// The terminator is tried before each item, so an item which looks like the terminator ends the repetition. If neither is accepted,
+/// the walker is moved back to where it started and the furthest of their errors is returned, preferring the item's
+pub fn many_till<'filedata, T, E>(
+    combinator: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+    terminator: impl Fn(&mut FileWalker<'filedata>) -> Result<E, ParsingError<'filedata>>,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<(Vec<T>, E), ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        let start = walker.get_marker();
+        let mut values = Vec::new();
+
+        loop {
+            let before = walker.get_marker();
+
+            let terminator_error = match terminator(walker) {
+                Ok(end) => return Ok((values, end)),
+                Err(e) if !e.is_recoverable() => {
+                    walker.pop_back(start);
+                    return Err(e);
+                }
+                Err(e) => e
+            };
+            walker.pop_back(before);
+
+            match combinator(walker) {
+                Ok(value) if walker.get_marker() != before => values.push(value),
+                // An item which consumes nothing would be accepted forever without reaching the terminator
+                Ok(_) => {
+                    walker.pop_back(start);
+                    return Err(terminator_error);
+                }
+                Err(e) if !e.is_recoverable() => {
+                    walker.pop_back(start);
+                    return Err(e);
+                }
+                Err(e) => {
+                    walker.pop_back(start);
+                    return Err(terminator_error.furthest(e));
+                }
+            }
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts one nested bracket-like item and everything nested inside it, using a stack rather than recursion so deeply nested input
+/// cannot overflow the call stack
+///
+/// Inside each item, `close` is tried with the item's opener before `open` is tried for a nested item. `on_enter` is called with each
+/// opener and its depth, starting at zero, and can reject it, such as to limit the depth. `on_exit` builds the value of an item from
+/// its opener, the values of the items nested directly inside it, and its closer
+pub fn iterate_nested<'filedata, O, C, R>(
+    open: impl Fn(&mut FileWalker<'filedata>) -> Result<O, ParsingError<'filedata>>,
+    close: impl Fn(&mut FileWalker<'filedata>, &O) -> Result<C, ParsingError<'filedata>>,
+    on_enter: impl Fn(&O, usize) -> Result<(), ParsingError<'filedata>>,
+    on_exit: impl Fn(O, Vec<R>, C) -> R,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<R, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        let start = walker.get_marker();
+
+        let mut parse = || {
+            let first = open(walker)?;
+            on_enter(&first, 0)?;
+
+            // The items which are open, from the outermost, with the values of the items already closed inside them
+            let mut stack: Vec<(O, Vec<R>)> = vec![(first, Vec::new())];
+
+            loop {
+                let before = walker.get_marker();
+                let (opener, _) = stack.last().unwrap();
+
+                match close(walker, opener) {
+                    Ok(closer) => {
+                        let (opener, children) = stack.pop().unwrap();
+                        let value = on_exit(opener, children, closer);
+
+                        match stack.last_mut() {
+                            Some((_, siblings)) => siblings.push(value),
+                            None => return Ok(value)
+                        }
+                    }
+                    Err(close_error) if !close_error.is_recoverable() => return Err(close_error),
+                    Err(close_error) => {
+                        walker.pop_back(before);
+
+                        match open(walker) {
+                            Ok(opener) => {
+                                on_enter(&opener, stack.len())?;
+                                stack.push((opener, Vec::new()));
+                            }
+                            // The closer is preferred when both fail at the same place, as it is what the innermost item needs
+                            Err(open_error) => return Err(open_error.furthest(close_error))
+                        }
+                    }
+                }
+            }
+        };
+
+        let result = parse();
+        if result.is_err() {
+            walker.pop_back(start);
+        }
+
+        result
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Runs the wrapped parser without consuming any input, returning its value or its error
+pub fn peek<'filedata, T>(
+    combinator: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        let start = walker.get_marker();
+        let result = combinator(walker);
+        walker.pop_back(start);
+
+        result
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Succeeds without consuming any input if the wrapped parser fails, such as to check a keyword is not followed by another letter
+///
+/// If the wrapped parser succeeds, the error is at the current location and shows what it accepted. Unrecoverable errors of the
+/// wrapped parser are returned, since the grammar committed to it
+pub fn not<'filedata, T>(
+    combinator: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<(), ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        let start = walker.get_marker();
+        let result = combinator(walker);
+        let span = walker.span_from_marker_to_here(start).unwrap();
+        walker.pop_back(start);
+
+        match result {
+            Ok(_) => Err(ParsingError(span.location, ErrorKind::InverseFailedGot(span.data))),
+            Err(e) if !e.is_recoverable() => Err(e),
+            Err(_) => Ok(())
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Runs the wrapped parser without consuming any input, seeing only the next `n_chars` characters, so the cost of the lookahead is bounded
+pub fn lookahead_within<'filedata, T>(
+    n_chars: usize,
+    combinator: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        let rest = walker.current_string();
+        let end = rest.char_indices().nth(n_chars).map_or(rest.len(), |(index, _)| index);
+        let window = Span::from_components(walker.current_location(), &rest[..end]);
+
+        combinator(&mut FileWalker::from_span(&window)).map_err(|e| {
+            // Failing at the end of a window which cut the input short means the parser needed to look further
+            let mut window_end = FileWalker::from_span(&window);
+            while window_end.step().is_some() {}
+
+            if end < rest.len() && e.0 == window_end.current_location() {
+                ParsingError(e.0, ErrorKind::LookaheadExceeded(n_chars))
+            }
+            else {
+                e
+            }
+        })
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Replaces any error from the wrapped parser with a single expectation phrased for the language, such as `expected(type_name, "a type name")`, at the same location
+pub fn expected<'filedata, T>(
+    combinator: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+    description: &'static str,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        combinator(walker).map_err(|e| match e.is_recoverable() {
+            true => ParsingError(e.0, ErrorKind::ExpectedKind(description)),
+            // A committed error already points at the real problem
+            false => e
+        })
+    }
+}
+
+/// How a list accepted by `list_items` ended
+enum ListEnd<'filedata> {
+    /// The list ended normally, after an item failed if there is an error
+    Complete(Option<ParsingError<'filedata>>),
+    /// The list ended early with an error
+    Failed(ParsingError<'filedata>)
+}
+
+/// Accept the items of a list, stopping at the first error which ends it early
+///
+/// An item after a separator which fails after getting into the input, such as `-` without its number, ends the list early with its
+/// error, rather than the separator being taken as trailing. On an error, the items and separators before it are kept and the walker
+/// is left just after the last item
+fn list_items<'filedata, T>(
+    walker: &mut FileWalker<'filedata>,
+    item: &impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+    separator: &impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>>,
+    policy: TrailingPolicy,
+    items: &mut impl Collection<T>,
+    separators: &mut Vec<Span<'filedata>>,
+) -> ListEnd<'filedata> {
+    let mut after_item = walker.get_marker();
+
+    let item_error = loop {
+        let item_start = walker.current_location();
+
+        match item(walker) {
+            Ok(value) => items.push(value),
+            Err(e) if !e.is_recoverable() => {
+                walker.pop_back(after_item);
+                return ListEnd::Failed(e);
+            }
+            Err(e) if !separators.is_empty() && e.0.partial_cmp(&item_start) == Some(std::cmp::Ordering::Greater) => {
+                separators.pop();
+                walker.pop_back(after_item);
+                return ListEnd::Failed(e);
+            }
+            Err(e) => break Some(e)
+        }
+        after_item = walker.get_marker();
+
+        match separator(walker) {
+            Ok(span) => separators.push(span),
+            Err(e) if policy == TrailingPolicy::Required || !e.is_recoverable() => {
+                walker.pop_back(after_item);
+                return ListEnd::Failed(e);
+            }
+            Err(_) => break None
+        }
+    };
+
+    // The list has a trailing separator if there is a separator after every item
+    if policy == TrailingPolicy::Forbidden && !items.is_empty() && separators.len() == items.len() {
+        let trailing = separators.pop().unwrap();
+        walker.pop_back(after_item);
+        return ListEnd::Failed(ParsingError(trailing.location, ErrorKind::TrailingSeparator(trailing.data)));
+    }
+
+    ListEnd::Complete(item_error)
+}
+
+/// Accept the items of a list into a `List`, see `list_items`
+fn list_of<'filedata, T>(
+    walker: &mut FileWalker<'filedata>,
+    item: &impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+    separator: &impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>>,
+    policy: TrailingPolicy,
+) -> (List<'filedata, T>, ListEnd<'filedata>) {
+    let mut list = List { items: Vec::new(), separators: Vec::new() };
+    let end = list_items(walker, item, separator, policy, &mut list.items, &mut list.separators);
+
+    (list, end)
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts any number of items separated by a separator, where the policy decides if the list may end with a separator
+///
+/// A separator which is not followed by an item ends the list, and is reported as a trailing separator if they are forbidden. An item
+/// after a separator which fails part way through fails the list with its error
+pub fn list<'filedata, T>(
+    item: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+    separator: impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>>,
+    policy: TrailingPolicy,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<List<'filedata, T>, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        let start = walker.get_marker();
+
+        match list_of(walker, &item, &separator, policy) {
+            (list, ListEnd::Complete(_)) => Ok(list),
+            (_, ListEnd::Failed(error)) => {
+                walker.pop_back(start);
+                Err(error)
+            }
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts any number of items separated by a separator like `list`, returning only the items
+pub fn separated_list0<'filedata, T>(
+    item: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+    separator: impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>>,
+    policy: TrailingPolicy,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<Vec<T>, ParsingError<'filedata>> {
+    separated_list0_into(item, separator, policy, 0)
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts any number of items like `separated_list0`, collecting them into a collection with room for `capacity` of them
+pub fn separated_list0_into<'filedata, T, C: Collection<T>>(
+    item: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+    separator: impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>>,
+    policy: TrailingPolicy,
+    capacity: usize,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<C, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        let start = walker.get_marker();
+        let mut items = C::with_capacity(capacity);
+
+        match list_items(walker, &item, &separator, policy, &mut items, &mut Vec::new()) {
+            ListEnd::Complete(_) => Ok(items),
+            ListEnd::Failed(error) => {
+                walker.pop_back(start);
+                Err(error)
+            }
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts at least one item separated by a separator like `list`, returning only the items, or the error of the first item if there are none
+pub fn separated_list1<'filedata, T>(
+    item: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+    separator: impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>>,
+    policy: TrailingPolicy,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<Vec<T>, ParsingError<'filedata>> {
+    separated_list1_into(item, separator, policy, 0)
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts at least one item like `separated_list1`, collecting them into a collection with room for `capacity` of them
+pub fn separated_list1_into<'filedata, T, C: Collection<T>>(
+    item: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+    separator: impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>>,
+    policy: TrailingPolicy,
+    capacity: usize,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<C, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        let start = walker.get_marker();
+        let mut items = C::with_capacity(capacity);
+
+        match list_items(walker, &item, &separator, policy, &mut items, &mut Vec::new()) {
+            // The list is only empty when the first item fails
+            ListEnd::Complete(Some(error)) if items.is_empty() => Err(error),
+            ListEnd::Complete(_) => Ok(items),
+            ListEnd::Failed(error) => {
+                walker.pop_back(start);
+                Err(error)
+            }
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts a list like `list`, but when the list ends with an error the items before it are kept, and the walker is left after the last of them
+pub fn list_partial<'filedata, T>(
+    item: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+    separator: impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>>,
+    policy: TrailingPolicy,
+) -> impl Fn(&mut FileWalker<'filedata>) -> PartialResult<'filedata, List<'filedata, T>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        match list_of(walker, &item, &separator, policy) {
+            (list, ListEnd::Complete(_)) => (Some(list), Vec::new()),
+            (list, ListEnd::Failed(error)) => (Some(list), vec![error])
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Adapts a parser to return a `PartialResult`, with either its value or its error
+pub fn partial<'filedata, T>(
+    combinator: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+) -> impl Fn(&mut FileWalker<'filedata>) -> PartialResult<'filedata, T> {
+    move |walker: &mut FileWalker<'filedata>| {
+        match combinator(walker) {
+            Ok(value) => (Some(value), Vec::new()),
+            Err(e) => (None, vec![e])
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
     use crate::{
-        accepts_while, alt, but_not, map, one_of, opt, pair, tag, take_while, triple, ErrorKind,
-        FileWalker, Location, ParsingError, take_if,
+        accepts_while, alt, alt_checked, but_not, choice, Choice, context, cut, delimited, expected, find_all, iterate_nested, list, list_partial, lookahead_within, many0, many0_into, many1, many1_into, many_till, not, partial, peek, preceded, separated_list0, separated_list0_into, separated_list1, separated_list1_into, map, one_of, opt, pair, tag, take_while, terminated, triple, ErrorKind,
+        FileWalker, List, Location, ParsingError, Span, TrailingPolicy, take_if,
     };
 
     #[test]
@@ -289,6 +1125,28 @@ mod test {
         );
     }
 
+    #[test]
+    fn sequences_keep_one_value() {
+        let mut walker = FileWalker::from_data("(x);y", "input");
+        assert_eq!(terminated(delimited(tag("("), tag("x"), tag(")")), tag(";"))(&mut walker).unwrap().data, "x");
+        assert_eq!(preceded(tag(""), tag("y"))(&mut walker).unwrap().data, "y");
+
+        // A failure in any part returns the walker to where it started
+        for data in ["(x]", "(y)", "x)"] {
+            let mut walker = FileWalker::from_data(data, "input");
+            assert!(delimited(tag("("), tag("x"), tag(")"))(&mut walker).is_err());
+            assert_eq!(walker.current_string(), data);
+        }
+
+        let mut walker = FileWalker::from_data("x:", "input");
+        assert_eq!(
+            terminated(tag("x"), tag(";"))(&mut walker),
+            Err(ParsingError(Location::from_components(1, 0, "input"), ErrorKind::ExpectedTag(";")))
+        );
+        assert!(preceded(tag("x"), tag(";"))(&mut walker).is_err());
+        assert_eq!(walker.current_string(), "x:");
+    }
+
     #[test]
     fn opt_ok() {
         let comb_a = tag("Hello");
@@ -329,6 +1187,72 @@ mod test {
         );
     }
 
+    #[test]
+    fn alt_furthest_err() {
+        let call = map(pair(tag("f("), tag(")")), |(name, _)| name);
+
+        // The first alternative got further into the input, so its error is kept and the walker is still moved back
+        let mut walker = FileWalker::from_data("f(x)", "input");
+        assert_eq!(alt(&call, tag("g"))(&mut walker),
+            Err(ParsingError(Location::from_components(2, 0, "input"), ErrorKind::ExpectedTag(")"))));
+        assert_eq!(walker.current_string(), "f(x)");
+
+        // The losing alternatives can be inspected by trying them directly
+        let errors = (&call, tag("g"), tag("h")).choose(&mut walker).unwrap_err();
+        let (deepest, losing) = ParsingError::deepest(errors).unwrap();
+        assert_eq!(deepest.1, ErrorKind::ExpectedTag(")"));
+        assert_eq!(losing.into_iter().map(|error| error.1).collect::<Vec<_>>(), [ErrorKind::ExpectedTag("g"), ErrorKind::ExpectedTag("h")]);
+        assert_eq!(ParsingError::deepest([]), None);
+    }
+
+    #[test]
+    fn alt_checked_ok() {
+        let comb_a = tag("Hello");
+        let comb_b = take_while(|c| c.is_ascii_digit(), "digit");
+
+        let mut walker = FileWalker::from_data("Hello World", "input");
+        let v = alt_checked(&comb_a, &comb_b)(&mut walker).unwrap();
+        assert_eq!(v.data, "Hello");
+        assert_eq!(walker.current_string(), " World");
+
+        let mut walker = FileWalker::from_data("1234 hello", "input");
+        let v = alt_checked(&comb_a, &comb_b)(&mut walker).unwrap();
+        assert_eq!(v.data, "1234");
+        assert_eq!(walker.current_string(), " hello");
+
+        // Both accepting the same input is not an ambiguity
+        let comb_c = take_while(|c| c.is_alphabetic(), "alphabetic");
+        let mut walker = FileWalker::from_data("Hello World", "input");
+        let v = alt_checked(&comb_a, &comb_c)(&mut walker).unwrap();
+        assert_eq!(v.data, "Hello");
+        assert_eq!(walker.current_string(), " World");
+    }
+
+    #[test]
+    fn alt_checked_err() {
+        let comb_a = tag("He");
+        let comb_b = tag("Hello");
+
+        let mut walker = FileWalker::from_data("Hello World", "input");
+        assert_eq!(
+            alt_checked(&comb_a, &comb_b)(&mut walker),
+            Err(ParsingError(
+                Location::from_components(0, 0, "input"),
+                ErrorKind::AmbiguousAlternatives { first: "He", second: "Hello" }
+            ))
+        );
+        assert_eq!(walker.current_string(), "Hello World");
+
+        let mut walker = FileWalker::from_data("World", "input");
+        assert_eq!(
+            alt_checked(&comb_a, &comb_b)(&mut walker),
+            Err(ParsingError(
+                Location::from_components(0, 0, "input"),
+                ErrorKind::ExpectedTag("Hello")
+            ))
+        );
+    }
+
     #[test]
     fn but_not_ok() {
         let comb_a = take_while(|c| c.is_uppercase(), "uppercase");
@@ -390,11 +1314,12 @@ mod test {
     fn accepts_while_err() {
         let comb = alt(tag("Balance"), tag("alcony"));
 
+        // The first alternative got further, so its error is reported
         assert_eq!(
             accepts_while(&comb)(&mut FileWalker::from_data("Balcony", "input")),
             Err(ParsingError(
-                Location::from_components(0, 0, "input"),
-                ErrorKind::ExpectedTag("alcony")
+                Location::from_components(3, 0, "input"),
+                ErrorKind::TagDiverged { tag: "Balance", matched: Span::from_components(Location::from_components(0, 0, "input"), "Bal"), found: "c" }
             ))
         );
 
@@ -459,4 +1384,451 @@ mod test {
             ))
         );
     }
+
+    #[test]
+    fn lookahead_within_ok() {
+        let mut walker = FileWalker::from_data("Hello World", "input");
+
+        let span = lookahead_within(5, tag("Hello"))(&mut walker).unwrap();
+        assert_eq!(span, Span::from_components(Location::from_components(0, 0, "input"), "Hello"));
+        assert_eq!(walker.current_string(), "Hello World");
+    }
+
+    #[test]
+    fn lookahead_within_err() {
+        let mut walker = FileWalker::from_data("Hello World", "input");
+        assert_eq!(
+            lookahead_within(3, tag("Hello"))(&mut walker),
+            Err(ParsingError(Location::from_components(3, 0, "input"), ErrorKind::LookaheadExceeded(3))));
+
+        // Failures within the window are reported as they are
+        assert_eq!(
+            lookahead_within(4, tag("Help"))(&mut walker).unwrap_err().1,
+            ErrorKind::TagDiverged { tag: "Help", matched: Span::from_components(Location::from_components(0, 0, "input"), "Hel"), found: "l" });
+
+        // As is reaching the end of the input itself
+        let mut walker = FileWalker::from_data("Hel", "input");
+        assert!(matches!(lookahead_within(3, tag("Hello"))(&mut walker).unwrap_err().1, ErrorKind::TagDiverged { .. }));
+    }
+
+    #[test]
+    fn expected_replaces_error() {
+        let operator = expected(one_of("!#$%&*+./<=>?@"), "an operator");
+
+        let mut walker = FileWalker::from_data("a + b", "input");
+        let error = operator(&mut walker).unwrap_err();
+        assert_eq!(error, ParsingError(Location::from_components(0, 0, "input"), ErrorKind::ExpectedKind("an operator")));
+        assert_eq!(error.to_string(), "expected an operator");
+
+        // The location of the original error is kept
+        let mut walker = FileWalker::from_data("Help", "input");
+        assert_eq!(
+            expected(tag("Hello"), "a greeting")(&mut walker),
+            Err(ParsingError(Location::from_components(3, 0, "input"), ErrorKind::ExpectedKind("a greeting"))));
+
+        assert_eq!(operator(&mut FileWalker::from_data("+", "input")).unwrap().data, "+");
+    }
+
+    #[test]
+    fn list_trailing_policy() {
+        let number = || take_while(|c: char| c.is_ascii_digit(), "number");
+        let parse = |input, policy| {
+            let mut walker = FileWalker::from_data(input, "input");
+            let result = list(number(), tag(","), policy)(&mut walker);
+            (result, walker.current_string())
+        };
+
+        let (result, rest) = parse("1,2,3)", TrailingPolicy::Allowed);
+        let result = result.unwrap();
+        assert_eq!(result.items.iter().map(|span| span.data).collect::<Vec<_>>(), vec!["1", "2", "3"]);
+        assert_eq!(result.separators.iter().map(|span| span.location.column).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(result.trailing(), None);
+        assert_eq!(rest, ")");
+
+        let (result, rest) = parse("1,2,)", TrailingPolicy::Allowed);
+        assert_eq!(result.unwrap().trailing().unwrap().location, Location::from_components(3, 0, "input"));
+        assert_eq!(rest, ")");
+
+        let (result, rest) = parse("1,2,)", TrailingPolicy::Forbidden);
+        assert_eq!(result, Err(ParsingError(Location::from_components(3, 0, "input"), ErrorKind::TrailingSeparator(","))));
+        assert_eq!(result.unwrap_err().to_string(), "trailing `,` not allowed here");
+        assert_eq!(rest, "1,2,)");
+
+        let (result, _) = parse("1,2)", TrailingPolicy::Forbidden);
+        assert_eq!(result.unwrap().items.len(), 2);
+
+        // The error of an item cut short after a separator is kept, and the list is not taken to have ended
+        let mut walker = FileWalker::from_data("1,2,-)", "input");
+        let signed = map(pair(opt(tag("-")), number()), |(_, number)| number);
+        assert_eq!(list(&signed, tag(","), TrailingPolicy::Allowed)(&mut walker),
+            Err(ParsingError(Location::from_components(5, 0, "input"), ErrorKind::ExpectedKind("number"))));
+        assert_eq!(walker.current_string(), "1,2,-)");
+
+        let (result, _) = parse("1,2,)", TrailingPolicy::Required);
+        assert_eq!(result.unwrap().separators.len(), 2);
+
+        let (result, rest) = parse("1,2)", TrailingPolicy::Required);
+        assert_eq!(result, Err(ParsingError(Location::from_components(3, 0, "input"), ErrorKind::ExpectedTag(","))));
+        assert_eq!(rest, "1,2)");
+
+        // An empty list never has a trailing separator
+        let (result, rest) = parse(")", TrailingPolicy::Required);
+        assert_eq!(result, Ok(List { items: vec![], separators: vec![] }));
+        assert_eq!(rest, ")");
+    }
+
+    #[test]
+    fn list_partial_keeps_prefix() {
+        let number = || take_while(|c: char| c.is_ascii_digit(), "number");
+        let parse = |input, policy| {
+            let mut walker = FileWalker::from_data(input, "input");
+            let (list, errors) = list_partial(number(), tag(","), policy)(&mut walker);
+            let list = list.unwrap();
+            (list.items.iter().map(|span| span.data).collect::<Vec<_>>(), list.separators.len(), errors, walker.current_string())
+        };
+
+        assert_eq!(parse("1,2,3)", TrailingPolicy::Forbidden), (vec!["1", "2", "3"], 2, vec![], ")"));
+
+        // The items before a late failure are kept, and the walker is left after the last of them
+        assert_eq!(parse("1,2,)", TrailingPolicy::Forbidden), (vec!["1", "2"], 1,
+            vec![ParsingError(Location::from_components(3, 0, "input"), ErrorKind::TrailingSeparator(","))], ",)"));
+        assert_eq!(parse("1,2)", TrailingPolicy::Required), (vec!["1", "2"], 1,
+            vec![ParsingError(Location::from_components(3, 0, "input"), ErrorKind::ExpectedTag(","))], ")"));
+
+        // An item which fails part way through is reported, rather than the separator before it being taken as trailing
+        let signed = || map(pair(opt(tag("-")), number()), |(_, number)| number);
+        for policy in [TrailingPolicy::Allowed, TrailingPolicy::Forbidden] {
+            let mut walker = FileWalker::from_data("1,2,-x)", "input");
+            let (list, errors) = list_partial(signed(), tag(","), policy)(&mut walker);
+            assert_eq!(list.unwrap().items.len(), 2);
+            assert_eq!(errors, vec![ParsingError(Location::from_components(5, 0, "input"), ErrorKind::ExpectedKind("number"))]);
+            assert_eq!(walker.current_string(), ",-x)");
+        }
+
+        // An item which fails where it starts cannot be told apart from the end of the list, so the separator is trailing
+        assert_eq!(parse("1,2,x)", TrailingPolicy::Allowed), (vec!["1", "2"], 2, vec![], "x)"));
+        assert_eq!(parse("1,2,x)", TrailingPolicy::Forbidden), (vec!["1", "2"], 1,
+            vec![ParsingError(Location::from_components(3, 0, "input"), ErrorKind::TrailingSeparator(","))], ",x)"));
+    }
+
+    #[test]
+    fn partial_adapter() {
+        let mut walker = FileWalker::from_data("Hello!", "input");
+        assert_eq!(partial(tag("Hello"))(&mut walker).0.unwrap().data, "Hello");
+
+        let (value, errors) = partial(tag("World"))(&mut walker);
+        assert_eq!(value, None);
+        assert_eq!(errors, vec![ParsingError(Location::from_components(5, 0, "input"), ErrorKind::ExpectedTag("World"))]);
+        assert_eq!(walker.current_string(), "!");
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Group<'filedata> {
+        open: &'filedata str,
+        children: Vec<Group<'filedata>>
+    }
+
+    fn group<'filedata>(max_depth: usize) -> impl Fn(&mut FileWalker<'filedata>) -> Result<Group<'filedata>, ParsingError<'filedata>> {
+        iterate_nested(
+            alt(tag("("), tag("[")),
+            |walker: &mut FileWalker<'filedata>, open: &Span<'filedata>| if open.data == "(" { tag(")")(walker) } else { tag("]")(walker) },
+            move |open: &Span<'filedata>, depth| if depth < max_depth {
+                Ok(())
+            }
+            else {
+                Err(ParsingError(open.location, ErrorKind::ExpectedKind("shallower nesting")))
+            },
+            |open: Span<'filedata>, children, _| Group { open: open.data, children }
+        )
+    }
+
+    #[test]
+    fn iterate_nested_builds_tree() {
+        let mut walker = FileWalker::from_data("([]())rest", "input");
+        assert_eq!(group(10)(&mut walker), Ok(Group { open: "(", children: vec![
+            Group { open: "[", children: vec![] },
+            Group { open: "(", children: vec![] },
+        ] }));
+        assert_eq!(walker.current_string(), "rest");
+
+        // A mismatched closer reports the closer the innermost item needs, and the walker is moved back
+        let mut walker = FileWalker::from_data("([)]", "input");
+        assert_eq!(group(10)(&mut walker), Err(ParsingError(Location::from_components(2, 0, "input"), ErrorKind::ExpectedTag("]"))));
+        assert_eq!(walker.current_string(), "([)]");
+
+        let mut walker = FileWalker::from_data("(([]))", "input");
+        assert_eq!(group(2)(&mut walker), Err(ParsingError(Location::from_components(2, 0, "input"), ErrorKind::ExpectedKind("shallower nesting"))));
+    }
+
+    #[test]
+    fn iterate_nested_deep_input() {
+        let depth = 200_000;
+        let data = format!("{}{}", "(".repeat(depth), ")".repeat(depth));
+
+        // Counts the items rather than building a tree, which would be dropped recursively
+        let count = iterate_nested(tag("("), |walker: &mut FileWalker, _: &Span| tag(")")(walker), |_, _| Ok(()),
+            |_, children: Vec<usize>, _| children.iter().sum::<usize>() + 1);
+        assert_eq!(count(&mut FileWalker::from_data(&data, "input")), Ok(depth));
+    }
+
+    #[test]
+    fn many_collects_values() {
+        let digit = || map(take_if(|c: char| c.is_ascii_digit(), "a digit"), |span| span.data);
+
+        let mut walker = FileWalker::from_data("123x", "input");
+        assert_eq!(many0(digit())(&mut walker), Ok(vec!["1", "2", "3"]));
+        assert_eq!(walker.current_string(), "x");
+        assert_eq!(many0(digit())(&mut walker), Ok(vec![]));
+        assert_eq!(many1(digit())(&mut walker), Err(ParsingError(Location::from_components(3, 0, "input"), ErrorKind::ExpectedOneOfKind("a digit"))));
+        assert_eq!(walker.current_string(), "x");
+
+        // An item which fails part way through is backtracked over, leaving the items before it
+        let mut walker = FileWalker::from_data("ab,ab,a;", "input");
+        assert_eq!(many1(pair(tag("ab"), tag(",")))(&mut walker).unwrap().len(), 2);
+        assert_eq!(walker.current_string(), "a;");
+
+        // Parsers which succeed without consuming anything do not repeat forever
+        let mut walker = FileWalker::from_data("x", "input");
+        assert_eq!(many0(opt(tag("y")))(&mut walker), Ok(vec![]));
+        assert_eq!(many1(opt(tag("y")))(&mut walker).unwrap_err().1, ErrorKind::ExpectedKind("input to be consumed"));
+    }
+
+    #[test]
+    fn many_till_terminator() {
+        let digit = || map(take_if(|c: char| c.is_ascii_digit(), "a digit"), |span| span.data);
+
+        let mut walker = FileWalker::from_data("12;3", "input");
+        let (digits, end) = many_till(digit(), tag(";"))(&mut walker).unwrap();
+        assert_eq!(digits, vec!["1", "2"]);
+        assert_eq!(end.data, ";");
+        assert_eq!(walker.current_string(), "3");
+
+        // Neither an item nor the terminator, the item's error is preferred and the walker is moved back
+        let mut walker = FileWalker::from_data("12x;", "input");
+        assert_eq!(many_till(digit(), tag(";"))(&mut walker),
+            Err(ParsingError(Location::from_components(2, 0, "input"), ErrorKind::ExpectedOneOfKind("a digit"))));
+        assert_eq!(walker.current_string(), "12x;");
+
+        // The terminator is tried first
+        let mut walker = FileWalker::from_data("ab", "input");
+        assert_eq!(many_till(take_if(|_| true, "anything"), tag("a"))(&mut walker).unwrap().0, Vec::<Span>::new());
+
+        let mut walker = FileWalker::from_data("x", "input");
+        assert_eq!(many_till(opt(tag("y")), tag(";"))(&mut walker), Err(ParsingError(Location::from_components(0, 0, "input"), ErrorKind::ExpectedTag(";"))));
+    }
+
+    #[test]
+    fn separated_lists() {
+        let number = || map(take_while(|c: char| c.is_ascii_digit(), "number"), |span| span.data);
+
+        let mut walker = FileWalker::from_data("1, 2, 3)", "input");
+        assert_eq!(separated_list0(number(), tag(", "), TrailingPolicy::Forbidden)(&mut walker), Ok(vec!["1", "2", "3"]));
+        assert_eq!(walker.current_string(), ")");
+        assert_eq!(separated_list0(number(), tag(", "), TrailingPolicy::Forbidden)(&mut walker), Ok(vec![]));
+
+        // At least one item is needed, and the error is the first item's
+        assert_eq!(separated_list1(number(), tag(", "), TrailingPolicy::Forbidden)(&mut walker),
+            Err(ParsingError(Location::from_components(7, 0, "input"), ErrorKind::ExpectedKind("number"))));
+
+        // The trailing separator policy is the same as `list`'s
+        let mut walker = FileWalker::from_data("1, 2, )", "input");
+        assert_eq!(separated_list1(number(), tag(", "), TrailingPolicy::Allowed)(&mut walker), Ok(vec!["1", "2"]));
+        assert_eq!(walker.current_string(), ")");
+
+        let mut walker = FileWalker::from_data("1, 2, )", "input");
+        assert_eq!(separated_list1(number(), tag(", "), TrailingPolicy::Forbidden)(&mut walker),
+            Err(ParsingError(Location::from_components(4, 0, "input"), ErrorKind::TrailingSeparator(", "))));
+        assert_eq!(walker.current_string(), "1, 2, )");
+    }
+
+    #[test]
+    fn collect_with_capacity() {
+        let number = || map(take_while(|c: char| c.is_ascii_digit(), "number"), |span| span.data);
+
+        // Values are collected in the order they were parsed, into the collection the caller chooses
+        let mut walker = FileWalker::from_data("1,22,3 4", "input");
+        let items: Vec<&str> = separated_list1_into(number(), tag(","), TrailingPolicy::Forbidden, 8)(&mut walker).unwrap();
+        assert_eq!(items, ["1", "22", "3"]);
+        assert!(items.capacity() >= 8);
+
+        let spaces: Vec<_> = many0_into(tag(" "), 2)(&mut walker).unwrap();
+        assert_eq!(spaces.len(), 1);
+        assert_eq!(many1_into::<_, Vec<_>>(number(), 1)(&mut walker), Ok(vec!["4"]));
+        assert_eq!(separated_list0_into::<_, Vec<_>>(number(), tag(","), TrailingPolicy::Forbidden, 4)(&mut walker), Ok(vec![]));
+        assert_eq!(many1_into::<_, Vec<_>>(number(), 1)(&mut walker),
+            Err(ParsingError(Location::from_components(8, 0, "input"), ErrorKind::ExpectedKind("number"))));
+    }
+
+    #[test]
+    #[cfg(feature = "small-vec")]
+    fn collect_small_items() {
+        use crate::SmallItems;
+
+        let number = || map(take_while(|c: char| c.is_ascii_digit(), "number"), |span| span.data);
+
+        let mut walker = FileWalker::from_data("1, 2, 3, 4", "input");
+        let items: SmallItems<&str> = separated_list0_into(number(), tag(", "), TrailingPolicy::Forbidden, 0)(&mut walker).unwrap();
+        assert_eq!(items.as_slice(), ["1", "2", "3", "4"]);
+        assert!(!items.spilled());
+
+        let mut walker = FileWalker::from_data("1, 2, 3, 4, 5", "input");
+        let items: SmallItems<&str> = separated_list0_into(number(), tag(", "), TrailingPolicy::Forbidden, 0)(&mut walker).unwrap();
+        assert_eq!(items.len(), 5);
+        assert!(items.spilled());
+    }
+
+    #[test]
+    fn choice_of_many() {
+        let mut walker = FileWalker::from_data("let x", "input");
+        assert_eq!(choice((tag("fn"), tag("let"), tag("if")))(&mut walker).map(|span| span.data), Ok("let"));
+        assert_eq!(walker.current_string(), " x");
+
+        // Every expectation at the same location is listed, once each
+        let error = choice((tag("fn"), tag("let"), one_of("+-"), tag("fn")))(&mut walker).unwrap_err();
+        assert_eq!(error.to_string(), "expected one of `fn`, `let`, +-");
+        assert_eq!(walker.current_string(), " x");
+
+        // Only the alternatives which got furthest are listed
+        let mut walker = FileWalker::from_data("(x", "input");
+        let call = map(pair(tag("("), tag(")")), |(open, _)| open);
+        assert_eq!(choice((tag("["), call, tag("{")))(&mut walker),
+            Err(ParsingError(Location::from_components(1, 0, "input"), ErrorKind::ExpectedTag(")"))));
+
+        // Nested choices are flattened
+        let mut walker = FileWalker::from_data("x", "input");
+        let error = choice((choice((tag("a"), tag("b"))), tag("c")))(&mut walker).unwrap_err();
+        assert_eq!(error.1, ErrorKind::ExpectedAlternatives(vec![ErrorKind::ExpectedTag("a"), ErrorKind::ExpectedTag("b"), ErrorKind::ExpectedTag("c")]));
+    }
+
+    #[test]
+    fn choice_of_boxed() {
+        type Parser<'filedata> = Box<dyn Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>>>;
+
+        let keywords: Vec<Parser> = ["fn", "let", "if"].into_iter().map(|keyword| Box::new(tag(keyword)) as Parser).collect();
+        let mut walker = FileWalker::from_data("if", "input");
+        assert_eq!(choice(keywords)(&mut walker).map(|span| span.data), Ok("if"));
+
+        let mut walker = FileWalker::from_data("x", "input");
+        assert_eq!(choice([tag("a"), tag("b")])(&mut walker).unwrap_err().to_string(), "expected one of `a`, `b`");
+        assert_eq!(choice(Vec::<Parser>::new())(&mut walker).unwrap_err().to_string(), "expected one of no alternatives");
+    }
+
+    #[test]
+    fn context_chain() {
+        let parameter = context("parameter", pair(take_while(|c| c.is_ascii_alphabetic(), "a name"), tag(": int")));
+        let function = context("function declaration", triple(tag("fn f("), &parameter, tag(")")));
+
+        let walker = FileWalker::from_data("fn f(x: bool)", "input");
+        let error = function(&mut walker.clone()).unwrap_err();
+        assert_eq!(error.0, Location::from_components(8, 0, "input"));
+        assert_eq!(error.to_string(), "expected `: int`, but the input diverges after `: `");
+        assert_eq!(error.contexts(), ["function declaration", "parameter"]);
+        assert!(matches!(error.root_cause().1, ErrorKind::TagDiverged { .. }));
+
+        let settings = crate::ErrorDisplaySettings { colored: false, ..Default::default() };
+        let message = error.to_string();
+        let contexts = error.contexts();
+        let render = crate::ErrorRender::new(crate::ErrorLevel::Error, &settings, &message, &error.0, error.notes(), &walker).with_context_chain(&contexts);
+
+        crate::assert_text_eq!(render.to_string(), "\
+error: expected `: int`, but the input diverges after `: `
+   --> column 9 line 1 in input
+  1 |fn f(x: bool)
+    |        ^ input diverges here
+    |      ^^ matched up to here
+    = while parsing function declaration
+        while parsing parameter
+");
+
+        // Errors outside of any labelled construct have no contexts
+        let error = function(&mut FileWalker::from_data("fun", "input")).unwrap_err();
+        assert_eq!(error.contexts(), ["function declaration"]);
+        assert_eq!(error.root_cause().contexts(), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn cut_commits() {
+        let name = || take_while(|c| c.is_ascii_alphabetic(), "a name");
+        let function = || map(pair(tag("fn "), cut(pair(name(), tag("()")))), |(keyword, _)| keyword);
+        let expression = || map(pair(name(), tag("(")), |(name, _)| name);
+
+        // Without the keyword, the function fails normally and the next alternative is tried
+        let mut walker = FileWalker::from_data("f(", "input");
+        assert_eq!(alt(function(), expression())(&mut walker).map(|span| span.data), Ok("f"));
+
+        // After the keyword, the function's error is reported rather than backtracked over
+        let mut walker = FileWalker::from_data("fn f(", "input");
+        let error = alt(function(), expression())(&mut walker).unwrap_err();
+        assert!(!error.is_recoverable());
+        assert_eq!(error.0, Location::from_components(5, 0, "input"));
+        assert_eq!(error.to_string(), "expected `()`, but the input diverges after `(`");
+        assert!(matches!(error.root_cause().1, ErrorKind::TagDiverged { .. }));
+        assert_eq!(walker.current_string(), "fn f(");
+
+        assert_eq!(opt(function())(&mut walker), Err(error.clone()));
+        assert_eq!(choice((function(), expression()))(&mut walker), Err(error.clone()));
+        assert_eq!(many0(function())(&mut walker), Err(error.clone()));
+        assert_eq!(walker.current_string(), "fn f(");
+
+        // Items after the first are committed to as well, and the error survives labelling and `expected`
+        let mut walker = FileWalker::from_data("fn f()fn g(", "input");
+        assert_eq!(many1(function())(&mut walker).unwrap_err().0, Location::from_components(11, 0, "input"));
+        assert_eq!(walker.current_string(), "fn f()fn g(");
+        let mut walker = FileWalker::from_data("fn (", "input");
+        assert!(!context("function", expected(function(), "a function"))(&mut walker).unwrap_err().is_recoverable());
+
+        let mut walker = FileWalker::from_data("fn f(), fn (", "input");
+        let error = separated_list0(function(), tag(", "), TrailingPolicy::Allowed)(&mut walker).unwrap_err();
+        assert_eq!(error.0, Location::from_components(11, 0, "input"));
+        assert_eq!(walker.current_string(), "fn f(), fn (");
+
+        // A committed error is kept over a recoverable one which got further, so an enclosing `alt` cannot backtrack past the cut
+        let committed = || alt(map(tag("abcd"), |_| ()), map(pair(tag("a"), cut(tag("z"))), |_| ()));
+        let mut walker = FileWalker::from_data("abcx", "input");
+        let error = alt(committed(), map(tag("abc"), |_| ()))(&mut walker).unwrap_err();
+        assert!(!error.is_recoverable());
+        assert_eq!(error.0, Location::from_components(1, 0, "input"));
+        assert_eq!(walker.current_string(), "abcx");
+        assert_eq!(alt_checked(map(tag("abcd"), |_| ()), map(pair(tag("a"), cut(tag("z"))), |_| ()))(&mut walker), Err(error.clone()));
+    }
+
+    #[test]
+    fn find_all_matches() {
+        let mut walker = FileWalker::from_data("let x = 1; // x\nlet xs = x + 22;", "input");
+        tag("let ")(&mut walker).unwrap();
+
+        // Matches do not overlap, and the search starts from the walker's location
+        let names = find_all(take_while(|c| c.is_ascii_alphabetic(), "a name"))(&mut walker).unwrap();
+        assert_eq!(names.iter().map(|name| name.span.data).collect::<Vec<_>>(), ["x", "x", "let", "xs", "x"]);
+        assert_eq!(names[3].span.location, Location::from_components(4, 1, "input"));
+        assert_eq!(walker.current_string(), "");
+
+        // The walker does not move while iterating, and matches which consume nothing are skipped
+        let walker = FileWalker::from_data("a1b22", "input");
+        let numbers: Vec<_> = walker.find_iter(many0(one_of("0123456789"))).map(|number| (number.span.location.column, number.value.len())).collect();
+        assert_eq!(numbers, [(1, 1), (3, 2)]);
+        assert_eq!(walker.current_string(), "a1b22");
+    }
+
+    #[test]
+    fn peek_and_not() {
+        let mut walker = FileWalker::from_data("letter = 1", "input");
+        let keyword = pair(tag("let"), not(take_if(|c| c.is_alphanumeric(), "a letter")));
+
+        assert_eq!(peek(tag("let"))(&mut walker).map(|span| span.data), Ok("let"));
+        assert_eq!(walker.current_string(), "letter = 1");
+        assert_eq!(peek(tag("var"))(&mut walker).unwrap_err().0, Location::from_components(0, 0, "input"));
+
+        // A keyword which is the start of a longer name is rejected at the letter after it
+        assert_eq!(keyword(&mut walker), Err(ParsingError(Location::from_components(3, 0, "input"), ErrorKind::InverseFailedGot("t"))));
+        assert_eq!(walker.current_string(), "letter = 1");
+
+        let mut walker = FileWalker::from_data("let x", "input");
+        assert_eq!(keyword(&mut walker).map(|(span, _)| span.data), Ok("let"));
+        assert_eq!(walker.current_string(), " x");
+
+        // Unrecoverable errors are not taken as the parser failing to match
+        assert!(!not(cut(tag("y")))(&mut walker).unwrap_err().is_recoverable());
+        assert_eq!(walker.current_string(), " x");
+    }
 }
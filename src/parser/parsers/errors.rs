@@ -1,14 +1,438 @@
-use crate::Location;
+use crate::{CharClass, ErrorLevel, FileWalker, Location, Note, Span};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ErrorKind<'filedata> {
     ExpectedTag(&'static str),
     ExpectedKind(&'static str),
     ExpectedOneOfKind(&'static str),
+    /// A character outside of a class was found
+    ExpectedClass(CharClass),
     ExpectedOneOf(&'static str),
+    /// None of a table of tags matched
+    ExpectedOneOfTags(Vec<&'static str>),
     InverseFailedGot(&'filedata str),
+    /// A closing delimiter was found which does not match the opening delimiter it closes
+    MismatchedDelimiter {
+        open_span: Span<'filedata>,
+        expected: &'filedata str,
+        found: &'filedata str
+    },
+    /// A tag which matched `matched` before the input diverged at `found`
+    TagDiverged {
+        tag: &'static str,
+        matched: Span<'filedata>,
+        found: &'filedata str
+    },
+    /// Both alternatives of an `alt_checked` accepted the input, with different lengths
+    AmbiguousAlternatives {
+        first: &'filedata str,
+        second: &'filedata str
+    },
+    /// The end of the input was reached before the closing delimiter of a construct
+    Unterminated(&'static str),
+    /// Input remained where the end of the input was expected
+    ExpectedEndOfInput(&'filedata str),
+    /// A keyword was found where an identifier was expected
+    ReservedKeyword(&'filedata str),
+    /// A digit separator which is not between two digits, or between the digits and the suffix
+    MisplacedSeparator(&'filedata str),
+    /// A field of a literal which is out of range, such as the month `13` in a date
+    InvalidField {
+        field: &'static str,
+        found: &'filedata str
+    },
+    /// A parser run by `lookahead_within` needed to see further than the given number of characters
+    LookaheadExceeded(usize),
+    /// The end of the file was reached where more input was expected
+    UnexpectedEndOfFile(&'static str),
+    /// A separator after the last item of a list which does not allow one
+    TrailingSeparator(&'filedata str),
+    /// Fewer than the required number of characters of a kind were found
+    TooFew {
+        kind: &'static str,
+        minimum: usize,
+        found: usize
+    },
+    /// None of the alternatives of a `choice` were accepted, each of which is an expectation such as `ExpectedTag`
+    ExpectedAlternatives(Vec<ErrorKind<'filedata>>),
+    /// A word which is not the expected symbol
+    ExpectedSymbol {
+        expected: &'static str,
+        found: &'filedata str
+    },
+    /// A token which is not one of those expected, from the token parsers of `parser::lexer`, with the name of its kind if known
+    ///
+    /// The found text is empty at the end of the tokens
+    UnexpectedToken {
+        expected: Vec<&'static str>,
+        found_kind: Option<&'static str>,
+        found: &'filedata str
+    },
+    /// A word which is close to, but not the same as, the expected tag
+    DidYouMean {
+        expected: &'static str,
+        found: &'filedata str
+    },
+    /// An error which occurred while parsing a labelled construct, see `context`, displayed as the error itself
+    InContext(&'static str, Box<ParsingError<'filedata>>),
+    /// An error after the grammar committed to a construct with `cut`, which alternatives do not recover from, displayed as the error itself
+    Unrecoverable(Box<ParsingError<'filedata>>),
     DemoError
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ParsingError<'filedata>(pub Location<'filedata>, pub ErrorKind<'filedata>);
+
+impl<'filedata> ParsingError<'filedata> {
+    /// Keep whichever of two errors occurred further into the file, preferring `other` if they are at the same location or in different files
+    ///
+    /// An unrecoverable error is kept over a recoverable one wherever they are, so choosing between errors never backtracks past a `cut`
+    pub fn furthest(self, other: Self) -> Self {
+        match (self.is_recoverable(), other.is_recoverable()) {
+            (false, true) => return self,
+            (true, false) => return other,
+            _ => {}
+        }
+
+        match self.0.partial_cmp(&other.0) {
+            Some(std::cmp::Ordering::Greater) => self,
+            _ => other
+        }
+    }
+
+    /// Split the errors of alternatives into the one which got furthest into the file, as `alt` and `choice` report, and the others in order
+    ///
+    /// Of errors at the same location the last is chosen, and None is returned if there are no errors
+    pub fn deepest(errors: impl IntoIterator<Item = Self>) -> Option<(Self, Vec<Self>)> {
+        let mut losing: Vec<Self> = errors.into_iter().collect();
+        let deepest = (0..losing.len()).reduce(|deepest, i| match losing[deepest].0.partial_cmp(&losing[i].0) {
+            Some(std::cmp::Ordering::Greater) => deepest,
+            _ => i
+        })?;
+
+        let error = losing.remove(deepest);
+        Some((error, losing))
+    }
+
+    /// Get the labels of the constructs being parsed when the error occurred, from the outermost, for `ErrorRender::with_context_chain`
+    pub fn contexts(&self) -> Vec<&'static str> {
+        let mut contexts = Vec::new();
+        let mut error = self;
+
+        loop {
+            match &error.1 {
+                ErrorKind::InContext(context, inner) => {
+                    contexts.push(*context);
+                    error = inner;
+                }
+                ErrorKind::Unrecoverable(inner) => error = inner,
+                _ => return contexts
+            }
+        }
+    }
+
+    /// Get the error without the constructs it occurred in
+    pub fn root_cause(&self) -> &Self {
+        match &self.1 {
+            ErrorKind::InContext(_, inner) | ErrorKind::Unrecoverable(inner) => inner.root_cause(),
+            _ => self
+        }
+    }
+
+    /// Check if alternatives may be tried after this error, which is false once the grammar has committed to a construct with `cut`
+    pub fn is_recoverable(&self) -> bool {
+        match &self.1 {
+            ErrorKind::Unrecoverable(_) => false,
+            ErrorKind::InContext(_, inner) => inner.is_recoverable(),
+            _ => true
+        }
+    }
+
+    /// Check if the error is because the input ended early, such as at an unclosed bracket or an operator missing its operand, so a
+    /// REPL should read another line rather than report it
+    ///
+    /// The walker is over the input which was parsed, at or before the error. Errors expecting more input, and errors at the end of the
+    /// input or in the whitespace after it, are incomplete
+    pub fn is_incomplete(&self, walker: &FileWalker) -> bool {
+        let error = self.root_cause();
+        if matches!(error.1, ErrorKind::UnexpectedEndOfFile(_) | ErrorKind::Unterminated(_)) {
+            return true;
+        }
+
+        let end = walker.current_location().advanced_over(walker.current_string().trim_end());
+        matches!(error.0.partial_cmp(&end), Some(std::cmp::Ordering::Equal | std::cmp::Ordering::Greater))
+    }
+
+    /// Get the notes which annotate the spans referenced by this error, for use with an `ErrorRender`
+    pub fn notes(&self) -> Vec<Note<'filedata, '_>> {
+        match &self.1 {
+            ErrorKind::InContext(_, inner) | ErrorKind::Unrecoverable(inner) => inner.notes(),
+            ErrorKind::MismatchedDelimiter { open_span, found, .. } => vec![
+                Note::new(open_span, "opening delimiter here", ErrorLevel::Info),
+                Note::new(&Span::from_components(self.0, found), "does not match the opening delimiter", ErrorLevel::Error),
+            ],
+            ErrorKind::TagDiverged { matched, found, .. } => vec![
+                Note::new(matched, "matched up to here", ErrorLevel::Info),
+                Note::new(&Span::from_components(self.0, found), "input diverges here", ErrorLevel::Error),
+            ],
+            ErrorKind::AmbiguousAlternatives { first, second } => vec![
+                Note::new(&Span::from_components(self.0, first), "accepted by the first alternative", ErrorLevel::Info),
+                Note::new(&Span::from_components(self.0, second), "accepted by the second alternative", ErrorLevel::Info),
+            ],
+            ErrorKind::InvalidField { found, .. } => vec![
+                Note::new(&Span::from_components(self.0, found), "out of range", ErrorLevel::Error),
+            ],
+            ErrorKind::TrailingSeparator(found) => vec![
+                Note::new(&Span::from_components(self.0, found), "remove this separator", ErrorLevel::Error),
+            ],
+            ErrorKind::ExpectedSymbol { found, .. } => vec![
+                Note::new(&Span::from_components(self.0, found), "found this word", ErrorLevel::Error),
+            ],
+            ErrorKind::UnexpectedToken { found: "", .. } => vec![
+                Note::new(&Span::from_components(self.0, ""), "the tokens end here", ErrorLevel::Error),
+            ],
+            ErrorKind::UnexpectedToken { found, .. } => vec![
+                Note::new(&Span::from_components(self.0, found), "found this token", ErrorLevel::Error),
+            ],
+            ErrorKind::DidYouMean { found, .. } => vec![
+                Note::new(&Span::from_components(self.0, found), "not recognised", ErrorLevel::Error),
+            ],
+            ErrorKind::UnexpectedEndOfFile(_) => vec![
+                Note::new(&Span::from_components(self.0, ""), "the file ends here", ErrorLevel::Error),
+            ],
+            _ => Vec::new()
+        }
+    }
+}
+
+impl<'filedata> ErrorKind<'filedata> {
+    /// Get what an error expected, such as ``a number`` or `` `(` ``, if it is only an expectation
+    pub fn expectation(&self) -> Option<String> {
+        match self {
+            ErrorKind::ExpectedTag(tag) => Some(format!("`{}`", tag)),
+            ErrorKind::ExpectedKind(kind) | ErrorKind::ExpectedOneOfKind(kind) | ErrorKind::ExpectedOneOf(kind) => Some(kind.to_string()),
+            ErrorKind::ExpectedClass(class) => Some(class.to_string()),
+            ErrorKind::ExpectedSymbol { expected, .. } => Some(format!("`{}`", expected)),
+            ErrorKind::UnexpectedToken { expected, .. } => Some(list_or(expected)),
+            ErrorKind::ExpectedOneOfTags(_) | ErrorKind::ExpectedAlternatives(_) => Some(self.to_string().trim_start_matches("expected one of ").to_string()),
+            ErrorKind::InContext(_, inner) => inner.1.expectation(),
+            _ => None
+        }
+    }
+}
+
+impl<'filedata> std::fmt::Display for ErrorKind<'filedata> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorKind::ExpectedTag(tag) => write!(f, "expected `{}`", tag),
+            ErrorKind::ExpectedKind(kind) => write!(f, "expected {}", kind),
+            ErrorKind::ExpectedOneOfKind(kind) => write!(f, "expected {}", kind),
+            ErrorKind::ExpectedClass(class) if class.is_list() => write!(f, "expected one of {}", class),
+            ErrorKind::ExpectedClass(class) => write!(f, "expected {}", class),
+            ErrorKind::ExpectedOneOf(options) => write!(f, "expected one of {}", options),
+            ErrorKind::ExpectedOneOfTags(tags) => {
+                write!(f, "expected one of ")?;
+                for (i, tag) in tags.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "`{}`", tag)?;
+                }
+                Ok(())
+            }
+            ErrorKind::InverseFailedGot(got) => write!(f, "unexpected `{}`", got),
+            ErrorKind::MismatchedDelimiter { expected, found, .. } => write!(f, "mismatched delimiter, expected `{}` but found `{}`", expected, found),
+            ErrorKind::TagDiverged { tag, matched, .. } => write!(f, "expected `{}`, but the input diverges after `{}`", tag, matched.data),
+            ErrorKind::AmbiguousAlternatives { first, second } => write!(f, "ambiguous grammar, both `{}` and `{}` are accepted here", first, second),
+            ErrorKind::Unterminated(close) => write!(f, "unterminated construct, expected `{}` before the end of the input", close),
+            ErrorKind::ExpectedEndOfInput(rest) => write!(f, "expected the end of the input, found `{}`", rest.chars().next().unwrap_or_default()),
+            ErrorKind::ReservedKeyword(word) => write!(f, "expected an identifier, found the keyword `{}`", word),
+            ErrorKind::MisplacedSeparator(separator) => write!(f, "misplaced digit separator `{}`", separator),
+            ErrorKind::InvalidField { field, found } => write!(f, "invalid {} `{}`", field, found),
+            ErrorKind::LookaheadExceeded(limit) => write!(f, "lookahead exceeded the limit of {} characters", limit),
+            ErrorKind::UnexpectedEndOfFile(expected) => write!(f, "unexpected end of file, expected {}", expected),
+            ErrorKind::TrailingSeparator(separator) => write!(f, "trailing `{}` not allowed here", separator),
+            ErrorKind::TooFew { kind, minimum, found } => write!(f, "expected at least {} {}, found {}", minimum, kind, found),
+            ErrorKind::ExpectedAlternatives(alternatives) => {
+                write!(f, "expected one of ")?;
+                for (i, alternative) in alternatives.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", alternative.expectation().unwrap_or_else(|| alternative.to_string()))?;
+                }
+                Ok(())
+            }
+            ErrorKind::ExpectedSymbol { expected, found } => write!(f, "expected `{}`, found `{}`", expected, found),
+            ErrorKind::UnexpectedToken { expected, found: "", .. } => write!(f, "expected {}, found the end of the input", list_or(expected)),
+            // Kinds named by their text, such as `,`, are not named again before it
+            ErrorKind::UnexpectedToken { expected, found_kind: Some(kind), found } if *kind != format!("`{}`", found) =>
+                write!(f, "expected {}, found {} `{}`", list_or(expected), kind, found),
+            ErrorKind::UnexpectedToken { expected, found, .. } => write!(f, "expected {}, found `{}`", list_or(expected), found),
+            ErrorKind::DidYouMean { expected, found } => write!(f, "unexpected `{}`, did you mean `{}`?", found, expected),
+            ErrorKind::InContext(_, inner) | ErrorKind::Unrecoverable(inner) => write!(f, "{}", inner),
+            ErrorKind::DemoError => write!(f, "demo error"),
+        }
+    }
+}
+
+/// Join alternatives as they are read, such as `a, b or c`
+fn list_or(items: &[&str]) -> String {
+    match items {
+        [] => String::new(),
+        [item] => item.to_string(),
+        [rest @ .., last] => format!("{} or {}", rest.join(", "), last)
+    }
+}
+
+impl<'filedata> std::fmt::Display for ParsingError<'filedata> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ErrorDisplaySettings, ErrorKind, ErrorLevel, ErrorRender, FileWalker, Location, ParsingError, context, cut, not_eof, pair, tag, take_until_balanced, take_while};
+
+    #[test]
+    fn furthest() {
+        let early = ParsingError(Location::from_components(4, 1, "input"), ErrorKind::ExpectedTag("a"));
+        let late = ParsingError(Location::from_components(0, 2, "input"), ErrorKind::ExpectedTag("b"));
+        let tie = ParsingError(Location::from_components(0, 2, "input"), ErrorKind::ExpectedTag("c"));
+
+        assert_eq!(early.clone().furthest(late.clone()), late);
+        assert_eq!(late.clone().furthest(early.clone()), late);
+        assert_eq!(late.clone().furthest(tie.clone()), tie);
+
+        let committed = ParsingError(early.0, ErrorKind::Unrecoverable(Box::new(early.clone())));
+        assert_eq!(late.clone().furthest(committed.clone()), committed);
+        assert_eq!(committed.clone().furthest(late.clone()), committed);
+    }
+
+    #[test]
+    fn incomplete_input() {
+        // A line of a REPL, a sum of numbers or bracketed text
+        fn operand<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<(), ParsingError<'filedata>> {
+            match tag("(")(walker) {
+                Ok(_) => take_until_balanced("(", ")", None)(walker).map(|_| ()),
+                Err(_) => take_while(|c| c.is_ascii_digit(), "a number")(walker).map(|_| ())
+            }
+        }
+
+        fn parse(line: &str) -> Result<(), bool> {
+            let walker = FileWalker::from_data(line, "repl");
+            let mut parse_walker = walker.clone();
+            let result = (|| {
+                operand(&mut parse_walker)?;
+                while tag(" +")(&mut parse_walker).is_ok() {
+                    context("an operand", cut(pair(tag(" "), operand)))(&mut parse_walker)?;
+                }
+                tag(";")(&mut parse_walker)
+            })();
+
+            result.map(|_| ()).map_err(|error| error.is_incomplete(&walker))
+        }
+
+        assert_eq!(parse("1 + 2;"), Ok(()));
+        assert_eq!(parse("1 + 2"), Err(true));
+        assert_eq!(parse("1 +"), Err(true));
+        assert_eq!(parse("1 + (2 + \n"), Err(true));
+        assert_eq!(parse("1 + 2 \n\t"), Err(true));
+
+        assert_eq!(parse("1 + x;"), Err(false));
+        assert_eq!(parse("1 + 2 3"), Err(false));
+
+        // Errors in other files are never incomplete
+        let error = ParsingError(Location::from_components(3, 0, "other"), ErrorKind::ExpectedTag(";"));
+        assert!(!error.is_incomplete(&FileWalker::from_data("1 +", "repl")));
+    }
+
+    #[test]
+    fn mismatched_delimiter_render() {
+        let walker = FileWalker::from_data("<abc>\n</abd>", "input");
+
+        let mut parse_walker = walker.clone();
+        tag("<")(&mut parse_walker).unwrap();
+        let open_span = take_while(|c| c.is_alphabetic(), "name")(&mut parse_walker).unwrap();
+        tag(">\n</")(&mut parse_walker).unwrap();
+        let found = take_while(|c| c.is_alphabetic(), "name")(&mut parse_walker).unwrap();
+
+        let error = ParsingError(found.location, ErrorKind::MismatchedDelimiter { open_span, expected: open_span.data, found: found.data });
+        assert_eq!(error.to_string(), "mismatched delimiter, expected `abc` but found `abd`");
+
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+        let message = error.to_string();
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, &message, &error.0, error.notes(), &walker);
+
+        crate::assert_text_eq!(render.to_string(), "\
+error: mismatched delimiter, expected `abc` but found `abd`
+   --> column 3 line 2 in input
+  1 |<abc>
+    | ^^^ opening delimiter here
+  2 |</abd>
+    |  ^^^ does not match the opening delimiter
+");
+    }
+
+    #[test]
+    fn tag_diverged_render() {
+        let walker = FileWalker::from_data("fn main() {\n    retrun 4;\n}", "input");
+
+        let mut parse_walker = walker.clone();
+        tag("fn main() {\n    ")(&mut parse_walker).unwrap();
+        let error = tag("return")(&mut parse_walker).unwrap_err();
+
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+        let message = error.to_string();
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, &message, &error.0, error.notes(), &walker);
+
+        crate::assert_text_eq!(render.to_string(), "\
+error: expected `return`, but the input diverges after `ret`
+   --> column 8 line 2 in input
+  1 |fn main() {
+  2 |    retrun 4;
+    |       ^ input diverges here
+    |    ^^^ matched up to here
+  3 |}
+");
+    }
+
+    #[test]
+    fn unexpected_end_of_file_render() {
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+
+        let render = |data: &'static str| {
+            let walker = FileWalker::from_data(data, "input");
+            let mut parse_walker = walker.clone();
+            tag(data)(&mut parse_walker).unwrap();
+
+            let error = not_eof("an expression")(&mut parse_walker).unwrap_err();
+            let message = error.to_string();
+            ErrorRender::new(ErrorLevel::Error, &settings, &message, &error.0, error.notes(), &walker).to_string()
+        };
+
+        crate::assert_text_eq!(render(""), "\
+error: unexpected end of file, expected an expression
+   --> column 1 line 1 in input
+  1 |<end of file>
+    |^ the file ends here
+");
+
+        crate::assert_text_eq!(render("let x =\n"), "\
+error: unexpected end of file, expected an expression
+   --> column 1 line 2 in input
+  1 |let x =
+  2 |<end of file>
+    |^ the file ends here
+");
+
+        // Without a trailing newline, the end of the file is on the last line
+        crate::assert_text_eq!(render("let x ="), "\
+error: unexpected end of file, expected an expression
+   --> column 8 line 1 in input
+  1 |let x =
+    |       ^ the file ends here
+");
+    }
+}
@@ -0,0 +1,516 @@
+use crate::{Diagnostic, ErrorKind, ErrorLevel, FileWalker, ParsingError, Span, Spanned};
+use crate::parser::parsers::leaves::{suggestion_distance, word_like};
+
+/// A set of reserved words, built from a static list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeywordSet {
+    keywords: &'static [&'static str],
+    /// Whether keywords match regardless of ASCII case, in which case the keywords as listed are their canonical form
+    ignore_case: bool
+}
+
+/// How the letters of a word are cased
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Casing {
+    /// `SELECT`
+    Upper,
+    /// `select`
+    Lower,
+    /// `Select`
+    Capitalized,
+    /// `SeLeCt`, or a word without letters
+    Mixed
+}
+
+/// Checks that case insensitive keywords are cased consistently, following the casing of the first keyword which is not mixed case
+#[derive(Debug, Clone, Default)]
+pub struct KeywordCasing<'filedata> {
+    first: Option<(Casing, Span<'filedata>)>
+}
+
+/// A class of characters, used to describe which characters may appear in a token
+///
+/// Classes display as the characters they contain, such as `a-z, A-Z, _`, which `take_class` uses to describe what it expected
+#[derive(Debug, Clone, Copy)]
+pub enum CharClass {
+    /// Any alphabetic character, as defined by `char::is_alphabetic`
+    Alphabetic,
+    /// Any alphanumeric character, as defined by `char::is_alphanumeric`
+    Alphanumeric,
+    /// Any ASCII digit
+    Digit,
+    /// Any whitespace character
+    Whitespace,
+    /// An inclusive range of characters
+    Range(char, char),
+    /// Any of the characters in the string
+    Chars(&'static str),
+    /// Any character matching at least one of the classes
+    Union(&'static [CharClass]),
+    /// Any character accepted by the predicate
+    Custom(fn(char) -> bool),
+    /// The characters of another class, displayed with a description such as `a hexadecimal digit`
+    Described(&'static str, &'static CharClass)
+}
+
+/// The rules for the characters of an identifier, and the keywords which may not be used as one
+#[derive(Debug, Clone, Copy)]
+pub struct IdentSpec {
+    pub start: CharClass,
+    pub continue_: CharClass,
+    pub keywords: KeywordSet
+}
+
+impl CharClass {
+    /// Check if a character is in the class
+    pub fn contains(&self, c: char) -> bool {
+        match self {
+            CharClass::Alphabetic => c.is_alphabetic(),
+            CharClass::Alphanumeric => c.is_alphanumeric(),
+            CharClass::Digit => c.is_ascii_digit(),
+            CharClass::Whitespace => c.is_whitespace(),
+            CharClass::Range(low, high) => (*low..=*high).contains(&c),
+            CharClass::Chars(chars) => chars.contains(c),
+            CharClass::Union(classes) => classes.iter().any(|class| class.contains(c)),
+            CharClass::Custom(f) => f(c),
+            CharClass::Described(_, class) => class.contains(c),
+        }
+    }
+
+    /// Check if the class displays as a list of characters rather than a description
+    pub fn is_list(&self) -> bool {
+        matches!(self, CharClass::Range(..) | CharClass::Chars(_) | CharClass::Union(_))
+    }
+}
+
+impl IdentSpec {
+    /// The identifiers accepted by `identifier`, a letter or underscore followed by any number of letters, digits and underscores
+    pub const DEFAULT: IdentSpec = IdentSpec::new(
+        CharClass::Union(&[CharClass::Alphabetic, CharClass::Chars("_")]),
+        CharClass::Union(&[CharClass::Alphanumeric, CharClass::Chars("_")])
+    );
+
+    /// Construct a spec for identifiers made of characters from the given classes, without any keywords
+    pub const fn new(start: CharClass, continue_: CharClass) -> Self {
+        Self { start, continue_, keywords: KeywordSet::new(&[]) }
+    }
+
+    /// Exclude a set of keywords from the identifiers
+    pub const fn with_keywords(self, keywords: KeywordSet) -> Self {
+        Self { keywords, ..self }
+    }
+}
+
+impl KeywordSet {
+    /// Construct a keyword set from a static list of keywords
+    pub const fn new(keywords: &'static [&'static str]) -> Self {
+        Self { keywords, ignore_case: false }
+    }
+
+    /// Match the keywords regardless of ASCII case, as in SQL or BASIC, the keywords as listed are used as their canonical form
+    pub const fn ignoring_case(self) -> Self {
+        Self { ignore_case: true, ..self }
+    }
+
+    /// Check if the keywords match regardless of case
+    pub fn ignores_case(&self) -> bool {
+        self.ignore_case
+    }
+
+    /// Get the keywords in the set
+    pub fn keywords(&self) -> &'static [&'static str] {
+        self.keywords
+    }
+
+    fn matches(&self, keyword: &str, word: &str) -> bool {
+        if self.ignore_case {
+            keyword.eq_ignore_ascii_case(word)
+        }
+        else {
+            keyword == word
+        }
+    }
+
+    /// Check if a word is one of the keywords
+    pub fn is_keyword(&self, word: &str) -> bool {
+        self.canonical(word).is_some()
+    }
+
+    /// Get the keyword a word matches, as it is listed in the set
+    pub fn canonical(&self, word: &str) -> Option<&'static str> {
+        self.keywords.iter().copied().find(|keyword| self.matches(keyword, word))
+    }
+
+    /// Get the longest keyword at the start of some text which is not immediately followed by more of a word, as it is listed in the set
+    pub fn longest_match(&self, text: &str) -> Option<&'static str> {
+        self.keywords.iter()
+            .copied()
+            .filter(|keyword| text.get(..keyword.len()).is_some_and(|word| self.matches(keyword, word)))
+            .filter(|keyword| !text[keyword.len()..].starts_with(|c| IdentSpec::DEFAULT.continue_.contains(c)))
+            .max_by_key(|keyword| keyword.len())
+    }
+}
+
+impl Casing {
+    /// Get the casing of a word's letters
+    pub fn of(word: &str) -> Self {
+        let mut letters = word.chars().filter(|c| c.is_alphabetic());
+
+        match letters.next() {
+            Some(first) if letters.clone().all(char::is_uppercase) && first.is_uppercase() => Casing::Upper,
+            Some(first) if letters.clone().all(char::is_lowercase) && first.is_lowercase() => Casing::Lower,
+            Some(first) if letters.all(char::is_lowercase) && first.is_uppercase() => Casing::Capitalized,
+            _ => Casing::Mixed
+        }
+    }
+
+    /// Write a word in this casing, mixed case leaves the word as it is
+    pub fn apply(&self, word: &str) -> String {
+        match self {
+            Casing::Upper => word.to_uppercase(),
+            Casing::Lower => word.to_lowercase(),
+            Casing::Capitalized => {
+                let mut chars = word.chars();
+                chars.next().map_or_else(String::new, |first| first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect())
+            }
+            Casing::Mixed => word.to_string()
+        }
+    }
+}
+
+impl<'filedata> KeywordCasing<'filedata> {
+    /// Construct a checker which has not seen any keywords
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check the casing of a keyword, returning a warning if it differs from the casing of the first keyword
+    pub fn check(&mut self, keyword: &Span<'filedata>) -> Option<Diagnostic<'filedata>> {
+        let casing = Casing::of(keyword.data);
+
+        match self.first {
+            None if casing != Casing::Mixed => {
+                self.first = Some((casing, *keyword));
+                None
+            }
+            Some((expected, first)) if casing != expected => Some(
+                Diagnostic::new(ErrorLevel::Warning, format!("inconsistent keyword casing, expected `{}`", expected.apply(keyword.data)), keyword.location)
+                    .with_note(keyword, "keyword cased differently", ErrorLevel::Warning)
+                    .with_note(&first, "the first keyword sets the casing", ErrorLevel::Info)
+            ),
+            _ => None
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts the longest keyword of a set which is not followed by more of a word, so `if` does not match the start of `iffy`
+pub fn keyword<'filedata>(keywords: KeywordSet) -> impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        keyword_cased(keywords)(walker).map(|keyword| keyword.span)
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts a keyword like `keyword`, returning the keyword as it is listed in the set along with the span of the keyword as it was written
+pub fn keyword_cased<'filedata>(keywords: KeywordSet) -> impl Fn(&mut FileWalker<'filedata>) -> Result<Spanned<'filedata, &'static str>, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        match keywords.longest_match(walker.current_string()) {
+            Some(keyword) => {
+                // Only ASCII case is ignored, so the keyword as written has the same number of characters
+                let start = walker.get_marker();
+                keyword.chars().for_each(|_| { walker.step(); });
+
+                Ok(Spanned::new(walker.span_from_marker_to_here(start).unwrap(), keyword))
+            }
+            None => Err(ParsingError(walker.current_location(), ErrorKind::ExpectedKind("keyword")))
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts a keyword like `keyword`, but if the input is a word within `max_distance` edits of a keyword, fails suggesting the closest one, or the first of the closest
+pub fn keyword_suggesting<'filedata>(keywords: KeywordSet, max_distance: usize) -> impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        keyword(keywords)(walker).map_err(|error| {
+            let closest = keywords.keywords().iter()
+                .filter_map(|keyword| {
+                    let found = word_like(walker.current_string(), keyword);
+                    let distance = if keywords.ignores_case() {
+                        suggestion_distance(&found.to_ascii_lowercase(), &keyword.to_ascii_lowercase(), max_distance)
+                    }
+                    else {
+                        suggestion_distance(found, keyword, max_distance)
+                    };
+
+                    distance.map(|distance| (distance, *keyword, found))
+                })
+                .min_by_key(|(distance, _, _)| *distance);
+
+            match closest {
+                Some((_, expected, found)) => ParsingError(walker.current_location(), ErrorKind::DidYouMean { expected, found }),
+                None => error
+            }
+        })
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts an identifier, a letter or underscore followed by any number of letters, digits and underscores
+pub fn identifier<'filedata>() -> impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
+    identifier_excluding(KeywordSet::new(&[]))
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts an identifier which is not one of a set of keywords
+pub fn identifier_excluding<'filedata>(keywords: KeywordSet) -> impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
+    let identifier = identifier_with(IdentSpec::DEFAULT.with_keywords(keywords));
+
+    move |walker: &mut FileWalker<'filedata>| identifier(walker).map(|identifier| identifier.span)
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts the longest identifier following the rules of a spec, so it always ends at a word boundary, failing if it is one of the spec's keywords
+pub fn identifier_with<'filedata>(spec: IdentSpec) -> impl Fn(&mut FileWalker<'filedata>) -> Result<Spanned<'filedata, &'filedata str>, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        let start = walker.get_marker();
+
+        match walker.step() {
+            Some(c) if spec.start.contains(c) => {}
+            _ => {
+                walker.pop_back(start);
+                return Err(ParsingError(walker.current_location(), ErrorKind::ExpectedKind("identifier")));
+            }
+        }
+
+        while walker.current_string().starts_with(|c| spec.continue_.contains(c)) {
+            walker.step();
+        }
+
+        let span = walker.span_from_marker_to_here(start).unwrap();
+        if spec.keywords.is_keyword(span.data) {
+            walker.pop_back(start);
+            return Err(ParsingError(span.location, ErrorKind::ReservedKeyword(span.data)));
+        }
+
+        Ok(Spanned::new(span, span.data))
+    }
+}
+
+impl PartialEq for CharClass {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (CharClass::Range(low, high), CharClass::Range(other_low, other_high)) => low == other_low && high == other_high,
+            (CharClass::Chars(chars), CharClass::Chars(other)) => chars == other,
+            (CharClass::Union(classes), CharClass::Union(other)) => classes == other,
+            (CharClass::Custom(f), CharClass::Custom(other)) => std::ptr::fn_addr_eq(*f, *other),
+            (CharClass::Described(description, class), CharClass::Described(other_description, other)) =>
+                description == other_description && class == other,
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other)
+        }
+    }
+}
+
+impl Eq for CharClass {}
+
+impl std::fmt::Display for CharClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CharClass::Alphabetic => write!(f, "a letter"),
+            CharClass::Alphanumeric => write!(f, "a letter or digit"),
+            CharClass::Digit => write!(f, "0-9"),
+            CharClass::Whitespace => write!(f, "whitespace"),
+            CharClass::Range(low, high) => write!(f, "{}-{}", low, high),
+            CharClass::Chars(chars) => {
+                for (i, c) in chars.chars().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", c)?;
+                }
+                Ok(())
+            }
+            CharClass::Union(classes) => {
+                for (i, class) in classes.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", class)?;
+                }
+                Ok(())
+            }
+            CharClass::Custom(_) => write!(f, "a matching character"),
+            CharClass::Described(description, _) => write!(f, "{}", description),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{CharClass, Casing, ErrorKind, ErrorLevel, FileWalker, IdentSpec, KeywordCasing, KeywordSet, Location, ParsingError, Span, identifier,
+        identifier_excluding, identifier_with, keyword, keyword_cased, keyword_suggesting};
+
+    const KEYWORDS: KeywordSet = KeywordSet::new(&["if", "in", "int", "else"]);
+
+    #[test]
+    fn keyword_longest_match() {
+        assert!(KEYWORDS.is_keyword("int"));
+        assert!(!KEYWORDS.is_keyword("integer"));
+
+        let mut walker = FileWalker::from_data("int x", "input");
+        assert_eq!(keyword(KEYWORDS)(&mut walker).unwrap().data, "int");
+
+        let mut walker = FileWalker::from_data("in x", "input");
+        assert_eq!(keyword(KEYWORDS)(&mut walker).unwrap().data, "in");
+    }
+
+    #[test]
+    fn keyword_word_boundary() {
+        let mut walker = FileWalker::from_data("iffy", "input");
+        assert_eq!(
+            keyword(KEYWORDS)(&mut walker),
+            Err(ParsingError(Location::from_components(0, 0, "input"), ErrorKind::ExpectedKind("keyword"))));
+        assert_eq!(walker.current_string(), "iffy");
+
+        // A shorter keyword is not accepted in place of a longer one which runs into more of a word
+        let mut walker = FileWalker::from_data("ints", "input");
+        assert!(keyword(KEYWORDS)(&mut walker).is_err());
+        let mut walker = FileWalker::from_data("if(x)", "input");
+        assert_eq!(keyword(KEYWORDS)(&mut walker).unwrap().data, "if");
+    }
+
+    #[test]
+    fn keyword_suggesting_closest() {
+        let mut walker = FileWalker::from_data("esle {", "input");
+        assert_eq!(
+            keyword_suggesting(KEYWORDS, 2)(&mut walker),
+            Err(ParsingError(Location::from_components(0, 0, "input"), ErrorKind::DidYouMean { expected: "else", found: "esle" })));
+
+        // The closest keyword is suggested
+        let mut walker = FileWalker::from_data("intt", "input");
+        assert_eq!(keyword_suggesting(KEYWORDS, 2)(&mut walker).unwrap_err().1, ErrorKind::DidYouMean { expected: "int", found: "intt" });
+
+        let mut walker = FileWalker::from_data("while", "input");
+        assert_eq!(keyword_suggesting(KEYWORDS, 1)(&mut walker).unwrap_err().1, ErrorKind::ExpectedKind("keyword"));
+
+        let mut walker = FileWalker::from_data("else {", "input");
+        assert_eq!(keyword_suggesting(KEYWORDS, 2)(&mut walker).unwrap().data, "else");
+    }
+
+    #[test]
+    fn identifier_excluding_keywords() {
+        let mut walker = FileWalker::from_data("_iffy2 else", "input");
+        assert_eq!(identifier_excluding(KEYWORDS)(&mut walker).unwrap().data, "_iffy2");
+
+        walker.step();
+        assert_eq!(
+            identifier_excluding(KEYWORDS)(&mut walker),
+            Err(ParsingError(Location::from_components(7, 0, "input"), ErrorKind::ReservedKeyword("else"))));
+        assert_eq!(walker.current_string(), "else");
+
+        // Without excluding keywords, any identifier is accepted
+        assert_eq!(identifier()(&mut walker).unwrap().data, "else");
+
+        let mut walker = FileWalker::from_data("2x", "input");
+        assert_eq!(
+            identifier()(&mut walker),
+            Err(ParsingError(Location::from_components(0, 0, "input"), ErrorKind::ExpectedKind("identifier"))));
+    }
+
+    #[test]
+    fn identifier_with_spec() {
+        // Lisp style identifiers, which may contain hyphens and end in `?` or `!`
+        const LISP: IdentSpec = IdentSpec::new(
+            CharClass::Union(&[CharClass::Alphabetic, CharClass::Chars("*+<>=")]),
+            CharClass::Union(&[CharClass::Alphanumeric, CharClass::Chars("-*+<>=?!")])
+        ).with_keywords(KeywordSet::new(&["define", "lambda"]));
+
+        let mut walker = FileWalker::from_data("list->vector? x", "input");
+        let ident = identifier_with(LISP)(&mut walker).unwrap();
+        assert_eq!(ident.value, "list->vector?");
+        assert_eq!(ident.span.location, Location::from_components(0, 0, "input"));
+        assert_eq!(walker.current_string(), " x");
+
+        let mut walker = FileWalker::from_data("lambda", "input");
+        assert_eq!(
+            identifier_with(LISP)(&mut walker),
+            Err(ParsingError(Location::from_components(0, 0, "input"), ErrorKind::ReservedKeyword("lambda"))));
+
+        // A keyword is only rejected as a whole word
+        let mut walker = FileWalker::from_data("lambda-list", "input");
+        assert_eq!(identifier_with(LISP)(&mut walker).unwrap().value, "lambda-list");
+
+        let mut walker = FileWalker::from_data("-x", "input");
+        assert_eq!(
+            identifier_with(LISP)(&mut walker),
+            Err(ParsingError(Location::from_components(0, 0, "input"), ErrorKind::ExpectedKind("identifier"))));
+        assert_eq!(walker.current_string(), "-x");
+    }
+
+    #[test]
+    fn char_class_contains() {
+        let hex = CharClass::Union(&[CharClass::Digit, CharClass::Range('a', 'f'), CharClass::Range('A', 'F')]);
+
+        assert!("09afAF".chars().all(|c| hex.contains(c)));
+        assert!(!"gG_ ".chars().any(|c| hex.contains(c)));
+        assert!(CharClass::Custom(|c| c.is_ascii_punctuation()).contains('!'));
+        assert!(CharClass::Whitespace.contains('\t'));
+    }
+
+    #[test]
+    fn keyword_ignoring_case() {
+        const SQL: KeywordSet = KeywordSet::new(&["SELECT", "FROM", "WHERE"]).ignoring_case();
+
+        let mut walker = FileWalker::from_data("Select name from users", "input");
+        let select = keyword_cased(SQL)(&mut walker).unwrap();
+        assert_eq!(select.value, "SELECT");
+        assert_eq!(select.span, Span::from_components(Location::from_components(0, 0, "input"), "Select"));
+
+        walker.step();
+        assert_eq!(identifier_excluding(SQL)(&mut walker).unwrap().data, "name");
+        walker.step();
+        assert_eq!(
+            identifier_excluding(SQL)(&mut walker),
+            Err(ParsingError(Location::from_components(12, 0, "input"), ErrorKind::ReservedKeyword("from"))));
+        assert_eq!(keyword(SQL)(&mut walker).unwrap().data, "from");
+
+        // Suggestions also ignore case
+        let mut walker = FileWalker::from_data("SELEKT", "input");
+        assert_eq!(keyword_suggesting(SQL, 1)(&mut walker).unwrap_err().1, ErrorKind::DidYouMean { expected: "SELECT", found: "SELEKT" });
+
+        // The default set is case sensitive
+        assert!(!KEYWORDS.is_keyword("If"));
+        assert_eq!(SQL.canonical("wHeRe"), Some("WHERE"));
+    }
+
+    #[test]
+    fn keyword_casing_warnings() {
+        assert_eq!(Casing::of("SELECT"), Casing::Upper);
+        assert_eq!(Casing::of("select"), Casing::Lower);
+        assert_eq!(Casing::of("Select"), Casing::Capitalized);
+        assert_eq!(Casing::of("SeLect"), Casing::Mixed);
+        assert_eq!(Casing::Capitalized.apply("sELECT"), "Select");
+
+        const SQL: KeywordSet = KeywordSet::new(&["SELECT", "FROM"]).ignoring_case();
+        let mut walker = FileWalker::from_data("SELECT a from b", "input");
+        let mut casing = KeywordCasing::new();
+
+        let select = keyword(SQL)(&mut walker).unwrap();
+        assert_eq!(casing.check(&select), None);
+
+        walker.step();
+        identifier()(&mut walker).unwrap();
+        walker.step();
+        let from = keyword(SQL)(&mut walker).unwrap();
+        let warning = casing.check(&from).unwrap();
+        assert_eq!(warning.level, ErrorLevel::Warning);
+        assert_eq!(warning.message, "inconsistent keyword casing, expected `FROM`");
+        assert_eq!(warning.notes[1].span, select);
+    }
+}
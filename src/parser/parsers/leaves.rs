@@ -1,14 +1,30 @@
-use crate::{FileWalker, Span, ParsingError, ErrorKind};
+use crate::{CharClass, FileWalker, Span, ParsingError, ErrorKind};
 
 #[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
 pub fn tag<'filedata>(s: &'static str) -> impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
     move |walker: &mut FileWalker<'filedata>| {
         let start = walker.get_marker();
 
         for c in s.chars() {
+            let before = walker.get_marker();
+
             if walker.step() != Some(c) {
+                // If nothing matched, the whole tag was expected at the start
+                if before == start {
+                    walker.pop_back(start);
+                    return Err(ParsingError(walker.get_location_of_marker(start).unwrap(), ErrorKind::ExpectedTag(s)));
+                }
+
+                // Otherwise, report the point where the input diverged from the tag
+                walker.pop_back(before);
+                let matched = walker.span_from_marker_to_here(start).unwrap();
+                let location = walker.current_location();
+                let rest = walker.current_string();
+                let found = &rest[..rest.chars().next().map_or(0, char::len_utf8)];
+
                 walker.pop_back(start);
-                return Err(ParsingError(walker.get_location_of_marker(start).unwrap(), ErrorKind::ExpectedTag(s)));
+                return Err(ParsingError(location, ErrorKind::TagDiverged { tag: s, matched, found }));
             }
         }
 
@@ -16,7 +32,69 @@ pub fn tag<'filedata>(s: &'static str) -> impl Fn(&mut FileWalker<'filedata>) ->
     }
 }
 
+/// The longest word which is compared against tags for suggestions, longer words are never considered similar
+const MAX_SUGGESTION_LENGTH: usize = 32;
+
+/// Get the word at the start of some text to compare against a tag, made of word characters if the tag starts with one, or symbols otherwise
+pub(crate) fn word_like<'a>(text: &'a str, tag: &str) -> &'a str {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let wordy = tag.starts_with(is_word);
+
+    let end = text.char_indices()
+        .find(|(_, c)| c.is_whitespace() || is_word(*c) != wordy)
+        .map_or(text.len(), |(index, _)| index);
+
+    &text[..end]
+}
+
+/// Get the number of single character insertions, deletions, substitutions and transpositions of adjacent characters which turn a word into
+/// a target, or None if it is more than `max_distance`, the word is the target itself, or the word is too long to compare
+pub(crate) fn suggestion_distance(word: &str, target: &str, max_distance: usize) -> Option<usize> {
+    let word: Vec<char> = word.chars().collect();
+    let target: Vec<char> = target.chars().collect();
+
+    if word.is_empty() || word == target || word.len() > MAX_SUGGESTION_LENGTH || word.len().abs_diff(target.len()) > max_distance {
+        return None;
+    }
+
+    // The distances from each prefix of the word to the prefixes of the target, keeping the two previous rows for transpositions
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=target.len()).collect();
+    for i in 1..=word.len() {
+        let mut row = vec![i; target.len() + 1];
+        for j in 1..=target.len() {
+            let substitution = previous[j - 1] + (word[i - 1] != target[j - 1]) as usize;
+            row[j] = substitution.min(previous[j] + 1).min(row[j - 1] + 1);
+
+            if i > 1 && j > 1 && word[i - 1] == target[j - 2] && word[i - 2] == target[j - 1] {
+                row[j] = row[j].min(before[j - 2] + 1);
+            }
+        }
+
+        before = std::mem::replace(&mut previous, row);
+    }
+
+    Some(previous[target.len()]).filter(|distance| *distance <= max_distance)
+}
+
 #[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts a tag like `tag`, but if the input is a word within `max_distance` edits of the tag, fails suggesting the tag instead
+pub fn tag_suggesting<'filedata>(s: &'static str, max_distance: usize) -> impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        tag(s)(walker).map_err(|error| {
+            let found = word_like(walker.current_string(), s);
+
+            match suggestion_distance(found, s, max_distance) {
+                Some(_) => ParsingError(walker.current_location(), ErrorKind::DidYouMean { expected: s, found }),
+                None => error
+            }
+        })
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
 pub fn one_of<'filedata>(s: &'static str)  -> impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
     move |walker: &mut FileWalker<'filedata>| {
         let start = walker.get_marker();
@@ -34,6 +112,7 @@ pub fn one_of<'filedata>(s: &'static str)  -> impl Fn(&mut FileWalker<'filedata>
 }
 
 #[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
 pub fn take_while<'filedata>(
     f: impl Fn(char) -> bool, kind: &'static str
 ) -> impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
@@ -58,6 +137,36 @@ pub fn take_while<'filedata>(
 }
 
 #[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts characters while the predicate holds, failing without consuming any input if fewer than `minimum` were accepted
+pub fn take_at_least<'filedata>(
+    minimum: usize, f: impl Fn(char) -> bool, kind: &'static str
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        let s = walker.current_string();
+        let start = walker.get_marker();
+        let mut found = 0;
+
+        for c in s.chars() {
+            if !f(c) {
+                break;
+            }
+            walker.step();
+            found += 1;
+        }
+
+        if found < minimum {
+            walker.pop_back(start);
+            Err(ParsingError(walker.current_location(), ErrorKind::TooFew { kind, minimum, found }))
+        }
+        else {
+            Ok(walker.span_from_marker_to_here(start).unwrap())
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
 pub fn take_if<'filedata>(
     f: impl Fn(char) -> bool, kind: &'static str
 )  -> impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
@@ -76,9 +185,162 @@ pub fn take_if<'filedata>(
     }
 }
 
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts a single character from a class, describing the class if it fails
+pub fn take_class<'filedata>(class: CharClass) -> impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        match walker.current_string().chars().next() {
+            Some(c) if class.contains(c) => {
+                let start = walker.get_marker();
+                walker.step();
+                Ok(walker.span_from_marker_to_here(start).unwrap())
+            }
+            _ => Err(ParsingError(walker.current_location(), ErrorKind::ExpectedClass(class)))
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Consumes input up to a closing delimiter, skipping over nested pairs of delimiters and any character following the escape character, returns the consumed span and the span of the closing delimiter
+///
+/// Panics if either delimiter is empty, since an empty delimiter is found at every position without consuming anything
+pub fn take_until_balanced<'filedata>(
+    open: &'static str, close: &'static str, escape: Option<char>
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<(Span<'filedata>, Span<'filedata>), ParsingError<'filedata>> {
+    assert!(!open.is_empty() && !close.is_empty(), "balanced delimiters cannot be empty");
+
+    move |walker: &mut FileWalker<'filedata>| {
+        let start = walker.get_marker();
+        let mut depth = 0usize;
+
+        loop {
+            let rest = walker.current_string();
+
+            // Check for a closing delimiter first, so delimiters which open and close with the same text never nest
+            if rest.starts_with(close) {
+                if depth == 0 {
+                    let inner = walker.span_from_marker_to_here(start).unwrap();
+                    let close_span = tag(close)(walker)?;
+
+                    return Ok((inner, close_span));
+                }
+
+                depth -= 1;
+                tag(close)(walker)?;
+            }
+            else if rest.starts_with(open) {
+                depth += 1;
+                tag(open)(walker)?;
+            }
+            else {
+                match walker.step() {
+                    Some(c) if Some(c) == escape => { walker.step(); }
+                    Some(_) => {}
+                    None => {
+                        walker.pop_back(start);
+                        return Err(ParsingError(walker.get_location_of_marker(start).unwrap(), ErrorKind::Unterminated(close)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts the longest of a table of tags, returning the value associated with it and the span of the tag
+pub fn match_tags<'filedata, 'a, T: Clone>(
+    table: &'a [(&'static str, T)]
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<(T, Span<'filedata>), ParsingError<'filedata>> + 'a {
+    move |walker: &mut FileWalker<'filedata>| {
+        let rest = walker.current_string();
+
+        match table.iter().filter(|(s, _)| rest.starts_with(s)).max_by_key(|(s, _)| s.len()) {
+            Some((s, value)) => Ok((value.clone(), tag(s)(walker)?)),
+            None => Err(ParsingError(walker.current_location(), ErrorKind::ExpectedOneOfTags(table.iter().map(|(s, _)| *s).collect())))
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts only the end of the file, returning an empty span there
+pub fn at_eof<'filedata>() -> impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        let rest = walker.current_string();
+
+        // The remaining input is an empty slice at the end of the file's data, so the span refers to the file
+        if rest.is_empty() {
+            Ok(Span::from_components(walker.current_location(), rest))
+        }
+        else {
+            Err(ParsingError(walker.current_location(), ErrorKind::ExpectedEndOfInput(rest)))
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Fails with an unexpected end of file if there is no input remaining, without consuming any input
+pub fn not_eof<'filedata>(expected: &'static str) -> impl Fn(&mut FileWalker<'filedata>) -> Result<(), ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        if walker.current_string().is_empty() {
+            Err(ParsingError(walker.current_location(), ErrorKind::UnexpectedEndOfFile(expected)))
+        }
+        else {
+            Ok(())
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts the longest run of characters which are not whitespace, for simple command languages split on whitespace
+pub fn word<'filedata>() -> impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        let rest = walker.current_string();
+
+        match rest.chars().next() {
+            None => Err(ParsingError(walker.current_location(), ErrorKind::UnexpectedEndOfFile("a word"))),
+            Some(c) if c.is_whitespace() => Err(ParsingError(walker.current_location(), ErrorKind::ExpectedKind("a word"))),
+            Some(_) => {
+                let start = walker.get_marker();
+                while walker.current_string().starts_with(|c: char| !c.is_whitespace()) {
+                    walker.step();
+                }
+                Ok(walker.span_from_marker_to_here(start).unwrap())
+            }
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts a `word` which is exactly the given symbol, so `symbol("let")` does not accept the start of `letter` as `tag` would
+pub fn symbol<'filedata>(s: &'static str) -> impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
+    let word = word();
+
+    move |walker: &mut FileWalker<'filedata>| {
+        let start = walker.get_marker();
+
+        match word(walker) {
+            Ok(found) if found.data == s => Ok(found),
+            Ok(found) => {
+                walker.pop_back(start);
+                Err(ParsingError(found.location, ErrorKind::ExpectedSymbol { expected: s, found: found.data }))
+            }
+            Err(ParsingError(location, ErrorKind::UnexpectedEndOfFile(_))) => Err(ParsingError(location, ErrorKind::UnexpectedEndOfFile(s))),
+            Err(ParsingError(location, _)) => Err(ParsingError(location, ErrorKind::ExpectedTag(s)))
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::{FileWalker, Location, Span, tag, ParsingError, ErrorKind, take_while, one_of, take_if};
+    use crate::{FileWalker, Location, Span, tag, ParsingError, ErrorKind, take_while, take_at_least, one_of, take_if, take_class, CharClass, take_until_balanced, match_tags, at_eof, not_eof, tag_suggesting, word, symbol};
+    use super::suggestion_distance;
 
     #[test]
     fn tag_ok() {
@@ -112,16 +374,38 @@ mod test {
     fn tag_partial_failure() {
         let mut walker = FileWalker::from_data("High beams", "test.txt");
 
-        // Make sure that tag will reject a tag which it starts to match
+        // Make sure that tag will reject a tag which it starts to match, pointing at where it diverged
         assert_eq!(tag("Highway")(&mut walker), Err(ParsingError(
-            Location::from_components(0, 0, "test.txt"),
-            ErrorKind::ExpectedTag("Highway")
+            Location::from_components(4, 0, "test.txt"),
+            ErrorKind::TagDiverged {
+                tag: "Highway",
+                matched: Span::from_components(Location::from_components(0, 0, "test.txt"), "High"),
+                found: " "
+            }
         )));
 
         // And make sure it returns the walker to its original state
         assert_eq!(walker.current_string(), "High beams");
     }
 
+    #[test]
+    fn tag_partial_failure_at_end() {
+        let mut walker = FileWalker::from_data("Hö", "test.txt");
+
+        // Make sure that running out of input part way through a tag reports the end of the input
+        assert_eq!(tag("Höhe")(&mut walker), Err(ParsingError(
+            Location::from_components(2, 0, "test.txt"),
+            ErrorKind::TagDiverged {
+                tag: "Höhe",
+                matched: Span::from_components(Location::from_components(0, 0, "test.txt"), "Hö"),
+                found: ""
+            }
+        )));
+
+        // And make sure it returns the walker to its original state
+        assert_eq!(walker.current_string(), "Hö");
+    }
+
     #[test]
     fn one_of_ok() {
         let mut walker = FileWalker::from_data("Hello World!", "test.txt");
@@ -211,6 +495,24 @@ mod test {
         assert_eq!(walker.current_string(), "This  \t\n\n  \r\n Hi");
     }
 
+    #[test]
+    fn take_class_describes_class() {
+        const START: CharClass = CharClass::Union(&[CharClass::Range('a', 'z'), CharClass::Range('A', 'Z'), CharClass::Chars("_")]);
+        const HEX: CharClass = CharClass::Described("a hexadecimal digit", &CharClass::Union(&[CharClass::Digit, CharClass::Range('a', 'f')]));
+
+        let mut walker = FileWalker::from_data("_9", "test.txt");
+        assert_eq!(take_class(START)(&mut walker).unwrap().data, "_");
+
+        let error = take_class(START)(&mut walker).unwrap_err();
+        assert_eq!(error, ParsingError(Location::from_components(1, 0, "test.txt"), ErrorKind::ExpectedClass(START)));
+        assert_eq!(error.to_string(), "expected one of a-z, A-Z, _");
+        assert_eq!(walker.current_string(), "9");
+
+        assert_eq!(take_class(HEX)(&mut walker).unwrap().data, "9");
+        assert_eq!(take_class(HEX)(&mut walker).unwrap_err().to_string(), "expected a hexadecimal digit");
+        assert_eq!(take_class(CharClass::Alphabetic)(&mut walker).unwrap_err().to_string(), "expected a letter");
+    }
+
     #[test]
     fn take_if_ok() {
         let mut walker = FileWalker::from_data("HEllo", "test.txt");
@@ -260,4 +562,163 @@ mod test {
         // And make sure it keeps the original text
         assert_eq!(walker.current_string(), "This  \t\n\n  \r\n Hi");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn take_until_balanced_ok() {
+        let mut walker = FileWalker::from_data("${ {a: 1}[\\}] } rest", "test.txt");
+        tag("${")(&mut walker).unwrap();
+
+        // Make sure that nested delimiters and escaped characters are skipped over
+        assert_eq!(take_until_balanced("{", "}", Some('\\'))(&mut walker), Ok((
+            Span::from_components(Location::from_components(2, 0, "test.txt"), " {a: 1}[\\}] "),
+            Span::from_components(Location::from_components(14, 0, "test.txt"), "}")
+        )));
+
+        // And make sure it stops right after the closing delimiter
+        assert_eq!(walker.current_string(), " rest");
+
+        let mut walker = FileWalker::from_data("\"quoted\" rest", "test.txt");
+        tag("\"")(&mut walker).unwrap();
+
+        // Make sure that identical opening and closing delimiters do not nest
+        assert_eq!(take_until_balanced("\"", "\"", None)(&mut walker), Ok((
+            Span::from_components(Location::from_components(1, 0, "test.txt"), "quoted"),
+            Span::from_components(Location::from_components(7, 0, "test.txt"), "\"")
+        )));
+    }
+
+    #[test]
+    fn take_until_balanced_failure() {
+        let mut walker = FileWalker::from_data("${ {a: 1 }", "test.txt");
+        tag("${")(&mut walker).unwrap();
+
+        // Make sure that running out of input reports the unterminated construct
+        assert_eq!(take_until_balanced("{", "}", None)(&mut walker), Err(ParsingError(
+            Location::from_components(2, 0, "test.txt"),
+            ErrorKind::Unterminated("}")
+        )));
+
+        // And make sure it keeps the original text
+        assert_eq!(walker.current_string(), " {a: 1 }");
+    }
+
+    #[test]
+    #[should_panic(expected = "balanced delimiters cannot be empty")]
+    fn take_until_balanced_empty_delimiter() {
+        let _ = take_until_balanced::<'static>("", ")", None);
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Op {
+        Less,
+        LessEqual,
+        Shift
+    }
+
+    #[test]
+    fn match_tags_longest() {
+        let operators = [("<", Op::Less), ("<<", Op::Shift), ("<=", Op::LessEqual)];
+        let mut walker = FileWalker::from_data("<= <<<", "test.txt");
+
+        // Make sure the longest tag is matched, regardless of the order of the table
+        assert_eq!(match_tags(&operators)(&mut walker), Ok((Op::LessEqual, Span::from_components(Location::from_components(0, 0, "test.txt"), "<="))));
+        walker.step();
+        assert_eq!(match_tags(&operators)(&mut walker).unwrap().0, Op::Shift);
+        assert_eq!(match_tags(&operators)(&mut walker).unwrap().0, Op::Less);
+
+        // And that failing lists every tag
+        let error = match_tags(&operators)(&mut walker).unwrap_err();
+        assert_eq!(error, ParsingError(Location::from_components(6, 0, "test.txt"), ErrorKind::ExpectedOneOfTags(vec!["<", "<<", "<="])));
+        assert_eq!(error.to_string(), "expected one of `<`, `<<`, `<=`");
+    }
+
+    #[test]
+    fn eof_parsers() {
+        let mut walker = FileWalker::from_data("ab\n", "test.txt");
+
+        assert_eq!(not_eof("a letter")(&mut walker), Ok(()));
+        assert_eq!(at_eof()(&mut walker), Err(ParsingError(Location::from_components(0, 0, "test.txt"), ErrorKind::ExpectedEndOfInput("ab\n"))));
+
+        tag("ab\n")(&mut walker).unwrap();
+        assert_eq!(at_eof()(&mut walker), Ok(Span::from_components(Location::from_components(0, 1, "test.txt"), "")));
+        assert_eq!(not_eof("a letter")(&mut walker), Err(ParsingError(
+            Location::from_components(0, 1, "test.txt"),
+            ErrorKind::UnexpectedEndOfFile("a letter")
+        )));
+    }
+
+    #[test]
+    fn take_at_least_counts() {
+        let hyphens = take_at_least(2, |c| c == '-', "hyphens");
+
+        let mut walker = FileWalker::from_data("--- x", "test.txt");
+        assert_eq!(hyphens(&mut walker), Ok(Span::from_components(Location::from_components(0, 0, "test.txt"), "---")));
+        assert_eq!(walker.current_string(), " x");
+
+        let mut walker = FileWalker::from_data("-x", "test.txt");
+        let error = hyphens(&mut walker).unwrap_err();
+        assert_eq!(error, ParsingError(Location::from_components(0, 0, "test.txt"), ErrorKind::TooFew { kind: "hyphens", minimum: 2, found: 1 }));
+        assert_eq!(error.to_string(), "expected at least 2 hyphens, found 1");
+        assert_eq!(walker.current_string(), "-x");
+
+        // A minimum of zero always succeeds, possibly with an empty span
+        let mut walker = FileWalker::from_data("x", "test.txt");
+        assert_eq!(take_at_least(0, |c| c == '-', "hyphens")(&mut walker).map(|span| span.data), Ok(""));
+    }
+
+    #[test]
+    fn suggestion_distances() {
+        assert_eq!(suggestion_distance("retrun", "return", 2), Some(1));
+        assert_eq!(suggestion_distance("retur", "return", 2), Some(1));
+        assert_eq!(suggestion_distance("rteurn", "return", 2), Some(1));
+        assert_eq!(suggestion_distance("rtrn", "return", 2), Some(2));
+        assert_eq!(suggestion_distance("rtrn", "return", 1), None);
+        assert_eq!(suggestion_distance("return", "return", 2), None);
+        assert_eq!(suggestion_distance("", "if", 2), None);
+        assert_eq!(suggestion_distance(&"a".repeat(40), &"a".repeat(39), 2), None);
+    }
+
+    #[test]
+    fn tag_suggesting_typo() {
+        let mut walker = FileWalker::from_data("retrun x;", "test.txt");
+        let error = tag_suggesting("return", 2)(&mut walker).unwrap_err();
+        assert_eq!(error, ParsingError(Location::from_components(0, 0, "test.txt"), ErrorKind::DidYouMean { expected: "return", found: "retrun" }));
+        assert_eq!(error.to_string(), "unexpected `retrun`, did you mean `return`?");
+        assert_eq!(walker.current_string(), "retrun x;");
+
+        // Words which are not close keep the original error
+        let mut walker = FileWalker::from_data("print x;", "test.txt");
+        assert_eq!(tag_suggesting("return", 2)(&mut walker).unwrap_err().1, ErrorKind::ExpectedTag("return"));
+
+        // Symbols are compared against the symbols at the start of the input
+        let mut walker = FileWalker::from_data("=< b", "test.txt");
+        assert_eq!(tag_suggesting("<=", 1)(&mut walker).unwrap_err().1, ErrorKind::DidYouMean { expected: "<=", found: "=<" });
+
+        let mut walker = FileWalker::from_data("return x;", "test.txt");
+        assert_eq!(tag_suggesting("return", 2)(&mut walker).unwrap().data, "return");
+    }
+
+    #[test]
+    fn word_and_symbol() {
+        let mut walker = FileWalker::from_data("load möbius.txt  \n", "input");
+        assert_eq!(symbol("load")(&mut walker).unwrap().data, "load");
+        assert_eq!(word()(&mut walker), Err(ParsingError(Location::from_components(4, 0, "input"), ErrorKind::ExpectedKind("a word"))));
+
+        take_while(char::is_whitespace, "whitespace")(&mut walker).unwrap();
+        assert_eq!(word()(&mut walker).unwrap().data, "möbius.txt");
+
+        take_while(char::is_whitespace, "whitespace")(&mut walker).unwrap();
+        assert_eq!(word()(&mut walker), Err(ParsingError(Location::from_components(0, 1, "input"), ErrorKind::UnexpectedEndOfFile("a word"))));
+        assert_eq!(symbol("quit")(&mut walker), Err(ParsingError(Location::from_components(0, 1, "input"), ErrorKind::UnexpectedEndOfFile("quit"))));
+
+        // Unlike a tag, a symbol must be the whole word
+        let mut walker = FileWalker::from_data("letter", "input");
+        let error = symbol("let")(&mut walker).unwrap_err();
+        assert_eq!(error, ParsingError(Location::from_components(0, 0, "input"), ErrorKind::ExpectedSymbol { expected: "let", found: "letter" }));
+        assert_eq!(error.to_string(), "expected `let`, found `letter`");
+        assert_eq!(error.notes().len(), 1);
+        assert_eq!(walker.current_string(), "letter");
+
+        assert_eq!(symbol("let")(&mut FileWalker::from_data(" let", "input")).unwrap_err().1, ErrorKind::ExpectedTag("let"));
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{ErrorKind, FileWalker, ParsingError, Span, one_of, tag, take_while};
+
+/// An RFC 3339 timestamp, such as `2024-02-29T13:45:00.5+01:00`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp<'filedata> {
+    pub span: Span<'filedata>,
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    /// The second, which may be 60 for a leap second
+    pub second: u8,
+    pub nanosecond: u32,
+    /// The offset from UTC in minutes, which is zero for `Z`
+    pub offset_minutes: i16
+}
+
+/// A semantic version, such as `1.4.0-beta.2+build.5`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Version<'filedata> {
+    pub span: Span<'filedata>,
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    /// The dot separated pre-release identifiers following `-`, without the `-`
+    pub pre_release: Option<Span<'filedata>>,
+    /// The dot separated build metadata following `+`, without the `+`
+    pub build: Option<Span<'filedata>>
+}
+
+/// Get the number of days in a month of a year in the proleptic Gregorian calendar
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31
+    }
+}
+
+/// Accept exactly `count` ASCII digits and check their value is within a range, returning the value
+fn fixed_digits<'filedata>(walker: &mut FileWalker<'filedata>, count: usize, field: &'static str, range: std::ops::RangeInclusive<u32>) -> Result<u32, ParsingError<'filedata>> {
+    let location = walker.current_location();
+    let rest = walker.current_string();
+
+    match rest.get(..count) {
+        Some(digits) if digits.chars().all(|c| c.is_ascii_digit()) => {
+            let value = digits.parse().unwrap();
+            if !range.contains(&value) {
+                return Err(ParsingError(location, ErrorKind::InvalidField { field, found: digits }));
+            }
+
+            skip_chars(walker, count);
+            Ok(value)
+        }
+        _ => Err(ParsingError(location, ErrorKind::ExpectedKind(field)))
+    }
+}
+
+/// Step over a number of ASCII characters which have already been checked
+fn skip_chars(walker: &mut FileWalker, count: usize) {
+    for _ in 0..count {
+        walker.step();
+    }
+}
+
+fn timestamp_inner<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Timestamp<'filedata>, ParsingError<'filedata>> {
+    let start = walker.get_marker();
+
+    let year = fixed_digits(walker, 4, "year", 0..=9999)? as u16;
+    tag("-")(walker)?;
+    let month = fixed_digits(walker, 2, "month", 1..=12)? as u8;
+    tag("-")(walker)?;
+    let day = fixed_digits(walker, 2, "day", 1..=days_in_month(year, month) as u32)? as u8;
+    one_of("Tt ")(walker)?;
+    let hour = fixed_digits(walker, 2, "hour", 0..=23)? as u8;
+    tag(":")(walker)?;
+    let minute = fixed_digits(walker, 2, "minute", 0..=59)? as u8;
+    tag(":")(walker)?;
+    let second = fixed_digits(walker, 2, "second", 0..=60)? as u8;
+
+    // Only the first nine digits of the fraction are significant to nanosecond precision
+    let nanosecond = if walker.current_string().starts_with('.') {
+        tag(".")(walker)?;
+        let fraction = take_while(|c| c.is_ascii_digit(), "fractional second")(walker)?;
+        fraction.data.chars()
+            .chain(std::iter::repeat('0'))
+            .take(9)
+            .fold(0, |value, c| value * 10 + c.to_digit(10).unwrap())
+    }
+    else {
+        0
+    };
+
+    let offset_minutes = if one_of("Zz")(walker).is_ok() {
+        0
+    }
+    else {
+        let sign = one_of("+-")(walker).map_err(|_| ParsingError(walker.current_location(), ErrorKind::ExpectedKind("time zone offset")))?;
+        let hours = fixed_digits(walker, 2, "offset hour", 0..=23)? as i16;
+        tag(":")(walker)?;
+        let minutes = fixed_digits(walker, 2, "offset minute", 0..=59)? as i16;
+
+        if sign.data == "-" { -(hours * 60 + minutes) } else { hours * 60 + minutes }
+    };
+
+    Ok(Timestamp {
+        span: walker.span_from_marker_to_here(start).unwrap(),
+        year, month, day, hour, minute, second, nanosecond, offset_minutes
+    })
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts an RFC 3339 timestamp, checking each field is within range for the date
+pub fn timestamp<'filedata>() -> impl Fn(&mut FileWalker<'filedata>) -> Result<Timestamp<'filedata>, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        let start = walker.get_marker();
+
+        let result = timestamp_inner(walker);
+        if result.is_err() {
+            walker.pop_back(start);
+        }
+
+        result
+    }
+}
+
+/// Accept a version number without leading zeros
+fn version_number<'filedata>(walker: &mut FileWalker<'filedata>, field: &'static str) -> Result<u64, ParsingError<'filedata>> {
+    let digits = take_while(|c| c.is_ascii_digit(), field)(walker)?;
+
+    match digits.data.parse() {
+        Ok(value) if digits.data == "0" || !digits.data.starts_with('0') => Ok(value),
+        _ => Err(ParsingError(digits.location, ErrorKind::InvalidField { field, found: digits.data }))
+    }
+}
+
+/// Accept dot separated identifiers of ASCII alphanumerics and hyphens, checking numeric identifiers have no leading zeros if required
+fn version_identifiers<'filedata>(walker: &mut FileWalker<'filedata>, field: &'static str, check_numeric: bool) -> Result<Span<'filedata>, ParsingError<'filedata>> {
+    let start = walker.get_marker();
+
+    loop {
+        let identifier = take_while(|c| c.is_ascii_alphanumeric() || c == '-', field)(walker)?;
+
+        let numeric = identifier.data.chars().all(|c| c.is_ascii_digit());
+        if check_numeric && numeric && identifier.data.len() > 1 && identifier.data.starts_with('0') {
+            return Err(ParsingError(identifier.location, ErrorKind::InvalidField { field, found: identifier.data }));
+        }
+
+        if tag(".")(walker).is_err() {
+            break;
+        }
+    }
+
+    Ok(walker.span_from_marker_to_here(start).unwrap())
+}
+
+fn version_inner<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Version<'filedata>, ParsingError<'filedata>> {
+    let start = walker.get_marker();
+
+    let major = version_number(walker, "major version")?;
+    tag(".")(walker)?;
+    let minor = version_number(walker, "minor version")?;
+    tag(".")(walker)?;
+    let patch = version_number(walker, "patch version")?;
+
+    let pre_release = match tag("-")(walker) {
+        Ok(_) => Some(version_identifiers(walker, "pre-release identifier", true)?),
+        Err(_) => None
+    };
+
+    let build = match tag("+")(walker) {
+        Ok(_) => Some(version_identifiers(walker, "build identifier", false)?),
+        Err(_) => None
+    };
+
+    Ok(Version {
+        span: walker.span_from_marker_to_here(start).unwrap(),
+        major, minor, patch, pre_release, build
+    })
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts a semantic version, as described by Semantic Versioning 2.0.0
+pub fn version<'filedata>() -> impl Fn(&mut FileWalker<'filedata>) -> Result<Version<'filedata>, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        let start = walker.get_marker();
+
+        let result = version_inner(walker);
+        if result.is_err() {
+            walker.pop_back(start);
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ErrorKind, FileWalker, Location, ParsingError, timestamp, version};
+
+    #[test]
+    fn timestamp_ok() {
+        let mut walker = FileWalker::from_data("2024-02-29T13:45:07.25+01:30 next", "input");
+        let value = timestamp()(&mut walker).unwrap();
+
+        assert_eq!(value.span.data, "2024-02-29T13:45:07.25+01:30");
+        assert_eq!((value.year, value.month, value.day), (2024, 2, 29));
+        assert_eq!((value.hour, value.minute, value.second), (13, 45, 7));
+        assert_eq!(value.nanosecond, 250_000_000);
+        assert_eq!(value.offset_minutes, 90);
+        assert_eq!(walker.current_string(), " next");
+
+        let mut walker = FileWalker::from_data("1990-12-31 23:59:60Z", "input");
+        let value = timestamp()(&mut walker).unwrap();
+        assert_eq!((value.second, value.nanosecond, value.offset_minutes), (60, 0, 0));
+    }
+
+    #[test]
+    fn timestamp_err() {
+        let mut walker = FileWalker::from_data("2024-13-01T00:00:00Z", "input");
+        assert_eq!(
+            timestamp()(&mut walker),
+            Err(ParsingError(Location::from_components(5, 0, "input"), ErrorKind::InvalidField { field: "month", found: "13" })));
+        assert_eq!(walker.current_string(), "2024-13-01T00:00:00Z");
+
+        // Not a leap year
+        let mut walker = FileWalker::from_data("2023-02-29T00:00:00Z", "input");
+        assert_eq!(
+            timestamp()(&mut walker),
+            Err(ParsingError(Location::from_components(8, 0, "input"), ErrorKind::InvalidField { field: "day", found: "29" })));
+
+        let mut walker = FileWalker::from_data("2023-02-28T00:00:00", "input");
+        assert_eq!(
+            timestamp()(&mut walker),
+            Err(ParsingError(Location::from_components(19, 0, "input"), ErrorKind::ExpectedKind("time zone offset"))));
+    }
+
+    #[test]
+    fn version_ok() {
+        let mut walker = FileWalker::from_data("1.40.0-beta.2+build.05", "input");
+        let value = version()(&mut walker).unwrap();
+
+        assert_eq!((value.major, value.minor, value.patch), (1, 40, 0));
+        assert_eq!(value.pre_release.unwrap().data, "beta.2");
+        assert_eq!(value.build.unwrap().data, "build.05");
+        assert_eq!(value.span.data, "1.40.0-beta.2+build.05");
+
+        let mut walker = FileWalker::from_data("0.1.2", "input");
+        let value = version()(&mut walker).unwrap();
+        assert_eq!((value.pre_release, value.build), (None, None));
+    }
+
+    #[test]
+    fn version_err() {
+        let mut walker = FileWalker::from_data("1.02.0", "input");
+        assert_eq!(
+            version()(&mut walker),
+            Err(ParsingError(Location::from_components(2, 0, "input"), ErrorKind::InvalidField { field: "minor version", found: "02" })));
+        assert_eq!(walker.current_string(), "1.02.0");
+
+        let mut walker = FileWalker::from_data("1.0.0-alpha.01", "input");
+        assert_eq!(
+            version()(&mut walker),
+            Err(ParsingError(Location::from_components(12, 0, "input"), ErrorKind::InvalidField { field: "pre-release identifier", found: "01" })));
+
+        let mut walker = FileWalker::from_data("1.0.0-", "input");
+        assert_eq!(
+            version()(&mut walker),
+            Err(ParsingError(Location::from_components(6, 0, "input"), ErrorKind::ExpectedKind("pre-release identifier"))));
+    }
+}
@@ -1,9 +1,17 @@
 pub mod errors;
 pub mod combinators;
 pub mod leaves;
+pub mod keywords;
+pub mod numbers;
+#[cfg(feature = "literals")]
+pub mod literals;
 pub mod r#trait;
 
 pub use errors::*;
 pub use combinators::*;
 pub use r#trait::*;
 pub use leaves::*;
+pub use keywords::*;
+pub use numbers::*;
+#[cfg(feature = "literals")]
+pub use literals::*;
@@ -0,0 +1,309 @@
+use crate::{ErrorKind, FileLocationMarker, FileWalker, KeywordSet, ParsingError, Span, tag};
+
+/// A base which integer literals may be written in, introduced by a prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberBase {
+    /// The text before the digits, such as `0x`, `$` or `16#`
+    pub prefix: &'static str,
+    pub radix: u32,
+    /// Text which must follow the digits, such as the closing `#` of `16#FF#`
+    pub terminator: Option<&'static str>
+}
+
+/// The `0x`, `0o` and `0b` prefixes used by C-like languages
+pub const C_STYLE_BASES: &[NumberBase] = &[
+    NumberBase { prefix: "0x", radix: 16, terminator: None },
+    NumberBase { prefix: "0o", radix: 8, terminator: None },
+    NumberBase { prefix: "0b", radix: 2, terminator: None },
+];
+
+/// The conventions accepted by the `number` parser
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberFormat {
+    /// A character which may be placed between digits to group them, such as `_` in `1_000_000`
+    pub separator: Option<char>,
+    /// Suffixes which may follow the digits, such as `u32` in `10u32`
+    pub suffixes: &'static [&'static str],
+    /// The prefixed bases literals may be written in
+    pub bases: &'static [NumberBase],
+    /// The base of literals without a prefix, or None if a prefix is required
+    pub unprefixed_radix: Option<u32>,
+    /// Characters which introduce an exponent of the base, such as `e` in `1e6`
+    pub exponents: &'static [char]
+}
+
+impl NumberFormat {
+    /// Construct the default format, decimal literals with C-like prefixes and `_` separators
+    pub const fn new() -> Self {
+        Self { separator: Some('_'), suffixes: &[], bases: C_STYLE_BASES, unprefixed_radix: Some(10), exponents: &[] }
+    }
+}
+
+impl std::default::Default for NumberFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An integer literal, with the digits and suffix split out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Number<'filedata> {
+    /// The whole literal, including any prefix and suffix
+    pub span: Span<'filedata>,
+    /// The digits of the literal, including any separators between them
+    pub digits: Span<'filedata>,
+    /// The base the digits are written in, given by the prefix of the literal
+    pub radix: u32,
+    /// The digits of the exponent, including its sign
+    pub exponent: Option<Span<'filedata>>,
+    pub suffix: Option<Span<'filedata>>
+}
+
+impl<'filedata> Number<'filedata> {
+    /// Get the value of the literal, or None if it is too large to represent or has a negative exponent
+    pub fn value(&self, format: &NumberFormat) -> Option<u128> {
+        let digits: String = self.digits.data.chars().filter(|c| Some(*c) != format.separator).collect();
+        let value = u128::from_str_radix(&digits, self.radix).ok()?;
+
+        match self.exponent {
+            Some(exponent) => value.checked_mul(u128::from(self.radix).checked_pow(exponent.data.trim_start_matches('+').parse().ok()?)?),
+            None => Some(value)
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts an integer literal with an optional base prefix, digit separators and suffix
+///
+/// Separators must be placed between digits, or once between the digits and the suffix, as in `0xFF_u8`
+pub fn number<'filedata>(format: NumberFormat) -> impl Fn(&mut FileWalker<'filedata>) -> Result<Number<'filedata>, ParsingError<'filedata>> {
+    move |walker: &mut FileWalker<'filedata>| {
+        let start = walker.get_marker();
+
+        let rest = walker.current_string();
+        let base = format.bases.iter().filter(|base| rest.starts_with(base.prefix)).max_by_key(|base| base.prefix.len());
+
+        let (radix, terminator) = match (base, format.unprefixed_radix) {
+            (Some(base), _) => {
+                tag(base.prefix)(walker)?;
+                (base.radix, base.terminator)
+            }
+            (None, Some(radix)) if rest.starts_with(|c: char| c.is_digit(radix)) => (radix, None),
+            _ => return Err(ParsingError(walker.current_location(), ErrorKind::ExpectedKind("number")))
+        };
+
+        let digits_start = walker.get_marker();
+        let mut digits_end = digits_start;
+        let mut separator = None;
+
+        loop {
+            match walker.current_string().chars().next() {
+                Some(c) if c.is_digit(radix) => {
+                    walker.step();
+                    digits_end = walker.get_marker();
+                    separator = None;
+                }
+                Some(c) if Some(c) == format.separator => {
+                    // A separator must follow a digit, not the prefix or another separator
+                    if digits_end == digits_start || separator.is_some() {
+                        return misplaced_separator(walker, start);
+                    }
+
+                    separator = Some(walker.get_marker());
+                    walker.step();
+                }
+                _ => break
+            }
+        }
+
+        if digits_end == digits_start {
+            let location = walker.current_location();
+            walker.pop_back(start);
+            return Err(ParsingError(location, ErrorKind::ExpectedKind("digit")));
+        }
+
+        if let Some(terminator) = terminator {
+            if let Some(separator) = separator {
+                walker.pop_back(separator);
+                return misplaced_separator(walker, start);
+            }
+
+            if let Err(error) = tag(terminator)(walker) {
+                walker.pop_back(start);
+                return Err(error);
+            }
+        }
+
+        let exponent = exponent(walker, format.exponents);
+
+        let suffix = match KeywordSet::new(format.suffixes).longest_match(walker.current_string()) {
+            Some(suffix) => Some(tag(suffix)(walker)?),
+            None => None
+        };
+
+        // A trailing separator is only allowed directly before a suffix
+        if let Some(separator) = separator.filter(|_| exponent.is_some() || suffix.is_none()) {
+            walker.pop_back(separator);
+            return misplaced_separator(walker, start);
+        }
+
+        let mut digits_walker = walker.clone();
+        digits_walker.pop_back(digits_end);
+
+        Ok(Number {
+            span: walker.span_from_marker_to_here(start).unwrap(),
+            digits: digits_walker.span_from_marker_to_here(digits_start).unwrap(),
+            radix,
+            exponent,
+            suffix
+        })
+    }
+}
+
+/// Accept an exponent marker followed by a signed decimal exponent, returning the span of the exponent's sign and digits
+///
+/// Nothing is consumed if the marker is not followed by digits, so the marker can begin a suffix instead
+fn exponent<'filedata>(walker: &mut FileWalker<'filedata>, markers: &[char]) -> Option<Span<'filedata>> {
+    let rest = walker.current_string();
+    let marker = rest.chars().next().filter(|c| markers.contains(c))?;
+
+    let after_marker = &rest[marker.len_utf8()..];
+    let sign = after_marker.chars().next().filter(|c| *c == '+' || *c == '-').map_or(0, char::len_utf8);
+    let digits = after_marker[sign..].chars().take_while(char::is_ascii_digit).count();
+
+    if digits == 0 {
+        return None;
+    }
+
+    walker.step();
+    let start = walker.get_marker();
+    for _ in 0..sign + digits {
+        walker.step();
+    }
+
+    walker.span_from_marker_to_here(start)
+}
+
+/// Report the separator at the current location as misplaced, and backtrack to the start of the literal
+fn misplaced_separator<'filedata, T>(walker: &mut FileWalker<'filedata>, start: FileLocationMarker) -> Result<T, ParsingError<'filedata>> {
+    let location = walker.current_location();
+    let rest = walker.current_string();
+    let separator = &rest[..rest.chars().next().map_or(0, char::len_utf8)];
+
+    walker.pop_back(start);
+    Err(ParsingError(location, ErrorKind::MisplacedSeparator(separator)))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ErrorKind, FileWalker, Location, NumberBase, NumberFormat, ParsingError, number};
+
+    const FORMAT: NumberFormat = NumberFormat { separator: Some('_'), suffixes: &["u8", "u32", "i32"], ..NumberFormat::new() };
+
+    #[test]
+    fn number_separators_and_suffixes() {
+        let mut walker = FileWalker::from_data("1_000_000u32 rest", "input");
+        let literal = number(FORMAT)(&mut walker).unwrap();
+        assert_eq!(literal.span.data, "1_000_000u32");
+        assert_eq!(literal.digits.data, "1_000_000");
+        assert_eq!(literal.suffix.unwrap().data, "u32");
+        assert_eq!(literal.suffix.unwrap().location, Location::from_components(9, 0, "input"));
+        assert_eq!(literal.value(&FORMAT), Some(1_000_000));
+        assert_eq!(walker.current_string(), " rest");
+
+        let mut walker = FileWalker::from_data("0xFF_u8", "input");
+        let literal = number(FORMAT)(&mut walker).unwrap();
+        assert_eq!(literal.radix, 16);
+        assert_eq!(literal.digits.data, "FF");
+        assert_eq!(literal.suffix.unwrap().data, "u8");
+        assert_eq!(literal.value(&FORMAT), Some(255));
+
+        let mut walker = FileWalker::from_data("42", "input");
+        let literal = number(NumberFormat::default())(&mut walker).unwrap();
+        assert_eq!(literal.suffix, None);
+        assert_eq!(literal.value(&FORMAT), Some(42));
+    }
+
+    #[test]
+    fn number_misplaced_separators() {
+        for (input, column) in [("1__000", 2), ("0x_FF", 2), ("100_", 3), ("100_u16", 3)] {
+            let mut walker = FileWalker::from_data(input, "input");
+            assert_eq!(
+                number(FORMAT)(&mut walker),
+                Err(ParsingError(Location::from_components(column, 0, "input"), ErrorKind::MisplacedSeparator("_"))),
+                "{}", input);
+            assert_eq!(walker.current_string(), input);
+        }
+
+        // Without separators, the literal just ends
+        let mut walker = FileWalker::from_data("1_000", "input");
+        assert_eq!(number(NumberFormat { separator: None, ..FORMAT })(&mut walker).unwrap().span.data, "1");
+    }
+
+    #[test]
+    fn number_err() {
+        let mut walker = FileWalker::from_data("x10", "input");
+        assert_eq!(
+            number(FORMAT)(&mut walker),
+            Err(ParsingError(Location::from_components(0, 0, "input"), ErrorKind::ExpectedKind("number"))));
+
+        let mut walker = FileWalker::from_data("0b2", "input");
+        assert_eq!(
+            number(FORMAT)(&mut walker),
+            Err(ParsingError(Location::from_components(2, 0, "input"), ErrorKind::ExpectedKind("digit"))));
+        assert_eq!(walker.current_string(), "0b2");
+    }
+
+    #[test]
+    fn number_bases() {
+        // Ada style based literals, with an exponent of the base
+        const ADA: NumberFormat = NumberFormat {
+            bases: &[
+                NumberBase { prefix: "16#", radix: 16, terminator: Some("#") },
+                NumberBase { prefix: "2#", radix: 2, terminator: Some("#") },
+            ],
+            exponents: &['E', 'e'],
+            ..NumberFormat::new()
+        };
+
+        let mut walker = FileWalker::from_data("16#FF_FF#E1;", "input");
+        let literal = number(ADA)(&mut walker).unwrap();
+        assert_eq!(literal.span.data, "16#FF_FF#E1");
+        assert_eq!(literal.digits.data, "FF_FF");
+        assert_eq!(literal.exponent.unwrap().data, "1");
+        assert_eq!(literal.value(&ADA), Some(0xFFFF0));
+        assert_eq!(walker.current_string(), ";");
+
+        let mut walker = FileWalker::from_data("12e+3", "input");
+        let literal = number(ADA)(&mut walker).unwrap();
+        assert_eq!(literal.radix, 10);
+        assert_eq!(literal.value(&ADA), Some(12_000));
+
+        // An exponent marker without digits is not part of the literal
+        let mut walker = FileWalker::from_data("12else", "input");
+        assert_eq!(number(ADA)(&mut walker).unwrap().span.data, "12");
+
+        let mut walker = FileWalker::from_data("2#101", "input");
+        assert_eq!(number(ADA)(&mut walker), Err(ParsingError(Location::from_components(5, 0, "input"), ErrorKind::ExpectedTag("#"))));
+        assert_eq!(walker.current_string(), "2#101");
+
+        // A separator cannot come directly before the terminator
+        let mut walker = FileWalker::from_data("16#FF_#", "input");
+        assert_eq!(number(ADA)(&mut walker), Err(ParsingError(Location::from_components(5, 0, "input"), ErrorKind::MisplacedSeparator("_"))));
+        assert_eq!(walker.current_string(), "16#FF_#");
+
+        // Pascal style hexadecimal, where only prefixed literals are accepted
+        const PASCAL_HEX: NumberFormat = NumberFormat {
+            bases: &[NumberBase { prefix: "$", radix: 16, terminator: None }],
+            unprefixed_radix: None,
+            ..NumberFormat::new()
+        };
+
+        let mut walker = FileWalker::from_data("$1F", "input");
+        assert_eq!(number(PASCAL_HEX)(&mut walker).unwrap().value(&PASCAL_HEX), Some(31));
+
+        let mut walker = FileWalker::from_data("31", "input");
+        assert_eq!(number(PASCAL_HEX)(&mut walker), Err(ParsingError(Location::from_components(0, 0, "input"), ErrorKind::ExpectedKind("number"))));
+    }
+}
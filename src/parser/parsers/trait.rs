@@ -1,2 +1,91 @@
-// use crate::FileWalker;
-// pub trait Combinator<'filedata, T> = Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError>;
\ No newline at end of file
+use std::rc::Rc;
+
+use crate::{Choice, FileWalker, ParsingError};
+
+/// Any parser, which is any function from a walker to a value or an error, for adaptors which are written as methods
+pub trait Combinator<'filedata, T>: Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>> {
+    /// Erase the type of the parser, so parsers built in different ways can be stored together or chosen at runtime
+    fn boxed(self) -> BoxedParser<'filedata, T> where Self: Sized + 'filedata {
+        BoxedParser { parser: Rc::new(self) }
+    }
+}
+
+impl<'filedata, T, P: Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>> Combinator<'filedata, T> for P {}
+
+/// The type a boxed parser's type is erased to
+type DynParser<'filedata, T> = dyn Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>> + 'filedata;
+
+/// A parser whose type has been erased by `Combinator::boxed`
+///
+/// Cloning is O(1) as clones share the parser. Boxed parsers are called like any other parser, and are passed to combinators by
+/// reference, as `&*parser`
+pub struct BoxedParser<'filedata, T> {
+    parser: Rc<DynParser<'filedata, T>>
+}
+
+impl<'filedata, T> std::ops::Deref for BoxedParser<'filedata, T> {
+    type Target = DynParser<'filedata, T>;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.parser
+    }
+}
+
+impl<T> Clone for BoxedParser<'_, T> {
+    fn clone(&self) -> Self {
+        Self { parser: Rc::clone(&self.parser) }
+    }
+}
+
+impl<T> std::fmt::Debug for BoxedParser<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "BoxedParser({:p})", Rc::as_ptr(&self.parser))
+    }
+}
+
+impl<'filedata, T> Choice<'filedata, T> for [BoxedParser<'filedata, T>] {
+    fn choose(&self, walker: &mut FileWalker<'filedata>) -> Result<T, Vec<ParsingError<'filedata>>> {
+        let parsers: Vec<_> = self.iter().map(|parser| &**parser).collect();
+        parsers.choose(walker)
+    }
+}
+
+impl<'filedata, T> Choice<'filedata, T> for Vec<BoxedParser<'filedata, T>> {
+    fn choose(&self, walker: &mut FileWalker<'filedata>) -> Result<T, Vec<ParsingError<'filedata>>> {
+        self[..].choose(walker)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{BoxedParser, Combinator, FileWalker, choice, many1, map, pair, tag, take_while};
+
+    /// Builds a parser for `name = value` settings from the names known at runtime, each with its own kind of value
+    fn settings<'filedata>(names: &[(&'static str, bool)]) -> BoxedParser<'filedata, (&'filedata str, &'filedata str)> {
+        let number = map(take_while(|c| c.is_ascii_digit(), "a number"), |span| span.data).boxed();
+        let word = map(take_while(|c| c.is_ascii_alphabetic(), "a word"), |span| span.data).boxed();
+
+        let settings: Vec<_> = names.iter().map(|&(name, numeric)| {
+            let value = if numeric { number.clone() } else { word.clone() };
+            map(pair(tag(name), move |walker: &mut FileWalker<'filedata>| value(walker)), |(name, value)| (name.data, value)).boxed()
+        }).collect();
+
+        choice(settings).boxed()
+    }
+
+    #[test]
+    fn boxed_parsers() {
+        let setting = settings(&[("width=", true), ("mode=", false)]);
+
+        let mut walker = FileWalker::from_data("mode=fast", "input");
+        assert_eq!(setting(&mut walker), Ok(("mode=", "fast")));
+
+        // Boxed parsers are passed to other combinators by reference
+        let mut walker = FileWalker::from_data("width=80mode=slow", "input");
+        assert_eq!(many1(&*setting.clone())(&mut walker), Ok(vec![("width=", "80"), ("mode=", "slow")]));
+
+        let mut walker = FileWalker::from_data("width=wide", "input");
+        assert_eq!(setting(&mut walker).unwrap_err().to_string(), "expected a number");
+        assert_eq!(walker.current_string(), "width=wide");
+    }
+}
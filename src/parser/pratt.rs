@@ -0,0 +1,299 @@
+//! Parses expressions of prefix, infix and postfix operators by their binding powers, as a Pratt parser
+//!
+//! Grammars of operators written with combinators alone must be rewritten to avoid left recursion, with a rule for each level of
+//! precedence. Instead the operators are registered with a precedence, and the parser decides how far each operand extends
+
+use crate::{FileWalker, ParsingError, Span};
+
+/// Which way a chain of infix operators of the same precedence groups, `a - b - c` is `(a - b) - c` as `-` is left associative
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Assoc {
+    Left,
+    Right
+}
+
+type Operator<'a, 'filedata> = Box<dyn Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> + 'a>;
+type Atom<'a, 'filedata, T> = Box<dyn Fn(&mut FileWalker<'filedata>, &PrattParser<'a, 'filedata, T>) -> Result<T, ParsingError<'filedata>> + 'a>;
+type Trivia<'a, 'filedata> = Box<dyn Fn(&mut FileWalker<'filedata>) -> Result<(), ParsingError<'filedata>> + 'a>;
+type Unary<'a, 'filedata, T> = Box<dyn Fn(Span<'filedata>, Span<'filedata>, T) -> T + 'a>;
+type Binary<'a, 'filedata, T> = Box<dyn Fn(Span<'filedata>, Span<'filedata>, T, T) -> T + 'a>;
+
+/// An operator and how tightly it binds to the operands on its left and right
+struct Registered<'a, 'filedata, B> {
+    operator: Operator<'a, 'filedata>,
+    left: u16,
+    right: u16,
+    build: B
+}
+
+/// Parses expressions from atoms and the operators registered with it, building a value for each operator applied
+///
+/// Higher precedences bind more tightly. Operators of each kind are tried in the order they were registered, so an operator which
+/// starts with another, such as `**` and `*`, should be registered first. The span given when building an operator covers its
+/// operands, but not any trivia before or after the expression
+pub struct PrattParser<'a, 'filedata, T> {
+    atom: Atom<'a, 'filedata, T>,
+    trivia: Option<Trivia<'a, 'filedata>>,
+    prefix: Vec<Registered<'a, 'filedata, Unary<'a, 'filedata, T>>>,
+    infix: Vec<Registered<'a, 'filedata, Binary<'a, 'filedata, T>>>,
+    postfix: Vec<Registered<'a, 'filedata, Unary<'a, 'filedata, T>>>
+}
+
+/// An operator found after an operand
+enum Following<'a, 'b, 'filedata, T> {
+    Infix(&'b Registered<'a, 'filedata, Binary<'a, 'filedata, T>>, Span<'filedata>),
+    Postfix(&'b Registered<'a, 'filedata, Unary<'a, 'filedata, T>>, Span<'filedata>)
+}
+
+impl<'a, 'filedata, T> PrattParser<'a, 'filedata, T> {
+    /// Construct a parser of expressions without any operators, from a parser of the atoms between them such as numbers and names
+    ///
+    /// The atom parser is given the expression parser, for atoms which contain expressions such as parentheses and calls
+    pub fn new(atom: impl Fn(&mut FileWalker<'filedata>, &PrattParser<'a, 'filedata, T>) -> Result<T, ParsingError<'filedata>> + 'a) -> Self {
+        Self { atom: Box::new(atom), trivia: None, prefix: Vec::new(), infix: Vec::new(), postfix: Vec::new() }
+    }
+
+    /// Skip trivia, such as whitespace, before each atom and operator
+    pub fn with_trivia(mut self, trivia: impl Fn(&mut FileWalker<'filedata>) -> Result<(), ParsingError<'filedata>> + 'a) -> Self {
+        self.trivia = Some(Box::new(trivia));
+        self
+    }
+
+    /// Register a prefix operator, built from the span of the expression, the span of the operator and the operand
+    pub fn prefix(
+        mut self,
+        operator: impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> + 'a,
+        precedence: u8,
+        build: impl Fn(Span<'filedata>, Span<'filedata>, T) -> T + 'a
+    ) -> Self {
+        let power = binding_power(precedence);
+        self.prefix.push(Registered { operator: Box::new(operator), left: 0, right: power, build: Box::new(build) });
+        self
+    }
+
+    /// Register an infix operator, built from the span of the expression, the span of the operator and the left and right operands
+    pub fn infix(
+        mut self,
+        operator: impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> + 'a,
+        precedence: u8,
+        assoc: Assoc,
+        build: impl Fn(Span<'filedata>, Span<'filedata>, T, T) -> T + 'a
+    ) -> Self {
+        let power = binding_power(precedence);
+        let (left, right) = match assoc {
+            Assoc::Left => (power, power + 1),
+            Assoc::Right => (power + 1, power),
+        };
+
+        self.infix.push(Registered { operator: Box::new(operator), left, right, build: Box::new(build) });
+        self
+    }
+
+    /// Register a postfix operator, built from the span of the expression, the span of the operator and the operand
+    pub fn postfix(
+        mut self,
+        operator: impl Fn(&mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> + 'a,
+        precedence: u8,
+        build: impl Fn(Span<'filedata>, Span<'filedata>, T) -> T + 'a
+    ) -> Self {
+        let power = binding_power(precedence);
+        self.postfix.push(Registered { operator: Box::new(operator), left: power, right: 0, build: Box::new(build) });
+        self
+    }
+
+    /// Parse an expression, moving the walker back to where it started if it fails
+    pub fn parse(&self, walker: &mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>> {
+        self.parse_with_minimum(walker, 0)
+    }
+
+    /// Parse an expression whose operators all bind more tightly than a precedence, such as the operand of a custom operator
+    pub fn parse_above(&self, walker: &mut FileWalker<'filedata>, precedence: u8) -> Result<T, ParsingError<'filedata>> {
+        self.parse_with_minimum(walker, binding_power(precedence) + 2)
+    }
+
+    fn parse_with_minimum(&self, walker: &mut FileWalker<'filedata>, minimum: u16) -> Result<T, ParsingError<'filedata>> {
+        let start = walker.get_marker();
+
+        self.expression(walker, minimum).inspect_err(|_| {
+            walker.pop_back(start);
+        })
+    }
+
+    /// Skip any trivia, for atoms which accept more input after an expression within them, such as a closing parenthesis
+    pub fn skip_trivia(&self, walker: &mut FileWalker<'filedata>) -> Result<(), ParsingError<'filedata>> {
+        match &self.trivia {
+            Some(trivia) => {
+                let before = walker.get_marker();
+                match trivia(walker) {
+                    Err(e) if !e.is_recoverable() => Err(e),
+                    Err(_) => {
+                        walker.pop_back(before);
+                        Ok(())
+                    }
+                    Ok(()) => Ok(())
+                }
+            }
+            None => Ok(())
+        }
+    }
+
+    /// Find the first of the operators which is accepted, leaving the walker after it, or None if none are
+    fn find<'b, B>(
+        &self, walker: &mut FileWalker<'filedata>, operators: &'b [Registered<'a, 'filedata, B>]
+    ) -> Result<Option<(&'b Registered<'a, 'filedata, B>, Span<'filedata>)>, ParsingError<'filedata>> {
+        for registered in operators {
+            let before = walker.get_marker();
+
+            match (registered.operator)(walker) {
+                Ok(span) => return Ok(Some((registered, span))),
+                Err(e) if !e.is_recoverable() => return Err(e),
+                Err(_) => {
+                    walker.pop_back(before);
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Parse an expression whose operators bind at least as tightly as the minimum power
+    fn expression(&self, walker: &mut FileWalker<'filedata>, minimum: u16) -> Result<T, ParsingError<'filedata>> {
+        self.skip_trivia(walker)?;
+        let start = walker.get_marker();
+
+        let mut left = match self.find(walker, &self.prefix)? {
+            Some((prefix, operator)) => {
+                let operand = self.expression(walker, prefix.right)?;
+                (prefix.build)(walker.span_from_marker_to_here(start).unwrap(), operator, operand)
+            }
+            None => (self.atom)(walker, self)?
+        };
+
+        loop {
+            let before = walker.get_marker();
+            self.skip_trivia(walker)?;
+
+            let following = match self.find(walker, &self.postfix)? {
+                Some((postfix, operator)) => Some(Following::Postfix(postfix, operator)),
+                None => self.find(walker, &self.infix)?.map(|(infix, operator)| Following::Infix(infix, operator))
+            };
+
+            left = match following {
+                Some(Following::Postfix(postfix, operator)) if postfix.left >= minimum => {
+                    (postfix.build)(walker.span_from_marker_to_here(start).unwrap(), operator, left)
+                }
+                Some(Following::Infix(infix, operator)) if infix.left >= minimum => {
+                    let right = self.expression(walker, infix.right)?;
+                    (infix.build)(walker.span_from_marker_to_here(start).unwrap(), operator, left, right)
+                }
+                // An operator which binds less tightly belongs to an enclosing expression
+                _ => {
+                    walker.pop_back(before);
+                    return Ok(left);
+                }
+            };
+        }
+    }
+}
+
+/// Get the power an operator of a precedence binds with, leaving room between precedences for associativity
+fn binding_power(precedence: u8) -> u16 {
+    (precedence as u16 + 1) * 2
+}
+
+impl<T> std::fmt::Debug for PrattParser<'_, '_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrattParser")
+            .field("prefix", &self.prefix.len())
+            .field("infix", &self.infix.len())
+            .field("postfix", &self.postfix.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+
+    use crate::parser::pratt::{Assoc, PrattParser};
+    use crate::{ErrorKind, FileWalker, Location, ParsingError, Span, alt, map, tag, take_while};
+
+    /// Builds expressions as s-expressions, so the structure the operators were parsed into is visible
+    fn calculator<'a>() -> PrattParser<'a, 'a, String> {
+        PrattParser::new(|walker, expression| {
+            alt(
+                map(take_while(|c| c.is_ascii_alphanumeric(), "an expression"), |span| span.data.to_string()),
+                |walker: &mut FileWalker<'a>| {
+                    tag("(")(walker)?;
+                    let inner = expression.parse(walker)?;
+                    expression.skip_trivia(walker)?;
+                    tag(")")(walker)?;
+                    Ok(inner)
+                }
+            )(walker)
+        })
+        .with_trivia(|walker| take_while(|c| c == ' ', "whitespace")(walker).map(|_| ()))
+        .infix(tag("+"), 1, Assoc::Left, |_, op, left, right| format!("({} {} {})", op.data, left, right))
+        .infix(tag("-"), 1, Assoc::Left, |_, op, left, right| format!("({} {} {})", op.data, left, right))
+        .infix(tag("**"), 3, Assoc::Right, |_, op, left, right| format!("({} {} {})", op.data, left, right))
+        .infix(tag("*"), 2, Assoc::Left, |_, op, left, right| format!("({} {} {})", op.data, left, right))
+        .prefix(tag("-"), 4, |_, op, operand| format!("({} {})", op.data, operand))
+        .postfix(tag("!"), 5, |_, op, operand| format!("({} {})", op.data, operand))
+    }
+
+    fn parse(data: &str) -> Result<(String, &str), ParsingError<'_>> {
+        let mut walker = FileWalker::from_data(data, "input");
+        let value = calculator().parse(&mut walker)?;
+
+        Ok((value, walker.current_string()))
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        assert_eq!(parse("1 + 2 * 3").unwrap().0, "(+ 1 (* 2 3))");
+        assert_eq!(parse("1 - 2 - 3").unwrap().0, "(- (- 1 2) 3)");
+        assert_eq!(parse("2 ** 3 ** 2").unwrap().0, "(** 2 (** 3 2))");
+        assert_eq!(parse("-a ** 2").unwrap().0, "(** (- a) 2)");
+        assert_eq!(parse("-a! * b").unwrap().0, "(* (- (! a)) b)");
+        assert_eq!(parse("(1 + 2) * -(3)").unwrap().0, "(* (+ 1 2) (- 3))");
+        assert_eq!(parse("- - 1 - -1").unwrap().0, "(- (- (- 1)) (- 1))");
+
+        // Trivia after the expression is left for whatever follows it
+        assert_eq!(parse("1 + 2 ;").unwrap(), ("(+ 1 2)".to_string(), " ;"));
+    }
+
+    #[test]
+    fn operator_spans() {
+        let spans = RefCell::new(Vec::new());
+        let record = |span: Span<'static>, _: Span, _: (), _: ()| spans.borrow_mut().push((span.location.column, span.data));
+
+        let parser = PrattParser::new(|walker, _| take_while(|c| c.is_ascii_digit(), "a number")(walker).map(|_| ()))
+            .with_trivia(|walker| take_while(|c| c == ' ', "whitespace")(walker).map(|_| ()))
+            .infix(tag("+"), 1, Assoc::Left, record)
+            .infix(tag("*"), 2, Assoc::Left, record)
+            .postfix(tag("?"), 3, |span, op, _| spans.borrow_mut().push((op.location.column, span.data)));
+
+        let mut walker = FileWalker::from_data("  1 + 2 *  3? ;", "input");
+        parser.parse(&mut walker).unwrap();
+        assert_eq!(spans.take(), [(12, "3?"), (6, "2 *  3?"), (2, "1 + 2 *  3?")]);
+    }
+
+    #[test]
+    fn expression_errors() {
+        // A missing operand fails the whole expression, moving the walker back to where it started
+        let mut walker = FileWalker::from_data("1 + (2 * )", "input");
+        assert_eq!(
+            calculator().parse(&mut walker),
+            Err(ParsingError(Location::from_components(9, 0, "input"), ErrorKind::ExpectedTag("(")))
+        );
+        assert_eq!(walker.current_string(), "1 + (2 * )");
+
+        assert_eq!(parse("(1 + 2"), Err(ParsingError(Location::from_components(6, 0, "input"), ErrorKind::ExpectedTag(")"))));
+        assert_eq!(parse("1 +").unwrap_err().0, Location::from_components(3, 0, "input"));
+
+        // An operand may require operators which bind more tightly than a precedence
+        let mut walker = FileWalker::from_data("1 * 2 + 3", "input");
+        assert_eq!(calculator().parse_above(&mut walker, 1).unwrap(), "(* 1 2)");
+        assert_eq!(walker.current_string(), " + 3");
+    }
+}
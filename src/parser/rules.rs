@@ -0,0 +1,196 @@
+use std::cell::RefCell;
+
+use crate::{FileWalker, ParsingError};
+
+/// The rules which were active at the furthest failure seen so far
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Failure {
+    filename: String,
+    line: usize,
+    column: usize,
+    rules: Vec<&'static str>
+}
+
+#[derive(Debug, Default)]
+struct TraceState {
+    active: Vec<&'static str>,
+    furthest: Option<Failure>
+}
+
+/// Tracks the stack of labelled rules being parsed, to explain which constructs were being parsed when an error occurred
+///
+/// The stack is only copied when a rule fails further into the input than any failure before it, so tracing is cheap while parsing succeeds.
+/// A trace follows a single parse, so grammars written as functions take it as an argument, and each file or thread uses its own
+#[derive(Debug, Default)]
+pub struct RuleTrace {
+    state: RefCell<TraceState>
+}
+
+/// Removes a rule from the stack when it is left, even if parsing it panicked
+struct ActiveRule<'a>(&'a RuleTrace);
+
+impl RuleTrace {
+    /// Construct a trace with no active rules
+    pub const fn new() -> Self {
+        Self { state: RefCell::new(TraceState { active: Vec::new(), furthest: None }) }
+    }
+
+    /// Forget the furthest failure, before parsing another file
+    pub fn clear(&self) {
+        self.state.borrow_mut().furthest = None;
+    }
+
+    /// Get the rules which were being parsed when an error occurred, from the outermost, or None if the error is not the furthest failure
+    pub fn rules_for(&self, error: &ParsingError) -> Option<Vec<&'static str>> {
+        match &self.state.borrow().furthest {
+            Some(failure) if failure.filename == error.0.filename && failure.line == error.0.line && failure.column == error.0.column => {
+                Some(failure.rules.clone())
+            }
+            _ => None
+        }
+    }
+
+    fn enter(&self, name: &'static str) -> ActiveRule<'_> {
+        self.state.borrow_mut().active.push(name);
+        ActiveRule(self)
+    }
+
+    fn failed(&self, error: &ParsingError) {
+        let mut state = self.state.borrow_mut();
+
+        // The innermost rule to fail sees the deepest stack, so an equally far failure only replaces it with one at least as deep,
+        // which keeps the last alternative tried at that depth as `alt` does. A failure in another file is always newer
+        let replace = match &state.furthest {
+            Some(failure) if failure.filename != error.0.filename => true,
+            Some(failure) => match (error.0.line, error.0.column).cmp(&(failure.line, failure.column)) {
+                std::cmp::Ordering::Greater => true,
+                std::cmp::Ordering::Equal => state.active.len() >= failure.rules.len(),
+                std::cmp::Ordering::Less => false
+            },
+            None => true
+        };
+
+        if replace {
+            let rules = state.active.clone();
+            state.furthest = Some(Failure { filename: error.0.filename.to_string(), line: error.0.line, column: error.0.column, rules });
+        }
+    }
+}
+
+impl Drop for ActiveRule<'_> {
+    fn drop(&mut self) {
+        self.0.state.borrow_mut().active.pop();
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Labels a rule of the grammar, so the chain of rules being parsed can be found with `RuleTrace::rules_for` if it fails
+pub fn rule<'filedata, 'a, T>(
+    trace: &'a RuleTrace,
+    name: &'static str,
+    combinator: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>> + 'a,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>> + 'a {
+    move |walker: &mut FileWalker<'filedata>| {
+        let _active = trace.enter(name);
+        let result = combinator(walker);
+        if let Err(error) = &result {
+            trace.failed(error);
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::rules::{RuleTrace, rule};
+    use crate::{ErrorDisplaySettings, ErrorLevel, ErrorRender, FileWalker, ParsingError, Span, alt, opt, tag, take_while};
+
+    fn expr<'filedata>(trace: &RuleTrace, walker: &mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
+        rule(trace, "expr", |walker: &mut FileWalker<'filedata>| {
+            let start = walker.get_marker();
+            term(trace, walker)?;
+            while opt(tag(" + "))(walker)?.is_some() {
+                term(trace, walker)?;
+            }
+            Ok(walker.span_from_marker_to_here(start).unwrap())
+        })(walker)
+    }
+
+    fn term<'filedata>(trace: &RuleTrace, walker: &mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
+        rule(trace, "term", |walker: &mut FileWalker<'filedata>| {
+            let start = walker.get_marker();
+            factor(trace, walker)?;
+            while opt(tag(" * "))(walker)?.is_some() {
+                factor(trace, walker)?;
+            }
+            Ok(walker.span_from_marker_to_here(start).unwrap())
+        })(walker)
+    }
+
+    fn factor<'filedata>(trace: &RuleTrace, walker: &mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
+        rule(trace, "factor", alt(
+            rule(trace, "literal", take_while(|c| c.is_ascii_digit(), "a number")),
+            rule(trace, "parenthesized expression", |walker: &mut FileWalker<'filedata>| {
+                let start = walker.get_marker();
+                tag("(")(walker)?;
+                expr(trace, walker)?;
+                tag(")")(walker)?;
+                Ok(walker.span_from_marker_to_here(start).unwrap())
+            })
+        ))(walker)
+    }
+
+    #[test]
+    fn rule_trace() {
+        let walker = FileWalker::from_data("1 + (2 * x)", "input");
+
+        let trace = RuleTrace::new();
+        let error = expr(&trace, &mut walker.clone()).unwrap_err();
+        let rules = trace.rules_for(&error).unwrap();
+        assert_eq!(rules, ["expr", "term", "factor", "parenthesized expression", "expr", "term", "factor", "parenthesized expression"]);
+
+        let settings = ErrorDisplaySettings::default().with_colored(false).with_verbose(true);
+        let message = error.to_string();
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, &message, &error.0, error.notes(), &walker).with_rule_chain(&rules);
+
+        crate::assert_text_eq!(render.to_string(), "\
+error: expected `(`
+   --> column 10 line 1 in input
+    = while parsing expr
+        while parsing term
+          while parsing factor
+            while parsing parenthesized expression
+              while parsing expr
+                while parsing term
+                  while parsing factor
+                    while parsing parenthesized expression
+");
+
+        // Outside of verbose mode, the chain is not shown
+        let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+        let render = ErrorRender::new(ErrorLevel::Error, &settings, &message, &error.0, error.notes(), &walker).with_rule_chain(&rules);
+        assert_eq!(render.to_string(), "error: expected `(`\n   --> column 10 line 1 in input\n");
+
+        // Successful parses do not leave rules on the stack, and errors which are not the furthest have no chain
+        assert!(expr(&trace, &mut FileWalker::from_data("(1 + 2) * 3", "input")).is_ok());
+        trace.clear();
+        let error = term(&trace, &mut FileWalker::from_data("x", "input")).unwrap_err();
+        assert_eq!(trace.rules_for(&error), Some(vec!["term", "factor", "parenthesized expression"]));
+        assert_eq!(trace.rules_for(&ParsingError(crate::Location::from_components(3, 0, "input"), error.1.clone())), None);
+        assert_eq!(trace.rules_for(&ParsingError(crate::Location::from_components(0, 0, "other"), error.1)), None);
+    }
+
+    #[test]
+    fn rule_trace_panic() {
+        let trace = RuleTrace::new();
+        let panicking = rule(&trace, "panicking", |_: &mut FileWalker| -> Result<(), ParsingError> { panic!("rule panicked") });
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| panicking(&mut FileWalker::from_data("x", "input"))));
+        assert!(result.is_err());
+
+        // The rule which panicked is not left on the stack
+        let error = term(&trace, &mut FileWalker::from_data("x", "input")).unwrap_err();
+        assert_eq!(trace.rules_for(&error), Some(vec!["term", "factor", "parenthesized expression"]));
+    }
+}
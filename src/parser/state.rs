@@ -0,0 +1,273 @@
+//! User state which rolls back with the walker when a parser backtracks, such as an indentation stack or the modes of a lexer
+//!
+//! State changed by an alternative which later fails would otherwise be left out of step with the input. A `StatefulWalker` pairs a
+//! `FileWalker` with state implementing `Snapshot`, and its markers capture the state along with the position, so `pop_back` restores
+//! both. Ordinary parsers are lifted with `stateless`, and `stateful_pair`, `stateful_alt` and `stateful_opt` combine parsers over it
+
+use crate::parser::lexer::ModeStack;
+use crate::{FileLocationMarker, FileWalker, Location, ParsingError, Span};
+
+/// State which can be saved and restored, so it can be rolled back along with the walker
+pub trait Snapshot {
+    /// The saved form of the state, which may be smaller than the state itself
+    type Saved;
+
+    /// Save the current state
+    fn snapshot(&self) -> Self::Saved;
+
+    /// Return to a saved state
+    fn restore(&mut self, saved: Self::Saved);
+}
+
+/// A `FileWalker` along with user state, which is restored with the position when the walker is moved back to a marker
+#[derive(Debug, Clone)]
+pub struct StatefulWalker<'filedata, S> {
+    pub walker: FileWalker<'filedata>,
+    pub state: S
+}
+
+/// A marker for a location within a file and the state of a `StatefulWalker` there, saved as `Snapshot::Saved`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatefulMarker<Saved> {
+    marker: FileLocationMarker,
+    saved: Saved
+}
+
+impl Snapshot for () {
+    type Saved = ();
+
+    fn snapshot(&self) {}
+
+    fn restore(&mut self, _: ()) {}
+}
+
+impl<T: Clone> Snapshot for Vec<T> {
+    type Saved = Vec<T>;
+
+    fn snapshot(&self) -> Vec<T> {
+        self.clone()
+    }
+
+    fn restore(&mut self, saved: Vec<T>) {
+        *self = saved;
+    }
+}
+
+impl<M: Clone> Snapshot for ModeStack<M> {
+    type Saved = ModeStack<M>;
+
+    fn snapshot(&self) -> ModeStack<M> {
+        self.clone()
+    }
+
+    fn restore(&mut self, saved: ModeStack<M>) {
+        *self = saved;
+    }
+}
+
+impl<A: Snapshot, B: Snapshot> Snapshot for (A, B) {
+    type Saved = (A::Saved, B::Saved);
+
+    fn snapshot(&self) -> Self::Saved {
+        (self.0.snapshot(), self.1.snapshot())
+    }
+
+    fn restore(&mut self, (a, b): Self::Saved) {
+        self.0.restore(a);
+        self.1.restore(b);
+    }
+}
+
+impl<'filedata, S: Snapshot> StatefulWalker<'filedata, S> {
+    /// Construct a walker with the state to start from
+    pub fn new(walker: FileWalker<'filedata>, state: S) -> Self {
+        Self { walker, state }
+    }
+
+    /// Get the location of the currently referenced character
+    pub fn current_location(&self) -> Location<'filedata> {
+        self.walker.current_location()
+    }
+
+    /// Get the location of the currently referenced character and the current state as a `StatefulMarker`
+    pub fn get_marker(&self) -> StatefulMarker<S::Saved> {
+        StatefulMarker { marker: self.walker.get_marker(), saved: self.state.snapshot() }
+    }
+
+    /// Return to a previous location in the file and the state there and return true, if the marker does not point to the boundary of
+    /// a character, return false and change neither
+    pub fn pop_back(&mut self, marker: StatefulMarker<S::Saved>) -> bool {
+        if !self.walker.pop_back(marker.marker) {
+            return false;
+        }
+
+        self.state.restore(marker.saved);
+        true
+    }
+
+    /// Get the span from a marker to the current location (not including it), returns None if the marker is after the current location
+    pub fn span_from_marker_to_here(&self, marker: &StatefulMarker<S::Saved>) -> Option<Span<'filedata>> {
+        self.walker.span_from_marker_to_here(marker.marker)
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Lifts a parser into a parser over a `StatefulWalker` which leaves the state as it is
+pub fn stateless<'filedata, S, T>(
+    parser: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>,
+) -> impl Fn(&mut StatefulWalker<'filedata, S>) -> Result<T, ParsingError<'filedata>> {
+    move |walker: &mut StatefulWalker<'filedata, S>| parser(&mut walker.walker)
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts the first parser then the second, moving the walker and the state back to where they were if the second fails
+pub fn stateful_pair<'filedata, S: Snapshot, A, B>(
+    first: impl Fn(&mut StatefulWalker<'filedata, S>) -> Result<A, ParsingError<'filedata>>,
+    second: impl Fn(&mut StatefulWalker<'filedata, S>) -> Result<B, ParsingError<'filedata>>,
+) -> impl Fn(&mut StatefulWalker<'filedata, S>) -> Result<(A, B), ParsingError<'filedata>> {
+    move |walker: &mut StatefulWalker<'filedata, S>| {
+        let start = walker.get_marker();
+
+        let value_a = first(walker)?;
+
+        match second(walker) {
+            Err(e) => {
+                walker.pop_back(start);
+                Err(e)
+            }
+            Ok(value_b) => Ok((value_a, value_b)),
+        }
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Tries the first parser, then the second if it fails, returning the error which got further like `alt`
+///
+/// The walker and the state are moved back to where they were before each alternative which fails, so state changed by a failed
+/// alternative is never seen by the next
+pub fn stateful_alt<'filedata, S: Snapshot, A>(
+    first: impl Fn(&mut StatefulWalker<'filedata, S>) -> Result<A, ParsingError<'filedata>>,
+    second: impl Fn(&mut StatefulWalker<'filedata, S>) -> Result<A, ParsingError<'filedata>>,
+) -> impl Fn(&mut StatefulWalker<'filedata, S>) -> Result<A, ParsingError<'filedata>> {
+    move |walker: &mut StatefulWalker<'filedata, S>| {
+        let start = walker.get_marker();
+
+        let first_error = match first(walker) {
+            Ok(value) => return Ok(value),
+            Err(e) => e
+        };
+
+        walker.pop_back(start);
+        if !first_error.is_recoverable() {
+            return Err(first_error);
+        }
+
+        let start = walker.get_marker();
+        second(walker).map_err(|second_error| {
+            walker.pop_back(start);
+            first_error.furthest(second_error)
+        })
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Accepts the parser if it can, otherwise moves the walker and the state back to where they were and returns None
+pub fn stateful_opt<'filedata, S: Snapshot, A>(
+    parser: impl Fn(&mut StatefulWalker<'filedata, S>) -> Result<A, ParsingError<'filedata>>,
+) -> impl Fn(&mut StatefulWalker<'filedata, S>) -> Result<Option<A>, ParsingError<'filedata>> {
+    move |walker: &mut StatefulWalker<'filedata, S>| {
+        let start = walker.get_marker();
+
+        match parser(walker) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) => {
+                walker.pop_back(start);
+                match e.is_recoverable() {
+                    true => Ok(None),
+                    false => Err(e)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::lexer::ModeStack;
+    use crate::parser::state::{Snapshot, StatefulWalker, stateful_alt, stateful_opt, stateful_pair, stateless};
+    use crate::{ErrorKind, FileWalker, Location, ParsingError, cut, tag, take_while};
+
+    /// Accepts a line's indentation, pushing it onto the stack of indentation levels if it is deeper than the current level
+    fn indent<'filedata>(walker: &mut StatefulWalker<'filedata, Vec<usize>>) -> Result<usize, ParsingError<'filedata>> {
+        let depth = take_while(|c| c == ' ', "indentation")(&mut walker.walker)?.data.len();
+        if walker.state.last().is_none_or(|&level| depth > level) {
+            walker.state.push(depth);
+        }
+
+        Ok(depth)
+    }
+
+    /// Accepts the start of an `if` block, leaving its level pushed and the indentation consumed when the keyword is missing
+    fn block<'filedata>(walker: &mut StatefulWalker<'filedata, Vec<usize>>) -> Result<usize, ParsingError<'filedata>> {
+        let depth = indent(walker)?;
+        tag("if")(&mut walker.walker)?;
+
+        Ok(depth)
+    }
+
+    #[test]
+    fn state_rolls_back_through_alt() {
+        // The first alternative pushes a level before failing, which is undone before the second is tried
+        let statement = stateful_pair(stateless(tag("    ")), stateless(tag("x")));
+        let line = stateful_alt(block, |walker: &mut StatefulWalker<'_, Vec<usize>>| statement(walker).map(|_| 0));
+
+        let mut walker = StatefulWalker::new(FileWalker::from_data("    x", "input"), vec![0]);
+        assert_eq!(line(&mut walker), Ok(0));
+        assert_eq!(walker.state, [0]);
+
+        let mut walker = StatefulWalker::new(FileWalker::from_data("    if", "input"), vec![0]);
+        assert_eq!(line(&mut walker), Ok(4));
+        assert_eq!(walker.state, [0, 4]);
+
+        // When every alternative fails, both the walker and the state are where they started
+        let mut walker = StatefulWalker::new(FileWalker::from_data("    y", "input"), vec![0]);
+        assert_eq!(line(&mut walker), Err(ParsingError(Location::from_components(4, 0, "input"), ErrorKind::ExpectedTag("x"))));
+        assert_eq!(walker.state, [0]);
+        assert_eq!(walker.walker.current_string(), "    y");
+
+        assert_eq!(stateful_opt(block)(&mut walker), Ok(None));
+        assert_eq!(walker.state, [0]);
+    }
+
+    #[test]
+    fn markers_capture_state() {
+        let mut modes = ModeStack::new("code");
+        let saved = modes.snapshot();
+        modes.push("string");
+        modes.restore(saved);
+        assert_eq!(modes.depth(), 0);
+
+        // Moving back to a marker restores the modes with the position, and the state of each half of a pair separately
+        let mut walker = StatefulWalker::new(FileWalker::from_data("\"text\"", "input"), (ModeStack::new("code"), vec![1]));
+        let start = walker.get_marker();
+        tag("\"")(&mut walker.walker).unwrap();
+        walker.state.0.push("string");
+        walker.state.1.push(2);
+        assert_eq!(walker.span_from_marker_to_here(&start).unwrap().data, "\"");
+
+        assert!(walker.pop_back(start));
+        assert_eq!((walker.state.0.depth(), walker.state.1.as_slice()), (0, [1].as_slice()));
+        assert_eq!(walker.current_location(), Location::from_components(0, 0, "input"));
+
+        // Committed errors are reported without trying the next alternative, after the state is restored
+        let mut walker = StatefulWalker::new(FileWalker::from_data("    y", "input"), vec![0]);
+        let committed = stateful_pair(indent, stateless(cut(tag("if"))));
+        let error = stateful_alt(committed, stateful_pair(indent, stateless(tag("y"))))(&mut walker).unwrap_err();
+        assert!(!error.is_recoverable());
+        assert_eq!(walker.state, [0]);
+    }
+}
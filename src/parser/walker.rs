@@ -1,7 +1,9 @@
-use crate::Span;
-use super::Location;
+use crate::{ParsingError, Pos, Span, Spanned, SyntheticSpan};
+use super::{Location, location::advance_position};
 
 /// Walks through a file, producing characters one at a time
+/// 
+/// Walkers only reference the file data, so cloning one is O(1), which makes them cheap to fork for speculative parsing
 #[derive(Debug, Clone)]
 pub struct FileWalker<'filedata> {
     all_data: &'filedata str,
@@ -11,8 +13,22 @@ pub struct FileWalker<'filedata> {
     line: usize
 }
 
+/// An iterator over the lines of a walker's remaining input as spans, see `FileWalker::lines_spanned`
+#[derive(Debug, Clone)]
+pub struct SpannedLines<'filedata> {
+    lines: std::str::Lines<'filedata>,
+    location: Location<'filedata>
+}
+
+/// An iterator over the non-overlapping matches of a parser in a walker's remaining input, see `FileWalker::find_iter`
+#[derive(Debug, Clone)]
+pub struct FindIter<'filedata, P> {
+    walker: FileWalker<'filedata>,
+    parser: P
+}
+
 /// A marker for a location within a file
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct FileLocationMarker {
     index: usize,
     column: usize,
@@ -47,6 +63,26 @@ impl<'filedata> FileWalker<'filedata> {
         Location::from_components(self.column, self.line, self.filename)
     }
 
+    /// Get a synthetic span for text inserted at the current location, which does not appear in the file
+    pub fn synthesize(&self, data: &'filedata str) -> SyntheticSpan<'filedata> {
+        SyntheticSpan::from_components(self.current_location(), data)
+    }
+
+    /// Get the name of the file being walked
+    pub fn filename(&self) -> &'filedata str {
+        self.filename
+    }
+
+    /// Get all of the data being walked, regardless of the current position
+    pub fn all_data(&self) -> &'filedata str {
+        self.all_data
+    }
+
+    /// Get the compact position of the currently referenced character, panics if the file is too large to be addressed by a `Pos`
+    pub fn current_pos(&self) -> Pos {
+        Pos(u32::try_from(self.current_byte_index).expect("file is too large for compact positions"))
+    }
+
     /// Get the location of the currently referenced character as a `FileLocationMaker`
     pub fn get_marker(&self) -> FileLocationMarker {
         FileLocationMarker {
@@ -73,13 +109,7 @@ impl<'filedata> FileWalker<'filedata> {
 
         if let Some(c) = character {
             self.current_byte_index += c.len_utf8();
-            if c == '\n' {
-                self.line += 1;
-                self.column = 0;
-            }
-            else {
-                self.column += 1;
-            }
+            (self.line, self.column) = advance_position(self.line, self.column, c);
         }
 
         character
@@ -98,6 +128,21 @@ impl<'filedata> FileWalker<'filedata> {
         }
     }
 
+    /// Construct an independent copy of this walker at the same position, for parsing speculatively without disturbing this one
+    pub fn fork(&self) -> Self {
+        self.clone()
+    }
+
+    /// Move this walker to the position reached by a fork of it and return true, if the fork is walking different data, return false and do not move
+    pub fn join(&mut self, fork: &FileWalker<'filedata>) -> bool {
+        if std::ptr::eq(self.all_data, fork.all_data) {
+            self.pop_back(fork.get_marker())
+        }
+        else {
+            false
+        }
+    }
+
     /// Get the span representing a portion of the file from a given marker to the cursor (not including the character the cursor is pointing at), returns none if the marker does not point to a valid unicode boundary, or if the marker is after the current location.
     pub fn span_from_marker_to_here(&self, marker: FileLocationMarker) -> Option<Span<'filedata>> {
         if marker.index == self.current_byte_index {
@@ -108,7 +153,7 @@ impl<'filedata> FileWalker<'filedata> {
         }
         else {
             let location = Location::from_components(marker.column, marker.line, self.filename);
-            let data = Some(&self.all_data[marker.index..self.current_byte_index]).expect("The unicode assumption was violated");
+            let data = &self.all_data[marker.index..self.current_byte_index];
 
             Some(Span::from_components(location, data))
         }
@@ -124,8 +169,48 @@ impl<'filedata> FileWalker<'filedata> {
         }
     }
 
+    /// Check if a span refers to the data of the file being walked, and is located in it
+    pub fn contains_span(&self, span: &Span) -> bool {
+        let start = self.all_data.as_ptr() as usize;
+        let span_start = span.data.as_ptr() as usize;
+
+        span.location.filename == self.filename && span_start >= start && span_start + span.data.len() <= start + self.all_data.len()
+    }
+
+    /// Get an empty span of the file's data at a location, or None if the location is not within the file
+    pub fn empty_span_at(&self, location: &Location) -> Option<Span<'filedata>> {
+        let line_start = if location.line == 0 { 0 } else {
+            self.all_data.match_indices('\n').nth(location.line - 1)?.0 + 1
+        };
+        let line = self.all_data[line_start..].split('\n').next().unwrap_or_default();
+
+        // The column just after the end of the line is still a valid location, for example when inserting at the end of the line
+        let column_index = line.char_indices().map(|(index, _)| index).chain(std::iter::once(line.len())).nth(location.column)?;
+        let index = line_start + column_index;
+
+        Some(Span::from_components(Location::from_components(location.column, location.line, self.filename), &self.all_data[index..index]))
+    }
+
+    /// Iterate over the lines of the input from the current location as spans, excluding the `\n` or `\r\n` ending each
+    ///
+    /// A walker which has not been stepped yields every line of its data
+    pub fn lines_spanned(&self) -> SpannedLines<'filedata> {
+        SpannedLines {
+            lines: self.current_string().lines(),
+            location: self.current_location()
+        }
+    }
+
+    /// Iterate over the matches of a parser in the input from the current location, without moving the walker
+    ///
+    /// The parser is tried at each character in turn, and the search continues after the end of each match, so matches do not
+    /// overlap. Matches which consume nothing are skipped, since they would be found at every character
+    pub fn find_iter<T, P: Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>>(&self, parser: P) -> FindIter<'filedata, P> {
+        FindIter { walker: self.clone(), parser }
+    }
+
     /// Get a span a certain number of lines (potentially) away from the line the span given is on
-    pub fn expand_span(&self, span: &Span, lines_away: usize) -> Span {
+    pub fn expand_span(&self, span: &Span, lines_away: usize) -> Span<'filedata> {
         // Get the index of the span within the file
         assert!(span.data.as_ptr() as usize >= self.all_data.as_ptr() as usize);
         let span_byte_index = span.data.as_ptr() as usize - self.all_data.as_ptr() as usize;
@@ -142,10 +227,10 @@ impl<'filedata> FileWalker<'filedata> {
             let mut lines_remaining = span.location.line - start_line_number + 1;
             let mut current_index = span_byte_index;
 
+            // Newlines are a single byte which cannot appear within another character, so the bytes can be searched directly
             while current_index > 0 {
                 current_index -= 1;
-                while current_index > 0 && !self.all_data.is_char_boundary(current_index) {}
-                if self.all_data[current_index.. current_index + 2].starts_with('\n') {
+                if self.all_data.as_bytes()[current_index] == b'\n' {
                     lines_remaining -= 1;
                     if lines_remaining == 0 {
                         current_index += 1;
@@ -175,6 +260,45 @@ impl<'filedata> FileWalker<'filedata> {
     }
 }
 
+impl<'filedata> std::iter::Iterator for SpannedLines<'filedata> {
+    type Item = Span<'filedata>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.lines.next()?;
+        let span = Span::from_components(self.location, line);
+        self.location = Location::from_components(0, self.location.line + 1, self.location.filename);
+
+        Some(span)
+    }
+}
+
+impl<'filedata, P> FindIter<'filedata, P> {
+    /// Get a walker at the position the search has reached, which is the end of the input once every match has been found
+    pub fn walker(&self) -> &FileWalker<'filedata> {
+        &self.walker
+    }
+}
+
+impl<'filedata, T, P: Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>>> std::iter::Iterator for FindIter<'filedata, P> {
+    type Item = Spanned<'filedata, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let start = self.walker.get_marker();
+
+            if let Ok(value) = (self.parser)(&mut self.walker) {
+                let span = self.walker.span_from_marker_to_here(start).unwrap();
+                if !span.data.is_empty() {
+                    return Some(Spanned::new(span, value));
+                }
+            }
+
+            self.walker.pop_back(start);
+            self.walker.step()?;
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::{FileWalker, Location, Span};
@@ -194,6 +318,35 @@ mod test {
         assert_eq!(walker.step(), None);
     }
 
+    #[test]
+    pub fn empty_span_at() {
+        let data = "Mö\nbius";
+        let walker = FileWalker::from_data(data, "hello.txt");
+
+        let span = walker.empty_span_at(&Location::from_components(2, 0, "hello.txt")).unwrap();
+        assert_eq!(span.data, "");
+        assert_eq!(span.data.as_ptr(), data[3..].as_ptr());
+
+        let span = walker.empty_span_at(&Location::from_components(1, 1, "hello.txt")).unwrap();
+        assert_eq!(span.data.as_ptr(), data[5..].as_ptr());
+
+        assert_eq!(walker.empty_span_at(&Location::from_components(3, 0, "hello.txt")), None);
+        assert_eq!(walker.empty_span_at(&Location::from_components(0, 2, "hello.txt")), None);
+    }
+
+    #[test]
+    pub fn contains_span() {
+        let data = "Möbius";
+        let walker = FileWalker::from_data(data, "hello.txt");
+
+        assert!(walker.contains_span(&Span::from_components(Location::from_components(1, 0, "hello.txt"), &data[1..3])));
+        assert!(walker.contains_span(&Span::from_components(Location::from_components(6, 0, "hello.txt"), &data[7..])));
+        assert!(!walker.contains_span(&Span::from_components(Location::from_components(0, 0, "hello.txt"), "Mobius")));
+
+        // A span of the same data located in another file, such as a copy of the buffer, is not in this file
+        assert!(!walker.contains_span(&Span::from_components(Location::from_components(1, 0, "other.txt"), &data[1..3])));
+    }
+
     #[test]
     pub fn simple_walk_current_str() {
         let data = "Möbius";
@@ -530,15 +683,36 @@ mod test {
         assert_eq!(walker.span_from_marker_to_here(later), Some(Span::from_components(Location::from_components(0, 1, "hello.txt"), "bi\r")));
     }
 
+    #[test]
+    pub fn fork_and_join() {
+        let mut walker = FileWalker::from_data("Möbius", "hello.txt");
+        walker.step();
+
+        let mut fork = walker.fork();
+        assert_eq!(fork.get_marker(), walker.get_marker());
+
+        fork.step();
+        fork.step();
+        assert_ne!(fork.get_marker(), walker.get_marker());
+        assert_eq!(walker.current_string(), "öbius");
+
+        // Joining moves the original walker to where the fork got to
+        assert!(walker.join(&fork));
+        assert_eq!(walker.get_marker(), fork.get_marker());
+        assert_eq!(walker.current_string(), "ius");
+        assert_eq!(walker.current_location(), Location::from_components(3, 0, "hello.txt"));
+
+        // But a walker over other data cannot be joined
+        let other = FileWalker::from_data("Mobius", "other.txt");
+        assert!(!walker.join(&other));
+        assert_eq!(walker.current_string(), "ius");
+    }
+
     #[test]
     pub fn simple_expand_span() {
         let mut walker = FileWalker::from_data("abc\ndef\nghi\njkl\nmno\npqr\nstu\nvwx\nyz0", "input");
 
-        let mut line_spans = Vec::new();
-
-        for (i, line) in walker.all_data.lines().enumerate() {
-            line_spans.push(Span::from_components(Location::from_components(0, i, "input"), line));
-        }
+        let line_spans: Vec<_> = walker.lines_spanned().collect();
 
         loop {
             let marker = walker.get_marker();
@@ -559,4 +733,24 @@ mod test {
             
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn lines_spanned() {
+        let data = "ab\r\n\ncdé\nf";
+        let mut walker = FileWalker::from_data(data, "input");
+
+        assert_eq!(walker.lines_spanned().collect::<Vec<_>>(), vec![
+            Span::from_components(Location::from_components(0, 0, "input"), &data[0..2]),
+            Span::from_components(Location::from_components(0, 1, "input"), &data[4..4]),
+            Span::from_components(Location::from_components(0, 2, "input"), &data[5..9]),
+            Span::from_components(Location::from_components(0, 3, "input"), &data[10..11]),
+        ]);
+
+        // Lines start from the current location
+        walker.step();
+        assert_eq!(walker.lines_spanned().next(), Some(Span::from_components(Location::from_components(1, 0, "input"), "b")));
+
+        // A trailing newline does not start another line
+        assert_eq!(FileWalker::from_data("a\n", "input").lines_spanned().count(), 1);
+    }
+}
@@ -0,0 +1,18 @@
+//! The commonly used items of the crate, for importing with `use compiler_utils::prelude::*`
+//!
+//! Items are only added to the prelude once their names are settled, so importing it will not break when new subsystems are added
+
+pub use crate::errors::{Diagnostic, DiagnosticBatch, ErrorDisplaySettings, ErrorLevel, ErrorRender, Note};
+
+pub use crate::parser::{
+    CharClass, ErrorKind, FileLocationMarker, FileWalker, IdentSpec, KeywordSet, Location, NumberFormat, ParsingError, Span, Spanned,
+    SyntheticSpan
+};
+
+pub use crate::parser::{
+    accepts, accepts_while, alt, alt_checked, but_not, choice, cut, delimited, expected, find_all, identifier, identifier_excluding,
+    identifier_with, keyword, lookahead_within, many0, many1, many_till, map, match_tags, not, number, one_of, opt, pair, peek,
+    preceded, symbol, tag, take_at_least, take_class, take_if, take_until_balanced, take_while, terminated, triple, word
+};
+
+pub use crate::driver::{RenderedDiagnostics, parse_file, parse_str};
@@ -0,0 +1,126 @@
+use std::sync::Mutex;
+
+use crate::{FileWalker, ParsingError};
+
+/// How often a labelled rule was tried and how often it succeeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuleCoverage {
+    pub name: &'static str,
+    pub attempts: usize,
+    pub successes: usize
+}
+
+/// Records which labelled rules of a grammar succeeded, to find rules that are never exercised by its tests
+///
+/// A `Coverage` can be constructed in a `static`, so grammars written as functions can share one across a test run
+#[derive(Debug, Default)]
+pub struct Coverage {
+    rules: Mutex<Vec<RuleCoverage>>
+}
+
+impl Coverage {
+    /// Construct a tracker with no rules
+    pub const fn new() -> Self {
+        Self { rules: Mutex::new(Vec::new()) }
+    }
+
+    fn update(&self, name: &'static str, f: impl FnOnce(&mut RuleCoverage)) {
+        let mut rules = self.rules.lock().unwrap_or_else(|e| e.into_inner());
+
+        match rules.iter_mut().find(|rule| rule.name == name) {
+            Some(rule) => f(rule),
+            None => {
+                let mut rule = RuleCoverage { name, attempts: 0, successes: 0 };
+                f(&mut rule);
+                rules.push(rule);
+            }
+        }
+    }
+
+    /// Add a rule to the report without recording an attempt, so it is reported even if it is never tried
+    pub fn declare(&self, name: &'static str) {
+        self.update(name, |_| {});
+    }
+
+    /// Record an attempt to apply a rule
+    pub fn record(&self, name: &'static str, success: bool) {
+        self.update(name, |rule| {
+            rule.attempts += 1;
+            rule.successes += success as usize;
+        });
+    }
+
+    /// Get the coverage of every rule, in the order they were first seen
+    pub fn rules(&self) -> Vec<RuleCoverage> {
+        self.rules.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Get the names of the rules which never succeeded
+    pub fn unexercised(&self) -> Vec<&'static str> {
+        self.rules().iter().filter(|rule| rule.successes == 0).map(|rule| rule.name).collect()
+    }
+
+    /// Describe how many rules were exercised, listing those which never succeeded
+    pub fn report(&self) -> String {
+        let rules = self.rules();
+        let exercised = rules.iter().filter(|rule| rule.successes > 0).count();
+
+        let mut report = format!("{} of {} rules exercised\n", exercised, rules.len());
+        for rule in rules.iter().filter(|rule| rule.successes == 0) {
+            match rule.attempts {
+                0 => report.push_str(&format!("  {}: never attempted\n", rule.name)),
+                1 => report.push_str(&format!("  {}: failed the only attempt\n", rule.name)),
+                attempts => report.push_str(&format!("  {}: failed all {} attempts\n", rule.name, attempts)),
+            }
+        }
+
+        report
+    }
+}
+
+#[inline]
+#[must_use = "parsers do nothing unless applied to a walker"]
+/// Labels a rule of a grammar, recording whether each application of it succeeds
+pub fn covered<'filedata, 'a, T>(
+    coverage: &'a Coverage,
+    name: &'static str,
+    combinator: impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>> + 'a,
+) -> impl Fn(&mut FileWalker<'filedata>) -> Result<T, ParsingError<'filedata>> + 'a {
+    coverage.declare(name);
+
+    move |walker: &mut FileWalker<'filedata>| {
+        let result = combinator(walker);
+        coverage.record(name, result.is_ok());
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FileWalker, alt, tag};
+    use crate::testing::{Coverage, RuleCoverage, covered};
+
+    static COVERAGE: Coverage = Coverage::new();
+
+    #[test]
+    fn coverage_report() {
+        COVERAGE.declare("float");
+
+        let literal = alt(covered(&COVERAGE, "true", tag("true")), covered(&COVERAGE, "false", tag("false")));
+        literal(&mut FileWalker::from_data("true", "input")).unwrap();
+        literal(&mut FileWalker::from_data("true", "input")).unwrap();
+        literal(&mut FileWalker::from_data("maybe", "input")).unwrap_err();
+
+        assert_eq!(COVERAGE.rules(), vec![
+            RuleCoverage { name: "float", attempts: 0, successes: 0 },
+            RuleCoverage { name: "true", attempts: 3, successes: 2 },
+            RuleCoverage { name: "false", attempts: 1, successes: 0 },
+        ]);
+        assert_eq!(COVERAGE.unexercised(), vec!["float", "false"]);
+        assert_eq!(COVERAGE.report(), "\
+1 of 3 rules exercised
+  float: never attempted
+  false: failed the only attempt
+");
+    }
+}
@@ -0,0 +1,148 @@
+/// A change to a single line, as found by `line_edits`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEdit<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str)
+}
+
+/// Get the shortest list of line edits which turn the expected text into the found text
+///
+/// Where lines are replaced, the removed lines come before the added ones, as in other diffs
+pub fn line_edits<'a>(expected: &'a str, found: &'a str) -> Vec<LineEdit<'a>> {
+    let expected: Vec<_> = expected.split('\n').collect();
+    let found: Vec<_> = found.split('\n').collect();
+
+    // The length of the longest common subsequence of the remaining lines, from each pair of positions
+    let mut lengths = vec![vec![0usize; found.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..found.len()).rev() {
+            lengths[i][j] = if expected[i] == found[j] {
+                lengths[i + 1][j + 1] + 1
+            }
+            else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < found.len() {
+        if i < expected.len() && j < found.len() && expected[i] == found[j] {
+            edits.push(LineEdit::Same(expected[i]));
+            i += 1;
+            j += 1;
+        }
+        else if j < found.len() && (i == expected.len() || lengths[i][j + 1] > lengths[i + 1][j]) {
+            edits.push(LineEdit::Added(found[j]));
+            j += 1;
+        }
+        else {
+            edits.push(LineEdit::Removed(expected[i]));
+            i += 1;
+        }
+    }
+
+    edits
+}
+
+/// Render the differences between two texts as a unified diff, with a number of lines of context around each change
+pub fn unified_diff(expected: &str, found: &str, context: usize) -> String {
+    let edits = line_edits(expected, found);
+    let changes: Vec<_> = edits.iter().enumerate()
+        .filter(|(_, edit)| !matches!(edit, LineEdit::Same(_)))
+        .map(|(index, _)| index)
+        .collect();
+
+    let mut output = String::from("--- expected\n+++ found\n");
+
+    let mut start = 0;
+    while start < changes.len() {
+        // Changes close enough for their context to touch share a hunk
+        let mut end = start;
+        while end + 1 < changes.len() && changes[end + 1] - changes[end] <= 2 * context + 1 {
+            end += 1;
+        }
+
+        let first = changes[start].saturating_sub(context);
+        let last = (changes[end] + context + 1).min(edits.len());
+
+        let expected_line = edits[..first].iter().filter(|edit| !matches!(edit, LineEdit::Added(_))).count();
+        let found_line = edits[..first].iter().filter(|edit| !matches!(edit, LineEdit::Removed(_))).count();
+        let expected_count = edits[first..last].iter().filter(|edit| !matches!(edit, LineEdit::Added(_))).count();
+        let found_count = edits[first..last].iter().filter(|edit| !matches!(edit, LineEdit::Removed(_))).count();
+
+        output.push_str(&format!("@@ -{},{} +{},{} @@\n", expected_line + 1, expected_count, found_line + 1, found_count));
+        for edit in &edits[first..last] {
+            match edit {
+                LineEdit::Same(line) => output.push_str(&format!(" {}\n", line)),
+                LineEdit::Removed(line) => output.push_str(&format!("-{}\n", line)),
+                LineEdit::Added(line) => output.push_str(&format!("+{}\n", line)),
+            }
+        }
+
+        start = end + 1;
+    }
+
+    output
+}
+
+/// Assert that a text is equal to the expected text, showing a line based diff of them if it is not
+#[macro_export]
+macro_rules! assert_text_eq {
+    ($found:expr, $expected:expr) => {{
+        let found: &str = &$found;
+        let expected: &str = &$expected;
+
+        if expected != found {
+            panic!("text does not match the expected text\n{}", $crate::testing::unified_diff(expected, found, 3));
+        }
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::{LineEdit, line_edits, unified_diff};
+
+    #[test]
+    fn line_edits_minimal() {
+        assert_eq!(line_edits("a\nb\nc", "a\nx\nc"), vec![
+            LineEdit::Same("a"),
+            LineEdit::Removed("b"),
+            LineEdit::Added("x"),
+            LineEdit::Same("c"),
+        ]);
+
+        assert_eq!(line_edits("a\nb", "a\nb"), vec![LineEdit::Same("a"), LineEdit::Same("b")]);
+    }
+
+    #[test]
+    fn unified_diff_hunks() {
+        let expected = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10";
+        let found = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11";
+
+        assert_eq!(unified_diff(expected, found, 1), "\
+--- expected
++++ found
+@@ -2,3 +2,3 @@
+ 2
+-3
++three
+ 4
+@@ -10,1 +10,2 @@
+ 10
++11
+");
+
+        // With more context, the changes share a hunk
+        assert!(unified_diff(expected, found, 4).matches("@@ -").count() == 1);
+        assert_eq!(unified_diff(expected, expected, 3), "--- expected\n+++ found\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "text does not match the expected text\n--- expected\n+++ found\n@@ -1,2 +1,2 @@\n first\n-second\n+Second\n")]
+    fn assert_text_eq_failure() {
+        assert_text_eq!(String::from("first\nSecond"), "first\nsecond");
+    }
+}
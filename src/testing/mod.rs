@@ -0,0 +1,124 @@
+pub mod diff;
+pub mod coverage;
+
+pub use diff::*;
+pub use coverage::*;
+
+use crate::{Diagnostic, ErrorDisplaySettings, FileWalker, ParsingError};
+
+/// Render a `ParsingError` as an uncolored diagnostic, for use in test failure messages
+pub fn render_parsing_error(error: &ParsingError, walker: &FileWalker) -> String {
+    let settings = ErrorDisplaySettings { colored: false, ..Default::default() };
+
+    Diagnostic::from_parsing_error(error).render(&settings, walker).to_string()
+}
+
+/// Assert that a parser accepts the whole of an input, returning the parsed value
+///
+/// On failure, the panic message includes the rendered diagnostic. If an expected value is given, the value must be equal to it,
+/// and a diff of their `Debug` output is shown if it is not
+#[macro_export]
+macro_rules! assert_parses {
+    ($parser:expr, $input:expr) => {{
+        let mut walker = $crate::FileWalker::from_data($input, "input");
+
+        match ($parser)(&mut walker) {
+            Ok(value) => {
+                if !walker.current_string().is_empty() {
+                    let error = $crate::ParsingError(walker.current_location(), $crate::ErrorKind::ExpectedEndOfInput(walker.current_string()));
+                    panic!("parser did not consume the whole input\n{}", $crate::testing::render_parsing_error(&error, &walker));
+                }
+
+                value
+            }
+            Err(error) => panic!("parser failed\n{}", $crate::testing::render_parsing_error(&error, &walker)),
+        }
+    }};
+    ($parser:expr, $input:expr, $expected:expr) => {{
+        let value = $crate::assert_parses!($parser, $input);
+        let expected = $expected;
+
+        if value != expected {
+            panic!("parser produced an unexpected value\n{}", $crate::testing::unified_diff(&format!("{:#?}", expected), &format!("{:#?}", value), 3));
+        }
+
+        value
+    }};
+}
+
+/// Assert that a parser rejects an input with the given `ErrorKind`, returning the error
+///
+/// If the parser fails with a different error, the panic message includes a diff of the kinds and the rendered diagnostic
+#[macro_export]
+macro_rules! assert_parse_fails {
+    ($parser:expr, $input:expr, $expected_kind:expr) => {{
+        let mut walker = $crate::FileWalker::from_data($input, "input");
+        let expected_kind = $expected_kind;
+
+        match ($parser)(&mut walker) {
+            Ok(_) => panic!("parser unexpectedly accepted the input, stopping at {:?}", $crate::Span::from_components(walker.current_location(), walker.current_string())),
+            Err(error) => {
+                if error.1 != expected_kind {
+                    let diff = $crate::testing::unified_diff(&format!("{:#?}", expected_kind), &format!("{:#?}", error.1), 3);
+                    panic!("parser failed with an unexpected error\n{}{}", diff, $crate::testing::render_parsing_error(&error, &walker));
+                }
+
+                error
+            }
+        }
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ErrorKind, map, one_of, pair, tag};
+
+    #[test]
+    fn assert_parses_ok() {
+        let (a, b) = assert_parses!(pair(tag("Hello"), one_of("!?")), "Hello?");
+        assert_eq!(a.data, "Hello");
+        assert_eq!(b.data, "?");
+    }
+
+    #[test]
+    #[should_panic(expected = "parser failed\nerror: expected `World`")]
+    fn assert_parses_failure() {
+        assert_parses!(pair(tag("Hello"), tag("World")), "Hello!");
+    }
+
+    #[test]
+    #[should_panic(expected = "parser did not consume the whole input\nerror: expected the end of the input, found `!`")]
+    fn assert_parses_trailing() {
+        assert_parses!(tag("Hello"), "Hello!");
+    }
+
+    #[test]
+    fn assert_parse_fails_ok() {
+        let error = assert_parse_fails!(tag("Hello"), "Help", ErrorKind::TagDiverged { tag: "Hello", matched: crate::Span::from_components(crate::Location::from_components(0, 0, "input"), "Hel"), found: "p" });
+        assert_eq!(error.0.column, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "parser failed with an unexpected error\n--- expected\n+++ found\n@@ -1,3 +1,3 @@\n-ExpectedOneOf(\n-    \"!?\",\n+ExpectedTag(\n+    \"Hello\",\n )\nerror: expected `Hello`")]
+    fn assert_parse_fails_wrong_kind() {
+        assert_parse_fails!(tag("Hello"), "World", ErrorKind::ExpectedOneOf("!?"));
+    }
+
+    #[test]
+    fn assert_parses_value() {
+        let span = assert_parses!(tag("Hello"), "Hello", crate::Span::from_components(crate::Location::from_components(0, 0, "input"), "Hello"));
+        assert_eq!(span.data, "Hello");
+    }
+
+    #[test]
+    #[should_panic(expected = "parser produced an unexpected value\n--- expected\n+++ found\n@@ -1,1 +1,1 @@\n-4\n+5\n")]
+    fn assert_parses_wrong_value() {
+        assert_parses!(map(tag("Hello"), |span| span.data.len()), "Hello", 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "parser unexpectedly accepted the input, stopping at input:1:6 \"\"")]
+    fn assert_parse_fails_accepted() {
+        assert_parse_fails!(tag("Hello"), "Hello", ErrorKind::ExpectedTag("Hello"));
+    }
+}
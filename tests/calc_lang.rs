@@ -0,0 +1,114 @@
+//! Runs the pipeline of the `calc_lang` example, checking that the lexer, parser, interpreter and diagnostics compose
+
+#[path = "../examples/calc_lang/lang.rs"]
+mod lang;
+
+use compiler_utils::driver::emit::{DiagnosticEmitter, RenderTarget};
+use compiler_utils::prelude::*;
+
+/// Render the diagnostics of a run without colors
+fn render(run: &lang::Run, walker: &FileWalker) -> String {
+    let mut emitter = DiagnosticEmitter::new(RenderTarget::Plain, Vec::new());
+    for diagnostic in &run.diagnostics {
+        emitter.emit(diagnostic, walker).unwrap();
+    }
+
+    String::from_utf8(emitter.into_inner()).unwrap()
+}
+
+#[test]
+fn run_program() {
+    let walker = FileWalker::from_data("let x = 7; # seven\nlet y = x * -(2 + 1);\nprint y - x / 2;\nprint 10 - 4 - 3;", "input.calc");
+    let run = lang::run(&walker);
+
+    assert_eq!(run.output, [-24, 3]);
+    assert!(run.diagnostics.is_empty());
+
+    let mut tokens = walker.clone();
+    assert_eq!(lang::lex(&mut tokens).unwrap().tokens()[..3].iter().map(|token| token.value).collect::<Vec<_>>(), [
+        lang::Token::Let, lang::Token::Name, lang::Token::Equals
+    ]);
+}
+
+#[test]
+fn recover_from_syntax_errors() {
+    let walker = FileWalker::from_data("let x = ;\nprint 1 +;\nprint 2\nprint 3;\nlet y = 4;\nprint y;", "input.calc");
+    let run = lang::run(&walker);
+
+    // Each error skips to the next `;`, which for a missing `;` is the end of the statement after it
+    assert_eq!(run.output, [4]);
+    compiler_utils::assert_text_eq!(render(&run, &walker), "\
+error: expected an expression, found `;`
+   --> column 9 line 1 in input.calc
+  1 |let x = ;
+    |        ^ found this token
+  2 |print 1 +;
+error: expected an expression, found `;`
+   --> column 10 line 2 in input.calc
+  1 |let x = ;
+  2 |print 1 +;
+    |         ^ found this token
+  3 |print 2
+error: expected `;`
+   --> column 8 line 3 in input.calc
+  2 |print 1 +;
+  3 |print 2
+    |       ^
+  4 |print 3;
+");
+}
+
+#[test]
+fn report_errors_while_running() {
+    let walker = FileWalker::from_data("let a = b + 1;\nlet big = 9223372036854775807 + 1;\nprint big;\nprint 1 / (a - a);\nlet c = 2;", "input.calc");
+    let run = lang::run(&walker);
+
+    // Names whose value failed are not reported again where they are used
+    assert!(run.output.is_empty());
+    assert_eq!(run.diagnostics.iter().map(|diagnostic| diagnostic.code.unwrap()).collect::<Vec<_>>(), ["E0001", "E0003", "W0001"]);
+    compiler_utils::assert_text_eq!(render(&run, &walker), "\
+error[E0001]: unknown name `b`
+   --> column 9 line 1 in input.calc
+  1 |let a = b + 1;
+    |        ^ not declared
+  2 |let big = 9223372036854775807 + 1;
+error[E0003]: arithmetic overflow
+   --> column 11 line 2 in input.calc
+  1 |let a = b + 1;
+  2 |let big = 9223372036854775807 + 1;
+    |          ^^^^^^^^^^^^^^^^^^^^^^^ does not fit in 64 bits
+  3 |print big;
+warning[W0001]: `c` is never used
+   --> column 5 line 5 in input.calc
+  4 |print 1 / (a - a);
+  5 |let c = 2;
+    |    ^ declared here
+");
+    assert!(lang::CODES.explain("W0001").unwrap().starts_with("W0001 (unused-name, warning by default)"));
+}
+
+#[test]
+fn pretty_print() {
+    let walker = FileWalker::from_data("let x=((1+2))*3-(4-5) ;print -(x)/(2*x)  ;print 1-(2+3);", "input.calc");
+    let stream = lang::lex(&mut walker.clone()).unwrap();
+    let (statements, errors) = lang::parse(&stream);
+    assert!(errors.is_empty());
+
+    // Only the parentheses which change the meaning are kept
+    let pretty = lang::pretty(&statements.unwrap());
+    assert_eq!(pretty, "let x = (1 + 2) * 3 - (4 - 5);\nprint -x / (2 * x);\nprint 1 - (2 + 3);\n");
+
+    let reparsed = FileWalker::from_data(&pretty, "pretty.calc");
+    assert_eq!(lang::pretty(&lang::run(&reparsed).statements), pretty);
+}
+
+#[test]
+fn lexer_errors() {
+    let walker = FileWalker::from_data("let x = 1 $ 2;", "input.calc");
+    let run = lang::run(&walker);
+
+    assert!(run.statements.is_empty());
+    assert_eq!(run.diagnostics.len(), 1);
+    assert_eq!(run.diagnostics[0].message, "expected a token");
+    assert_eq!(run.diagnostics[0].location, Location::from_components(10, 0, "input.calc"));
+}
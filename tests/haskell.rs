@@ -1,4 +1,5 @@
 use compiler_utils::*;
+use compiler_utils::parser::{choice, take_at_least};
 
 fn asc_symbol<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
     one_of("!#$%&*+./<=>?@\\^|=~:")(walker)
@@ -65,13 +66,7 @@ fn symbol<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Span<'filedat
 }
 
 fn graphic<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
-    alt(
-        alt(one_of("\"'"),
-            special),
-        alt(
-            alt(small, large),
-            alt(symbol, digit))
-    )(walker)
+    choice((one_of("\"'"), special, small, large, symbol, digit))(walker)
 }
 
 fn any<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
@@ -99,12 +94,7 @@ fn closecom<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Span<'filed
 }
 
 fn dashes<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
-    let start = walker.get_marker();
-    tag("--")(walker)?;
-
-    while let Ok(Some(_)) = opt(tag("-"))(walker) {}
-
-    Ok(walker.span_from_marker_to_here(start).unwrap())
+    take_at_least(2, |c| c == '-', "dashes")(walker)
 }
 
 fn comment<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
@@ -1,10 +1,22 @@
 use compiler_utils::*;
+use compiler_utils::parser::{delimited, iterate_nested};
 
+/// Accepts a group of brackets, returning the groups nested directly inside it, if there are any
 #[inline]
-fn parens<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<(), ParsingError<'filedata>> {
+fn parens<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Option<Span<'filedata>>, ParsingError<'filedata>> {
     alt(
-        map(triple(tag("("), opt(accepts_while(parens)), tag(")")), |_| ()),
-        map(triple(tag("["),opt(accepts_while(parens)), tag("]")), |_| ())
+        delimited(tag("("), opt(accepts_while(parens)), tag(")")),
+        delimited(tag("["), opt(accepts_while(parens)), tag("]"))
+    )(walker)
+}
+
+/// The same grammar as `parens`, without recursing once per nesting level
+fn parens_iterative<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<(), ParsingError<'filedata>> {
+    iterate_nested(
+        alt(tag("("), tag("[")),
+        |walker: &mut FileWalker<'filedata>, open: &Span<'filedata>| if open.data == "(" { tag(")")(walker) } else { tag("]")(walker) },
+        |_, _| Ok(()),
+        |_, _, _| ()
     )(walker)
 }
 
@@ -63,6 +75,10 @@ fn test_nested() {
 
     parens(&mut walker).unwrap();
     assert!(walker.current_string().is_empty());
+
+    // The brackets are dropped, keeping what was between them
+    assert_eq!(assert_parses!(parens, "[()[]]").map(|inner| inner.data), Some("()[]"));
+    assert_eq!(assert_parses!(parens, "()"), None);
 }
 
 #[test]
@@ -78,4 +94,29 @@ fn test_nested_failure() {
     
     let mut walker = FileWalker::from_data("[[]", "input");
     assert!(parens(&mut walker).is_err());
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_assert_parses() {
+    for input in ["()", "[]", "(())", "([])", "[()]", "[[]]"] {
+        assert_parses!(parens, input);
+    }
+
+    assert_parse_fails!(parens, "(]", ErrorKind::ExpectedTag(")"));
+    assert_parse_fails!(parens, "[(]", ErrorKind::ExpectedTag("]"));
+}
+
+#[test]
+fn test_iterative() {
+    for input in ["()", "[]", "(())", "([])", "[()]", "[[]]", "(()[])"] {
+        assert_parses!(parens_iterative, input);
+    }
+
+    for input in ["(", ")", "[)", "(]", "([)]", "([)", "[(]", "[[]"] {
+        assert!(parens_iterative(&mut FileWalker::from_data(input, "input")).is_err(), "{} should not parse", input);
+    }
+
+    // Far deeper than the recursive grammar could go without overflowing the stack
+    let deep = format!("{}{}", "([".repeat(100_000), "])".repeat(100_000));
+    assert_parses!(parens_iterative, &deep);
+}
@@ -0,0 +1,68 @@
+use compiler_utils::*;
+use compiler_utils::errors::Diagnostic;
+use compiler_utils::parser::{LineIndex, identifier};
+
+fn assignment<'filedata>(walker: &mut FileWalker<'filedata>) -> Result<Span<'filedata>, ParsingError<'filedata>> {
+    let start = walker.get_marker();
+    identifier()(walker)?;
+    tag(" = ")(walker)?;
+    take_while(|c| c.is_ascii_digit(), "a number")(walker)?;
+    tag(";")(walker)?;
+
+    Ok(walker.span_from_marker_to_here(start).unwrap())
+}
+
+#[test]
+fn test_parse_on_worker_threads() {
+    let files = ["a = 1;", "b = 2", "c = ;"];
+
+    // Each file is parsed on its own thread, and the errors are sent back without borrowing the file
+    let handles: Vec<_> = files.iter().enumerate().map(|(i, data)| {
+        let data = data.to_string();
+        std::thread::spawn(move || {
+            let mut walker = FileWalker::from_data(&data, "input");
+            let index = LineIndex::from_walker(&walker);
+
+            assignment(&mut walker).err().map(|error| (i, Diagnostic::from(error).compact(&index).unwrap()))
+        })
+    }).collect();
+
+    let errors: Vec<_> = handles.into_iter().filter_map(|handle| handle.join().unwrap()).collect();
+    assert_eq!(errors.len(), 2);
+
+    // The reporting thread expands the errors against its own copy of each file
+    let settings = ErrorDisplaySettings::default().with_colored(false);
+    let rendered: Vec<_> = errors.iter().map(|(i, compact)| {
+        let walker = FileWalker::from_data(files[*i], "input");
+        let diagnostic = compact.expand(&LineIndex::from_walker(&walker)).unwrap();
+        diagnostic.render(&settings, &walker).to_string()
+    }).collect();
+
+    assert_eq!(rendered[0], "\
+error: expected `;`
+   --> column 6 line 1 in input
+  1 |b = 2
+    |     ^
+");
+    assert_eq!(rendered[1], "\
+error: expected a number
+   --> column 5 line 1 in input
+  1 |c = ;
+    |    ^
+");
+}
+
+#[test]
+fn test_spans_cross_scoped_threads() {
+    let data = String::from("x = 1;\ny = 2;");
+
+    // Spans only borrow the file, so scoped threads can share them directly
+    let spans = std::thread::scope(|scope| {
+        let (first, second) = data.split_at(7);
+        let first = scope.spawn(|| assignment(&mut FileWalker::from_data(first, "input")));
+        let second = scope.spawn(|| assignment(&mut FileWalker::from_data(second, "input")));
+        [first.join().unwrap().unwrap(), second.join().unwrap().unwrap()]
+    });
+
+    assert_eq!(spans.map(|span| span.data), ["x = 1;", "y = 2;"]);
+}